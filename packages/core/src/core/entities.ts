@@ -94,7 +94,8 @@ export class Entities extends Group implements NetIntercept {
         let object = this.map.get(id);
 
         switch (operation) {
-          case "CREATE": {
+          case "CREATE":
+          case "ENTER": {
             if (object) {
               return;
             }
@@ -125,6 +126,18 @@ export class Entities extends Group implements NetIntercept {
             object.parent?.remove(object);
             object.onDelete?.(metadata);
 
+            break;
+          }
+          case "LEAVE": {
+            if (!object) {
+              return;
+            }
+
+            this.map.delete(id);
+
+            object.parent?.remove(object);
+            object.onDelete?.(metadata);
+
             break;
           }
         }
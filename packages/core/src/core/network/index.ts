@@ -11,6 +11,12 @@ export * from "./intercept";
 
 const { Message } = protocol;
 
+/**
+ * The number of reliable message IDs remembered. The server gives up on resending a message after
+ * a few tries, so only the most recent IDs can ever arrive again.
+ */
+const MAX_ACKED = 1024;
+
 /**
  * A custom WebSocket type that supports protocol buffer sending.
  */
@@ -179,6 +185,17 @@ export class Network {
 
   private packetQueue: any[] = [];
 
+  /**
+   * The token the server handed this client in its last "INIT" message, sent when joining so that
+   * the server resumes this client's session if it reconnects.
+   */
+  private session: string | null = null;
+
+  /**
+   * The IDs of the reliable messages already received, so that resent ones are only handled once.
+   */
+  private acked = new Set<number>();
+
   /**
    * Create a new network instance.
    */
@@ -308,6 +325,7 @@ export class Network {
       json: {
         world,
         username: this.clientInfo.username,
+        metadata: this.session ? { session: this.session } : {},
       },
     });
 
@@ -488,7 +506,22 @@ export class Network {
    * the network intercepts.
    */
   private onMessage = async (message: MessageProtocol) => {
-    const { type } = message;
+    const { type, ack } = message;
+
+    if (ack) {
+      this.send({ type: "ACK", ack });
+
+      if (this.acked.has(ack)) {
+        return;
+      }
+
+      this.acked.add(ack);
+
+      // Sets iterate in insertion order, so the first ID is the oldest.
+      if (this.acked.size > MAX_ACKED) {
+        this.acked.delete(this.acked.values().next().value);
+      }
+    }
 
     if (type === "ERROR") {
       const { text } = message;
@@ -498,7 +531,11 @@ export class Network {
     }
 
     if (type === "INIT") {
-      const { id } = message.json;
+      const { id, session } = message.json;
+
+      if (session) {
+        this.session = session;
+      }
 
       if (id) {
         if (this.clientInfo.id && this.clientInfo.id !== id) {
@@ -3,6 +3,41 @@ import * as fflate from "fflate";
 
 const { Message, Entity } = protocol;
 
+// Inflate and decode a buffer, unpacking batched messages in order.
+const unpack = (buffer) => {
+  if (buffer[0] === 0x78 && buffer[1] === 0x9c) {
+    buffer = fflate.unzlibSync(buffer);
+  }
+
+  const decoded = Message.decode(buffer);
+
+  if (decoded.type === Message.Type.BATCH) {
+    return decoded.batch.flatMap(unpack);
+  }
+
+  return [decoded];
+};
+
+// Turn 16-bit quantized positions and UVs back into floats.
+const dequantize = (geometry) => {
+  const { quantizedPositions, quantizedUvs, scale, offset, uvScale, uvOffset } =
+    geometry;
+  const read = (bytes) =>
+    new Uint16Array(bytes.slice().buffer, 0, bytes.byteLength >> 1);
+
+  geometry.positions = Array.from(
+    read(quantizedPositions),
+    (value, i) => offset[i % 3] + value * scale
+  );
+  geometry.uvs = Array.from(
+    read(quantizedUvs),
+    (value) => (uvOffset || 0) + value * (uvScale || 0)
+  );
+
+  delete geometry.quantizedPositions;
+  delete geometry.quantizedUvs;
+};
+
 // @ts-ignore
 onconnect = (e) => {
   const port = e.ports[0];
@@ -16,12 +51,8 @@ onconnect = (e) => {
 
     const transferables = [];
 
-    const messages = buffers.map((buffer) => {
-      if (buffer[0] === 0x78 && buffer[1] === 0x9c) {
-        buffer = fflate.unzlibSync(buffer);
-      }
-
-      const message = Message.toObject(Message.decode(buffer), {
+    const messages = buffers.flatMap(unpack).map((decoded) => {
+      const message = Message.toObject(decoded, {
         defaults: true,
       });
       message.type = Message.Type[message.type];
@@ -78,6 +109,10 @@ onconnect = (e) => {
           if (chunk.meshes) {
             chunk.meshes.forEach((mesh) => {
               mesh.geometries.forEach((geometry) => {
+                if (geometry && geometry.scale) {
+                  dequantize(geometry);
+                }
+
                 ["indices"].forEach((key) => {
                   if (geometry && geometry[key]) {
                     geometry[key] = new Uint16Array(geometry[key]);
@@ -92,7 +127,7 @@ onconnect = (e) => {
                   }
                 });
 
-                ["positions", "uvs"].forEach((key) => {
+                ["positions", "uvs", "colors"].forEach((key) => {
                   if (geometry && geometry[key]) {
                     geometry[key] = new Float32Array(geometry[key]);
                     transferables.push(geometry[key].buffer);
@@ -135,6 +135,12 @@ export type Block = {
    */
   lightReduce: boolean;
 
+  /**
+   * How many extra levels of red, green and blue torch light are lost entering this block. Sunlight is only
+   * reduced through `lightReduce`.
+   */
+  lightFilter: [number, number, number];
+
   /**
    * Whether or not does the block generate dynamic faces or AABB's. If this is true, the block will use
    * `dynamicFn` to generate the faces and AABB's.
@@ -165,6 +171,16 @@ export type Block = {
   isolatedFaces: Set<string>;
 
   isEntity: boolean;
+
+  /**
+   * The RGBA color the block's faces are multiplied by. White leaves the textures as they are.
+   */
+  tint: [number, number, number, number];
+
+  /**
+   * Whether or not the block's tint is further colored by the biome it sits in, like grass or leaves.
+   */
+  biomeTint: boolean;
 };
 
 /**
@@ -124,6 +124,11 @@ export class Chunks {
 
   public requested: Map<string, number> = new Map();
 
+  /**
+   * The generation status of the chunks that are on their way, for loading indicators.
+   */
+  public statuses: Map<string, ChunkProtocol["status"]> = new Map();
+
   public toAdd: Coords2[] = [];
 
   public toRequest: string[] = [];
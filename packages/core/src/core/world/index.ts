@@ -1858,9 +1858,15 @@ export class World<T = any> extends Scene implements NetIntercept {
           oy === -1 &&
           level === maxLightLevel
             ? 0
-            : 1);
+            : 1) -
+          (isSunlight
+            ? 0
+            : nBlock.lightFilter?.[
+                color === RED_LIGHT ? 0 : color === GREEN_LIGHT ? 1 : 2
+              ] ?? 0);
 
         if (
+          nextLevel <= 0 ||
           !LightUtils.canEnter(sourceTransparency, nTransparency, ox, oy, oz) ||
           (isSunlight
             ? this.getSunlightAt(nvx, nvy, nvz)
@@ -2391,12 +2397,22 @@ export class World<T = any> extends Scene implements NetIntercept {
 
           // Only process if we're interested.
           this.chunks.requested.delete(name);
+          this.chunks.statuses.delete(name);
           this.chunks.toProcess.push({ source: "load", data: chunk });
           this.chunks.toProcessSet.add(name);
         });
 
         break;
       }
+      case "STATUS": {
+        const { chunks } = message;
+
+        chunks.forEach(({ x, z, status }) => {
+          this.chunks.statuses.set(ChunkUtils.getChunkName([x, z]), status);
+        });
+
+        break;
+      }
       case "UPDATE": {
         const { updates } = message;
 
@@ -2971,12 +2987,18 @@ export class World<T = any> extends Scene implements NetIntercept {
 
     const meshes = geometries
       .map((geo) => {
-        const { voxel, at, faceName, indices, lights, positions, uvs } = geo;
+        const { voxel, at, faceName, indices, lights, positions, uvs, colors } =
+          geo;
         const geometry = new BufferGeometry();
 
         geometry.setAttribute("position", new BufferAttribute(positions, 3));
         geometry.setAttribute("uv", new BufferAttribute(uvs, 2));
         geometry.setAttribute("light", new BufferAttribute(lights, 1));
+
+        // Tinted blocks come with an RGBA color per vertex, untinted ones with none.
+        if (colors && colors.length) {
+          geometry.setAttribute("color", new BufferAttribute(colors, 4));
+        }
         geometry.setIndex(new BufferAttribute(indices, 1));
 
         let material = this.getBlockFaceMaterial(
@@ -3153,7 +3175,11 @@ export class World<T = any> extends Scene implements NetIntercept {
         this.setVoxelRotationAt(vx, vy, vz, updatedRotation);
       }
 
-      if (updatedBlock.isOpaque || updatedBlock.lightReduce) {
+      if (
+        updatedBlock.isOpaque ||
+        updatedBlock.lightReduce ||
+        updatedBlock.lightFilter?.some((filter) => filter > 0)
+      ) {
         if (this.getSunlightAt(vx, vy, vz) > 0) {
           this.removeLight([vx, vy, vz], "SUNLIGHT");
         }
@@ -6,6 +6,7 @@ export type GeometryProtocol = {
   uvs: Float32Array;
   indices: Uint32Array;
   lights: Uint32Array;
+  colors?: Float32Array;
 };
 
 export type MeshProtocol = {
@@ -20,6 +21,11 @@ export type ChunkProtocol = {
   meshes: MeshProtocol[];
   voxels: Uint32Array;
   lights: Uint32Array;
+
+  /**
+   * Where the chunk is in its generation, only set in `STATUS` messages.
+   */
+  status?: "generating" | "meshing" | "ready";
 };
 
 export type PeerProtocol<T> = {
@@ -28,7 +34,12 @@ export type PeerProtocol<T> = {
   metadata: T;
 };
 
-export type EntityOperation = "CREATE" | "UPDATE" | "DELETE";
+export type EntityOperation =
+  | "CREATE"
+  | "UPDATE"
+  | "DELETE"
+  | "ENTER"
+  | "LEAVE";
 
 export type EntityProtocol<T> = {
   operation: EntityOperation;
@@ -83,10 +94,17 @@ export type MessageProtocol<
     | "TRANSPORT"
     | "EVENT"
     | "ACTION"
-    | "STATS";
+    | "STATS"
+    | "ACK"
+    | "STATUS";
   json?: T;
   text?: string;
 
+  /**
+   * Non-zero if the message is reliable and should be acknowledged.
+   */
+  ack?: number;
+
   chat?: ChatProtocol;
   method?: MethodProtocol<Method>;
 
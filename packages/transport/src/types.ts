@@ -28,7 +28,7 @@ export type PeerProtocol<T> = {
 };
 
 export type EntityProtocol<T> = {
-  operation: "CREATE" | "UPDATE" | "DELETE";
+  operation: "CREATE" | "UPDATE" | "DELETE" | "ENTER" | "LEAVE";
   id: string;
   type: string;
   metadata: T;
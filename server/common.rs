@@ -6,3 +6,15 @@ pub enum ClientFilter {
     Include(Vec<String>),
     Exclude(Vec<String>),
 }
+
+impl ClientFilter {
+    /// Check whether a client passes this filter.
+    pub fn includes(&self, id: &str) -> bool {
+        match self {
+            ClientFilter::All => true,
+            ClientFilter::Direct(target) => target == id,
+            ClientFilter::Include(ids) => ids.iter().any(|i| i == id),
+            ClientFilter::Exclude(ids) => !ids.iter().any(|i| i == id),
+        }
+    }
+}
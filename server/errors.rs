@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::{ChunkStatus, Vec3};
+
 #[derive(Debug, Clone)]
 pub struct AddWorldError;
 
@@ -8,3 +10,253 @@ impl fmt::Display for AddWorldError {
         write!(f, "could not add world.")
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerError {
+    /// No client is connected to a world by this ID.
+    ClientNotFound(String),
+
+    /// No world goes by this name.
+    WorldNotFound(String),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ClientNotFound(id) => write!(f, "client not found in any world: {id}."),
+            Self::WorldNotFound(name) => write!(f, "world not found: {name}."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtlasError {
+    /// The atlas has not been generated yet.
+    NotGenerated,
+
+    /// No group of faces goes by this name.
+    GroupNotFound(String),
+
+    /// The group has no face of this name, (group, face).
+    FaceNotFound(String, String),
+
+    /// The new texture of a face is of a different resolution than the old one, (group, face).
+    ResolutionMismatch(String, String),
+
+    /// A face's padded tile is larger than the max atlas dimension, (group, face).
+    TileTooLarge(String, String),
+
+    /// The frames of an animated group don't fit on a single atlas page together.
+    AnimationTooLarge(String),
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotGenerated => write!(f, "texture atlas has not been generated."),
+            Self::GroupNotFound(group) => write!(f, "texture group not found: {group}."),
+            Self::FaceNotFound(group, face) => {
+                write!(f, "face not found in texture group {group}: {face}.")
+            }
+            Self::ResolutionMismatch(group, face) => {
+                write!(
+                    f,
+                    "face resolution changed in texture group {group}: {face}."
+                )
+            }
+            Self::TileTooLarge(group, face) => {
+                write!(
+                    f,
+                    "face too large for the texture atlas in {group}: {face}."
+                )
+            }
+            Self::AnimationTooLarge(group) => {
+                write!(f, "animation frames don't fit on one atlas page: {group}.")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+    /// The model file could not be read.
+    Io(String),
+
+    /// A line of the model could not be parsed, (line number, reason).
+    Parse(usize, String),
+
+    /// A vertex of the model lies outside of the unit cube, (line number).
+    OutOfBounds(usize),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(reason) => write!(f, "could not read model: {reason}."),
+            Self::Parse(line, reason) => {
+                write!(f, "could not parse model at line {line}: {reason}.")
+            }
+            Self::OutOfBounds(line) => {
+                write!(f, "vertex at line {line} is outside of the unit cube.")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockConflict {
+    /// A fluid block has collision boxes, while fluids are meant to be moved through.
+    FluidWithAabbs,
+
+    /// An empty block has faces, which are never meshed.
+    EmptyWithFaces,
+
+    /// A fluid block can be waterlogged, which would fill it with another fluid.
+    WaterloggedFluid,
+}
+
+impl fmt::Display for BlockConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::FluidWithAabbs => write!(f, "fluid blocks cannot have aabbs"),
+            Self::EmptyWithFaces => write!(f, "empty blocks cannot have faces"),
+            Self::WaterloggedFluid => write!(f, "fluid blocks cannot be waterlogged"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockBuildError {
+    /// Name of the block that failed to build.
+    pub name: String,
+
+    /// Every contradictory combination of flags found on the block.
+    pub conflicts: Vec<BlockConflict>,
+}
+
+impl fmt::Display for BlockBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let conflicts = self
+            .conflicts
+            .iter()
+            .map(|conflict| conflict.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(f, "could not build block {}: {conflicts}.", self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchematicError {
+    /// The bytes could not be decompressed.
+    Decompress(String),
+
+    /// The bytes end before the size of the schematic.
+    MissingSize,
+
+    /// The number of voxels doesn't match the size of the schematic, (expected, actual).
+    SizeMismatch(usize, usize),
+
+    /// The size of the schematic holds more voxels than can be addressed.
+    SizeOverflow,
+}
+
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Decompress(reason) => write!(f, "could not decompress schematic: {reason}."),
+            Self::MissingSize => write!(f, "schematic is missing its size."),
+            Self::SizeOverflow => write!(f, "schematic size is too large."),
+            Self::SizeMismatch(expected, actual) => {
+                write!(
+                    f,
+                    "schematic should have {expected} voxels, found {actual}."
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VecLengthError(pub usize);
+
+impl fmt::Display for VecLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected 3 components, found {}.", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusError {
+    /// The status the chunk was at.
+    pub from: ChunkStatus,
+
+    /// The status the chunk was asked to go to.
+    pub to: ChunkStatus,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "chunk cannot go from {} to {}.",
+            self.from.name(),
+            self.to.name()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoxelError {
+    /// The chunk the voxel is in isn't loaded.
+    Unloaded(Vec3<i32>),
+
+    /// The voxel is above or below the chunks of the world.
+    OutOfHeight(Vec3<i32>),
+}
+
+impl fmt::Display for VoxelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unloaded(Vec3(vx, vy, vz)) => {
+                write!(f, "voxel ({vx}, {vy}, {vz}) is not in a loaded chunk.")
+            }
+            Self::OutOfHeight(Vec3(vx, vy, vz)) => {
+                write!(f, "voxel ({vx}, {vy}, {vz}) is out of the world's height.")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkOptionsError {
+    /// Chunks have to be at least a voxel wide.
+    ZeroSize,
+
+    /// The chunk doesn't span any voxels vertically, (min y, max height).
+    EmptyHeight(i32, usize),
+
+    /// The height of the chunk can't be split evenly into sub-chunks, (height, sub-chunks).
+    UnevenSubChunks(usize, usize),
+}
+
+impl fmt::Display for ChunkOptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ZeroSize => write!(f, "chunk size cannot be zero."),
+            Self::EmptyHeight(min_y, max_height) => {
+                write!(
+                    f,
+                    "chunk min y {min_y} is not below its max height {max_height}."
+                )
+            }
+            Self::UnevenSubChunks(height, sub_chunks) => {
+                write!(
+                    f,
+                    "chunk height {height} cannot be split into {sub_chunks} sub-chunks."
+                )
+            }
+        }
+    }
+}
@@ -5,6 +5,8 @@ mod server;
 mod types;
 mod world;
 
+use std::time::Duration;
+
 use actix::{Actor, Addr};
 use actix_cors::Cors;
 use actix_files::{Files, NamedFile};
@@ -17,13 +19,21 @@ use hashbrown::HashMap;
 use log::{info, warn};
 
 pub use common::*;
+pub use errors::*;
 pub use libs::*;
 pub use server::*;
 pub use types::*;
 pub use world::*;
 
+/// Connect query fields that are used by the route itself, rather than passed on as handshake fields.
+const CONNECT_FIELDS: [&str; 5] = ["secret", "client_id", "is_transport", "world", "username"];
+
 struct Config {
     serve: String,
+    heartbeat_timeout: u64,
+    compression_threshold: usize,
+    rate_limit: Option<(u32, f32)>,
+    rate_limit_kick: Option<u32>,
 }
 
 /// Entry point for our websocket route
@@ -32,6 +42,7 @@ async fn ws_route(
     stream: web::Payload,
     srv: web::Data<Addr<Server>>,
     secret: web::Data<Option<String>>,
+    config: web::Data<Config>,
     options: Query<HashMap<String, String>>,
 ) -> Result<HttpResponse, Error> {
     if !secret.is_none() {
@@ -64,16 +75,30 @@ async fn ws_route(
         info!("A new transport server has connected.");
     }
 
-    ws::start(
-        server::WsSession {
-            id,
-            name: None,
-            is_transport,
-            addr: srv.get_ref().clone(),
-        },
-        &req,
-        stream,
-    )
+    let mut session = server::WsSession::new(
+        &id,
+        is_transport,
+        Duration::from_millis(config.heartbeat_timeout),
+        srv.get_ref().clone(),
+    );
+    session.compression_threshold = config.compression_threshold;
+
+    // Transport servers are trusted, and not rate limited.
+    if !is_transport {
+        session.world = options.get("world").cloned();
+        session.name = options.get("username").cloned();
+        session.metadata = options
+            .iter()
+            .filter(|(key, _)| !CONNECT_FIELDS.contains(&key.as_str()))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+        session.limiter = config
+            .rate_limit
+            .map(|(burst, per_second)| server::RateLimiter::new(burst, per_second));
+        session.kick_after = config.rate_limit_kick;
+    }
+
+    ws::start(session, &req, stream)
 }
 
 /// Main website path, serving statically built index.html
@@ -105,8 +130,13 @@ impl Voxelize {
         let port = server.port.to_owned();
         let serve = server.serve.to_owned();
         let secret = server.secret.to_owned();
+        let heartbeat_timeout = server.heartbeat_timeout;
+        let compression_threshold = server.compression_threshold;
+        let rate_limit = server.rate_limit;
+        let rate_limit_kick = server.rate_limit_kick;
 
         let server_addr = server.start();
+        let shutdown_addr = server_addr.clone();
 
         if serve.is_empty() {
             info!("Attempting to serve static folder: {}", serve);
@@ -123,6 +153,10 @@ impl Voxelize {
                 .app_data(web::Data::new(server_addr.clone()))
                 .app_data(web::Data::new(Config {
                     serve: serve.to_owned(),
+                    heartbeat_timeout,
+                    compression_threshold,
+                    rate_limit,
+                    rate_limit_kick,
                 }))
                 .route("/", web::get().to(index))
                 .route("/ws/", web::get().to(ws_route))
@@ -138,6 +172,11 @@ impl Voxelize {
 
         info!("🍄  Voxelize backend running on http://{}:{}", addr, port);
 
-        srv.run().await
+        srv.run().await?;
+
+        // The HTTP server stops on a termination signal, persist everything before exiting.
+        shutdown_addr.send(Shutdown).await.ok();
+
+        Ok(())
     }
 }
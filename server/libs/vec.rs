@@ -4,6 +4,8 @@ use std::ops::{self, Index, IndexMut};
 
 use serde::{Deserialize, Serialize};
 
+use crate::VecLengthError;
+
 /// Vector2 implementation for Voxelize.
 #[derive(Debug, Eq, PartialEq, Clone, Default, Hash, Serialize, Deserialize)]
 pub struct Vec2<T>(pub T, pub T);
@@ -25,6 +27,28 @@ impl<T: Copy> Vec3<T> {
     }
 }
 
+impl<T> IntoIterator for Vec3<T> {
+    type Item = T;
+    type IntoIter = std::array::IntoIter<T, 3>;
+
+    /// Iterate over the components in x, y, then z order.
+    fn into_iter(self) -> Self::IntoIter {
+        [self.0, self.1, self.2].into_iter()
+    }
+}
+
+impl<T: Copy> TryFrom<&[T]> for Vec3<T> {
+    type Error = VecLengthError;
+
+    /// Construct a `Vec3` instance from a slice, failing unless it has exactly three elements.
+    fn try_from(slice: &[T]) -> Result<Self, Self::Error> {
+        match slice {
+            &[x, y, z] => Ok(Self(x, y, z)),
+            _ => Err(VecLengthError(slice.len())),
+        }
+    }
+}
+
 impl<T: Copy + 'static, U: cast::AsPrimitive<T>> From<&Vec3<U>> for Vec3<T> {
     fn from(other: &Vec3<U>) -> Self {
         Vec3(other.0.as_(), other.1.as_(), other.2.as_())
@@ -201,6 +225,11 @@ impl<T> Vec3<T>
 where
     T: Float,
 {
+    /// Dot product of two vectors.
+    pub fn dot(&self, other: &Self) -> T {
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2
+    }
+
     /// Length of the vector.
     pub fn len(&self) -> T {
         (self.0 * self.0 + self.1 * self.1 + self.2 * self.2).sqrt()
@@ -263,6 +292,33 @@ impl Vec3<f32> {
         dx * dx + dy * dy + dz * dz
     }
 
+    /// Reflect this vector off of a surface, such as bouncing a velocity off of a wall. The normal
+    /// of the surface is assumed to be normalized.
+    pub fn reflect(&self, normal: &Self) -> Self {
+        self.sub(&normal.scale(2.0 * self.dot(normal)))
+    }
+
+    /// Project this vector onto another. Projecting onto a zero vector gives a zero vector.
+    pub fn project_onto(&self, other: &Self) -> Self {
+        let sq_len = other.dot(other);
+        if sq_len == 0.0 {
+            return Self(0.0, 0.0, 0.0);
+        }
+        other.scale(self.dot(other) / sq_len)
+    }
+
+    /// Check if every component is within `epsilon` of the other vector's.
+    pub fn approx_eq(&self, other: &Self, epsilon: f32) -> bool {
+        (self.0 - other.0).abs() <= epsilon
+            && (self.1 - other.1).abs() <= epsilon
+            && (self.2 - other.2).abs() <= epsilon
+    }
+
+    /// Check that no component is NaN or infinite.
+    pub fn is_finite(&self) -> bool {
+        self.0.is_finite() && self.1.is_finite() && self.2.is_finite()
+    }
+
     pub fn set_mag(&mut self, mag: f32) -> &Self {
         let len = self.len();
         self.0 /= len;
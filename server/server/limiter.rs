@@ -0,0 +1,52 @@
+use std::time::Instant;
+
+/// A token bucket limiting how many messages a client can send. Bursts up to the bucket's capacity
+/// are tolerated, while sustained floods above the refill rate get cut off.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// The maximum number of messages that can be sent at once.
+    pub burst: f32,
+
+    /// The number of messages regained per second.
+    pub per_second: f32,
+
+    /// The number of messages dropped so far.
+    pub dropped: u32,
+
+    /// The number of messages that can currently be sent.
+    tokens: f32,
+
+    /// The last time the tokens were refilled.
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter with a full bucket.
+    pub fn new(burst: u32, per_second: f32) -> Self {
+        Self {
+            burst: burst as f32,
+            per_second,
+            dropped: 0,
+            tokens: burst as f32,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Try to let a message through at the given instant, counting it as dropped if the bucket is empty.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f32();
+
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.last_refill = self.last_refill.max(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+
+        self.dropped += 1;
+        false
+    }
+}
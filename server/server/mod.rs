@@ -1,3 +1,4 @@
+mod limiter;
 mod models;
 mod session;
 
@@ -5,6 +6,7 @@ use std::time::{Duration, Instant};
 
 use actix::{
     Actor, Addr, AsyncContext, Context, Handler, Message as ActixMessage, MessageResult, Recipient,
+    ResponseFuture,
 };
 use fern::colors::{Color, ColoredLevelConfig};
 use hashbrown::HashMap;
@@ -17,13 +19,14 @@ use serde_json::{json, Value};
 use std::sync::Arc;
 
 use crate::{
-    errors::AddWorldError,
+    errors::{AddWorldError, ServerError},
     world::{Registry, World, WorldConfig},
-    ChunkStatus, ClientJoinRequest, ClientLeaveRequest, ClientRequest, GetConfig, GetInfo, Mesher,
-    MessageQueue, Preload, Prepare, Stats, SyncWorld, Tick, TransportJoinRequest,
-    TransportLeaveRequest,
+    ChunkStatus, ClientJoinRequest, ClientLeaveRequest, ClientMoveRequest, ClientRequest,
+    GetConfig, GetInfo, Mesher, MessageQueue, Preload, Prepare, Stats, SyncWorld, Tick,
+    TransportJoinRequest, TransportLeaveRequest,
 };
 
+pub use limiter::*;
 pub use models::*;
 pub use session::*;
 
@@ -31,6 +34,10 @@ pub use session::*;
 pub struct OnJoinRequest {
     world: String,
     username: String,
+
+    /// Handshake fields supplied by the client, such as its protocol `version` or an auth token.
+    #[serde(default)]
+    metadata: HashMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,6 +48,23 @@ struct OnActionRequest {
 
 type ServerInfoHandle = fn(&Server) -> Value;
 
+/// The state of a client that disconnected, kept for a grace period so that the client can resume
+/// where it left off by joining again with the `session` token it was handed in its `Init` message.
+#[derive(Clone, Debug)]
+pub struct SuspendedSession {
+    /// The world the client was in.
+    pub world: String,
+
+    /// The username the client joined with.
+    pub username: String,
+
+    /// The handshake fields the client joined with, such as its `view_radius`.
+    pub metadata: HashMap<String, String>,
+
+    /// When the client disconnected.
+    pub left_at: Instant,
+}
+
 fn default_info_handle(server: &Server) -> Value {
     let mut info = HashMap::new();
 
@@ -173,15 +197,36 @@ pub struct Server {
     /// Whether the server should show debug information.
     pub debug: bool,
 
-    /// Interval to tick the server at.
+    /// Interval to tick the server at, used by worlds that don't set their own.
     pub interval: u64,
 
     /// A secret to join the server.
     pub secret: Option<String>,
 
+    /// Milliseconds of silence after which a client is considered dead and disconnected.
+    pub heartbeat_timeout: u64,
+
+    /// The size in bytes above which messages sent to clients are compressed.
+    pub compression_threshold: usize,
+
+    /// The burst size and messages per second each client is limited to, if any.
+    pub rate_limit: Option<(u32, f32)>,
+
+    /// The number of dropped messages after which a client is disconnected, if any.
+    pub rate_limit_kick: Option<u32>,
+
+    /// The inclusive range of protocol versions joining clients must have, if any.
+    pub protocol_versions: Option<(u32, u32)>,
+
+    /// Milliseconds a disconnected client's session is kept around to be resumed.
+    pub session_grace: u64,
+
     /// A map of all the worlds.
     pub worlds: HashMap<String, Addr<SyncWorld>>,
 
+    /// The intervals of worlds that tick at their own rate.
+    tick_intervals: HashMap<String, Duration>,
+
     /// Registry of the server.
     pub registry: Registry,
 
@@ -194,6 +239,12 @@ pub struct Server {
     /// What world each client ID is connected to, client ID <-> world ID.
     pub connections: HashMap<String, (Recipient<EncodedMessage>, String)>,
 
+    /// The username and handshake fields of each client in a world, by client ID.
+    handshakes: HashMap<String, (String, HashMap<String, String>)>,
+
+    /// Sessions of disconnected clients that can still be resumed, by session token.
+    pub suspended_sessions: HashMap<String, SuspendedSession>,
+
     /// The information sent to the client when requested.
     info_handle: ServerInfoHandle,
 
@@ -208,18 +259,24 @@ impl Server {
     }
 
     /// Add a world instance to the server. Different worlds have different configurations, and can hold
-    /// their own set of clients within. If the server has already started, the added world will be
-    /// started right away.
+    /// their own set of clients within. Once the server has started, worlds are added by sending it
+    /// an `AddWorld` instead, so that they get ticked.
     pub fn add_world(&mut self, mut world: World) -> Result<&mut Addr<SyncWorld>, AddWorldError> {
         let name = world.name.clone();
+
+        if self.worlds.contains_key(&name) {
+            return Err(AddWorldError);
+        }
+
         let saving = world.config().saving;
         let save_dir = world.config().save_dir.clone();
+        let tick_interval = world.tick_interval();
         world.ecs_mut().insert(self.registry.clone());
 
-        let addr = world.start();
+        self.worlds.insert(name.clone(), world.start());
 
-        if self.worlds.insert(name.clone(), addr).is_some() {
-            return Err(AddWorldError);
+        if let Some(tick_interval) = tick_interval {
+            self.tick_intervals.insert(name.clone(), tick_interval);
         }
 
         info!(
@@ -235,6 +292,24 @@ impl Server {
         Ok(self.worlds.get_mut(&name).unwrap())
     }
 
+    /// Set up a recurring task to tick a world at its own rate. The worlds run on their own
+    /// threads, so a slow world doesn't hold back the others.
+    fn schedule_ticks(&self, name: &str, ctx: &mut Context<Self>) {
+        let world = match self.worlds.get(name) {
+            Some(world) => world.clone(),
+            None => return,
+        };
+        let interval = self
+            .tick_intervals
+            .get(name)
+            .cloned()
+            .unwrap_or(Duration::from_millis(self.interval));
+
+        ctx.run_interval(interval, move |_, _| {
+            world.do_send(Tick);
+        });
+    }
+
     // /// Create a world in the server. Different worlds have different configurations, and can hold
     // /// their own set of clients within. If the server has already started, the added world will be
     // /// started right away.
@@ -263,10 +338,95 @@ impl Server {
         (self.info_handle)(self)
     }
 
+    /// Move a connected client to another world. The old world handles the client's pending
+    /// messages first, then hands the client over to the new world.
+    pub fn move_client(&mut self, client_id: &str, to_world: &str) -> Result<(), ServerError> {
+        let to = self
+            .worlds
+            .get(to_world)
+            .ok_or_else(|| ServerError::WorldNotFound(to_world.to_owned()))?
+            .to_owned();
+
+        let (_, from_world) = self
+            .connections
+            .get_mut(client_id)
+            .ok_or_else(|| ServerError::ClientNotFound(client_id.to_owned()))?;
+
+        if from_world == to_world {
+            return Ok(());
+        }
+
+        let from = self
+            .worlds
+            .get(from_world.as_str())
+            .ok_or_else(|| ServerError::WorldNotFound(from_world.to_owned()))?;
+
+        from.do_send(ClientMoveRequest {
+            id: client_id.to_owned(),
+            to,
+        });
+
+        *from_world = to_world.to_owned();
+
+        Ok(())
+    }
+
+    /// Check the handshake fields of a joining client against the supported protocol versions,
+    /// returning the reason if the client should be refused.
+    pub fn validate_handshake(&self, metadata: &HashMap<String, String>) -> Option<String> {
+        let (min, max) = self.protocol_versions?;
+
+        match metadata.get("version").and_then(|v| v.parse::<u32>().ok()) {
+            Some(version) if version >= min && version <= max => None,
+            Some(version) => Some(format!(
+                "Protocol version {} is not supported, expected {} to {}.",
+                version, min, max
+            )),
+            None => Some("Missing protocol version in handshake.".to_owned()),
+        }
+    }
+
+    /// Suspend the session of a disconnecting client, so that it can be resumed within the grace
+    /// period. Sessions whose grace period is over are reaped along the way.
+    fn suspend_session(&mut self, id: &str, world: &str) {
+        self.reap_sessions();
+
+        let (username, metadata) = match self.handshakes.remove(id) {
+            Some(handshake) => handshake,
+            None => return,
+        };
+
+        if let Some(token) = metadata.get("session").cloned() {
+            self.suspended_sessions.insert(
+                token,
+                SuspendedSession {
+                    world: world.to_owned(),
+                    username,
+                    metadata,
+                    left_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Take the suspended session of a token if it's still within the grace period, reaping the
+    /// sessions that have expired.
+    fn resume_session(&mut self, token: &str) -> Option<SuspendedSession> {
+        self.reap_sessions();
+        self.suspended_sessions.remove(token)
+    }
+
+    /// Forget the suspended sessions whose grace period is over.
+    fn reap_sessions(&mut self) {
+        let grace = Duration::from_millis(self.session_grace);
+        self.suspended_sessions
+            .retain(|_, session| session.left_at.elapsed() <= grace);
+    }
+
     /// Handler for client's message.
     pub(crate) fn on_request(&mut self, id: &str, data: Message) -> Option<String> {
         if data.r#type == MessageType::Join as i32 {
-            let json: OnJoinRequest = serde_json::from_str(&data.json)
+            let mut json: OnJoinRequest = serde_json::from_str(&data.json)
                 .expect("`on_join` error. Could not read JSON string.");
 
             if !self.lost_sessions.contains_key(id) {
@@ -276,14 +436,44 @@ impl Server {
                 ));
             }
 
+            if let Some(error) = self.validate_handshake(&json.metadata) {
+                return Some(error);
+            }
+
+            // A client resuming its session goes back to its world under its old username, with
+            // its old handshake fields filling in whatever it didn't send again.
+            if let Some(session) = json
+                .metadata
+                .remove("session")
+                .and_then(|token| self.resume_session(&token))
+            {
+                if self.worlds.contains_key(&session.world) {
+                    info!("Client at {} resumed its session in {}", id, session.world);
+
+                    let mut metadata = session.metadata;
+                    metadata.extend(json.metadata);
+
+                    json.world = session.world;
+                    json.username = session.username;
+                    json.metadata = metadata;
+                }
+            }
+
+            // The token to resume this session with is picked by the server, so that it can't be
+            // guessed, and handed to the client in its `Init` message.
+            json.metadata.insert("session".to_owned(), nanoid!());
+
             if let Some(world) = self.worlds.get_mut(&json.world) {
                 if let Some(addr) = self.lost_sessions.remove(id) {
                     world.do_send(ClientJoinRequest {
                         id: id.to_owned(),
-                        username: json.username,
+                        username: json.username.to_owned(),
+                        metadata: json.metadata.to_owned(),
                         addr: addr.clone(),
                     });
                     self.connections.insert(id.to_owned(), (addr, json.world));
+                    self.handshakes
+                        .insert(id.to_owned(), (json.username, json.metadata));
                     return None;
                 }
 
@@ -298,6 +488,7 @@ impl Server {
             if let Some(world) = self.worlds.get_mut(&data.text) {
                 if let Some((addr, _)) = self.connections.remove(id) {
                     self.lost_sessions.insert(id.to_owned(), addr);
+                    self.handshakes.remove(id);
 
                     world.do_send(ClientLeaveRequest { id: id.to_owned() });
                 }
@@ -515,6 +706,27 @@ pub struct Disconnect {
 #[rtype(result = "Value")]
 pub struct Info;
 
+/// Move a client to another world.
+#[derive(ActixMessage)]
+#[rtype(result = "Result<(), ServerError>")]
+pub struct MoveClient {
+    /// Id of the client session
+    pub id: String,
+
+    /// Name of the world to move to
+    pub world: String,
+}
+
+/// Add a world to a server that has already started, ticking it at its own rate right away.
+#[derive(ActixMessage)]
+#[rtype(result = "Result<Addr<SyncWorld>, AddWorldError>")]
+pub struct AddWorld(pub World);
+
+/// Shut down gracefully, resolving once every world has persisted its data.
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+pub struct Shutdown;
+
 /// Send message to specific world
 #[derive(ActixMessage)]
 #[rtype(result = "Option<String>")]
@@ -533,12 +745,25 @@ impl Actor for Server {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        // Set up a recurring task to tick all worlds
-        ctx.run_interval(Duration::from_millis(self.interval), |act, _| {
-            for world in act.worlds.values() {
-                world.do_send(Tick);
-            }
-        });
+        let names = self.worlds.keys().cloned().collect::<Vec<_>>();
+
+        for name in names {
+            self.schedule_ticks(&name, ctx);
+        }
+    }
+}
+
+/// Handler for adding a world to a running server.
+impl Handler<AddWorld> for Server {
+    type Result = Result<Addr<SyncWorld>, AddWorldError>;
+
+    fn handle(&mut self, msg: AddWorld, ctx: &mut Context<Self>) -> Self::Result {
+        let name = msg.0.name.clone();
+        let addr = self.add_world(msg.0)?.clone();
+
+        self.schedule_ticks(&name, ctx);
+
+        Ok(addr)
     }
 }
 
@@ -593,6 +818,8 @@ impl Handler<Disconnect> for Server {
             if let Some(world) = self.worlds.get_mut(&world_name) {
                 world.do_send(ClientLeaveRequest { id: msg.id.clone() });
             }
+
+            self.suspend_session(&msg.id, &world_name);
         }
 
         if let Some(_) = self.transport_sessions.remove(&msg.id) {
@@ -607,6 +834,37 @@ impl Handler<Disconnect> for Server {
     }
 }
 
+/// Handler for moving a client between worlds.
+impl Handler<MoveClient> for Server {
+    type Result = Result<(), ServerError>;
+
+    fn handle(&mut self, msg: MoveClient, _: &mut Context<Self>) -> Self::Result {
+        self.move_client(&msg.id, &msg.world)
+    }
+}
+
+/// Handler for shutting down every world.
+impl Handler<Shutdown> for Server {
+    type Result = ResponseFuture<()>;
+
+    fn handle(&mut self, _: Shutdown, _: &mut Context<Self>) -> Self::Result {
+        let worlds = self.worlds.values().cloned().collect::<Vec<_>>();
+
+        self.connections.clear();
+        self.lost_sessions.clear();
+        self.handshakes.clear();
+        self.suspended_sessions.clear();
+
+        Box::pin(async move {
+            for world in worlds {
+                if let Err(e) = world.send(Shutdown).await {
+                    warn!("Failed to shut down a world: {}", e);
+                }
+            }
+        })
+    }
+}
+
 /// Handler for server info request.
 impl Handler<Info> for Server {
     type Result = MessageResult<Info>;
@@ -630,6 +888,8 @@ const DEFAULT_PORT: u16 = 4000;
 const DEFAULT_ADDR: &str = "0.0.0.0";
 const DEFAULT_SERVE: &str = "";
 const DEFAULT_INTERVAL: u64 = 16;
+const DEFAULT_HEARTBEAT_TIMEOUT: u64 = 10000;
+const DEFAULT_SESSION_GRACE: u64 = 30000;
 
 /// Builder for a voxelize server.
 pub struct ServerBuilder {
@@ -640,6 +900,12 @@ pub struct ServerBuilder {
     interval: u64,
     secret: Option<String>,
     registry: Option<Registry>,
+    compression_threshold: usize,
+    heartbeat_timeout: u64,
+    rate_limit: Option<(u32, f32)>,
+    rate_limit_kick: Option<u32>,
+    protocol_versions: Option<(u32, u32)>,
+    session_grace: u64,
 }
 
 impl ServerBuilder {
@@ -653,6 +919,12 @@ impl ServerBuilder {
             interval: DEFAULT_INTERVAL,
             secret: None,
             registry: None,
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            rate_limit: None,
+            rate_limit_kick: None,
+            protocol_versions: None,
+            session_grace: DEFAULT_SESSION_GRACE,
         }
     }
 
@@ -699,8 +971,51 @@ impl ServerBuilder {
         self
     }
 
+    /// Configure the size in bytes above which outgoing messages are compressed.
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Configure the milliseconds a client can go without responding before being disconnected. Must
+    /// be above zero, as clients are pinged at half the timeout when it's shorter than the interval.
+    pub fn heartbeat_timeout(mut self, heartbeat_timeout: u64) -> Self {
+        self.heartbeat_timeout = heartbeat_timeout;
+        self
+    }
+
+    /// Configure the messages each client can send, as a burst size and a rate per second. Messages
+    /// above the limit are dropped.
+    pub fn rate_limit(mut self, burst: u32, per_second: f32) -> Self {
+        self.rate_limit = Some((burst, per_second));
+        self
+    }
+
+    /// Configure the number of dropped messages after which a flooding client is disconnected.
+    pub fn rate_limit_kick(mut self, dropped: u32) -> Self {
+        self.rate_limit_kick = Some(dropped);
+        self
+    }
+
+    /// Configure the inclusive range of protocol versions joining clients must send in their handshake.
+    pub fn protocol_versions(mut self, min: u32, max: u32) -> Self {
+        self.protocol_versions = Some((min, max));
+        self
+    }
+
+    /// Configure the milliseconds a disconnected client's session can be resumed for. Default is
+    /// 30 seconds.
+    pub fn session_grace(mut self, session_grace: u64) -> Self {
+        self.session_grace = session_grace;
+        self
+    }
+
     /// Instantiate a voxelize server instance.
     pub fn build(self) -> Server {
+        if self.heartbeat_timeout == 0 {
+            panic!("Heartbeat timeout should be above zero.");
+        }
+
         let mut registry = self.registry.unwrap_or(Registry::new());
         registry.generate();
 
@@ -715,15 +1030,24 @@ impl ServerBuilder {
             debug: self.debug,
             interval: self.interval,
             secret: self.secret,
+            heartbeat_timeout: self.heartbeat_timeout,
+            compression_threshold: self.compression_threshold,
+            rate_limit: self.rate_limit,
+            rate_limit_kick: self.rate_limit_kick,
+            protocol_versions: self.protocol_versions,
+            session_grace: self.session_grace,
 
             registry,
 
             started: false,
 
             connections: HashMap::default(),
+            handshakes: HashMap::default(),
+            suspended_sessions: HashMap::default(),
             lost_sessions: HashMap::default(),
             transport_sessions: HashMap::default(),
             worlds: HashMap::default(),
+            tick_intervals: HashMap::default(),
             info_handle: default_info_handle,
             action_handles: HashMap::default(),
         }
@@ -1,10 +1,20 @@
-use std::io::{Cursor, Write};
+use std::{
+    io::{Cursor, Read, Write},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use actix::Message as ActixMessage;
-use libflate::zlib::Encoder;
+use byteorder::{ByteOrder, LittleEndian};
+use hashbrown::HashMap;
+use libflate::zlib::{Decoder, Encoder};
 use prost::Message as ProstMesssage;
 
-use crate::libs::Ndarray;
+use serde_json::{Map, Value};
+
+use crate::{
+    libs::{Ndarray, Vec3},
+    EncodedMessage,
+};
 
 /// Protocol buffers generated by `prost.rs`.
 pub mod protocols {
@@ -37,22 +47,87 @@ pub fn encode_message(message: &Message) -> Vec<u8> {
     buf.reserve(message.encoded_len());
     message.encode(&mut buf).unwrap();
 
-    if buf.len() > 1024 {
-        let mut encoder = Encoder::new(Vec::new()).unwrap();
-        encoder.write_all(buf.as_slice()).unwrap();
-        buf = encoder.finish().into_result().unwrap();
+    buf
+}
+
+/// Compress an encoded message if it's larger than `threshold` bytes. Smaller messages are left
+/// as-is, as compression would only add overhead to them.
+pub fn compress_message(buf: Vec<u8>, threshold: usize) -> Vec<u8> {
+    if buf.len() > threshold {
+        return compress(&buf);
     }
 
     buf
 }
 
-/// Decode protocol buffers into a message struct.
+/// Decode protocol buffers into a message struct, inflating the buffer first if it was compressed.
+/// Compressed messages that inflate past `MAX_DECOMPRESSED_SIZE` are rejected.
 pub fn decode_message(buf: &[u8]) -> Result<Message, prost::DecodeError> {
+    if is_compressed(buf) {
+        let buf = decompress(buf, MAX_DECOMPRESSED_SIZE)
+            .map_err(|_| prost::DecodeError::new("invalid or oversized zlib data"))?;
+        return Message::decode(&mut Cursor::new(buf));
+    }
+
     Message::decode(&mut Cursor::new(buf))
 }
 
+/// Coalesce encoded messages into a single `Batch` message, so that they can be sent as one frame.
+/// The messages keep their order, and a single message is passed through as-is.
+pub fn batch_messages(mut messages: Vec<EncodedMessage>) -> EncodedMessage {
+    if messages.len() == 1 {
+        return messages.pop().unwrap();
+    }
+
+    let batch = Message {
+        r#type: MessageType::Batch as i32,
+        batch: messages.into_iter().map(|encoded| encoded.0).collect(),
+        ..Default::default()
+    };
+
+    EncodedMessage(batch.encode_to_vec())
+}
+
+/// The ID given to the next reliable message, starting at 1 as 0 marks an unreliable message.
+static NEXT_ACK_ID: AtomicU32 = AtomicU32::new(1);
+
+/// The default size in bytes above which encoded messages are compressed.
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// The largest size in bytes a compressed message from a client may inflate to.
+pub const MAX_DECOMPRESSED_SIZE: usize = 8 * 1024 * 1024;
+
+/// Check whether an encoded message is compressed. Compressed messages are flagged by their zlib
+/// header, which can never start a plain message as no message field has the number 15.
+pub fn is_compressed(buf: &[u8]) -> bool {
+    buf.len() > 1 && buf[0] == 0x78 && buf[1] == 0x9c
+}
+
+/// Deflate an encoded message into zlib data.
+pub fn compress(buf: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    encoder.write_all(buf).unwrap();
+    encoder.finish().into_result().unwrap()
+}
+
+/// Inflate zlib data back into an encoded message, failing if it inflates past `limit` bytes.
+pub fn decompress(buf: &[u8], limit: usize) -> std::io::Result<Vec<u8>> {
+    let decoder = Decoder::new(buf)?;
+    let mut decoded = Vec::new();
+    decoder.take(limit as u64 + 1).read_to_end(&mut decoded)?;
+
+    if decoded.len() > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Decompressed message is too large.",
+        ));
+    }
+
+    Ok(decoded)
+}
+
 /// Protocol buffer compatible geometry data structure.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct GeometryProtocol {
     pub voxel: u32,
     pub at: Vec<i32>,
@@ -61,6 +136,340 @@ pub struct GeometryProtocol {
     pub indices: Vec<i32>,
     pub uvs: Vec<f32>,
     pub lights: Vec<i32>,
+    pub colors: Vec<f32>,
+}
+
+/// The color of a vertex that isn't tinted.
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+impl GeometryProtocol {
+    /// Get the RGBA color of a vertex, white if this geometry isn't tinted.
+    pub fn color(&self, vertex: usize) -> [f32; 4] {
+        match self.colors.get(vertex * 4..vertex * 4 + 4) {
+            Some(&[r, g, b, a]) => [r, g, b, a],
+            _ => WHITE,
+        }
+    }
+
+    /// Encode this geometry into packed protocol buffer bytes, to be sent through `binary`.
+    pub fn encode(&self) -> Vec<u8> {
+        protocols::Geometry::from(self.to_owned()).encode_to_vec()
+    }
+
+    /// Merge vertices with the same position, UV, light and color into one, pointing the indices at
+    /// the merged vertices. Vertices keep the order they first appear in, and triangles are unchanged.
+    /// Malformed geometry, with an index out of range or UVs, lights or colors that don't match the
+    /// vertex count, is left as it is.
+    pub fn optimize(&mut self) {
+        let count = self.positions.len() / 3;
+        let has_uvs = self.uvs.len() == count * 2;
+        let has_lights = self.lights.len() == count;
+        let has_colors = self.colors.len() == count * 4;
+
+        let malformed = !self.positions.len().is_multiple_of(3)
+            || (!has_uvs && !self.uvs.is_empty())
+            || (!has_lights && !self.lights.is_empty())
+            || (!has_colors && !self.colors.is_empty())
+            || self
+                .indices
+                .iter()
+                .any(|&index| index < 0 || index as usize >= count);
+
+        if malformed {
+            return;
+        }
+
+        let mut merged: HashMap<Vec<u32>, i32> = HashMap::new();
+        let mut remap = Vec::with_capacity(count);
+
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        let mut lights = vec![];
+        let mut colors = vec![];
+
+        for i in 0..count {
+            let position = &self.positions[i * 3..i * 3 + 3];
+            let uv = if has_uvs {
+                &self.uvs[i * 2..i * 2 + 2]
+            } else {
+                &[]
+            };
+            let light = if has_lights { self.lights[i] } else { 0 };
+            let color = if has_colors {
+                &self.colors[i * 4..i * 4 + 4]
+            } else {
+                &[]
+            };
+
+            let key = position
+                .iter()
+                .chain(uv)
+                .chain(color)
+                .map(|value| value.to_bits())
+                .chain([light as u32])
+                .collect::<Vec<_>>();
+
+            let index = *merged.entry(key).or_insert_with(|| {
+                positions.extend_from_slice(position);
+                uvs.extend_from_slice(uv);
+                colors.extend_from_slice(color);
+
+                if has_lights {
+                    lights.push(light);
+                }
+
+                (positions.len() / 3 - 1) as i32
+            });
+
+            remap.push(index);
+        }
+
+        self.indices = self
+            .indices
+            .iter()
+            .map(|&index| remap[index as usize])
+            .collect();
+        self.positions = positions;
+
+        if has_uvs {
+            self.uvs = uvs;
+        }
+
+        if has_lights {
+            self.lights = lights;
+        }
+
+        if has_colors {
+            self.colors = colors;
+        }
+    }
+
+    /// Quantize the positions of this geometry to 16-bit steps relative to an offset, such as the
+    /// origin of its chunk, spanning `extent` units along each axis. Positions out of range are
+    /// clamped. UVs are quantized to 16 bits over the range they span, so UVs outside of 0 to 1
+    /// are kept too.
+    pub fn quantize(&self, offset: &Vec3<f32>, extent: f32) -> QuantizedGeometry {
+        let scale = extent / QUANTIZED_MAX;
+        let origin = [offset.0, offset.1, offset.2];
+
+        let (uv_min, uv_max) = Self::bounds(self.uvs.iter());
+        let uv_scale = (uv_max - uv_min) / QUANTIZED_MAX;
+
+        let positions = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                ((value - origin[i % 3]) / scale)
+                    .round()
+                    .clamp(0.0, QUANTIZED_MAX) as u16
+            })
+            .collect();
+
+        let uvs = self
+            .uvs
+            .iter()
+            .map(|value| {
+                if uv_scale == 0.0 {
+                    return 0;
+                }
+
+                ((value - uv_min) / uv_scale)
+                    .round()
+                    .clamp(0.0, QUANTIZED_MAX) as u16
+            })
+            .collect();
+
+        QuantizedGeometry {
+            voxel: self.voxel,
+            at: self.at.to_owned(),
+            face_name: self.face_name.to_owned(),
+            positions,
+            indices: self.indices.to_owned(),
+            uvs,
+            lights: self.lights.to_owned(),
+            colors: self.colors.to_owned(),
+            scale,
+            offset: offset.to_owned(),
+            uv_scale,
+            uv_offset: uv_min,
+        }
+    }
+
+    /// Quantize this geometry relative to the bounds of its own positions, which is how meshes are
+    /// sent when `config.quantize_geometry` is on.
+    pub fn quantize_to_fit(&self) -> QuantizedGeometry {
+        let mut offset = [0.0; 3];
+        let mut extent = 1.0_f32;
+
+        for (axis, min) in offset.iter_mut().enumerate() {
+            let (lo, hi) = Self::bounds(self.positions.iter().skip(axis).step_by(3));
+            *min = lo;
+            extent = extent.max(hi - lo);
+        }
+
+        self.quantize(&Vec3(offset[0], offset[1], offset[2]), extent)
+    }
+
+    /// The smallest and largest of some values, or zeros if there are none.
+    fn bounds<'a>(values: impl Iterator<Item = &'a f32>) -> (f32, f32) {
+        values
+            .fold(None, |bounds: Option<(f32, f32)>, &value| match bounds {
+                Some((min, max)) => Some((min.min(value), max.max(value))),
+                None => Some((value, value)),
+            })
+            .unwrap_or_default()
+    }
+
+    /// Decode packed protocol buffer bytes back into geometry data, dequantizing the positions and
+    /// UVs if they were quantized.
+    pub fn decode(buf: &[u8]) -> Result<Self, prost::DecodeError> {
+        let geometry = protocols::Geometry::decode(&mut Cursor::new(buf))?;
+
+        if geometry.scale != 0.0 {
+            return Ok(QuantizedGeometry::from(geometry).dequantize());
+        }
+
+        Ok(Self {
+            voxel: geometry.voxel,
+            at: geometry.at,
+            face_name: geometry.face_name,
+            positions: geometry.positions,
+            indices: geometry.indices,
+            uvs: geometry.uvs,
+            lights: geometry.lights,
+            colors: geometry.colors,
+        })
+    }
+}
+
+/// The largest value of a quantized position or UV.
+const QUANTIZED_MAX: f32 = u16::MAX as f32;
+
+/// Geometry data with 16-bit fixed point positions and UVs in place of floats, about halving the
+/// size of a mesh. Created by `geometry.quantize`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuantizedGeometry {
+    pub voxel: u32,
+    pub at: Vec<i32>,
+    pub face_name: Option<String>,
+    pub positions: Vec<u16>,
+    pub indices: Vec<i32>,
+    pub uvs: Vec<u16>,
+    pub lights: Vec<i32>,
+    pub colors: Vec<f32>,
+
+    /// The size of one step of the quantized positions.
+    pub scale: f32,
+
+    /// The position that the quantized positions are relative to.
+    pub offset: Vec3<f32>,
+
+    /// The size of one step of the quantized UVs.
+    pub uv_scale: f32,
+
+    /// The UV that the quantized UVs are relative to.
+    pub uv_offset: f32,
+}
+
+impl QuantizedGeometry {
+    /// Encode this geometry into packed protocol buffer bytes, to be sent through `binary`.
+    pub fn encode(&self) -> Vec<u8> {
+        protocols::Geometry::from(self.to_owned()).encode_to_vec()
+    }
+
+    /// Turn the quantized positions and UVs back into floats, within half a step of the originals.
+    pub fn dequantize(&self) -> GeometryProtocol {
+        let origin = [self.offset.0, self.offset.1, self.offset.2];
+
+        GeometryProtocol {
+            voxel: self.voxel,
+            at: self.at.to_owned(),
+            face_name: self.face_name.to_owned(),
+            positions: self
+                .positions
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| origin[i % 3] + value as f32 * self.scale)
+                .collect(),
+            indices: self.indices.to_owned(),
+            uvs: self
+                .uvs
+                .iter()
+                .map(|&value| self.uv_offset + value as f32 * self.uv_scale)
+                .collect(),
+            lights: self.lights.to_owned(),
+            colors: self.colors.to_owned(),
+        }
+    }
+}
+
+impl From<QuantizedGeometry> for protocols::Geometry {
+    fn from(geo: QuantizedGeometry) -> Self {
+        let pack = |values: &[u16]| {
+            let mut bytes = vec![0; values.len() * 2];
+            LittleEndian::write_u16_into(values, &mut bytes);
+            bytes
+        };
+
+        protocols::Geometry {
+            voxel: geo.voxel,
+            at: geo.at,
+            face_name: geo.face_name,
+            indices: geo.indices,
+            lights: geo.lights,
+            colors: geo.colors,
+            quantized_positions: pack(&geo.positions),
+            quantized_uvs: pack(&geo.uvs),
+            scale: geo.scale,
+            offset: vec![geo.offset.0, geo.offset.1, geo.offset.2],
+            uv_scale: geo.uv_scale,
+            uv_offset: geo.uv_offset,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<protocols::Geometry> for QuantizedGeometry {
+    fn from(geo: protocols::Geometry) -> Self {
+        let unpack = |bytes: &[u8]| {
+            let mut values = vec![0; bytes.len() / 2];
+            LittleEndian::read_u16_into(&bytes[..values.len() * 2], &mut values);
+            values
+        };
+        let offset = |i: usize| geo.offset.get(i).cloned().unwrap_or_default();
+
+        Self {
+            voxel: geo.voxel,
+            at: geo.at.to_owned(),
+            face_name: geo.face_name.to_owned(),
+            positions: unpack(&geo.quantized_positions),
+            indices: geo.indices.to_owned(),
+            uvs: unpack(&geo.quantized_uvs),
+            lights: geo.lights.to_owned(),
+            colors: geo.colors.to_owned(),
+            scale: geo.scale,
+            offset: Vec3(offset(0), offset(1), offset(2)),
+            uv_scale: geo.uv_scale,
+            uv_offset: geo.uv_offset,
+        }
+    }
+}
+
+impl From<GeometryProtocol> for protocols::Geometry {
+    fn from(geo: GeometryProtocol) -> Self {
+        protocols::Geometry {
+            voxel: geo.voxel,
+            at: geo.at,
+            face_name: geo.face_name,
+            indices: geo.indices,
+            positions: geo.positions,
+            lights: geo.lights,
+            colors: geo.colors,
+            uvs: geo.uvs,
+            ..Default::default()
+        }
+    }
 }
 
 /// Protocol buffer compatible mesh data structure.
@@ -68,6 +477,9 @@ pub struct GeometryProtocol {
 pub struct MeshProtocol {
     pub level: i32,
     pub geometries: Vec<GeometryProtocol>,
+
+    /// Whether the geometries are sent quantized, through `geometry.quantize_to_fit`.
+    pub quantized: bool,
 }
 
 /// Protocol buffer compatible chunk data structure.
@@ -79,6 +491,7 @@ pub struct ChunkProtocol {
     pub meshes: Vec<MeshProtocol>,
     pub voxels: Option<Ndarray<u32>>,
     pub lights: Option<Ndarray<u32>>,
+    pub status: String,
 }
 
 /// Protocol buffer compatible peer data structure.
@@ -108,6 +521,67 @@ pub struct EntityProtocol {
     pub metadata: Option<String>,
 }
 
+impl EntityProtocol {
+    /// Create a new entity protocol with empty metadata.
+    pub fn new(operation: EntityOperation, id: &str, etype: &str) -> Self {
+        Self {
+            operation,
+            id: id.to_owned(),
+            r#type: etype.to_owned(),
+            metadata: None,
+        }
+    }
+
+    /// Configure the position of the entity, sent as the `position` field of its metadata.
+    pub fn position(self, position: &Vec3<f32>) -> Self {
+        self.set("position", serde_json::to_value(position).unwrap())
+    }
+
+    /// Configure the direction of the entity, sent as the `direction` field of its metadata.
+    pub fn direction(self, direction: &Vec3<f32>) -> Self {
+        self.set("direction", serde_json::to_value(direction).unwrap())
+    }
+
+    /// Merge extra fields into the metadata of the entity.
+    pub fn metadata(self, metadata: &Map<String, Value>) -> Self {
+        metadata.iter().fold(self, |entity, (key, value)| {
+            entity.set(key, value.to_owned())
+        })
+    }
+
+    /// Get the position stored in the metadata of the entity, if any.
+    pub fn get_position(&self) -> Option<Vec3<f32>> {
+        self.get("position")
+    }
+
+    /// Get the direction stored in the metadata of the entity, if any.
+    pub fn get_direction(&self) -> Option<Vec3<f32>> {
+        self.get("direction")
+    }
+
+    fn set(mut self, key: &str, value: Value) -> Self {
+        let mut map = self.metadata_map();
+        map.insert(key.to_owned(), value);
+        self.metadata = Some(Value::Object(map).to_string());
+        self
+    }
+
+    fn get(&self, key: &str) -> Option<Vec3<f32>> {
+        self.metadata_map()
+            .remove(key)
+            .and_then(|value| serde_json::from_value(value).ok())
+    }
+
+    fn metadata_map(&self) -> Map<String, Value> {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| serde_json::from_str(metadata).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Protocol buffer compatible chat message data structure. The `type` acts as the channel of the
+/// message, such as a player's chat or a server announcement.
 #[derive(Debug, Clone, Default)]
 pub struct ChatMessageProtocol {
     pub r#type: String,
@@ -134,6 +608,7 @@ pub struct MessageBuilder {
 
     json: Option<String>,
     text: Option<String>,
+    binary: Option<Vec<u8>>,
 
     chat: Option<ChatMessageProtocol>,
     method: Option<MethodProtocol>,
@@ -143,6 +618,8 @@ pub struct MessageBuilder {
     events: Option<Vec<EventProtocol>>,
     chunks: Option<Vec<ChunkProtocol>>,
     updates: Option<Vec<UpdateProtocol>>,
+
+    reliable: bool,
 }
 
 impl MessageBuilder {
@@ -158,6 +635,13 @@ impl MessageBuilder {
         self
     }
 
+    /// Configure the binary data of the protocol, for packed data such as encoded geometries
+    /// that would be too bulky as JSON numbers.
+    pub fn binary(mut self, bytes: Vec<u8>) -> Self {
+        self.binary = Some(bytes);
+        self
+    }
+
     /// Configure the peers data of the protocol.
     pub fn peers(mut self, peers: &[PeerProtocol]) -> Self {
         self.peers = Some(peers.to_vec());
@@ -200,6 +684,13 @@ impl MessageBuilder {
         self
     }
 
+    /// Mark the message as reliable, to be resent to each client until the client acknowledges
+    /// it. Meant for messages that must not be lost, such as block edits.
+    pub fn reliable(mut self) -> Self {
+        self.reliable = true;
+        self
+    }
+
     /// Create a protocol buffer message.
     pub fn build(self) -> Message {
         let mut message = protocols::Message {
@@ -209,6 +700,11 @@ impl MessageBuilder {
 
         message.json = self.json.unwrap_or_default();
         message.text = self.text.unwrap_or_default();
+        message.binary = self.binary.unwrap_or_default();
+
+        if self.reliable {
+            message.ack = NEXT_ACK_ID.fetch_add(1, Ordering::Relaxed);
+        }
 
         if let Some(peers) = self.peers {
             message.peers = peers
@@ -257,14 +753,12 @@ impl MessageBuilder {
                             geometries: mesh
                                 .geometries
                                 .into_iter()
-                                .map(|geo| protocols::Geometry {
-                                    voxel: geo.voxel,
-                                    at: geo.at,
-                                    face_name: geo.face_name,
-                                    indices: geo.indices.to_owned(),
-                                    positions: geo.positions.to_owned(),
-                                    lights: geo.lights.to_owned(),
-                                    uvs: geo.uvs.to_owned(),
+                                .map(|geometry| {
+                                    if mesh.quantized {
+                                        geometry.quantize_to_fit().into()
+                                    } else {
+                                        geometry.into()
+                                    }
                                 })
                                 .collect(),
                         })
@@ -273,6 +767,7 @@ impl MessageBuilder {
                     voxels: chunk.voxels.unwrap_or_default().data,
                     x: chunk.x,
                     z: chunk.z,
+                    status: chunk.status,
                 })
                 .collect();
         }
@@ -1,12 +1,19 @@
+use std::time::{Duration, Instant};
+
 use actix::prelude::*;
 use actix_web_actors::ws;
-use log::warn;
+use hashbrown::HashMap;
+use log::{info, warn};
+use serde_json::json;
 
 use crate::{
     server::models, ClientMessage, Connect, Disconnect, EncodedMessage, Message, MessageType,
-    Server,
+    RateLimiter, Server, DEFAULT_COMPRESSION_THRESHOLD,
 };
 
+/// How often heartbeat pings are sent to the client.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Debug)]
 pub struct WsSession {
     /// unique session id
@@ -20,6 +27,134 @@ pub struct WsSession {
 
     /// Chat server
     pub addr: Addr<Server>,
+
+    /// The last time the client responded, pong or otherwise.
+    pub last_heartbeat: Instant,
+
+    /// How long the client can go without responding before being disconnected.
+    pub timeout: Duration,
+
+    /// The limiter of the messages this client sends, if any.
+    pub limiter: Option<RateLimiter>,
+
+    /// The number of dropped messages after which this client is disconnected, if any.
+    pub kick_after: Option<u32>,
+
+    /// The world to join as soon as the client connects, from the `world` of the connect query.
+    pub world: Option<String>,
+
+    /// The handshake fields to join the world with, such as the protocol `version` or `session`
+    /// token, from the rest of the connect query.
+    pub metadata: HashMap<String, String>,
+
+    /// The size in bytes above which messages sent to the client are compressed.
+    pub compression_threshold: usize,
+}
+
+impl WsSession {
+    /// Create a new websocket session for a client, an empty id getting assigned on connect.
+    pub fn new(id: &str, is_transport: bool, timeout: Duration, addr: Addr<Server>) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: None,
+            is_transport,
+            addr,
+            last_heartbeat: Instant::now(),
+            timeout,
+            limiter: None,
+            kick_after: None,
+            world: None,
+            metadata: HashMap::new(),
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+        }
+    }
+
+    /// The `Join` message for the world picked at connect, if any, carrying the handshake fields
+    /// of the connect query. The username defaults to the session ID.
+    pub fn join_message(&self) -> Option<Message> {
+        let world = self.world.as_ref()?;
+        let username = self.name.as_ref().unwrap_or(&self.id);
+
+        Some(
+            Message::new(&MessageType::Join)
+                .json(
+                    &json!({ "world": world, "username": username, "metadata": self.metadata })
+                        .to_string(),
+                )
+                .build(),
+        )
+    }
+
+    /// Join the world picked at connect, closing the connection if the world can't be joined.
+    fn join_world(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let data = match self.join_message() {
+            Some(data) => data,
+            None => return,
+        };
+
+        self.addr
+            .send(ClientMessage {
+                id: self.id.to_owned(),
+                data,
+            })
+            .into_actor(self)
+            .then(|res, _, ctx| {
+                match res {
+                    Ok(None) => {}
+                    Ok(Some(error_msg)) => {
+                        warn!("Error: {}", error_msg);
+                        ctx.close(Some(ws::CloseReason {
+                            code: ws::CloseCode::Policy,
+                            description: Some(error_msg),
+                        }));
+                        ctx.stop();
+                    }
+                    _ => ctx.stop(),
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    /// Check whether a message from the client should be handled, or dropped for exceeding the
+    /// rate limit. Returns `None` if the client has dropped too many messages and should be kicked.
+    pub fn allow_message(&mut self, now: Instant) -> Option<bool> {
+        let limiter = match self.limiter.as_mut() {
+            Some(limiter) => limiter,
+            None => return Some(true),
+        };
+
+        if limiter.allow(now) {
+            return Some(true);
+        }
+
+        match self.kick_after {
+            Some(kick_after) if limiter.dropped >= kick_after => None,
+            _ => Some(false),
+        }
+    }
+
+    /// Whether the client has been silent for longer than the timeout at the given instant.
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_heartbeat) > self.timeout
+    }
+
+    /// Ping the client periodically, stopping the session once it stops responding. Stopping
+    /// notifies the server, which removes the client from its world. Timeouts shorter than the
+    /// ping interval are checked twice as often as they are long.
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let interval = HEARTBEAT_INTERVAL.min(self.timeout / 2);
+
+        ctx.run_interval(interval, |act, ctx| {
+            if act.is_timed_out(Instant::now()) {
+                info!("Client {} timed out, disconnecting.", act.id);
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+    }
 }
 
 impl Actor for WsSession {
@@ -28,6 +163,8 @@ impl Actor for WsSession {
     /// Method is called on actor start.
     /// We register ws session with ChatServer
     fn started(&mut self, ctx: &mut Self::Context) {
+        self.heartbeat(ctx);
+
         // register self in chat server. `AsyncContext::wait` register
         // future within context, but context waits until this future resolves
         // before processing any other events.
@@ -47,7 +184,10 @@ impl Actor for WsSession {
             .into_actor(self)
             .then(|res, act, ctx| {
                 match res {
-                    Ok(res) => act.id = res,
+                    Ok(res) => {
+                        act.id = res;
+                        act.join_world(ctx);
+                    }
                     // something is wrong with chat server
                     _ => ctx.stop(),
                 }
@@ -70,7 +210,7 @@ impl Handler<EncodedMessage> for WsSession {
     type Result = ();
 
     fn handle(&mut self, msg: EncodedMessage, ctx: &mut Self::Context) {
-        ctx.binary(msg.0);
+        ctx.binary(models::compress_message(msg.0, self.compression_threshold));
     }
 }
 
@@ -93,9 +233,41 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
             Ok(msg) => msg,
         };
 
+        self.last_heartbeat = Instant::now();
+
         match msg {
             ws::Message::Binary(bytes) => {
-                let message = models::decode_message(&bytes.to_vec()).unwrap();
+                match self.allow_message(Instant::now()) {
+                    Some(true) => {}
+                    Some(false) => return,
+                    None => {
+                        warn!("Client {} is flooding, disconnecting.", self.id);
+                        ctx.binary(models::encode_message(
+                            &Message::new(&MessageType::Error)
+                                .text("Too many messages.")
+                                .build(),
+                        ));
+                        ctx.stop();
+                        return;
+                    }
+                }
+
+                let message = match models::decode_message(&bytes) {
+                    Ok(message) => message,
+                    Err(error) => {
+                        warn!(
+                            "Client {} sent a malformed message, disconnecting: {}",
+                            self.id, error
+                        );
+                        ctx.close(Some(ws::CloseReason {
+                            code: ws::CloseCode::Invalid,
+                            description: Some("Malformed message.".to_owned()),
+                        }));
+                        ctx.stop();
+                        return;
+                    }
+                };
+
                 self.addr
                     .send(ClientMessage {
                         id: self.id.to_owned(),
@@ -119,6 +291,9 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
                     })
                     .wait(ctx);
             }
+            ws::Message::Ping(bytes) => {
+                ctx.pong(&bytes);
+            }
             ws::Message::Close(reason) => {
                 ctx.close(reason);
                 ctx.stop();
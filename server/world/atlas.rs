@@ -0,0 +1,844 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use libflate::zlib::Encoder;
+
+use log::warn;
+
+use crate::{AtlasError, Block, Registry, UV};
+
+const DEFAULT_PADDING: u32 = 2;
+const DEFAULT_MAX_DIMENSION: u32 = 4096;
+const MISSING_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// Whether two group or face names refer to the same thing, ignoring surrounding whitespace and
+/// ASCII case the same way the registry matches block names.
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// A single texture to be drawn onto a texture atlas, stored as raw RGBA pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Face {
+    /// Name of the face, usually the name of the `BlockFace` it textures.
+    pub name: String,
+
+    /// Width of the texture in pixels.
+    pub width: u32,
+
+    /// Height of the texture in pixels.
+    pub height: u32,
+
+    /// Row-major RGBA pixel data, `width * height * 4` bytes long.
+    pub pixels: Vec<u8>,
+}
+
+impl Face {
+    /// Create a face texture from raw RGBA pixels.
+    pub fn new(name: &str, width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "Face pixels do not match its dimensions."
+        );
+
+        Self {
+            name: name.to_owned(),
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Create a square face texture filled with a single color.
+    pub fn color(name: &str, size: u32, color: [u8; 4]) -> Self {
+        let pixels = color
+            .iter()
+            .copied()
+            .cycle()
+            .take((size * size * 4) as usize)
+            .collect();
+
+        Self::new(name, size, size, pixels)
+    }
+
+    /// Sample a pixel of this face, with the coordinates clamped to the face.
+    fn sample(&self, x: i64, y: i64) -> [u8; 4] {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        let index = (y * self.width as usize + x) * 4;
+
+        [
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+            self.pixels[index + 3],
+        ]
+    }
+}
+
+/// A region of the atlas in UV coordinates, from its top-left `(u0, v0)` to its bottom-right
+/// `(u1, v1)` corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRegion {
+    /// Starting u-coordinate.
+    pub u0: f32,
+
+    /// Starting v-coordinate.
+    pub v0: f32,
+
+    /// Ending u-coordinate.
+    pub u1: f32,
+
+    /// Ending v-coordinate.
+    pub v1: f32,
+}
+
+impl From<&UV> for UvRegion {
+    fn from(uv: &UV) -> Self {
+        Self {
+            u0: uv.start_u,
+            v0: uv.start_v,
+            u1: uv.end_u,
+            v1: uv.end_v,
+        }
+    }
+}
+
+/// A group of faces added to the atlas together, usually the faces of one block.
+#[derive(Debug, Clone)]
+pub struct AtlasGroup {
+    /// Name of the group.
+    pub name: String,
+
+    /// The faces of this group, in the order they were added.
+    pub faces: Vec<Face>,
+
+    /// Where each face ended up on its atlas page. Filled in by `generate`.
+    pub ranges: Vec<UV>,
+
+    /// The atlas page each face ended up on. Filled in by `generate`.
+    pub pages: Vec<usize>,
+
+    /// The number of animation frames in this group. Static groups have 1 frame.
+    pub frames: usize,
+
+    /// How many animation frames to cycle through per second. `None` if static.
+    pub fps: Option<f32>,
+
+    /// The top-left pixel of each face's tile on the atlas.
+    slots: Vec<(u32, u32)>,
+
+    /// The width and height of each face's tile on the atlas.
+    sizes: Vec<(u32, u32)>,
+
+    /// The face textured by each face index, so block faces sharing a texture share its tile.
+    indices: Vec<Option<usize>>,
+}
+
+impl AtlasGroup {
+    /// Get where a face of this group ended up on the atlas, by the face's name.
+    pub fn range(&self, face: &str) -> Option<&UV> {
+        self.faces
+            .iter()
+            .position(|f| same_name(&f.name, face))
+            .and_then(|index| self.ranges.get(index))
+    }
+
+    /// Get the page and where a face of this group ended up on it, by the face's index. For groups
+    /// added by block, this is the index of the block face.
+    pub fn range_at(&self, face_index: usize) -> Option<(usize, &UV)> {
+        let index = self.indices.get(face_index).copied().flatten()?;
+        Some((*self.pages.get(index)?, self.ranges.get(index)?))
+    }
+}
+
+/// A texture atlas packs face textures into one square texture, so that all block textures
+/// can be rendered together. Each tile is surrounded by a gutter of its own edge pixels to
+/// prevent neighboring textures from bleeding into each other. When the tiles don't fit within
+/// `max_dimension`, they spill over onto more pages of the same size, to be used as a texture
+/// array. Group and face names are matched ignoring case and surrounding whitespace.
+pub struct TextureAtlas {
+    /// The width and height faces are scaled to if `scale_faces` is set, and of the missing
+    /// texture tile, in pixels.
+    pub tile_size: u32,
+
+    /// Whether faces are scaled to `tile_size`. Off by default, so every face is packed at its own
+    /// resolution, letting detailed faces sit next to regular ones.
+    pub scale_faces: bool,
+
+    /// The groups of faces on this atlas.
+    pub groups: Vec<AtlasGroup>,
+
+    /// The minimum width of the gutter around each tile, in pixels. Default is 2 pixels.
+    pub padding: u32,
+
+    /// The max width and height of an atlas page, in pixels. Default is 4096 pixels.
+    pub max_dimension: u32,
+
+    /// The width and height of each atlas page, in pixels.
+    dimension: u32,
+
+    /// The generated mip chain of each page, level 0 being the full resolution page.
+    pages: Vec<Vec<Vec<u8>>>,
+
+    /// Where the magenta missing texture tile ended up, used for faces not on the atlas.
+    missing: Option<(usize, UV)>,
+
+    /// The width of the gutter the atlas was last generated with.
+    gutter: u32,
+
+    /// Bumped every time the atlas' pixels change.
+    version: u64,
+
+    /// Whether the pixels have changed since the atlas was last marked clean.
+    dirty: bool,
+
+    /// The missing groups and faces already warned about, so each is only reported once.
+    reported: Mutex<HashSet<String>>,
+}
+
+impl TextureAtlas {
+    /// Create a new texture atlas, with missing textures and scaled faces `tile_size` pixels wide.
+    pub fn new(tile_size: u32) -> Self {
+        Self {
+            tile_size,
+            scale_faces: false,
+            groups: vec![],
+            padding: DEFAULT_PADDING,
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            dimension: 0,
+            pages: vec![],
+            missing: None,
+            gutter: 0,
+            version: 0,
+            dirty: false,
+            reported: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Add a group of faces onto the atlas. Takes effect the next time the atlas is generated.
+    pub fn add_faces(&mut self, name: &str, faces: &[Face]) {
+        self.groups.retain(|group| !same_name(&group.name, name));
+
+        self.groups.push(AtlasGroup {
+            name: name.to_owned(),
+            faces: faces.to_vec(),
+            ranges: vec![],
+            pages: vec![],
+            frames: 1,
+            fps: None,
+            slots: vec![],
+            sizes: vec![],
+            indices: (0..faces.len()).map(Some).collect(),
+        });
+    }
+
+    /// Add the faces of a block onto the atlas, grouped under the block's name. Each block face is
+    /// textured by the face of the same name in `textures`, so blocks like grass can have distinct
+    /// top, bottom and side textures. Block faces sharing a texture name share a tile.
+    pub fn add_block(&mut self, block: &Block, textures: &[Face]) {
+        let mut faces: Vec<Face> = vec![];
+        let mut indices = vec![];
+
+        for block_face in block.faces.iter() {
+            if let Some(index) = faces
+                .iter()
+                .position(|face| same_name(&face.name, &block_face.name))
+            {
+                indices.push(Some(index));
+                continue;
+            }
+
+            if let Some(texture) = textures
+                .iter()
+                .find(|face| same_name(&face.name, &block_face.name))
+            {
+                indices.push(Some(faces.len()));
+                faces.push(texture.to_owned());
+            } else {
+                indices.push(None);
+            }
+        }
+
+        self.add_faces(&block.name, &faces);
+
+        if let Some(group) = self.groups.last_mut() {
+            group.indices = indices;
+        }
+    }
+
+    /// Add an animated group of faces onto the atlas. The frames are laid out next to each other,
+    /// and the client cycles through them at `fps` frames per second.
+    pub fn add_animated_faces(&mut self, name: &str, frames: &[Face], fps: f32) {
+        assert!(
+            !frames.is_empty(),
+            "Animated faces need at least one frame."
+        );
+
+        self.groups.retain(|group| !same_name(&group.name, name));
+
+        self.groups.push(AtlasGroup {
+            name: name.to_owned(),
+            faces: frames.to_vec(),
+            ranges: vec![],
+            pages: vec![],
+            frames: frames.len(),
+            fps: Some(fps),
+            slots: vec![],
+            sizes: vec![],
+            indices: (0..frames.len()).map(Some).collect(),
+        });
+    }
+
+    /// Generate the atlas without any mipmaps.
+    pub fn generate(&mut self) -> Result<(), AtlasError> {
+        self.generate_with_mipmaps(0)
+    }
+
+    /// Generate the atlas along with `levels` levels of mipmaps, each half the size of the last.
+    /// The atlas is the smallest power-of-two square that fits every tile, and groups are laid out
+    /// in the order of their names, followed by the missing texture tile. Once a page of
+    /// `max_dimension` is full, the rest of the tiles go onto the next page, though the frames of
+    /// an animated group always share a page. Tiles are surrounded by a gutter of their own edge
+    /// pixels, at least `padding` wide and rounded up to `2^levels`, so even the smallest mip
+    /// never mixes tiles. Errors if a tile, or an animation's frames, can't fit on a single page
+    /// of `max_dimension`, leaving the atlas as it was.
+    pub fn generate_with_mipmaps(&mut self, levels: u32) -> Result<(), AtlasError> {
+        let scale = 1 << levels;
+
+        // Lay the groups out by name, so the same faces always end up in the same place.
+        self.groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Runs of tiles to be kept on the same page, which is every frame of an animated group
+        // and any other tile on its own, followed by one more for the missing texture.
+        let runs = self
+            .groups
+            .iter()
+            .flat_map(|group| {
+                let sizes = group.faces.iter().map(|face| self.tile_dimensions(face));
+
+                if group.fps.is_some() {
+                    vec![sizes.collect::<Vec<_>>()]
+                } else {
+                    sizes.map(|size| vec![size]).collect()
+                }
+            })
+            .chain([vec![(self.tile_size, self.tile_size)]])
+            .collect::<Vec<_>>();
+        let sizes = runs.iter().flatten().copied().collect::<Vec<_>>();
+
+        assert!(
+            sizes
+                .iter()
+                .all(|(width, height)| width.is_multiple_of(scale) && height.is_multiple_of(scale)),
+            "Tile sizes should be divisible by 2^levels."
+        );
+
+        let padding = self.padding.max(1).next_multiple_of(scale);
+
+        // The smallest power-of-two square that fits every tile, or full pages if they don't.
+        let mut dimension = 1;
+        let placements = loop {
+            if dimension >= self.max_dimension {
+                break Self::pack(&runs, padding, self.max_dimension)
+                    .ok_or_else(|| self.oversized(padding))?;
+            }
+
+            if let Some(placements) = Self::pack(&runs, padding, dimension) {
+                if placements.iter().all(|(page, _)| *page == 0) {
+                    break placements;
+                }
+            }
+
+            dimension *= 2;
+        };
+        let dimension = dimension.min(self.max_dimension);
+
+        let page_count = placements.last().map_or(0, |(page, _)| page + 1);
+
+        let mut pages = vec![vec![0; (dimension * dimension * 4) as usize]; page_count];
+        let mut placed = placements.into_iter().zip(sizes);
+
+        for group in self.groups.iter_mut() {
+            group.ranges.clear();
+            group.pages.clear();
+            group.slots.clear();
+            group.sizes.clear();
+
+            for face in group.faces.iter() {
+                let ((page, start), size) = placed.next().unwrap();
+
+                Self::draw_face(&mut pages[page], dimension, face, start, size, padding);
+
+                group.ranges.push(Self::inset_uv(start, size, dimension));
+                group.pages.push(page);
+                group.slots.push(start);
+                group.sizes.push(size);
+            }
+        }
+
+        let ((page, start), size) = placed.next().unwrap();
+        let missing = Face::color("missing", self.tile_size, MISSING_COLOR);
+
+        Self::draw_face(&mut pages[page], dimension, &missing, start, size, padding);
+
+        self.missing = Some((page, Self::inset_uv(start, size, dimension)));
+
+        self.pages = pages
+            .into_iter()
+            .map(|pixels| {
+                let mut mipmaps = vec![pixels];
+                for level in 1..=levels {
+                    let last = mipmaps.last().unwrap();
+                    mipmaps.push(Self::downsample(last, dimension >> (level - 1)));
+                }
+                mipmaps
+            })
+            .collect();
+
+        self.dimension = dimension;
+        self.gutter = padding;
+        self.version += 1;
+        self.dirty = true;
+
+        Ok(())
+    }
+
+    /// Replace the texture of an existing face in place, keeping its tile and UVs. The face is
+    /// found by its name within the group, and the mipmaps are regenerated around it. Unless faces
+    /// are scaled, the new texture should be of the same resolution as the old one.
+    pub fn replace_face_texture(&mut self, name: &str, face: &Face) -> Result<(), AtlasError> {
+        if self.pages.is_empty() {
+            return Err(AtlasError::NotGenerated);
+        }
+
+        let scale_faces = self.scale_faces;
+
+        let group = self
+            .groups
+            .iter_mut()
+            .find(|group| same_name(&group.name, name))
+            .ok_or_else(|| AtlasError::GroupNotFound(name.to_owned()))?;
+
+        let index = group
+            .faces
+            .iter()
+            .position(|f| same_name(&f.name, &face.name))
+            .ok_or_else(|| AtlasError::FaceNotFound(name.to_owned(), face.name.to_owned()))?;
+
+        let start = group.slots[index];
+        let size = group.sizes[index];
+
+        if !scale_faces && (face.width, face.height) != size {
+            return Err(AtlasError::ResolutionMismatch(
+                name.to_owned(),
+                face.name.to_owned(),
+            ));
+        }
+
+        let mipmaps = &mut self.pages[group.pages[index]];
+        group.faces[index] = face.to_owned();
+
+        Self::draw_face(
+            &mut mipmaps[0],
+            self.dimension,
+            face,
+            start,
+            size,
+            self.gutter,
+        );
+
+        for level in 1..mipmaps.len() {
+            mipmaps[level] = Self::downsample(&mipmaps[level - 1], self.dimension >> (level - 1));
+        }
+
+        self.version += 1;
+        self.dirty = true;
+
+        Ok(())
+    }
+
+    /// A number that is bumped every time the atlas' pixels change, so clients know to refetch it.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Whether the atlas has changed since it was last marked clean.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark the atlas as clean, usually after clients have been notified of the changes.
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Get the width and height of each generated atlas page in pixels.
+    pub fn dimension(&self) -> u32 {
+        self.dimension
+    }
+
+    /// Get the number of pages generated. 0 if the atlas hasn't been generated.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Get the generated pixels of a mip level of the first page, level 0 being the full
+    /// resolution atlas.
+    pub fn mipmap(&self, level: u32) -> Option<&[u8]> {
+        self.page_mipmap(0, level)
+    }
+
+    /// Get the generated pixels of a mip level of a page, level 0 being the full resolution page.
+    pub fn page_mipmap(&self, page: usize, level: u32) -> Option<&[u8]> {
+        self.pages
+            .get(page)
+            .and_then(|mipmaps| mipmaps.get(level as usize))
+            .map(|pixels| pixels.as_slice())
+    }
+
+    /// Get the width and height of a mip level in pixels.
+    pub fn mip_dimension(&self, level: u32) -> u32 {
+        self.dimension >> level
+    }
+
+    /// Get the number of mip levels generated, including the full resolution atlas.
+    pub fn mip_levels(&self) -> u32 {
+        self.pages.first().map_or(0, |mipmaps| mipmaps.len() as u32)
+    }
+
+    /// Get every full resolution page as raw RGBA pixels, one page after another the way a texture
+    /// array is laid out. Empty if the atlas hasn't been generated.
+    pub fn to_rgba_bytes(&self) -> Vec<u8> {
+        (0..self.page_count())
+            .flat_map(|page| self.page_rgba_bytes(page))
+            .collect()
+    }
+
+    /// Get a full resolution page as raw RGBA pixels. Empty if the page hasn't been generated.
+    pub fn page_rgba_bytes(&self, page: usize) -> Vec<u8> {
+        self.page_mipmap(page, 0)
+            .map(|pixels| pixels.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Save every full resolution page as a PNG image, the pages stacked on top of each other from
+    /// the first page down. Errors if the atlas hasn't been generated.
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        if self.pages.is_empty() {
+            return Err(io::Error::other(
+                "Texture atlas has not been generated yet.",
+            ));
+        }
+
+        let height = self.dimension * self.page_count() as u32;
+        Self::write_png(path, &self.to_rgba_bytes(), self.dimension, height)
+    }
+
+    /// Save a full resolution page as a PNG image. Errors if the page hasn't been generated.
+    pub fn save_page_png(&self, page: usize, path: &Path) -> io::Result<()> {
+        let pixels = self
+            .page_mipmap(page, 0)
+            .ok_or_else(|| io::Error::other("Texture atlas page has not been generated yet."))?;
+
+        Self::write_png(path, pixels, self.dimension, self.dimension)
+    }
+
+    /// Write raw RGBA pixels out as a PNG image.
+    fn write_png(path: &Path, pixels: &[u8], width: u32, height: u32) -> io::Result<()> {
+        let row = (width * 4) as usize;
+
+        // Every scanline is prefixed with a filter type of 0, no filtering.
+        let mut scanlines = Vec::with_capacity((row + 1) * height as usize);
+        for line in pixels.chunks(row) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(line);
+        }
+
+        let mut encoder = Encoder::new(vec![])?;
+        encoder.write_all(&scanlines)?;
+        let compressed = encoder.finish().into_result()?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 8-bit depth, RGBA color, default compression, filtering and no interlacing.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut file = File::create(path)?;
+        file.write_all(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A])?;
+        Self::write_png_chunk(&mut file, b"IHDR", &header)?;
+        Self::write_png_chunk(&mut file, b"IDAT", &compressed)?;
+        Self::write_png_chunk(&mut file, b"IEND", &[])?;
+
+        Ok(())
+    }
+
+    /// Get the page and UV region of a face of a group, by the face's index. For groups added by
+    /// block, this is the index of the block face, which is how the mesher looks up the UVs of each
+    /// quad. Faces that aren't on the atlas get the missing texture tile, with a warning the first
+    /// time each is looked up. `None` if the atlas hasn't been generated yet.
+    pub fn get_uv(&self, group: &str, face_index: usize) -> Option<(usize, UvRegion)> {
+        let (page, uv) = match self.get_group(group) {
+            Some(atlas_group) => match atlas_group.range_at(face_index) {
+                Some(range) => range,
+                None => {
+                    if self.first_report(format!("{group}#{face_index}")) {
+                        warn!("Face {face_index} of \"{group}\" is not on the texture atlas.");
+                    }
+                    self.missing_range()?
+                }
+            },
+            None => {
+                if self.first_report(group.to_owned()) {
+                    warn!("Group \"{group}\" is not on the texture atlas.");
+                }
+                self.missing_range()?
+            }
+        };
+
+        Some((page, UvRegion::from(uv)))
+    }
+
+    /// Get the page and UV region of the magenta missing texture tile. `None` if the atlas hasn't
+    /// been generated yet.
+    pub fn missing_uv(&self) -> Option<(usize, UvRegion)> {
+        self.missing_range()
+            .map(|(page, uv)| (page, UvRegion::from(uv)))
+    }
+
+    /// List the names of the registry's blocks with faces textured by something not on the atlas,
+    /// which would otherwise be meshed with the missing texture. Empty blocks aren't meshed, and
+    /// faces that are independent or isolated are textured on their own, so neither are checked.
+    pub fn validate(&self, registry: &Registry) -> Vec<String> {
+        let mut invalid = registry
+            .blocks_by_id
+            .values()
+            .filter(|block| !block.is_empty)
+            .filter(|block| {
+                let group = self.get_group(&block.name);
+
+                block
+                    .faces
+                    .iter()
+                    .filter(|face| !face.independent && !face.isolated)
+                    .any(|face| group.and_then(|group| group.range(&face.name)).is_none())
+            })
+            .map(|block| block.name.to_owned())
+            .collect::<Vec<_>>();
+
+        invalid.sort();
+        invalid
+    }
+
+    /// Whether a missing group or face hasn't been warned about yet, marking it as reported.
+    fn first_report(&self, key: String) -> bool {
+        self.reported
+            .lock()
+            .map(|mut reported| reported.insert(key))
+            .unwrap_or(false)
+    }
+
+    fn missing_range(&self) -> Option<(usize, &UV)> {
+        self.missing.as_ref().map(|(page, uv)| (*page, uv))
+    }
+
+    /// Get the ranges of a group of faces on the atlas.
+    pub fn get_group(&self, name: &str) -> Option<&AtlasGroup> {
+        self.groups
+            .iter()
+            .find(|group| same_name(&group.name, name))
+    }
+
+    /// Get the width and height of a face's tile, which is its own resolution unless faces are
+    /// scaled to the tile size.
+    fn tile_dimensions(&self, face: &Face) -> (u32, u32) {
+        if self.scale_faces {
+            (self.tile_size, self.tile_size)
+        } else {
+            (face.width, face.height)
+        }
+    }
+
+    /// Pack runs of tiles of the given sizes row by row onto pages of `dimension` pixels, starting a
+    /// new row when one is full and a new page when the rows are, or when a run would straddle two
+    /// pages. Returns the page and top-left pixel of each tile, or `None` if a padded tile, or a
+    /// whole run, is larger than a page.
+    fn pack(
+        runs: &[Vec<(u32, u32)>],
+        padding: u32,
+        dimension: u32,
+    ) -> Option<Vec<(usize, (u32, u32))>> {
+        let mut placements = Vec::with_capacity(runs.iter().map(Vec::len).sum());
+
+        let mut page = 0;
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+        for run in runs {
+            let start = placements.len();
+            let cursor = (page, x, y, row_height);
+            let mut fresh = false;
+
+            loop {
+                for &(width, height) in run {
+                    let slot_width = width + padding * 2;
+                    let slot_height = height + padding * 2;
+
+                    if slot_width > dimension || slot_height > dimension {
+                        return None;
+                    }
+
+                    if x + slot_width > dimension {
+                        x = 0;
+                        y += row_height;
+                        row_height = 0;
+                    }
+
+                    if y + slot_height > dimension {
+                        page += 1;
+                        x = 0;
+                        y = 0;
+                        row_height = 0;
+                    }
+
+                    placements.push((page, (x + padding, y + padding)));
+
+                    x += slot_width;
+                    row_height = row_height.max(slot_height);
+                }
+
+                if placements[start..]
+                    .iter()
+                    .all(|(p, _)| *p == placements[start].0)
+                {
+                    break;
+                }
+
+                // The run straddles a page, so start it over on a page of its own.
+                if fresh {
+                    return None;
+                }
+
+                placements.truncate(start);
+                (page, x, y, row_height) = (cursor.0 + 1, 0, 0, 0);
+                fresh = true;
+            }
+        }
+
+        Some(placements)
+    }
+
+    /// Find what keeps the atlas from fitting within `max_dimension`, either a tile too large for
+    /// a page or an animated group whose frames don't fit on a page together.
+    fn oversized(&self, padding: u32) -> AtlasError {
+        let max = self.max_dimension;
+
+        for group in self.groups.iter() {
+            let run = group
+                .faces
+                .iter()
+                .map(|face| self.tile_dimensions(face))
+                .collect::<Vec<_>>();
+
+            for (face, (width, height)) in group.faces.iter().zip(run.iter()) {
+                if width + padding * 2 > max || height + padding * 2 > max {
+                    return AtlasError::TileTooLarge(group.name.to_owned(), face.name.to_owned());
+                }
+            }
+
+            if group.fps.is_some() && Self::pack(&[run], padding, max).is_none() {
+                return AtlasError::AnimationTooLarge(group.name.to_owned());
+            }
+        }
+
+        AtlasError::TileTooLarge("missing".to_owned(), "missing".to_owned())
+    }
+
+    /// Get the UVs of a tile, inset by half a texel so bilinear sampling never reaches into the
+    /// gutter.
+    fn inset_uv((start_x, start_y): (u32, u32), (width, height): (u32, u32), dimension: u32) -> UV {
+        UV {
+            start_u: (start_x as f32 + 0.5) / dimension as f32,
+            end_u: ((start_x + width) as f32 - 0.5) / dimension as f32,
+            start_v: (start_y as f32 + 0.5) / dimension as f32,
+            end_v: ((start_y + height) as f32 - 0.5) / dimension as f32,
+        }
+    }
+
+    /// Draw a face onto a tile, duplicating its edge pixels into the surrounding gutter.
+    fn draw_face(
+        pixels: &mut [u8],
+        dimension: u32,
+        face: &Face,
+        (start_x, start_y): (u32, u32),
+        (width, height): (u32, u32),
+        padding: u32,
+    ) {
+        let width = width as i64;
+        let height = height as i64;
+        let padding = padding as i64;
+
+        for ty in -padding..height + padding {
+            for tx in -padding..width + padding {
+                // Nearest-neighbor scale the face into the tile, a no-op unless faces are scaled.
+                let fx = tx.clamp(0, width - 1) * face.width as i64 / width;
+                let fy = ty.clamp(0, height - 1) * face.height as i64 / height;
+
+                let x = (start_x as i64 + tx) as u32;
+                let y = (start_y as i64 + ty) as u32;
+                let index = ((y * dimension + x) * 4) as usize;
+
+                pixels[index..index + 4].copy_from_slice(&face.sample(fx, fy));
+            }
+        }
+    }
+
+    /// Write a length-prefixed, CRC-suffixed PNG chunk.
+    fn write_png_chunk(file: &mut File, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        let mut crc = 0xFFFF_FFFFu32;
+        for &byte in kind.iter().chain(data.iter()) {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+
+        file.write_all(&(data.len() as u32).to_be_bytes())?;
+        file.write_all(kind)?;
+        file.write_all(data)?;
+        file.write_all(&(!crc).to_be_bytes())
+    }
+
+    /// Halve an image by averaging every 2x2 block of pixels.
+    fn downsample(pixels: &[u8], dimension: u32) -> Vec<u8> {
+        let half = dimension / 2;
+        let mut result = vec![0; (half * half * 4) as usize];
+
+        for y in 0..half {
+            for x in 0..half {
+                for channel in 0..4 {
+                    let mut sum = 0;
+
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let index = (((y * 2 + dy) * dimension + x * 2 + dx) * 4) as usize;
+                        sum += pixels[index + channel] as u32;
+                    }
+
+                    result[((y * half + x) * 4) as usize + channel] = (sum / 4) as u8;
+                }
+            }
+        }
+
+        result
+    }
+}
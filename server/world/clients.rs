@@ -1,4 +1,4 @@
-use actix::Recipient;
+use actix::{dev::SendError, Recipient};
 use hashbrown::HashMap;
 
 use specs::Entity;
@@ -19,6 +19,42 @@ pub struct Client {
 
     /// Address to the client
     pub addr: Recipient<EncodedMessage>,
+
+    /// Handshake fields the client supplied when joining.
+    pub metadata: HashMap<String, String>,
+
+    /// The number of messages in a row that failed to reach the client.
+    pub failed_sends: u32,
+}
+
+impl Client {
+    /// Send an encoded message to the client, keeping count of the failures in a row.
+    pub fn send(&mut self, message: EncodedMessage) -> Result<(), SendError<EncodedMessage>> {
+        let result = send_encoded(&self.addr, message);
+
+        if result.is_ok() {
+            self.failed_sends = 0;
+        } else {
+            self.failed_sends += 1;
+        }
+
+        result
+    }
+}
+
+/// Send an encoded message to an address, failing only if the receiving end has closed. A message
+/// to a full mailbox is still queued up to be delivered.
+pub fn send_encoded(
+    addr: &Recipient<EncodedMessage>,
+    message: EncodedMessage,
+) -> Result<(), SendError<EncodedMessage>> {
+    match addr.try_send(message) {
+        Err(SendError::Full(message)) => {
+            addr.do_send(message);
+            Ok(())
+        }
+        result => result,
+    }
 }
 
 pub type Clients = HashMap<String, Client>;
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+
+use super::World;
+
+/// A handler of a command, called with the world, the ID of the sender and the parsed arguments.
+pub type CommandHandle = Arc<dyn Fn(&mut World, &str, &[String]) + Send + Sync>;
+
+/// A registry of chat commands, such as `/tp 0 10 0`.
+#[derive(Default, Clone)]
+pub struct CommandRegistry {
+    handles: HashMap<String, CommandHandle>,
+}
+
+impl CommandRegistry {
+    /// Create an empty command registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a command by its name, without the command symbol. Names are case-insensitive.
+    pub fn register<F: Fn(&mut World, &str, &[String]) + Send + Sync + 'static>(
+        &mut self,
+        name: &str,
+        handle: F,
+    ) {
+        self.handles.insert(name.to_lowercase(), Arc::new(handle));
+    }
+
+    /// Check if a command has been registered.
+    pub fn has(&self, name: &str) -> bool {
+        self.handles.contains_key(&name.to_lowercase())
+    }
+
+    /// Get the handler of a command.
+    pub fn get(&self, name: &str) -> Option<CommandHandle> {
+        self.handles.get(&name.to_lowercase()).cloned()
+    }
+
+    /// Split a command, without the command symbol, into its lower-cased name and arguments.
+    pub fn parse(command: &str) -> Option<(String, Vec<String>)> {
+        let mut parts = command.split_whitespace();
+        let name = parts.next()?.to_lowercase();
+
+        Some((name, parts.map(|part| part.to_owned()).collect()))
+    }
+}
@@ -1,7 +1,12 @@
+use hashbrown::HashSet;
 use specs::{Component, VecStorage};
 
 use crate::Vec2;
 
+/// Chunks within this many chunks of the center are requested by distance alone, regardless of
+/// where the entity is facing.
+const FACING_RADIUS: i32 = 2;
+
 /// A list of chunks that the entity is requesting to generate.
 #[derive(Default, Component)]
 #[storage(VecStorage)]
@@ -10,6 +15,13 @@ pub struct ChunkRequestsComp {
     // a 2d unit vector
     pub direction: Vec2<f32>,
     pub requests: Vec<Vec2<i32>>,
+
+    /// Chunks that have been sent to the entity and not unloaded since.
+    pub loaded: HashSet<Vec2<i32>>,
+
+    /// The radius the entity wants chunks streamed within, clamped to `config.stream_radius`.
+    /// Defaults to the server's radius.
+    pub view_radius: Option<usize>,
 }
 
 impl ChunkRequestsComp {
@@ -28,6 +40,11 @@ impl ChunkRequestsComp {
         self.direction = direction.to_owned();
     }
 
+    /// Set the radius the entity wants chunks streamed within.
+    pub fn set_view_radius(&mut self, radius: usize) {
+        self.view_radius = Some(radius);
+    }
+
     /// Add a chunk to the list of chunks requested.
     pub fn add(&mut self, coords: &Vec2<i32>) {
         if self.requests.contains(coords) {
@@ -37,17 +54,35 @@ impl ChunkRequestsComp {
         self.requests.push(coords.to_owned());
     }
 
+    /// Sort the requested chunks by distance to the center. Further out chunks behind the
+    /// direction the entity is facing count as up to twice as far, so the ones in view come first.
     pub fn sort(&mut self) {
-        self.requests.sort_by(|a, b| {
-            let a_dist = (a.0 - self.center.0).abs() + (a.1 - self.center.1).abs();
-            let b_dist = (b.0 - self.center.0).abs() + (b.1 - self.center.1).abs();
+        let center = self.center.to_owned();
+        let Vec2(fx, fz) = self.direction;
+        let facing = (fx * fx + fz * fz).sqrt();
+
+        let priority = |coords: &Vec2<i32>| {
+            let dx = coords.0 - center.0;
+            let dz = coords.1 - center.1;
+            let dist = (dx.abs() + dz.abs()) as f32;
+
+            if dist <= FACING_RADIUS as f32 || facing == 0.0 {
+                return dist;
+            }
+
+            let (dx, dz) = (dx as f32, dz as f32);
+            let dot = (dx * fx + dz * fz) / ((dx * dx + dz * dz).sqrt() * facing);
+
+            dist * (1.5 - dot / 2.0)
+        };
 
-            a_dist.cmp(&b_dist)
-        });
+        self.requests
+            .sort_by(|a, b| priority(a).total_cmp(&priority(b)));
     }
 
     /// Remove a chunk from the list of chunks requested.
     pub fn remove(&mut self, coords: &Vec2<i32>) {
         self.requests.retain(|c| c != coords);
+        self.loaded.remove(coords);
     }
 }
@@ -1,3 +1,4 @@
+use log::warn;
 use serde::Serialize;
 
 use super::generators::NoiseOptions;
@@ -30,6 +31,9 @@ pub struct WorldConfig {
     /// Max height of the world. Default is 256 blocks high.
     pub max_height: usize,
 
+    /// The lowest voxel y-coordinate of the world, which may be negative. Default is 0.
+    pub min_y: i32,
+
     /// Max light level that light can propagate. Default is 15 blocks.
     pub max_light_level: u32,
 
@@ -97,6 +101,63 @@ pub struct WorldConfig {
 
     /// Whether entities should be saved. Only applies if `saving` is true.
     pub save_entities: bool,
+
+    /// The chunk distance from the nearest client at which chunks start being meshed at a lower
+    /// level of detail. Each multiple of this distance drops one more level. Default is 0, disabled.
+    pub lod_distance: usize,
+
+    /// Whether chunk meshes are sent with 16-bit quantized positions and UVs instead of floats,
+    /// about halving their size. Default is false.
+    pub quantize_geometry: bool,
+
+    /// Milliseconds between the ticks of this world, overriding the server's interval. Default is `None`.
+    pub tick_interval: Option<u64>,
+
+    /// The number of random voxels picked in each loaded chunk per tick for random ticking. Default is 3.
+    pub random_tick_speed: usize,
+
+    /// The ID of the block that chunks are filled with and treated as empty. Default is 0.
+    pub air_id: u32,
+
+    /// The chunk radius around each client within which chunks are streamed to it, and outside of
+    /// which they're unloaded. Default is 0, leaving it to the clients to request chunks.
+    pub stream_radius: usize,
+
+    /// The distance in blocks around each client within which entities are sent to it, entering and
+    /// leaving its view as they cross it. Default is 0, going by the chunks the client has loaded.
+    pub entity_radius: f32,
+
+    /// The most chunks the world keeps loaded at once. Past it, the least recently accessed chunks
+    /// that no client is using are saved and unloaded. Only applies when `saving` is on, so that
+    /// edits aren't lost. Default is 0, unbounded.
+    pub max_loaded_chunks: usize,
+
+    /// The most entries of `chunks.history` kept to be undone. Past it, the oldest ones are
+    /// dropped. Default is 100 entries.
+    pub max_history: usize,
+
+    /// The position that clients spawn at when they join. Default is [0.0, 0.0, 0.0].
+    pub spawn: [f32; 3],
+
+    /// Milliseconds to wait for a client to acknowledge a reliable message before resending it.
+    /// Default is 1000 milliseconds.
+    pub ack_timeout: u64,
+
+    /// The number of times a reliable message is resent before it's given up on. Default is 5
+    /// times.
+    pub max_ack_retries: u32,
+
+    /// The most reliable messages a client can leave unacknowledged. Past it, the oldest ones are
+    /// given up on. Default is 256 messages.
+    pub max_unacked: usize,
+
+    /// The number of messages in a row that can fail to reach a client before it's removed as
+    /// disconnected. Default is 5 messages.
+    pub max_send_failures: u32,
+
+    /// Milliseconds between the fixed updates of this world, which run as many times per tick as
+    /// needed to keep up with the time that has passed. Default is 16 milliseconds.
+    pub fixed_timestep: u64,
 }
 
 impl Default for WorldConfig {
@@ -145,6 +206,20 @@ const DEFAULT_SAVING: bool = false;
 const DEFAULT_SAVE_DIR: &str = "";
 const DEFAULT_SAVE_INTERVAL: usize = 300;
 const DEFAULT_COMMAND_SYMBOL: &str = "/";
+const DEFAULT_LOD_DISTANCE: usize = 0;
+const DEFAULT_RANDOM_TICK_SPEED: usize = 3;
+const DEFAULT_AIR_ID: u32 = 0;
+const DEFAULT_MIN_Y: i32 = 0;
+const DEFAULT_STREAM_RADIUS: usize = 0;
+const DEFAULT_ENTITY_RADIUS: f32 = 0.0;
+const DEFAULT_MAX_LOADED_CHUNKS: usize = 0;
+const DEFAULT_MAX_HISTORY: usize = 100;
+const DEFAULT_SPAWN: [f32; 3] = [0.0, 0.0, 0.0];
+const DEFAULT_ACK_TIMEOUT: u64 = 1000;
+pub(crate) const DEFAULT_MAX_ACK_RETRIES: u32 = 5;
+pub(crate) const DEFAULT_MAX_UNACKED: usize = 256;
+const DEFAULT_MAX_SEND_FAILURES: u32 = 5;
+const DEFAULT_FIXED_TIMESTEP: u64 = 16;
 
 /// Builder for a world configuration.
 pub struct WorldConfigBuilder {
@@ -156,6 +231,7 @@ pub struct WorldConfigBuilder {
     preload: bool,
     preload_radius: usize,
     max_height: usize,
+    min_y: i32,
     max_light_level: u32,
     max_chunks_per_tick: usize,
     max_updates_per_tick: usize,
@@ -179,6 +255,21 @@ pub struct WorldConfigBuilder {
     save_interval: usize,
     command_symbol: String,
     save_entities: bool,
+    lod_distance: usize,
+    quantize_geometry: bool,
+    tick_interval: Option<u64>,
+    random_tick_speed: usize,
+    air_id: u32,
+    stream_radius: usize,
+    entity_radius: f32,
+    max_loaded_chunks: usize,
+    max_history: usize,
+    spawn: [f32; 3],
+    ack_timeout: u64,
+    max_ack_retries: u32,
+    max_unacked: usize,
+    max_send_failures: u32,
+    fixed_timestep: u64,
 }
 
 impl WorldConfigBuilder {
@@ -195,6 +286,7 @@ impl WorldConfigBuilder {
             preload: DEFAULT_PRELOAD,
             preload_radius: DEFAULT_PRELOAD_RADIUS,
             max_height: DEFAULT_MAX_HEIGHT,
+            min_y: DEFAULT_MIN_Y,
             max_light_level: DEFAULT_MAX_LIGHT_LEVEL,
             max_chunks_per_tick: DEFAULT_MAX_CHUNKS_PER_TICK,
             max_updates_per_tick: DEFAULT_MAX_UPDATES_PER_TICK,
@@ -216,6 +308,21 @@ impl WorldConfigBuilder {
             terrain: NoiseOptions::default(),
             command_symbol: DEFAULT_COMMAND_SYMBOL.to_owned(),
             save_entities: true,
+            lod_distance: DEFAULT_LOD_DISTANCE,
+            quantize_geometry: false,
+            tick_interval: None,
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            air_id: DEFAULT_AIR_ID,
+            stream_radius: DEFAULT_STREAM_RADIUS,
+            entity_radius: DEFAULT_ENTITY_RADIUS,
+            max_loaded_chunks: DEFAULT_MAX_LOADED_CHUNKS,
+            max_history: DEFAULT_MAX_HISTORY,
+            spawn: DEFAULT_SPAWN,
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            max_ack_retries: DEFAULT_MAX_ACK_RETRIES,
+            max_unacked: DEFAULT_MAX_UNACKED,
+            max_send_failures: DEFAULT_MAX_SEND_FAILURES,
+            fixed_timestep: DEFAULT_FIXED_TIMESTEP,
         }
     }
 
@@ -271,6 +378,12 @@ impl WorldConfigBuilder {
         self
     }
 
+    /// Configure the lowest y-coordinate of the world, which may be negative. Default is 0.
+    pub fn min_y(mut self, min_y: i32) -> Self {
+        self.min_y = min_y;
+        self
+    }
+
     /// Configure the maximum light level that propagates the world. Default is 15 blocks.
     pub fn max_light_level(mut self, max_light_level: u32) -> Self {
         assert!(max_light_level < 16, "Max light level cannot be >= 16.");
@@ -382,6 +495,104 @@ impl WorldConfigBuilder {
         self
     }
 
+    /// Configure the chunk distance at which chunks start being meshed at a lower level of detail. Default is 0, disabled.
+    pub fn lod_distance(mut self, lod_distance: usize) -> Self {
+        self.lod_distance = lod_distance;
+        self
+    }
+
+    /// Configure whether chunk meshes are sent quantized to 16 bits. Default is false.
+    pub fn quantize_geometry(mut self, quantize_geometry: bool) -> Self {
+        self.quantize_geometry = quantize_geometry;
+        self
+    }
+
+    /// Configure the milliseconds between the ticks of this world, overriding the server's interval.
+    pub fn tick_interval(mut self, tick_interval: u64) -> Self {
+        self.tick_interval = Some(tick_interval);
+        self
+    }
+
+    /// Configure the number of random voxels ticked in each loaded chunk per tick. Default is 3.
+    pub fn random_tick_speed(mut self, random_tick_speed: usize) -> Self {
+        self.random_tick_speed = random_tick_speed;
+        self
+    }
+
+    /// Configure the ID of the block that chunks are filled with and treated as empty. Default is 0.
+    pub fn air_id(mut self, air_id: u32) -> Self {
+        self.air_id = air_id;
+        self
+    }
+
+    /// Configure the chunk radius around each client within which chunks are streamed to it. Default is 0, disabled.
+    pub fn stream_radius(mut self, stream_radius: usize) -> Self {
+        self.stream_radius = stream_radius;
+        self
+    }
+
+    /// Configure the distance in blocks around each client within which entities are sent to it.
+    /// Default is 0, going by loaded chunks.
+    pub fn entity_radius(mut self, entity_radius: f32) -> Self {
+        self.entity_radius = entity_radius;
+        self
+    }
+
+    /// Configure the most chunks the world keeps loaded at once. Has no effect unless `saving` is
+    /// on too, as evicted chunks are reloaded from their saves. Default is 0, unbounded.
+    pub fn max_loaded_chunks(mut self, max_loaded_chunks: usize) -> Self {
+        self.max_loaded_chunks = max_loaded_chunks;
+        self
+    }
+
+    /// Configure the most entries of `chunks.history` kept to be undone, past which the oldest
+    /// ones are dropped. Default is 100 entries.
+    pub fn max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history;
+        self
+    }
+
+    /// Configure the position that clients spawn at when they join. Default is [0.0, 0.0, 0.0].
+    pub fn spawn(mut self, spawn: [f32; 3]) -> Self {
+        self.spawn = spawn;
+        self
+    }
+
+    /// Configure the milliseconds to wait for a client to acknowledge a reliable message before
+    /// resending it. Default is 1000 milliseconds.
+    pub fn ack_timeout(mut self, ack_timeout: u64) -> Self {
+        self.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Configure the number of times a reliable message is resent before it's given up on.
+    /// Default is 5 times.
+    pub fn max_ack_retries(mut self, max_ack_retries: u32) -> Self {
+        self.max_ack_retries = max_ack_retries;
+        self
+    }
+
+    /// Configure the most reliable messages a client can leave unacknowledged, past which the
+    /// oldest ones are given up on. Default is 256 messages.
+    pub fn max_unacked(mut self, max_unacked: usize) -> Self {
+        self.max_unacked = max_unacked;
+        self
+    }
+
+    /// Configure the number of messages in a row that can fail to reach a client before it's
+    /// removed as disconnected. Default is 5 messages.
+    pub fn max_send_failures(mut self, max_send_failures: u32) -> Self {
+        self.max_send_failures = max_send_failures;
+        self
+    }
+
+    /// Configure the milliseconds between the fixed updates of this world. Default is 16
+    /// milliseconds.
+    pub fn fixed_timestep(mut self, fixed_timestep: u64) -> Self {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
     /// Create a world configuration.
     pub fn build(self) -> WorldConfig {
         // Make sure there are still chunks in the world.
@@ -389,19 +600,28 @@ impl WorldConfigBuilder {
             panic!("Min/max chunk options do not make sense.");
         }
 
-        if self.max_height % self.sub_chunks != 0 {
-            panic!("Max height should be divisible by sub-chunks.");
+        if self.min_y >= self.max_height as i32 {
+            panic!("Min y should be below the max height.");
+        }
+
+        if !((self.max_height as i32 - self.min_y) as usize).is_multiple_of(self.sub_chunks) {
+            panic!("The height from min y to max height should be divisible by sub-chunks.");
         }
 
         if !self.saving && !self.save_dir.is_empty() {
             panic!("Save directory shouldn't be used unless `config.save` is set to true!");
         }
 
+        if self.max_loaded_chunks != 0 && !self.saving {
+            warn!("Max loaded chunks has no effect unless saving is on, so no chunks are evicted.");
+        }
+
         WorldConfig {
             max_clients: self.max_clients,
             chunk_size: self.chunk_size,
             sub_chunks: self.sub_chunks,
             max_height: self.max_height,
+            min_y: self.min_y,
             max_light_level: self.max_light_level,
             max_chunks_per_tick: self.max_chunks_per_tick,
             max_updates_per_tick: self.max_updates_per_tick,
@@ -429,6 +649,21 @@ impl WorldConfigBuilder {
             save_interval: self.save_interval,
             command_symbol: self.command_symbol,
             save_entities: self.save_entities,
+            lod_distance: self.lod_distance,
+            quantize_geometry: self.quantize_geometry,
+            tick_interval: self.tick_interval,
+            random_tick_speed: self.random_tick_speed,
+            air_id: self.air_id,
+            stream_radius: self.stream_radius,
+            entity_radius: self.entity_radius,
+            max_loaded_chunks: self.max_loaded_chunks,
+            max_history: self.max_history,
+            spawn: self.spawn,
+            ack_timeout: self.ack_timeout,
+            max_ack_retries: self.max_ack_retries,
+            max_unacked: self.max_unacked,
+            max_send_failures: self.max_send_failures,
+            fixed_timestep: self.fixed_timestep,
         }
     }
 }
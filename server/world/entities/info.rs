@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{MetadataComp, Vec3};
+
+/// A snapshot of a game entity in the world, such as a mob, an item or a projectile, as returned
+/// by `world.entities`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityInfo {
+    /// The ID of the entity.
+    pub id: String,
+
+    /// The type of the entity, which picks the loader it was spawned with.
+    pub kind: String,
+
+    /// The position of the entity, or the origin if it has none.
+    pub position: Vec3<f32>,
+
+    /// The velocity of the entity's rigid body, or zero if it has none.
+    pub velocity: Vec3<f32>,
+
+    /// The metadata of the entity, as last sent to the clients.
+    pub metadata: MetadataComp,
+}
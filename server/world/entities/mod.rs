@@ -1,3 +1,5 @@
+mod info;
 mod saver;
 
+pub use info::*;
 pub use saver::*;
@@ -47,6 +47,7 @@ impl Lights {
     ) {
         let WorldConfig {
             max_height,
+            min_y,
             min_chunk,
             max_chunk,
             max_light_level,
@@ -76,7 +77,7 @@ impl Lights {
             for [ox, oy, oz] in &VOXEL_NEIGHBORS {
                 let nvy = vy + oy;
 
-                if nvy < 0 || nvy >= max_height {
+                if nvy < *min_y || nvy >= max_height {
                     continue;
                 }
 
@@ -120,11 +121,14 @@ impl Lights {
                     } else {
                         1
                     };
+                let next_level = next_level.saturating_sub(n_block.get_light_filter(color));
 
                 // To not continue:
                 // (1) Light cannot be flooded from source block to neighbor.
                 // (2) Neighbor light level is greater or equal to self.
-                if !Lights::can_enter(&source_transparency, &n_transparency, *ox, *oy, *oz)
+                // (3) The neighbor filters out all that's left of this light.
+                if next_level == 0
+                    || !Lights::can_enter(&source_transparency, &n_transparency, *ox, *oy, *oz)
                     || (if is_sunlight {
                         space.get_sunlight(nvx, nvy, nvz)
                     } else {
@@ -185,7 +189,7 @@ impl Lights {
             for [ox, oy, oz] in &VOXEL_NEIGHBORS {
                 let nvy = vy + oy;
 
-                if nvy < 0 || nvy >= max_height {
+                if nvy < config.min_y || nvy >= max_height {
                     continue;
                 }
 
@@ -258,6 +262,7 @@ impl Lights {
     ) -> [VecDeque<LightNode>; 4] {
         let &WorldConfig {
             max_height,
+            min_y,
             max_light_level,
             ..
         } = config;
@@ -272,7 +277,7 @@ impl Lights {
 
         let mut mask = vec![max_light_level; (shape.0 * shape.2) as usize];
 
-        for y in (0..max_height as i32).rev() {
+        for y in (min_y..max_height as i32).rev() {
             for x in 0..shape.0 {
                 for z in 0..shape.2 {
                     let id = space.get_voxel(x + start_x, y, z + start_z);
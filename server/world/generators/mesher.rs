@@ -1,14 +1,21 @@
-use std::{collections::VecDeque, sync::Arc, time::Instant};
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use hashbrown::{HashMap, HashSet};
-use log::info;
+use log::trace;
 use rayon::{iter::IntoParallelIterator, prelude::ParallelIterator, ThreadPool, ThreadPoolBuilder};
 
 use crate::{
-    world::generators::lights::VOXEL_NEIGHBORS, Block, BlockFace, BlockRotation, Chunk, CornerData,
-    GeometryProtocol, LightColor, LightUtils, MeshProtocol, MessageType, Neighbors, Registry,
-    Space, Vec2, Vec3, VoxelAccess, WorldConfig, AABB, UV,
+    world::generators::lights::VOXEL_NEIGHBORS, BiomeMap, Block, BlockFace, BlockRotation, Chunk,
+    CornerData, GeometryProtocol, LightColor, LightUtils, MeshProtocol, MessageType, Neighbors,
+    Registry, Space, Vec2, Vec3, VoxelAccess, WorldConfig, AABB, UV,
 };
 
 use super::lights::Lights;
@@ -58,6 +65,15 @@ pub struct Mesher {
 
     /// The thread pool for meshing.
     pool: ThreadPool,
+
+    /// The number of sub-chunk meshes reused because nothing they depend on has changed.
+    cache_hits: Arc<AtomicUsize>,
+
+    /// The number of chunks handed to the mesher to be meshed.
+    processed: usize,
+
+    /// Whether the mesher has been drained, after which new chunks aren't meshed.
+    draining: bool,
 }
 
 impl Mesher {
@@ -76,9 +92,22 @@ impl Mesher {
                 .num_threads(64)
                 .build()
                 .unwrap(),
+            cache_hits: Arc::new(AtomicUsize::new(0)),
+            processed: 0,
+            draining: false,
         }
     }
 
+    /// The number of sub-chunk meshes reused instead of being remeshed.
+    pub fn cache_hits(&self) -> usize {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of chunks that have been handed to the mesher to be meshed.
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
+
     /// Add a chunk to be meshed.
     pub fn add_chunk(&mut self, coords: &Vec2<i32>, prioritized: bool) {
         if self.map.contains(coords) {
@@ -110,6 +139,34 @@ impl Mesher {
         self.queue.pop_front()
     }
 
+    /// Check to see if the mesher has no queued or meshing chunks.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty() && self.queue.is_empty()
+    }
+
+    /// Drop the queued chunks and block until the chunks being meshed are done, discarding them.
+    /// Chunks handed to the mesher afterwards are discarded without being meshed.
+    pub fn drain(&mut self) {
+        self.draining = true;
+        self.queue.clear();
+
+        while !self.map.is_empty() {
+            let coords = match self.receiver.recv() {
+                Ok((chunk, _)) => chunk.coords,
+                Err(_) => break,
+            };
+
+            if let Some(count) = self.skips.remove(&coords) {
+                if count > 0 {
+                    self.skips.insert(coords, count - 1);
+                    continue;
+                }
+            }
+
+            self.remove_chunk(&coords);
+        }
+    }
+
     /// Mesh a set of chunks.
     pub fn process(
         &mut self,
@@ -118,6 +175,10 @@ impl Mesher {
         registry: &Registry,
         config: &WorldConfig,
     ) {
+        if self.draining {
+            return;
+        }
+
         processes.iter().for_each(|(chunk, _)| {
             if self.map.contains(&chunk.coords) {
                 let curr_count = self.skips.remove(&chunk.coords).unwrap_or(0);
@@ -127,10 +188,14 @@ impl Mesher {
             self.map.insert(chunk.coords.to_owned());
         });
 
+        self.processed += processes.len();
+
         let sender = Arc::clone(&self.sender);
+        let cache_hits = Arc::clone(&self.cache_hits);
         let r#type = r#type.clone();
         let registry = Arc::new(registry.clone());
         let config = Arc::new(config.clone());
+        let biomes = BiomeMap::new(config.seed);
 
         self.pool.spawn(move || {
             processes
@@ -198,32 +263,78 @@ impl Mesher {
                         }
 
                         chunk.lights = space.get_lights(coords.0, coords.1).unwrap().clone();
+                        chunk.rehash();
                     }
 
+                    let key = Mesher::mesh_key(&chunk, &space);
+
                     for level in sub_chunks {
+                        let has_mesh = chunk
+                            .meshes
+                            .as_ref()
+                            .is_some_and(|meshes| meshes.contains_key(&level));
+
+                        if has_mesh && chunk.mesh_keys.get(&level) == Some(&key) {
+                            cache_hits.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+
+                        chunk.mesh_keys.insert(level, key);
+
                         let level = level as i32;
 
                         let min = Vec3(min_x, min_y + level * blocks_per_sub_chunk, min_z);
                         let max = Vec3(max_x, min_y + (level + 1) * blocks_per_sub_chunk, max_z);
 
-                        let geometries = Mesher::mesh_space(&min, &max, &space, &registry);
+                        // Chunks of only air have nothing to mesh, such as the chunks up in the sky.
+                        let geometries = if chunk.is_empty() {
+                            vec![]
+                        } else {
+                            Mesher::mesh_space_lod(
+                                &min,
+                                &max,
+                                &space,
+                                &registry,
+                                chunk.lod,
+                                Some(&biomes),
+                            )
+                        };
 
-                        chunk
-                            .meshes
-                            .get_or_insert_with(HashMap::new)
-                            .insert(level as u32, MeshProtocol { level, geometries });
+                        chunk.meshes.get_or_insert_with(HashMap::new).insert(
+                            level as u32,
+                            MeshProtocol {
+                                level,
+                                geometries,
+                                quantized: config.quantize_geometry,
+                            },
+                        );
                     }
 
-                    sender.send((chunk, r#type.clone())).unwrap();
+                    trace!("Chunk {:?} has been meshed.", coords);
+
+                    // Nobody is listening if the mesher was dropped while meshing, such as when
+                    // its world shuts down, so the result can be thrown away.
+                    let _ = sender.send((chunk, r#type.clone()));
                 });
         });
     }
 
     /// Attempt to retrieve the results from `mesher.process`
     pub fn results(&mut self) -> Vec<(Chunk, MessageType)> {
+        self.results_limited(usize::MAX)
+    }
+
+    /// Retrieve at most `max` results from `mesher.process` in the order they were done, leaving
+    /// the rest to be retrieved later. Skipped results do not count towards `max`.
+    pub fn results_limited(&mut self, max: usize) -> Vec<(Chunk, MessageType)> {
         let mut results = Vec::new();
 
-        while let Ok(result) = self.receiver.try_recv() {
+        while results.len() < max {
+            let result = match self.receiver.try_recv() {
+                Ok(result) => result,
+                Err(_) => break,
+            };
+
             if !self.map.contains(&result.0.coords) {
                 continue;
             }
@@ -242,12 +353,64 @@ impl Mesher {
         results
     }
 
-    /// Mesh this space and separate individual block types into their own meshes.
+    /// The number of results that are done, but haven't been retrieved yet.
+    pub fn done_count(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// A key of everything a chunk's mesh depends on: its own content, its level of detail, and the
+    /// ring of voxels and lights right outside of it.
+    fn mesh_key(chunk: &Chunk, space: &Space) -> u64 {
+        let Vec3(min_x, min_y, min_z) = chunk.min;
+        let Vec3(max_x, max_y, max_z) = chunk.max;
+
+        let mut hasher = DefaultHasher::new();
+        chunk.content_hash().hash(&mut hasher);
+        chunk.lod.hash(&mut hasher);
+
+        for vx in (min_x - 1)..=max_x {
+            for vz in (min_z - 1)..=max_z {
+                if vx >= min_x && vx < max_x && vz >= min_z && vz < max_z {
+                    continue;
+                }
+
+                for vy in min_y..max_y {
+                    space.get_raw_voxel(vx, vy, vz).hash(&mut hasher);
+                    space.get_raw_light(vx, vy, vz).hash(&mut hasher);
+                }
+            }
+        }
+
+        hasher.finish()
+    }
+
+    /// The color of a block's vertices at a voxel column, its tint multiplied by the color of the
+    /// column's biome if it's biome tinted. Returns `None` for untinted blocks, which are white.
+    fn voxel_color(block: &Block, vx: i32, vz: i32, biomes: Option<&BiomeMap>) -> Option<[f32; 4]> {
+        if !block.is_tinted() {
+            return None;
+        }
+
+        let [mut r, mut g, mut b, a] = block.tint;
+
+        if let (true, Some(biomes)) = (block.biome_tint, biomes) {
+            let [br, bg, bb] = biomes.biome_at(vx, vz).color();
+            r *= br;
+            g *= bg;
+            b *= bb;
+        }
+
+        Some([r, g, b, a])
+    }
+
+    /// Mesh this space and separate individual block types into their own meshes. Tinted blocks
+    /// color their vertices, by the biomes of `biomes` too if given.
     pub fn mesh_space(
         min: &Vec3<i32>,
         max: &Vec3<i32>,
         space: &dyn VoxelAccess,
         registry: &Registry,
+        biomes: Option<&BiomeMap>,
     ) -> Vec<GeometryProtocol> {
         let mut map: HashMap<String, GeometryProtocol> = HashMap::new();
 
@@ -256,7 +419,7 @@ impl Mesher {
 
         for vx in min_x..max_x {
             for vz in min_z..max_z {
-                let height = space.get_max_height(vx, vz) as i32;
+                let height = space.min_y() + space.get_max_height(vx, vz) as i32;
 
                 if min_y > height {
                     continue;
@@ -268,21 +431,12 @@ impl Mesher {
                     let rotation = space.get_voxel_rotation(vx, vy, vz);
                     let block = registry.get_block_by_id(voxel_id);
 
-                    let Block {
-                        id,
-                        is_see_through,
-                        is_empty,
-                        is_opaque,
-                        name,
-                        ..
-                    } = block.to_owned();
-
-                    if is_empty {
+                    if block.is_empty {
                         continue;
                     }
 
                     // Skip blocks that are completely covered with opaque blocks.
-                    if is_opaque {
+                    if block.is_opaque {
                         if !(VOXEL_NEIGHBORS
                             .into_iter()
                             .find(|&[x, y, z]| {
@@ -302,58 +456,230 @@ impl Mesher {
                         }
                     }
 
-                    let faces = block.get_faces(&Vec3(vx, vy, vz), space, registry);
-                    let uv_map = registry.get_uv_map(block);
+                    // Waterlogged voxels mesh their fluid on top of the block itself.
+                    let mut layers = vec![(block, voxel_id, rotation)];
 
-                    faces.iter().enumerate().for_each(|(idx, face)| {
-                        let key = if face.isolated {
-                            format!(
-                                "{}::{}::{}-{}-{}",
-                                name.to_lowercase(),
-                                face.name.to_lowercase(),
+                    if block.can_be_waterlogged
+                        && !block.is_fluid
+                        && space.is_waterlogged(vx, vy, vz)
+                    {
+                        if let Some(fluid) = registry.get_waterlog_fluid() {
+                            layers.push((fluid, fluid.id, BlockRotation::default()));
+                        }
+                    }
+
+                    for &(block, voxel_id, ref rotation) in layers.iter() {
+                        let &Block {
+                            id,
+                            is_see_through,
+                            ref name,
+                            ..
+                        } = block;
+
+                        let faces = block.get_faces(&Vec3(vx, vy, vz), space, registry);
+                        let uv_map = registry.get_uv_map(block);
+                        let color = Mesher::voxel_color(block, vx, vz, biomes);
+
+                        faces.iter().for_each(|face| {
+                            let key = if face.isolated {
+                                format!(
+                                    "{}::{}::{}-{}-{}",
+                                    name.to_lowercase(),
+                                    face.name.to_lowercase(),
+                                    vx,
+                                    vy,
+                                    vz
+                                )
+                            } else if face.independent {
+                                format!("{}::{}", name.to_lowercase(), face.name.to_lowercase())
+                            } else {
+                                name.to_lowercase()
+                            };
+
+                            let mut geometry = map.remove(&key).unwrap_or_default();
+
+                            geometry.voxel = id;
+
+                            if face.independent || face.isolated {
+                                geometry.face_name = Some(face.name.to_owned());
+                            }
+
+                            if face.isolated {
+                                geometry.at = vec![vx, vy, vz];
+                            }
+
+                            let vertices = geometry.positions.len() / 3;
+
+                            Mesher::process_face(
                                 vx,
                                 vy,
-                                vz
+                                vz,
+                                voxel_id,
+                                rotation,
+                                face,
+                                block,
+                                &uv_map,
+                                registry,
+                                space,
+                                is_see_through,
+                                &mut geometry.positions,
+                                &mut geometry.indices,
+                                &mut geometry.uvs,
+                                &mut geometry.lights,
+                                min,
+                            );
+
+                            if let Some(color) = color {
+                                for _ in vertices..geometry.positions.len() / 3 {
+                                    geometry.colors.extend_from_slice(&color);
+                                }
+                            }
+
+                            map.insert(key, geometry);
+                        });
+                    }
+                }
+            }
+        }
+
+        map.into_iter()
+            .map(|(_, geometry)| geometry)
+            .filter(|geometry| !geometry.indices.is_empty())
+            .collect()
+    }
+
+    /// Mesh this space at a level of detail. Each cell of `2^lod` voxels wide is sampled for its most
+    /// common block, and cells are meshed as if they were one big block. LOD 0 is full resolution.
+    pub fn mesh_space_lod(
+        min: &Vec3<i32>,
+        max: &Vec3<i32>,
+        space: &dyn VoxelAccess,
+        registry: &Registry,
+        lod: u8,
+        biomes: Option<&BiomeMap>,
+    ) -> Vec<GeometryProtocol> {
+        if lod == 0 {
+            return Mesher::mesh_space(min, max, space, registry, biomes);
+        }
+
+        let step = 1 << lod;
+        let mut map: HashMap<String, GeometryProtocol> = HashMap::new();
+
+        let &Vec3(min_x, min_y, min_z) = min;
+        let &Vec3(max_x, max_y, max_z) = max;
+
+        for cx in (min_x..max_x).step_by(step as usize) {
+            for cz in (min_z..max_z).step_by(step as usize) {
+                let mut height = space.min_y();
+                for vx in cx..cx + step {
+                    for vz in cz..cz + step {
+                        height = height.max(space.min_y() + space.get_max_height(vx, vz) as i32);
+                    }
+                }
+
+                if min_y > height {
+                    continue;
+                }
+
+                for cy in (min_y..max_y.min(height + 1)).step_by(step as usize) {
+                    let id = match Mesher::sample_cell(cx, cy, cz, step, space, registry) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+
+                    let block = registry.get_block_by_id(id);
+                    let uv_map = registry.get_uv_map(block);
+                    let color = Mesher::voxel_color(block, cx, cz, biomes);
+
+                    for face in block.faces.iter() {
+                        if face.isolated {
+                            continue;
+                        }
+
+                        let [dx, dy, dz] = face.dir;
+
+                        // Only axis-aligned faces make sense on a coarse cell.
+                        if dx.abs() + dy.abs() + dz.abs() != 1 {
+                            continue;
+                        }
+
+                        let nx = cx + dx * step;
+                        let ny = cy + dy * step;
+                        let nz = cz + dz * step;
+
+                        if let Some(n_id) = Mesher::sample_cell(nx, ny, nz, step, space, registry) {
+                            let n_block = registry.get_block_by_id(n_id);
+                            if n_block.is_opaque || n_id == id {
+                                continue;
+                            }
+                        }
+
+                        let key = if face.independent {
+                            format!(
+                                "{}::{}",
+                                block.name.to_lowercase(),
+                                face.name.to_lowercase()
                             )
-                        } else if face.independent {
-                            format!("{}::{}", name.to_lowercase(), face.name.to_lowercase())
                         } else {
-                            name.to_lowercase()
+                            block.name.to_lowercase()
                         };
 
                         let mut geometry = map.remove(&key).unwrap_or_default();
 
                         geometry.voxel = id;
 
-                        if face.independent || face.isolated {
+                        if face.independent {
                             geometry.face_name = Some(face.name.to_owned());
                         }
 
-                        if face.isolated {
-                            geometry.at = vec![vx, vy, vz];
+                        let UV {
+                            start_u,
+                            end_u,
+                            start_v,
+                            end_v,
+                        } = uv_map.get(&face.name).unwrap();
+
+                        // Sample the light right outside of the face.
+                        let light = space.get_raw_light(
+                            cx + if dx > 0 { step } else { dx },
+                            cy + if dy > 0 { step } else { dy },
+                            cz + if dz > 0 { step } else { dz },
+                        );
+
+                        let ndx = (geometry.positions.len() / 3) as i32;
+
+                        for CornerData { pos, uv } in face.corners.iter() {
+                            geometry
+                                .positions
+                                .push((cx - min_x) as f32 + pos[0] * step as f32);
+                            geometry
+                                .positions
+                                .push((cy - min_y) as f32 + pos[1] * step as f32);
+                            geometry
+                                .positions
+                                .push((cz - min_z) as f32 + pos[2] * step as f32);
+
+                            geometry.uvs.push(uv[0] * (end_u - start_u) + start_u);
+                            geometry.uvs.push(uv[1] * (end_v - start_v) + start_v);
+
+                            geometry.lights.push(light as i32 | 3 << 16);
+
+                            if let Some(color) = color {
+                                geometry.colors.extend_from_slice(&color);
+                            }
                         }
 
-                        Mesher::process_face(
-                            vx,
-                            vy,
-                            vz,
-                            voxel_id,
-                            &rotation,
-                            face,
-                            block,
-                            &uv_map,
-                            &registry,
-                            space,
-                            is_see_through,
-                            &mut geometry.positions,
-                            &mut geometry.indices,
-                            &mut geometry.uvs,
-                            &mut geometry.lights,
-                            min,
-                        );
+                        geometry.indices.extend_from_slice(&[
+                            ndx,
+                            ndx + 1,
+                            ndx + 2,
+                            ndx + 2,
+                            ndx + 1,
+                            ndx + 3,
+                        ]);
 
                         map.insert(key, geometry);
-                    });
+                    }
                 }
             }
         }
@@ -364,6 +690,43 @@ impl Mesher {
             .collect()
     }
 
+    /// Sample the dominant block of a LOD cell. Returns `None` if less than half the cell is filled.
+    fn sample_cell(
+        cx: i32,
+        cy: i32,
+        cz: i32,
+        step: i32,
+        space: &dyn VoxelAccess,
+        registry: &Registry,
+    ) -> Option<u32> {
+        let mut counts: HashMap<u32, i32> = HashMap::new();
+        let mut filled = 0;
+
+        for vx in cx..cx + step {
+            for vy in cy..cy + step {
+                for vz in cz..cz + step {
+                    let id = space.get_voxel(vx, vy, vz);
+
+                    if registry.get_block_by_id(id).is_empty {
+                        continue;
+                    }
+
+                    filled += 1;
+                    *counts.entry(id).or_insert(0) += 1;
+                }
+            }
+        }
+
+        if filled * 2 < step * step * step {
+            return None;
+        }
+
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(id, _)| id)
+    }
+
     #[inline]
     fn process_face(
         vx: i32,
@@ -426,7 +789,7 @@ impl Mesher {
         //    1. the neighbor is void or empty (air or DNE)
         // b. see_through mode
         //    1. itself is see-through (water & leaves)
-        //       - if the neighbor is the same, then mesh if standalone (leaves).
+        //       - if the neighbor is the same, then mesh if standalone (grass), cull otherwise (water).
         //       - not the same, and one is see-through, then mesh (leaves + water or leaves + stick).
         //       - not the same, and the bounding boxes do not intersect, then mesh.
         // c. opaque mode
@@ -436,16 +799,24 @@ impl Mesher {
             || (see_through
                 && !is_opaque
                 && !n_block_type.is_opaque
-                && ((is_see_through
-                    && neighbor_id == voxel_id
-                    && n_block_type.transparent_standalone)
+                && ((is_see_through && neighbor_id == voxel_id && block.transparent_standalone)
                     || (neighbor_id != voxel_id
                         && (is_see_through || n_block_type.is_see_through))
                     || ({
                         if is_see_through && !is_opaque && n_block_type.is_opaque {
-                            let block_aabbs = block.get_aabbs(&Vec3(vx, vy, vz), space, registry);
+                            let block_aabbs = block
+                                .get_aabbs(&Vec3(vx, vy, vz), space, registry)
+                                .iter()
+                                .map(|aabb| aabb.rotate(rotation))
+                                .collect::<Vec<_>>();
+                            let n_rotation = space.get_voxel_rotation(nvx, nvy, nvz);
+                            let n_block_aabbs = n_block_type
+                                .aabbs
+                                .iter()
+                                .map(|aabb| aabb.rotate(&n_rotation))
+                                .collect::<Vec<_>>();
                             let self_bounding = AABB::union(&block_aabbs);
-                            let mut n_bounding = AABB::union(&n_block_type.aabbs);
+                            let mut n_bounding = AABB::union(&n_block_aabbs);
                             n_bounding.translate(dir[0] as f32, dir[1] as f32, dir[2] as f32);
                             !(self_bounding.intersects(&n_bounding)
                                 || self_bounding.touches(&n_bounding))
@@ -4,6 +4,7 @@ mod mesher;
 mod noise;
 mod pipeline;
 mod spline;
+mod structures;
 mod terrain;
 mod trees;
 
@@ -13,5 +14,6 @@ pub use lsystem::*;
 pub use mesher::Mesher;
 pub use pipeline::*;
 pub use spline::SplineMap;
+pub use structures::*;
 pub use terrain::*;
 pub use trees::*;
@@ -1,13 +1,22 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
 use hashbrown::{HashMap, HashSet};
+use log::{trace, warn};
 use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 use rayon::{ThreadPool, ThreadPoolBuilder};
 
 use crate::{
-    Chunk, ChunkStatus, Registry, Space, SpaceData, Terrain, Vec2, Vec3, VoxelAccess, VoxelUpdate,
-    WorldConfig,
+    BiomeKind, BiomeMap, Chunk, ChunkStatus, NoiseOptions, Registry, SeededNoise, Space, SpaceData,
+    Terrain, Vec2, Vec3, VoxelAccess, VoxelUpdate, WorldConfig,
 };
 
 #[derive(Clone)]
@@ -16,6 +25,25 @@ pub struct Resources<'a> {
     pub config: &'a WorldConfig,
 }
 
+impl Resources<'_> {
+    /// Get a random number generator seeded by the world's seed, the chunk's coordinates and the
+    /// stage's name. A stage always gets the same random stream for the same chunk, regardless of
+    /// the order chunks are generated in.
+    pub fn rng(&self, coords: &Vec2<i32>, stage: &str) -> fastrand::Rng {
+        // FNV-1a hash of the stage's name, so stages don't share the same stream.
+        let name = stage.bytes().fold(0xCBF2_9CE4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        });
+
+        let seed = (self.config.seed as u64).wrapping_mul(73856093)
+            ^ (coords.0 as i64 as u64).wrapping_mul(19349663)
+            ^ (coords.1 as i64 as u64).wrapping_mul(83492791)
+            ^ name;
+
+        fastrand::Rng::with_seed(seed)
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct MetaStage {
     pub stages: Vec<Arc<dyn ChunkStage + Send + Sync>>,
@@ -39,7 +67,7 @@ impl ChunkStage for MetaStage {
     fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
         for stage in &self.stages {
             chunk = stage.process(chunk, resources.clone(), None);
-            chunk.calculate_max_height(&resources.registry);
+            chunk.calculate_max_height();
         }
 
         chunk
@@ -204,6 +232,323 @@ impl ChunkStage for BaseTerrainStage {
     }
 }
 
+/// A preset chunk stage to generate rolling hills from the world's seed. Columns are filled with
+/// stone and topped with grass, or sand in deserts if set, and anything below the sea level is
+/// filled with water.
+pub struct TerrainStage {
+    amplitude: f64,
+    frequency: f64,
+    sea_level: i32,
+    stone: u32,
+    grass: u32,
+    water: u32,
+    sand: Option<u32>,
+}
+
+impl TerrainStage {
+    pub fn new(stone: u32, grass: u32, water: u32) -> Self {
+        Self {
+            amplitude: 12.0,
+            frequency: 0.01,
+            sea_level: 32,
+            stone,
+            grass,
+            water,
+            sand: None,
+        }
+    }
+
+    /// Set how many blocks the hills can rise or sink from the sea level. Defaults to 12.
+    pub fn set_amplitude(&mut self, amplitude: f64) {
+        self.amplitude = amplitude;
+    }
+
+    /// Set how frequently the hills are sampled. The bigger, the more condensed. Defaults to 0.01.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Set the height up to which water is filled. Defaults to 32.
+    pub fn set_sea_level(&mut self, sea_level: i32) {
+        self.sea_level = sea_level;
+    }
+
+    /// Set the block to top desert biomes with instead of grass. Defaults to none, grass everywhere.
+    pub fn set_sand(&mut self, sand: u32) {
+        self.sand = Some(sand);
+    }
+}
+
+impl ChunkStage for TerrainStage {
+    fn name(&self) -> String {
+        "Terrain".to_owned()
+    }
+
+    fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
+        let Vec3(min_x, _, min_z) = chunk.min;
+        let Vec3(max_x, _, max_z) = chunk.max;
+
+        let noise = SeededNoise::new(
+            resources.config.seed,
+            &NoiseOptions::new()
+                .frequency(self.frequency)
+                .octaves(4)
+                .persistence(0.5)
+                .lacunarity(2.0)
+                .build(),
+        );
+        let max_height = resources.config.max_height as i32;
+        let biomes = self.sand.map(|_| BiomeMap::new(resources.config.seed));
+
+        for vx in min_x..max_x {
+            for vz in min_z..max_z {
+                let top = match (&biomes, self.sand) {
+                    (Some(biomes), Some(sand)) if biomes.biome_at(vx, vz) == BiomeKind::Desert => {
+                        sand
+                    }
+                    _ => self.grass,
+                };
+
+                let value = noise.get2d(vx, vz).clamp(-1.0, 1.0);
+                let height = (self.sea_level as f64 + value * self.amplitude).round() as i32;
+                let height = height.clamp(1, max_height - 1);
+
+                for vy in 0..height {
+                    chunk.set_voxel(vx, vy, vz, self.stone);
+                }
+
+                if height > self.sea_level {
+                    chunk.set_voxel(vx, height, vz, top);
+                } else {
+                    for vy in height..=self.sea_level.min(max_height - 1) {
+                        chunk.set_voxel(vx, vy, vz, self.water);
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+}
+
+/// A preset chunk stage to carve caves out of existing terrain using the world's seed. Caves are
+/// sampled in world space, so they continue seamlessly across chunk borders.
+pub struct CaveStage {
+    threshold: f64,
+    frequency: f64,
+    surface_margin: i32,
+    floor: i32,
+}
+
+impl Default for CaveStage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CaveStage {
+    pub fn new() -> Self {
+        Self {
+            threshold: 0.4,
+            frequency: 0.04,
+            surface_margin: 4,
+            floor: 1,
+        }
+    }
+
+    /// Set the noise value above which voxels are carved. The bigger, the rarer caves are. Defaults to 0.4.
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold;
+    }
+
+    /// Set how frequently caves are sampled. The bigger, the smaller the caves. Defaults to 0.04.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Set how many blocks under the surface are left uncarved. Defaults to 4.
+    pub fn set_surface_margin(&mut self, surface_margin: i32) {
+        self.surface_margin = surface_margin;
+    }
+
+    /// Set how many layers of bedrock at the bottom of the world are left uncarved. Defaults to 1.
+    pub fn set_floor(&mut self, floor: i32) {
+        self.floor = floor;
+    }
+}
+
+impl ChunkStage for CaveStage {
+    fn name(&self) -> String {
+        "Caves".to_owned()
+    }
+
+    fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
+        let Vec3(min_x, _, min_z) = chunk.min;
+        let Vec3(max_x, _, max_z) = chunk.max;
+
+        let noise = SeededNoise::new(
+            resources.config.seed,
+            &NoiseOptions::new()
+                .dimension(3)
+                .frequency(self.frequency)
+                .octaves(2)
+                .persistence(0.5)
+                .lacunarity(2.0)
+                .build(),
+        );
+
+        for vx in min_x..max_x {
+            for vz in min_z..max_z {
+                let surface = chunk.get_height(vx, vz) - self.surface_margin;
+
+                for vy in self.floor..surface {
+                    let id = chunk.get_voxel(vx, vy, vz);
+
+                    if chunk.is_air(vx, vy, vz) || resources.registry.get_block_by_id(id).is_fluid {
+                        continue;
+                    }
+
+                    if noise.get3d(vx, vy, vz) > self.threshold {
+                        chunk.set_voxel(vx, vy, vz, chunk.options.air_id);
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+}
+
+/// A vein of ore to be scattered through stone by the `OreStage`.
+#[derive(Clone, Debug)]
+pub struct OreVein {
+    /// The block ID of the ore.
+    pub id: u32,
+
+    /// How many veins are attempted per chunk. Fractions are the chance of one extra vein.
+    pub rarity: f32,
+
+    /// The lowest voxel y-coordinate this ore can be placed at.
+    pub min_y: i32,
+
+    /// The highest voxel y-coordinate this ore can be placed at.
+    pub max_y: i32,
+
+    /// How many voxels each vein tries to spread to.
+    pub vein_size: usize,
+}
+
+/// A preset chunk stage to scatter veins of ores through stone. Placement is seeded through
+/// `resources.rng`, so regenerating a chunk yields the same ores.
+pub struct OreStage {
+    stone: u32,
+    ores: Vec<OreVein>,
+}
+
+impl OreStage {
+    pub fn new(stone: u32) -> Self {
+        Self {
+            stone,
+            ores: vec![],
+        }
+    }
+
+    pub fn add_ore(
+        mut self,
+        id: u32,
+        rarity: f32,
+        min_y: i32,
+        max_y: i32,
+        vein_size: usize,
+    ) -> Self {
+        self.ores.push(OreVein {
+            id,
+            rarity,
+            min_y,
+            max_y,
+            vein_size,
+        });
+
+        self
+    }
+}
+
+impl ChunkStage for OreStage {
+    fn name(&self) -> String {
+        "Ores".to_owned()
+    }
+
+    fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
+        let Vec3(min_x, _, min_z) = chunk.min;
+        let Vec3(max_x, _, max_z) = chunk.max;
+
+        let mut rng = resources.rng(&chunk.coords, &self.name());
+
+        let max_height = resources.config.max_height as i32 - 1;
+
+        for ore in &self.ores {
+            let min_y = ore.min_y.max(0);
+            let max_y = ore.max_y.min(max_height);
+
+            if min_y > max_y {
+                continue;
+            }
+
+            let mut veins = ore.rarity.floor() as usize;
+            if rng.f32() < ore.rarity.fract() {
+                veins += 1;
+            }
+
+            for _ in 0..veins {
+                let mut vx = rng.i32(min_x..max_x);
+                let mut vy = rng.i32(min_y..=max_y);
+                let mut vz = rng.i32(min_z..max_z);
+
+                for _ in 0..ore.vein_size {
+                    if vx >= min_x
+                        && vx < max_x
+                        && vz >= min_z
+                        && vz < max_z
+                        && vy >= min_y
+                        && vy <= max_y
+                        && chunk.get_voxel(vx, vy, vz) == self.stone
+                    {
+                        chunk.set_voxel(vx, vy, vz, ore.id);
+                    }
+
+                    match rng.u8(0..3) {
+                        0 => vx += if rng.bool() { 1 } else { -1 },
+                        1 => vy += if rng.bool() { 1 } else { -1 },
+                        _ => vz += if rng.bool() { 1 } else { -1 },
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+}
+
+/// A snapshot of how busy a pipeline is.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineStats {
+    /// Chunks waiting in the queue to be processed.
+    pub pending: usize,
+
+    /// Chunks being processed, or done and waiting to be retrieved.
+    pub in_flight: usize,
+
+    /// Chunks retrieved from the pipeline through its last stage since it was created.
+    pub completed: usize,
+
+    /// The average time a chunk stage takes to process a chunk.
+    pub average_time: Duration,
+}
+
+/// The number of times a chunk is generated before it is given up on and left empty, when its
+/// stages keep panicking.
+pub const MAX_GENERATION_ATTEMPTS: usize = 3;
+
 /// A pipeline is strictly for holding the stages necessary to build the chunks.
 pub struct Pipeline {
     /// A list of stages that chunks are in.
@@ -218,32 +563,57 @@ pub struct Pipeline {
     /// A map of leftover changes from processing chunk stages.
     pub(crate) leftovers: HashMap<Vec2<i32>, Vec<VoxelUpdate>>,
 
+    /// The number of times each chunk's stages have panicked, until it gets through the pipeline.
+    pub(crate) failures: HashMap<Vec2<i32>, usize>,
+
     /// Sender of processed chunks from other threads to main thread.
     sender: Arc<Sender<(Chunk, Vec<VoxelUpdate>)>>,
 
     /// Receiver to receive processed chunks from other threads to main thread.
     receiver: Arc<Receiver<(Chunk, Vec<VoxelUpdate>)>>,
 
+    /// Sender of chunks whose stage panicked, along with the error.
+    failure_sender: Arc<Sender<(Vec2<i32>, String)>>,
+
+    /// Receiver of chunks whose stage panicked, along with the error.
+    failure_receiver: Arc<Receiver<(Vec2<i32>, String)>>,
+
+    /// The number of chunks retrieved from this pipeline through its last stage.
+    completed: usize,
+
+    /// The number of chunks processed by the stages, and the total nanoseconds it took.
+    processed: Arc<(AtomicU64, AtomicU64)>,
+
     /// Pipeline's thread pool to process chunks.
     pool: ThreadPool,
+
+    /// Whether the pipeline has been drained, after which new chunks aren't processed.
+    draining: bool,
 }
 
 impl Pipeline {
     /// Create a new chunk pipeline.
     pub fn new() -> Self {
         let (sender, receiver) = unbounded();
+        let (failure_sender, failure_receiver) = unbounded();
 
         Self {
             sender: Arc::new(sender),
             receiver: Arc::new(receiver),
+            failure_sender: Arc::new(failure_sender),
+            failure_receiver: Arc::new(failure_receiver),
+            completed: 0,
+            processed: Arc::new((AtomicU64::new(0), AtomicU64::new(0))),
             pool: ThreadPoolBuilder::new()
                 .thread_name(|index| format!("voxelize-chunking-{index}"))
                 .build()
                 .unwrap(),
             chunks: HashSet::new(),
             leftovers: HashMap::new(),
+            failures: HashMap::new(),
             queue: VecDeque::new(),
             stages: Vec::new(),
+            draining: false,
         }
     }
 
@@ -268,6 +638,17 @@ impl Pipeline {
         self.queue.retain(|c| c != coords);
     }
 
+    /// Cancel a chunk that is queued or being processed, discarding its result once it's done.
+    /// Returns false if the chunk isn't in the pipeline.
+    pub fn cancel_chunk(&mut self, coords: &Vec2<i32>) -> bool {
+        if !self.has_chunk(coords) && !self.queue.contains(coords) {
+            return false;
+        }
+
+        self.remove_chunk(coords);
+        true
+    }
+
     /// Check to see if a chunk coordinate is in the pipeline.
     pub fn has_chunk(&self, coords: &Vec2<i32>) -> bool {
         self.chunks.contains(coords)
@@ -278,6 +659,28 @@ impl Pipeline {
         self.queue.pop_front()
     }
 
+    /// Check to see if the pipeline has no queued or processing chunks.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.queue.is_empty()
+    }
+
+    /// Drop the queued chunks and block until the chunks being processed are done, discarding them.
+    /// Chunks handed to the pipeline afterwards are discarded without being processed.
+    pub fn drain(&mut self) {
+        self.draining = true;
+        self.queue.clear();
+
+        while !self.chunks.is_empty() {
+            match self.receiver.recv() {
+                Ok((chunk, _)) => self.remove_chunk(&chunk.coords),
+                Err(_) => break,
+            }
+        }
+
+        self.leftovers.clear();
+        self.failures.clear();
+    }
+
     /// Add a stage to the chunking pipeline.
     pub fn add_stage<T>(&mut self, stage: T)
     where
@@ -294,6 +697,10 @@ impl Pipeline {
         registry: &Registry,
         config: &WorldConfig,
     ) {
+        if self.draining {
+            return;
+        }
+
         processes.iter().for_each(|(chunk, _)| {
             self.chunks.insert(chunk.coords.to_owned());
         });
@@ -314,6 +721,8 @@ impl Pipeline {
             .collect();
 
         let sender = Arc::clone(&self.sender);
+        let failure_sender = Arc::clone(&self.failure_sender);
+        let processed = Arc::clone(&self.processed);
         let registry = registry.to_owned();
         let config = config.to_owned();
 
@@ -323,29 +732,72 @@ impl Pipeline {
                 .enumerate()
                 .for_each(|(_, (chunk, space, stage))| {
                     let sender = Arc::clone(&sender);
+                    let failure_sender = Arc::clone(&failure_sender);
+                    let processed = Arc::clone(&processed);
                     let registry = registry.clone();
                     let config = config.clone();
 
                     rayon::spawn_fifo(move || {
                         let mut changes = vec![];
-
-                        let mut chunk = stage.process(
-                            chunk,
-                            Resources {
-                                registry: &registry,
-                                config: &config,
-                            },
-                            space,
+                        let coords = chunk.coords.to_owned();
+                        let start = Instant::now();
+
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                            stage.process(
+                                chunk,
+                                Resources {
+                                    registry: &registry,
+                                    config: &config,
+                                },
+                                space,
+                            )
+                        }));
+
+                        processed.0.fetch_add(1, Ordering::Relaxed);
+                        processed
+                            .1
+                            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+                        let mut chunk = match result {
+                            Ok(chunk) => chunk,
+                            Err(payload) => {
+                                let reason = payload
+                                    .downcast_ref::<&str>()
+                                    .map(|reason| reason.to_string())
+                                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                                    .unwrap_or_default();
+                                let error = format!(
+                                    "Stage \"{}\" panicked on chunk {:?}: {}",
+                                    stage.name(),
+                                    coords,
+                                    reason
+                                );
+
+                                warn!("{}", error);
+
+                                // Nobody is listening for failures if the pipeline was dropped
+                                // while generating, so the report can be thrown away.
+                                let _ = failure_sender.send((coords, error));
+                                return;
+                            }
+                        };
+
+                        trace!(
+                            "Chunk {:?} went through stage \"{}\" in {:?}.",
+                            coords,
+                            stage.name(),
+                            start.elapsed()
                         );
 
                         // Calculate the max height after processing each chunk.
-                        chunk.calculate_max_height(&registry);
+                        chunk.calculate_max_height();
 
                         if !chunk.extra_changes.is_empty() {
                             changes.append(&mut chunk.extra_changes.drain(..).collect());
                         }
 
-                        sender.send((chunk, changes)).unwrap();
+                        // Nobody is listening if the pipeline was dropped while generating.
+                        let _ = sender.send((chunk, changes));
                     });
                 });
         });
@@ -353,18 +805,68 @@ impl Pipeline {
 
     /// Attempt to retrieve the results from `pipeline.process`
     pub fn results(&mut self) -> Vec<(Chunk, Vec<VoxelUpdate>)> {
+        self.results_limited(usize::MAX)
+    }
+
+    /// Retrieve at most `max` results from `pipeline.process` in the order they were done, leaving
+    /// the rest to be retrieved later.
+    pub fn results_limited(&mut self, max: usize) -> Vec<(Chunk, Vec<VoxelUpdate>)> {
         let mut results = Vec::new();
 
-        while let Ok(result) = self.receiver.try_recv() {
+        while results.len() < max {
+            let result = match self.receiver.try_recv() {
+                Ok(result) => result,
+                Err(_) => break,
+            };
+
             if self.chunks.contains(&result.0.coords) {
                 self.remove_chunk(&result.0.coords);
                 results.push(result);
             }
         }
 
+        let last = self.stages.len().saturating_sub(1);
+        self.completed += results
+            .iter()
+            .filter(|(chunk, _)| matches!(chunk.status, ChunkStatus::Generating(index) if index >= last))
+            .count();
+
         results
     }
 
+    /// Retrieve the chunks whose stages panicked since the last call, along with their errors.
+    /// These chunks are removed from the pipeline, so that they can be added back to retry.
+    pub fn failed_chunks(&mut self) -> Vec<(Vec2<i32>, String)> {
+        let mut failures = Vec::new();
+
+        while let Ok((coords, error)) = self.failure_receiver.try_recv() {
+            if self.chunks.contains(&coords) {
+                self.remove_chunk(&coords);
+                failures.push((coords, error));
+            }
+        }
+
+        failures
+    }
+
+    /// The number of results that are done, but haven't been retrieved yet.
+    pub fn done_count(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// Get a snapshot of the pipeline's queue depth and processing times.
+    pub fn stats(&self) -> PipelineStats {
+        let count = self.processed.0.load(Ordering::Relaxed);
+        let total = self.processed.1.load(Ordering::Relaxed);
+
+        PipelineStats {
+            pending: self.queue.len(),
+            in_flight: self.chunks.len(),
+            completed: self.completed,
+            average_time: Duration::from_nanos(total.checked_div(count).unwrap_or(0)),
+        }
+    }
+
     /// Merge consecutive chunk stages that don't require spaces together into meta stages.
     pub(crate) fn merge_stages(&mut self) {
         let mut new_stages: Vec<Arc<dyn ChunkStage + Send + Sync>> = vec![];
@@ -0,0 +1,256 @@
+use std::sync::Mutex;
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::{Chunk, ChunkStage, ChunkUtils, Resources, Space, Vec2, Vec3, VoxelAccess};
+
+/// A template of voxels to be stamped into the world, such as a tree or a small hut. Voxels
+/// with an ID of 0 are left untouched when the template is placed.
+#[derive(Clone, Debug)]
+pub struct StructureTemplate {
+    /// Name of the structure.
+    pub name: String,
+
+    /// Width, height and depth of the template.
+    pub size: Vec3<usize>,
+
+    /// The voxel within the template that sits right above the surface it's placed on.
+    pub anchor: Vec3<i32>,
+
+    /// Structures with a higher priority are placed over the ones with a lower priority.
+    pub priority: i32,
+
+    /// Block IDs of the template, in x, z, then y order.
+    voxels: Vec<u32>,
+}
+
+impl StructureTemplate {
+    /// Create an empty template of a certain size.
+    pub fn new(name: &str, size: &Vec3<usize>, anchor: &Vec3<i32>) -> Self {
+        Self {
+            name: name.to_owned(),
+            size: size.to_owned(),
+            anchor: anchor.to_owned(),
+            priority: 0,
+            voxels: vec![0; size.0 * size.1 * size.2],
+        }
+    }
+
+    /// Set the priority of this structure.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set a voxel of the template.
+    pub fn voxel(mut self, x: usize, y: usize, z: usize, id: u32) -> Self {
+        let index = self.index(x, y, z);
+        self.voxels[index] = id;
+        self
+    }
+
+    /// Fill an inclusive box of the template with a block.
+    pub fn fill(mut self, min: &Vec3<usize>, max: &Vec3<usize>, id: u32) -> Self {
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    self = self.voxel(x, y, z, id);
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Get a voxel of the template.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.voxels[self.index(x, y, z)]
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + z * self.size.0 + y * self.size.0 * self.size.2
+    }
+}
+
+/// A preset chunk stage to place structures on the surface of the terrain. Positions are seeded
+/// through `resources.rng`. Voxels overhanging into a neighboring chunk are held onto until that
+/// chunk has its structures placed, and stamped along with its own structures in order of priority.
+/// If the neighbor already had its structures placed, they are deferred through
+/// `chunk.extra_changes` unless a structure of a higher or the same priority was stamped there.
+#[derive(Default)]
+pub struct StructureStage {
+    structures: Vec<(StructureTemplate, f32)>,
+    overhangs: Mutex<Overhangs>,
+}
+
+/// The priority, voxel and block ID of a voxel stamped by a structure.
+type Stamp = (i32, Vec3<i32>, u32);
+
+/// Voxels of structures that overhang into neighboring chunks, and what they might overwrite. These
+/// are only held in memory, so the voxels waiting on a chunk are lost if the server stops first.
+#[derive(Default)]
+struct Overhangs {
+    /// Chunk coordinates -> voxels waiting for that chunk to have its structures placed.
+    pending: HashMap<Vec2<i32>, Vec<Stamp>>,
+
+    /// Chunk coordinates -> voxel -> priority of the structure voxels stamped in that chunk, kept
+    /// until every chunk that could overhang into it has had its structures placed.
+    stamped: HashMap<Vec2<i32>, HashMap<Vec3<i32>, i32>>,
+
+    /// The chunks that have had their structures placed, kept until the chunks around them no
+    /// longer need to check on them. Only the edge of the generated area is remembered.
+    placed: HashSet<Vec2<i32>>,
+}
+
+impl StructureStage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a structure to be placed, attempted `rarity` times per chunk. Fractions are the chance
+    /// of one extra attempt.
+    pub fn add_structure(mut self, template: StructureTemplate, rarity: f32) -> Self {
+        self.structures.push((template, rarity));
+        self
+    }
+
+    /// How many chunks away the structures can overhang into.
+    fn reach(&self, chunk_size: usize) -> i32 {
+        let size = self
+            .structures
+            .iter()
+            .map(|(template, _)| template.size.0.max(template.size.2))
+            .max()
+            .unwrap_or(0);
+
+        size.div_ceil(chunk_size) as i32
+    }
+}
+
+impl ChunkStage for StructureStage {
+    fn name(&self) -> String {
+        "Structures".to_owned()
+    }
+
+    fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
+        let Vec3(min_x, _, min_z) = chunk.min;
+        let Vec3(max_x, _, max_z) = chunk.max;
+        let chunk_size = resources.config.chunk_size;
+
+        let mut rng = resources.rng(&chunk.coords, &self.name());
+
+        let mut stamps: Vec<Stamp> = vec![];
+
+        for (template, rarity) in &self.structures {
+            let mut attempts = rarity.floor() as usize;
+            if rng.f32() < rarity.fract() {
+                attempts += 1;
+            }
+
+            for _ in 0..attempts {
+                let vx = rng.i32(min_x..max_x);
+                let vz = rng.i32(min_z..max_z);
+                let vy = chunk.get_height(vx, vz);
+
+                let surface = resources
+                    .registry
+                    .get_block_by_id(chunk.get_voxel(vx, vy, vz));
+                if surface.is_empty || surface.is_fluid {
+                    continue;
+                }
+
+                let Vec3(ax, ay, az) = template.anchor;
+                let Vec3(width, height, depth) = template.size;
+
+                for x in 0..width {
+                    for y in 0..height {
+                        for z in 0..depth {
+                            let id = template.get(x, y, z);
+
+                            if id == 0 {
+                                continue;
+                            }
+
+                            let voxel = Vec3(
+                                vx + x as i32 - ax,
+                                vy + 1 + y as i32 - ay,
+                                vz + z as i32 - az,
+                            );
+
+                            stamps.push((template.priority, voxel, id));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut overhangs = self.overhangs.lock().unwrap();
+        let Overhangs {
+            pending,
+            stamped,
+            placed,
+        } = &mut *overhangs;
+
+        // The neighbors' voxels go first, so that this chunk's own structures win ties.
+        if let Some(mut incoming) = pending.remove(&chunk.coords) {
+            incoming.append(&mut stamps);
+            stamps = incoming;
+        }
+        placed.insert(chunk.coords.to_owned());
+
+        // Lower priorities go first, so that higher priorities are stamped over them.
+        stamps.sort_by_key(|(priority, _, _)| *priority);
+
+        for (priority, voxel, id) in stamps {
+            let Vec3(vx, vy, vz) = voxel;
+            let coords = ChunkUtils::map_voxel_to_chunk(vx, vy, vz, chunk_size);
+
+            if coords != chunk.coords && !placed.contains(&coords) {
+                pending
+                    .entry(coords)
+                    .or_default()
+                    .push((priority, voxel, id));
+                continue;
+            }
+
+            let priorities = stamped.entry(coords.to_owned()).or_default();
+
+            if coords != chunk.coords
+                && priorities
+                    .get(&voxel)
+                    .is_some_and(|&stamped| stamped >= priority)
+            {
+                continue;
+            }
+
+            priorities.insert(voxel, priority);
+            chunk.set_voxel(vx, vy, vz, id);
+        }
+
+        let reach = self.reach(chunk_size);
+        let around = |coords: &Vec2<i32>, reach: i32| {
+            let Vec2(cx, cz) = *coords;
+            (-reach..=reach)
+                .flat_map(move |dx| (-reach..=reach).map(move |dz| Vec2(cx + dx, cz + dz)))
+        };
+        let settled = |coords: &Vec2<i32>| around(coords, reach).all(|n| placed.contains(&n));
+
+        // Forget what was stamped in the chunks that nothing can overhang into anymore.
+        for coords in around(&chunk.coords, reach) {
+            if settled(&coords) {
+                stamped.remove(&coords);
+            }
+        }
+
+        // Forget the chunks that were placed once every chunk that checks on them is settled too.
+        let forgotten = around(&chunk.coords, reach * 2)
+            .filter(|coords| around(coords, reach).all(|n| settled(&n)))
+            .collect::<Vec<_>>();
+
+        for coords in forgotten {
+            placed.remove(&coords);
+        }
+
+        chunk
+    }
+}
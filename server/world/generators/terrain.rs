@@ -25,6 +25,77 @@ impl Biome {
     }
 }
 
+/// The climate-based kinds of biomes a `BiomeMap` can classify a column into.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BiomeKind {
+    Desert,
+    Plains,
+    Forest,
+    Tundra,
+}
+
+impl BiomeKind {
+    /// The RGB color that blocks with `biome_tint`, like grass and leaves, are tinted in this biome.
+    pub fn color(&self) -> [f32; 3] {
+        match self {
+            Self::Desert => [0.75, 0.68, 0.4],
+            Self::Plains => [0.55, 0.78, 0.35],
+            Self::Forest => [0.38, 0.64, 0.25],
+            Self::Tundra => [0.5, 0.7, 0.6],
+        }
+    }
+}
+
+/// A seeded map of biomes, classifying each column by its temperature and humidity.
+#[derive(Clone)]
+pub struct BiomeMap {
+    temperature: SeededNoise,
+    humidity: SeededNoise,
+}
+
+impl BiomeMap {
+    /// Create a biome map from a world seed.
+    pub fn new(seed: u32) -> Self {
+        let options = NoiseOptions::new()
+            .frequency(0.002)
+            .octaves(3)
+            .persistence(0.5)
+            .lacunarity(2.0)
+            .build();
+
+        Self {
+            temperature: SeededNoise::new(seed, &options),
+            humidity: SeededNoise::new(seed.wrapping_add(1), &options),
+        }
+    }
+
+    /// Get the temperature at a voxel column, from -1.0 to 1.0.
+    pub fn temperature_at(&self, vx: i32, vz: i32) -> f64 {
+        self.temperature.get2d(vx, vz).clamp(-1.0, 1.0)
+    }
+
+    /// Get the humidity at a voxel column, from -1.0 to 1.0.
+    pub fn humidity_at(&self, vx: i32, vz: i32) -> f64 {
+        self.humidity.get2d(vx, vz).clamp(-1.0, 1.0)
+    }
+
+    /// Get the kind of biome at a voxel column.
+    pub fn biome_at(&self, vx: i32, vz: i32) -> BiomeKind {
+        let temperature = self.temperature_at(vx, vz);
+        let humidity = self.humidity_at(vx, vz);
+
+        if temperature < -0.3 {
+            BiomeKind::Tundra
+        } else if temperature > 0.3 && humidity < 0.0 {
+            BiomeKind::Desert
+        } else if humidity > 0.3 {
+            BiomeKind::Forest
+        } else {
+            BiomeKind::Plains
+        }
+    }
+}
+
 /// A seeded layered terrain for Voxelize world generation.
 #[derive(Clone)]
 pub struct Terrain {
@@ -0,0 +1,67 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Block, Stats, StatsJson, Vec3, World};
+
+/// The world information sent to a client in its `Init` message, everything the client needs to
+/// set up its renderer before any chunk arrives.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldInitData {
+    /// The ID of the client being initialized.
+    pub id: String,
+
+    /// The name of the world the client joined.
+    pub name: String,
+
+    /// The horizontal dimension of the chunks in the world.
+    pub chunk_size: usize,
+
+    /// The max height of the world.
+    pub max_height: usize,
+
+    /// The number of sub chunks a chunk is divided into.
+    pub sub_chunks: usize,
+
+    /// The seed of the world.
+    pub seed: u32,
+
+    /// The position the client spawns at.
+    pub spawn: Vec3<f32>,
+
+    /// Every block of the world's registry, by name.
+    pub blocks: HashMap<String, Block>,
+
+    /// The full configuration of the world.
+    pub options: Value,
+
+    /// The world's stats at the time the client joined.
+    pub stats: StatsJson,
+
+    /// The token the client can join with to resume its session after disconnecting, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+}
+
+impl WorldInitData {
+    /// Gather the init data of a world for a client.
+    pub fn new(world: &World, id: &str) -> Self {
+        let config = world.config();
+        let [sx, sy, sz] = config.spawn;
+
+        Self {
+            id: id.to_owned(),
+            name: world.name.to_owned(),
+            chunk_size: config.chunk_size,
+            max_height: config.max_height,
+            sub_chunks: config.sub_chunks,
+            seed: config.seed,
+            spawn: Vec3(sx, sy, sz),
+            blocks: world.registry().blocks_by_name.to_owned(),
+            options: serde_json::to_value(&*config).unwrap(),
+            stats: world.read_resource::<Stats>().get_stats(),
+            session: None,
+        }
+    }
+}
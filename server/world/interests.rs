@@ -75,3 +75,29 @@ impl ChunkInterests {
         }
     }
 }
+
+/// The entities each client currently has in view, by client ID, so that entities entering and
+/// leaving a client's view can be told apart from the ones it already knows of.
+#[derive(Debug, Default)]
+pub struct EntityInterests {
+    pub map: HashMap<String, HashSet<String>>,
+}
+
+impl EntityInterests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether a client has an entity in view.
+    pub fn is_visible(&self, client_id: &str, entity_id: &str) -> bool {
+        self.map
+            .get(client_id)
+            .map(|entities| entities.contains(entity_id))
+            .unwrap_or(false)
+    }
+
+    /// Forget everything a client had in view, such as when it leaves.
+    pub fn remove_client(&mut self, client_id: &str) {
+        self.map.remove(client_id);
+    }
+}
@@ -1,9 +1,17 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crossbeam_channel::{Receiver, Sender};
+use hashbrown::{HashMap, HashSet};
+use log::warn;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{common::ClientFilter, encode_message, server::Message, EncodedMessage};
+use crate::{
+    common::ClientFilter, encode_message, server::Message, EncodedMessage, MessageType,
+    DEFAULT_MAX_ACK_RETRIES, DEFAULT_MAX_UNACKED,
+};
 
 pub type MessageQueue = Vec<(Message, ClientFilter)>;
 
@@ -52,3 +60,149 @@ impl EncodedMessageQueue {
         result
     }
 }
+
+/// A reliable message sent to a client that the client has yet to acknowledge.
+struct PendingMessage {
+    message: Message,
+
+    /// When the message was last sent.
+    sent: Instant,
+
+    /// The number of times the message has been resent.
+    retries: u32,
+}
+
+/// Reliable messages sent to each client that the client has yet to acknowledge, resent to the
+/// client whenever they go unacknowledged for too long. Messages are given up on once they've been
+/// resent `max_retries` times, or once the client has more than `max_pending` of them.
+pub struct ReliableMessages {
+    /// How long to wait for an acknowledgement before resending a message.
+    pub timeout: Duration,
+
+    /// The number of times a message is resent before it's given up on.
+    pub max_retries: u32,
+
+    /// The most messages a client can leave unacknowledged, past which the oldest are given up on.
+    pub max_pending: usize,
+
+    /// Client ID -> unacknowledged messages, oldest first.
+    pending: HashMap<String, Vec<PendingMessage>>,
+}
+
+impl ReliableMessages {
+    /// Create an empty buffer of reliable messages.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            max_retries: DEFAULT_MAX_ACK_RETRIES,
+            max_pending: DEFAULT_MAX_UNACKED,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Hold onto a reliable message sent to a client until it's acknowledged. Unreliable messages
+    /// are ignored. Voxel updates in older pending messages that this message updates again are
+    /// dropped, so that they're never resent over the newer ones.
+    pub fn track(&mut self, id: &str, message: &Message) {
+        if message.ack == 0 {
+            return;
+        }
+
+        let messages = self.pending.entry(id.to_owned()).or_default();
+
+        if message.r#type == MessageType::Update as i32 && !message.updates.is_empty() {
+            let voxels = message
+                .updates
+                .iter()
+                .map(|update| (update.vx, update.vy, update.vz))
+                .collect::<HashSet<_>>();
+
+            messages.retain_mut(|pending| {
+                if pending.message.r#type != MessageType::Update as i32 {
+                    return true;
+                }
+
+                pending
+                    .message
+                    .updates
+                    .retain(|update| !voxels.contains(&(update.vx, update.vy, update.vz)));
+
+                !pending.message.updates.is_empty()
+            });
+        }
+
+        messages.push(PendingMessage {
+            message: message.to_owned(),
+            sent: Instant::now(),
+            retries: 0,
+        });
+
+        if messages.len() > self.max_pending {
+            let excess = messages.len() - self.max_pending;
+            messages.drain(..excess);
+            warn!(
+                "Client {} has too many unacknowledged messages, dropped {} of them.",
+                id, excess
+            );
+        }
+    }
+
+    /// Stop resending a message that a client has acknowledged.
+    pub fn ack(&mut self, id: &str, ack: u32) {
+        if let Some(messages) = self.pending.get_mut(id) {
+            messages.retain(|pending| pending.message.ack != ack);
+
+            if messages.is_empty() {
+                self.pending.remove(id);
+            }
+        }
+    }
+
+    /// Take the messages that have gone unacknowledged past the timeout, to be sent again to their
+    /// clients. They're kept until acknowledged or out of retries, and the wait starts over.
+    pub fn resend(&mut self) -> MessageQueue {
+        let now = Instant::now();
+        let mut resent = vec![];
+
+        for (id, messages) in self.pending.iter_mut() {
+            let before = messages.len();
+            messages.retain(|pending| {
+                now.duration_since(pending.sent) < self.timeout
+                    || pending.retries < self.max_retries
+            });
+
+            if messages.len() < before {
+                warn!(
+                    "Client {} didn't acknowledge {} messages, giving up on them.",
+                    id,
+                    before - messages.len()
+                );
+            }
+
+            for pending in messages.iter_mut() {
+                if now.duration_since(pending.sent) >= self.timeout {
+                    pending.sent = now;
+                    pending.retries += 1;
+                    resent.push((
+                        pending.message.to_owned(),
+                        ClientFilter::Direct(id.to_owned()),
+                    ));
+                }
+            }
+        }
+
+        self.pending.retain(|_, messages| !messages.is_empty());
+
+        resent
+    }
+
+    /// The number of messages a client has yet to acknowledge.
+    pub fn pending(&self, id: &str) -> usize {
+        self.pending.get(id).map_or(0, |messages| messages.len())
+    }
+
+    /// Forget the messages of a client, such as when it leaves.
+    pub fn remove(&mut self, id: &str) {
+        self.pending.remove(id);
+    }
+}
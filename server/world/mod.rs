@@ -1,11 +1,14 @@
+mod atlas;
 mod bookkeeping;
 mod clients;
+mod commands;
 mod components;
 mod config;
 mod entities;
 mod entity_ids;
 mod events;
 mod generators;
+mod init;
 mod interests;
 mod messages;
 mod physics;
@@ -19,8 +22,7 @@ mod utils;
 mod voxels;
 
 use actix::{
-    Actor, AsyncContext, Context, Handler, Message as ActixMessage, MessageResult, Recipient,
-    SyncContext,
+    dev::SendError, Actor, Handler, Message as ActixMessage, MessageResult, Recipient, SyncContext,
 };
 use actix::{Addr, SyncArbiter};
 use hashbrown::HashMap;
@@ -28,38 +30,43 @@ use log::{info, warn};
 use nanoid::nanoid;
 use profiler::Profiler;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::Value;
 use specs::{
     shred::{Fetch, FetchMut, Resource},
-    Builder, Component, DispatcherBuilder, Entity, EntityBuilder, Join, ReadStorage, SystemData,
-    World as ECSWorld, WorldExt, WriteStorage,
+    Builder, Component, Dispatcher, DispatcherBuilder, Entity, EntityBuilder, Join, ReadStorage,
+    SystemData, World as ECSWorld, WorldExt, WriteStorage,
 };
+use std::cell::RefCell;
 use std::f64::consts::E;
 use std::path::PathBuf;
 use std::sync::{Mutex, RwLock};
 use std::{env, sync::Arc};
 use std::{
     fs::{self, File},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
     encode_message,
     protocols::Peer,
     server::{Message, MessageType},
-    EncodedMessage, EntityOperation, EntityProtocol, PeerProtocol, Server, Vec2, Vec3,
+    ChatMessageProtocol, EncodedMessage, EntityOperation, EntityProtocol, PeerProtocol, Server,
+    Shutdown, Vec2, Vec3,
 };
 
 use super::common::ClientFilter;
 
+pub use atlas::*;
 pub use bookkeeping::*;
 pub use clients::*;
+pub use commands::*;
 pub use components::*;
 pub use config::*;
 pub use entities::*;
 pub use entity_ids::*;
 pub use events::*;
 pub use generators::*;
+pub use init::*;
 pub use interests::*;
 pub use messages::*;
 pub use physics::*;
@@ -73,6 +80,21 @@ pub use voxels::*;
 
 pub type Transports = HashMap<String, Recipient<EncodedMessage>>;
 
+type VoxelHandle = Arc<dyn Fn(&mut World, Vec3<i32>) + Send + Sync>;
+
+type BlockSetHandle = Arc<dyn Fn(&mut World, Vec3<i32>, u32, u32) + Send + Sync>;
+
+/// The most fixed updates a single tick can run to catch up, past which the time left is dropped.
+const MAX_FIXED_STEPS: u32 = 8;
+
+thread_local! {
+    /// World ID -> the dispatcher built for its fixed updates, along with the `fixed_generation` it
+    /// was built from. Dispatchers can hold thread-local systems, so they're kept by the thread that
+    /// ticks the world instead of on the world itself.
+    static FIXED_DISPATCHERS: RefCell<HashMap<String, (u64, Dispatcher<'static, 'static>)>> =
+        RefCell::new(HashMap::new());
+}
+
 /// The default client metadata parser, parses PositionComp and DirectionComp, and updates RigidBodyComp.
 pub fn default_client_parser(world: &mut World, metadata: &str, client_ent: Entity) {
     let metadata: PeerUpdate = match serde_json::from_str(metadata) {
@@ -130,12 +152,30 @@ pub struct World {
     /// The progress of preloading.
     pub preload_progress: f32,
 
+    /// The number of ticks this world has been asked to run.
+    pub ticks: u64,
+
+    /// The number of fixed updates this world has run.
+    pub fixed_ticks: u64,
+
+    /// Seconds that have passed but haven't been caught up on by fixed updates yet.
+    fixed_accumulator: f32,
+
+    /// When the world last ticked, to measure the time fixed updates have to catch up on.
+    last_tick: Option<Instant>,
+
     /// Entity component system world.
     ecs: ECSWorld,
 
     /// The modifier of the ECS dispatcher.
     dispatcher: Arc<dyn Fn() -> DispatcherBuilder<'static, 'static> + Send + Sync>,
 
+    /// The modifier of the ECS dispatcher run on every fixed update, if one was set.
+    fixed_dispatcher: Option<Arc<dyn Fn() -> DispatcherBuilder<'static, 'static> + Send + Sync>>,
+
+    /// Bumped whenever the fixed update systems are set, to rebuild their cached dispatcher.
+    fixed_generation: u64,
+
     /// The modifier of any new client.
     client_modifier: Option<Arc<dyn Fn(&mut World, Entity) + Send + Sync>>,
 
@@ -154,6 +194,21 @@ pub struct World {
     /// The handler for commands.
     command_handle: Option<Arc<dyn Fn(&mut World, &str, &str) + Send + Sync>>,
 
+    /// The registered commands, checked before the command handler.
+    commands: CommandRegistry,
+
+    /// The handler for scheduled block updates.
+    block_update_handle: Option<VoxelHandle>,
+
+    /// The handler for when a block is placed, broken or replaced.
+    block_set_handle: Option<BlockSetHandle>,
+
+    /// The handler for random block ticks.
+    random_tick_handle: Option<VoxelHandle>,
+
+    /// The seeded random generator used to pick voxels for random ticks.
+    random_tick_rng: fastrand::Rng,
+
     /// A map to spawn and create entities.
     entity_loaders:
         HashMap<String, Arc<dyn Fn(&mut World, MetadataComp) -> EntityBuilder + Send + Sync>>,
@@ -181,11 +236,13 @@ pub struct WorldInfo {
     pub config: WorldConfig,
     pub preloading: bool,
     pub preload_progress: f32,
+    pub ticks: u64,
+    pub clients: Vec<String>,
 }
 
 #[derive(ActixMessage)]
 #[rtype(result = "WorldInfo")]
-pub(crate) struct GetInfo;
+pub struct GetInfo;
 
 #[derive(ActixMessage)]
 #[rtype(result = "()")]
@@ -208,6 +265,7 @@ pub(crate) struct ClientRequest {
 pub(crate) struct ClientJoinRequest {
     pub id: String,
     pub username: String,
+    pub metadata: HashMap<String, String>,
     pub addr: Recipient<EncodedMessage>,
 }
 
@@ -217,6 +275,13 @@ pub(crate) struct ClientLeaveRequest {
     pub id: String,
 }
 
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+pub(crate) struct ClientMoveRequest {
+    pub id: String,
+    pub to: Addr<SyncWorld>,
+}
+
 #[derive(ActixMessage)]
 #[rtype(result = "()")]
 pub(crate) struct TransportJoinRequest {
@@ -246,6 +311,14 @@ impl Handler<Tick> for SyncWorld {
     }
 }
 
+impl Handler<Shutdown> for SyncWorld {
+    type Result = ();
+
+    fn handle(&mut self, _: Shutdown, _: &mut SyncContext<Self>) {
+        self.0.write().unwrap().shutdown();
+    }
+}
+
 impl Handler<Prepare> for SyncWorld {
     type Result = ();
 
@@ -268,11 +341,14 @@ impl Handler<GetInfo> for SyncWorld {
     fn handle(&mut self, _: GetInfo, _: &mut SyncContext<Self>) -> Self::Result {
         let world = self.0.read().unwrap();
         let config = world.config().make_copy();
+        let clients = world.clients().keys().cloned().collect();
         MessageResult(WorldInfo {
             name: world.name.clone(),
             config,
             preloading: world.preloading,
             preload_progress: world.preload_progress,
+            ticks: world.ticks,
+            clients,
         })
     }
 }
@@ -301,7 +377,7 @@ impl Handler<ClientJoinRequest> for SyncWorld {
         self.0
             .write()
             .unwrap()
-            .add_client(&msg.id, &msg.username, &msg.addr);
+            .add_client(&msg.id, &msg.username, &msg.addr, msg.metadata);
     }
 }
 
@@ -313,6 +389,28 @@ impl Handler<ClientLeaveRequest> for SyncWorld {
     }
 }
 
+impl Handler<ClientMoveRequest> for SyncWorld {
+    type Result = ();
+
+    fn handle(&mut self, msg: ClientMoveRequest, _: &mut SyncContext<Self>) {
+        let client = {
+            let mut world = self.0.write().unwrap();
+            let client = world.clients().get(&msg.id).cloned();
+            world.remove_client(&msg.id);
+            client
+        };
+
+        if let Some(client) = client {
+            msg.to.do_send(ClientJoinRequest {
+                id: client.id,
+                username: client.username,
+                metadata: client.metadata,
+                addr: client.addr,
+            });
+        }
+    }
+}
+
 impl Handler<TransportJoinRequest> for SyncWorld {
     type Result = ();
 
@@ -336,7 +434,8 @@ fn dispatcher() -> DispatcherBuilder<'static, 'static> {
         .with(PeersMetaSystem, "peers-meta", &[])
         .with(CurrentChunkSystem, "current-chunk", &[])
         .with(ChunkUpdatingSystem, "chunk-updating", &["current-chunk"])
-        .with(ChunkRequestsSystem, "chunk-requests", &["current-chunk"])
+        .with(ChunkStreamingSystem, "chunk-streaming", &["current-chunk"])
+        .with(ChunkRequestsSystem, "chunk-requests", &["chunk-streaming"])
         .with(
             ChunkGeneratingSystem,
             "chunk-generation",
@@ -344,7 +443,7 @@ fn dispatcher() -> DispatcherBuilder<'static, 'static> {
         )
         .with(ChunkSendingSystem, "chunk-sending", &["chunk-generation"])
         .with(ChunkSavingSystem, "chunk-saving", &["chunk-generation"])
-        .with(PhysicsSystem, "physics", &["current-chunk", "update-stats"])
+        .with(ChunkEvictingSystem, "chunk-evicting", &["chunk-saving"])
         .with(DataSavingSystem, "entities-saving", &["entities-meta"])
         .with(
             EntitiesSendingSystem,
@@ -371,6 +470,10 @@ fn dispatcher() -> DispatcherBuilder<'static, 'static> {
         .with(WalkTowardsSystem, "walk-towards", &["path-finding"])
 }
 
+fn fixed_dispatcher() -> DispatcherBuilder<'static, 'static> {
+    DispatcherBuilder::new().with(PhysicsSystem, "physics", &[])
+}
+
 #[derive(Serialize, Deserialize)]
 struct OnLoadRequest {
     center: Vec2<i32>,
@@ -394,12 +497,24 @@ struct BuiltInSetTimeMethodPayload {
     time: f32,
 }
 
+#[derive(Serialize, Deserialize)]
+struct BuiltInSetViewRadiusMethodPayload {
+    radius: usize,
+}
+
 #[derive(Serialize, Deserialize)]
 struct BuiltInUpdateBlockEntityMethodPayload {
     id: String,
     json: String,
 }
 
+impl Drop for World {
+    fn drop(&mut self) {
+        // Worlds are usually dropped by the thread that ticked them, which holds their dispatcher.
+        let _ = FIXED_DISPATCHERS.try_with(|dispatchers| dispatchers.borrow_mut().remove(&self.id));
+    }
+}
+
 impl World {
     /// Create a new voxelize world.
     pub fn new(name: &str, config: &WorldConfig) -> Self {
@@ -458,9 +573,14 @@ impl World {
         ecs.insert(Events::new());
         ecs.insert(Transports::new());
         ecs.insert(ChunkInterests::new());
+        ecs.insert(EntityInterests::new());
         ecs.insert(Bookkeeping::new());
         ecs.insert(KdTree::new());
         ecs.insert(EncodedMessageQueue::new());
+        let mut reliable = ReliableMessages::new(Duration::from_millis(config.ack_timeout));
+        reliable.max_retries = config.max_ack_retries;
+        reliable.max_pending = config.max_unacked;
+        ecs.insert(reliable);
         ecs.insert(Profiler::new(Duration::from_secs_f64(0.001)));
         ecs.insert(EntityIDs::new());
 
@@ -470,10 +590,16 @@ impl World {
             started: false,
             preloading: false,
             preload_progress: 0.0,
+            ticks: 0,
+            fixed_ticks: 0,
+            fixed_accumulator: 0.0,
+            last_tick: None,
 
             ecs,
 
             dispatcher: Arc::new(dispatcher),
+            fixed_dispatcher: None,
+            fixed_generation: 0,
             method_handles: HashMap::default(),
             event_handles: HashMap::default(),
             entity_loaders: HashMap::default(),
@@ -481,6 +607,11 @@ impl World {
             client_modifier: None,
             transport_handle: None,
             command_handle: None,
+            commands: CommandRegistry::new(),
+            block_update_handle: None,
+            block_set_handle: None,
+            random_tick_handle: None,
+            random_tick_rng: fastrand::Rng::with_seed(config.seed as u64),
             addr: None,
             server_addr: None,
         };
@@ -502,6 +633,20 @@ impl World {
             world.stats_mut().set_time(payload.time % time_per_day);
         });
 
+        world.set_method_handle("vox-builtin:set-view-radius", |world, id, payload| {
+            let payload: BuiltInSetViewRadiusMethodPayload = match serde_json::from_str(payload) {
+                Ok(payload) => payload,
+                Err(_) => {
+                    warn!(
+                        "Could not parse vox-builtin:set-view-radius payload: {}",
+                        payload
+                    );
+                    return;
+                }
+            };
+            world.set_view_radius(id, payload.radius);
+        });
+
         world.set_method_handle("vox-builtin:update-block-entity", |world, _, payload| {
             let payload: BuiltInUpdateBlockEntityMethodPayload = serde_json::from_str(payload)
                 .expect("Could not parse vox-builtin:update-block-entity payload.");
@@ -596,7 +741,7 @@ impl World {
     /// Add a transport address to this world.
     pub(crate) fn add_transport(&mut self, id: &str, addr: &Recipient<EncodedMessage>) {
         let init_message = self.generate_init_message(id);
-        self.send(addr, &init_message);
+        let _ = self.send(addr, &init_message);
         self.write_resource::<Transports>()
             .insert(id.to_owned(), addr.to_owned());
     }
@@ -606,19 +751,22 @@ impl World {
         self.write_resource::<Transports>().remove(id);
     }
 
-    /// Add a client to the world by an ID and an Actix actor address.
+    /// Add a client to the world by an ID and an Actix actor address, along with the handshake
+    /// fields it supplied when joining.
     pub(crate) fn add_client(
         &mut self,
         id: &str,
         username: &str,
         addr: &Recipient<EncodedMessage>,
+        metadata: HashMap<String, String>,
     ) {
-        let init_message = self.generate_init_message(id);
+        let init_message = self.init_message(id, metadata.get("session").map(String::as_str));
 
         let body =
             RigidBody::new(&AABB::new().scale_x(0.8).scale_y(1.8).scale_z(0.8).build()).build();
 
         let interactor = self.physics_mut().register(&body);
+        let [sx, sy, sz] = self.config().spawn;
 
         let ent = self
             .ecs
@@ -630,13 +778,22 @@ impl World {
             .with(ChunkRequestsComp::default())
             .with(CurrentChunkComp::default())
             .with(MetadataComp::default())
-            .with(PositionComp::default())
+            .with(PositionComp::new(sx, sy, sz))
             .with(DirectionComp::default())
             .with(RigidBodyComp::new(&body))
             .with(InteractorComp::new(&interactor))
             .with(CollisionsComp::new())
             .build();
 
+        if let Some(radius) = metadata
+            .get("view_radius")
+            .and_then(|radius| radius.parse::<usize>().ok())
+        {
+            if let Some(requests) = self.write_component::<ChunkRequestsComp>().get_mut(ent) {
+                requests.set_view_radius(radius);
+            }
+        }
+
         if let Some(modifier) = self.client_modifier.to_owned() {
             modifier(self, ent);
         }
@@ -648,12 +805,16 @@ impl World {
                 entity: ent,
                 username: username.to_owned(),
                 addr: addr.to_owned(),
+                metadata,
+                failed_sends: 0,
             },
         );
 
         self.entity_ids_mut().insert(id.to_owned(), ent.id());
 
-        self.send(addr, &init_message);
+        if let Some(client) = self.clients_mut().get_mut(id) {
+            let _ = client.send(EncodedMessage(encode_message(&init_message)));
+        }
 
         let join_message = Message::new(&MessageType::Join).text(id).build();
         self.broadcast(join_message, ClientFilter::All);
@@ -661,10 +822,29 @@ impl World {
         info!("Client at {} joined the server to world: {}", id, self.name);
     }
 
+    /// Set the radius a client wants chunks streamed within, clamped to `config.stream_radius`.
+    /// Chunks that fall out of the new radius are unloaded on the next tick.
+    pub fn set_view_radius(&mut self, client_id: &str, radius: usize) {
+        let client_ent = if let Some(client) = self.clients().get(client_id) {
+            client.entity.to_owned()
+        } else {
+            return;
+        };
+
+        if let Some(requests) = self
+            .write_component::<ChunkRequestsComp>()
+            .get_mut(client_ent)
+        {
+            requests.set_view_radius(radius);
+        }
+    }
+
     /// Remove a client from the world by endpoint.
     pub(crate) fn remove_client(&mut self, id: &str) {
         let removed = self.clients_mut().remove(id);
         self.entity_ids_mut().remove(id);
+        self.write_resource::<ReliableMessages>().remove(id);
+        self.write_resource::<EntityInterests>().remove_client(id);
 
         if let Some(client) = removed {
             {
@@ -735,6 +915,24 @@ impl World {
         dispatch: F,
     ) {
         self.dispatcher = Arc::new(dispatch);
+
+        // A custom dispatcher used to run every system, physics included, once per tick. Unless the
+        // fixed update was set too, it's left empty so that nothing runs twice.
+        if self.fixed_dispatcher.is_none() {
+            self.set_fixed_dispatcher(DispatcherBuilder::new);
+        }
+    }
+
+    /// Set the systems run on every fixed update. Defaults to `PhysicsSystem`, for a stable
+    /// timestep. Setting a custom dispatcher with `set_dispatcher` first leaves it empty instead.
+    pub fn set_fixed_dispatcher<
+        F: Fn() -> DispatcherBuilder<'static, 'static> + Send + Sync + 'static,
+    >(
+        &mut self,
+        dispatch: F,
+    ) {
+        self.fixed_dispatcher = Some(Arc::new(dispatch));
+        self.fixed_generation += 1;
     }
 
     pub fn set_client_modifier<F: Fn(&mut World, Entity) + Send + Sync + 'static>(
@@ -783,6 +981,90 @@ impl World {
         self.command_handle = Some(Arc::new(handle));
     }
 
+    /// Access the registered commands of this world.
+    pub fn commands(&self) -> &CommandRegistry {
+        &self.commands
+    }
+
+    /// Access the mutable command registry, to register new commands.
+    pub fn commands_mut(&mut self) -> &mut CommandRegistry {
+        &mut self.commands
+    }
+
+    pub fn set_block_update_handle<F: Fn(&mut World, Vec3<i32>) + Send + Sync + 'static>(
+        &mut self,
+        handle: F,
+    ) {
+        self.block_update_handle = Some(Arc::new(handle));
+    }
+
+    /// Set the handler for when a block changes, called with the voxel, the old ID and the new ID.
+    pub fn set_block_set_handle<F: Fn(&mut World, Vec3<i32>, u32, u32) + Send + Sync + 'static>(
+        &mut self,
+        handle: F,
+    ) {
+        self.block_set_handle = Some(Arc::new(handle));
+    }
+
+    pub fn set_random_tick_handle<F: Fn(&mut World, Vec3<i32>) + Send + Sync + 'static>(
+        &mut self,
+        handle: F,
+    ) {
+        self.random_tick_handle = Some(Arc::new(handle));
+    }
+
+    /// Schedule a block update at a voxel, to be fired into the block update handle after
+    /// `delay_ticks` ticks.
+    pub fn schedule_block_update(&mut self, voxel: &Vec3<i32>, delay_ticks: u32) {
+        let fire_at = self.stats().tick + delay_ticks as u64;
+        self.chunks_mut().schedule_block_update(voxel, fire_at);
+    }
+
+    /// Fire all the scheduled block updates that are due by the current tick.
+    pub fn process_block_updates(&mut self) {
+        let tick = self.stats().tick;
+        let due = self.chunks_mut().take_due_updates(tick);
+
+        if let Some(handle) = self.block_update_handle.to_owned() {
+            for voxel in due {
+                handle(self, voxel);
+            }
+        }
+    }
+
+    /// Fire all the block changes since the last call into the block set handle.
+    pub fn process_block_changes(&mut self) {
+        let changes = std::mem::take(&mut self.chunks_mut().block_changes);
+
+        if let Some(handle) = self.block_set_handle.to_owned() {
+            for (voxel, old_id, new_id) in changes {
+                handle(self, voxel, old_id, new_id);
+            }
+        }
+    }
+
+    /// Pick `config.random_tick_speed` random voxels in every ready chunk, and fire the ones
+    /// that are random-tickable into the random tick handle.
+    pub fn process_random_ticks(&mut self) {
+        let handle = if let Some(handle) = self.random_tick_handle.to_owned() {
+            handle
+        } else {
+            return;
+        };
+
+        let voxels = {
+            let count = self.ecs.read_resource::<WorldConfig>().random_tick_speed;
+            let chunks = self.ecs.read_resource::<Chunks>();
+            let registry = self.ecs.read_resource::<Registry>();
+
+            chunks.pick_random_ticks(&registry, &mut self.random_tick_rng, count)
+        };
+
+        for voxel in voxels {
+            handle(self, voxel);
+        }
+    }
+
     pub fn set_entity_loader<
         F: Fn(&mut World, MetadataComp) -> EntityBuilder + Send + Sync + 'static,
     >(
@@ -795,7 +1077,7 @@ impl World {
     }
 
     /// Handler for protobuf requests from clients.
-    pub(crate) fn on_request(&mut self, client_id: &str, data: Message) {
+    pub fn on_request(&mut self, client_id: &str, data: Message) {
         let msg_type = MessageType::from_i32(data.r#type).unwrap();
 
         match msg_type {
@@ -806,6 +1088,7 @@ impl World {
             MessageType::Chat => self.on_chat(client_id, data),
             MessageType::Update => self.on_update(client_id, data),
             MessageType::Event => self.on_event(client_id, data),
+            MessageType::Ack => self.on_ack(client_id, data),
             MessageType::Transport => {
                 if self.transport_handle.is_none() {
                     warn!("Transport calls are being called, but no transport handlers set!");
@@ -830,9 +1113,23 @@ impl World {
         self.write_resource::<MessageQueue>().push((data, filter));
     }
 
-    /// Send a direct message to an endpoint
-    pub fn send(&self, addr: &Recipient<EncodedMessage>, data: &Message) {
-        addr.do_send(EncodedMessage(encode_message(data)));
+    /// Broadcast a protobuf message to all the clients in the world except one, usually the sender.
+    pub fn broadcast_except(&mut self, exclude: &str, data: Message) {
+        self.broadcast(data, ClientFilter::Exclude(vec![exclude.to_owned()]));
+    }
+
+    /// Send a direct message to an endpoint, failing if the endpoint has closed.
+    pub fn send(
+        &self,
+        addr: &Recipient<EncodedMessage>,
+        data: &Message,
+    ) -> Result<(), SendError<EncodedMessage>> {
+        send_encoded(addr, EncodedMessage(encode_message(data)))
+    }
+
+    /// The interval this world ticks at, if it overrides the server's.
+    pub fn tick_interval(&self) -> Option<Duration> {
+        self.config().tick_interval.map(Duration::from_millis)
     }
 
     /// Access to the world's config.
@@ -989,6 +1286,88 @@ impl World {
         Some(ent)
     }
 
+    /// Spawn an entity of a kind at a position, returning its ID. Kinds with an entity loader are
+    /// built by it, and any other kind gets a bare entity with only a position. Clients with the
+    /// entity's chunk loaded are sent its creation on the next tick.
+    pub fn spawn_entity(&mut self, kind: &str, position: &Vec3<f32>) -> String {
+        if self.entity_loaders.contains_key(&kind.to_lowercase()) {
+            let ent = self.spawn_entity_at(kind, position).unwrap();
+            let ids = self.read_component::<IDComp>();
+            return ids.get(ent).unwrap().0.to_owned();
+        }
+
+        let id = nanoid!();
+        let Vec3(px, py, pz) = *position;
+
+        self.create_entity(&id, kind)
+            .with(PositionComp::new(px, py, pz))
+            .build();
+
+        id
+    }
+
+    /// Remove the entity of an ID from the world, returning whether it existed. Clients that were
+    /// sent the entity are sent its deletion on the next tick.
+    pub fn despawn_entity(&mut self, id: &str) -> bool {
+        let ent = (
+            &self.ecs.entities(),
+            &self.ecs.read_storage::<IDComp>(),
+            &self.ecs.read_storage::<EntityFlag>(),
+        )
+            .join()
+            .find(|(_, entity_id, _)| entity_id.0 == id)
+            .map(|(ent, _, _)| ent);
+
+        let ent = match ent {
+            Some(ent) => ent,
+            None => return false,
+        };
+
+        self.chunks_mut()
+            .block_entities
+            .retain(|_, block_entity| *block_entity != ent);
+
+        self.ecs.delete_entity(ent).is_ok()
+    }
+
+    /// Get a snapshot of every entity in the world, sorted by ID.
+    pub fn entities(&self) -> Vec<EntityInfo> {
+        let bookkeeping = self.read_resource::<Bookkeeping>();
+        let positions = self.read_component::<PositionComp>();
+        let bodies = self.read_component::<RigidBodyComp>();
+        let metadatas = self.read_component::<MetadataComp>();
+
+        let mut entities = (
+            &self.ecs.entities(),
+            &self.read_component::<IDComp>(),
+            &self.read_component::<ETypeComp>(),
+            &self.read_component::<EntityFlag>(),
+        )
+            .join()
+            .map(|(ent, id, etype, _)| EntityInfo {
+                id: id.0.to_owned(),
+                kind: etype.0.to_owned(),
+                position: positions
+                    .get(ent)
+                    .map(|position| position.0.to_owned())
+                    .unwrap_or_default(),
+                velocity: bodies
+                    .get(ent)
+                    .map(|body| body.0.velocity.to_owned())
+                    .unwrap_or_default(),
+                metadata: bookkeeping
+                    .entities
+                    .get(&id.0)
+                    .map(|(_, _, metadata)| metadata.to_owned())
+                    .or_else(|| metadatas.get(ent).cloned())
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+
+        entities.sort_by(|a, b| a.id.cmp(&b.id));
+        entities
+    }
+
     pub fn revive_entity(
         &mut self,
         id: &str,
@@ -1093,6 +1472,32 @@ impl World {
         }
     }
 
+    /// Shut the world down gracefully, returning once everything is persisted. Queued chunk jobs
+    /// are dropped, the ones in flight are waited for, every loaded chunk is saved, and the clients
+    /// are told to disconnect.
+    pub fn shutdown(&mut self) {
+        self.pipeline_mut().drain();
+        self.mesher_mut().drain();
+
+        if self.config().saving {
+            let saved = self.chunks_mut().save_all();
+            info!("Saved {} chunks of world: {}", saved, self.name);
+        }
+
+        let message = Message::new(&MessageType::Error)
+            .text("Server is shutting down.")
+            .build();
+        let ids = self.clients().keys().cloned().collect::<Vec<_>>();
+
+        for id in ids {
+            let addr = self.clients().get(&id).unwrap().addr.to_owned();
+            let _ = self.send(&addr, &message);
+            self.remove_client(&id);
+        }
+
+        info!("World {} has shut down.", self.name);
+    }
+
     /// Preload the chunks in the world.
     pub(crate) fn preload(&mut self) {
         let radius = self.config().preload_radius as i32;
@@ -1127,6 +1532,14 @@ impl World {
             self.started = true;
         }
 
+        self.ticks += 1;
+
+        let now = Instant::now();
+        let elapsed = self
+            .last_tick
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+        self.last_tick = Some(now);
+
         if self.preloading {
             let light_padding = (self.config().max_light_level as f32
                 / self.config().chunk_size as f32)
@@ -1181,11 +1594,90 @@ impl World {
         let mut dispatcher = (self.dispatcher)().build();
         dispatcher.dispatch(&self.ecs);
 
+        self.advance_fixed(elapsed);
+
+        self.remove_dead_clients();
+
+        self.process_block_changes();
+        self.process_block_updates();
+        self.process_random_ticks();
+
         self.write_resource::<Profiler>().summarize();
 
         self.ecs.maintain();
     }
 
+    /// Catch up on `elapsed` seconds with fixed updates, running `fixed_update` once for every whole
+    /// `fixed_timestep` that has passed and carrying the rest over to the next call. Returns the
+    /// number of fixed updates run, which is capped so a slow tick can't snowball into slower ones.
+    pub fn advance_fixed(&mut self, elapsed: f32) -> u32 {
+        let timestep = self.config().fixed_timestep as f32 / 1000.0;
+
+        if timestep <= 0.0 {
+            return 0;
+        }
+
+        self.fixed_accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.fixed_accumulator >= timestep {
+            if steps >= MAX_FIXED_STEPS {
+                self.fixed_accumulator = 0.0;
+                break;
+            }
+
+            self.fixed_update(timestep);
+            self.fixed_accumulator -= timestep;
+            steps += 1;
+        }
+
+        steps
+    }
+
+    /// Run the fixed update systems once, with `stats.delta` set to `dt` seconds while they run.
+    pub fn fixed_update(&mut self, dt: f32) {
+        let delta = std::mem::replace(&mut self.write_resource::<Stats>().delta, dt);
+
+        FIXED_DISPATCHERS.with(|dispatchers| {
+            let mut dispatchers = dispatchers.borrow_mut();
+
+            let stale = dispatchers
+                .get(&self.id)
+                .is_none_or(|(generation, _)| *generation != self.fixed_generation);
+
+            if stale {
+                let dispatcher = match &self.fixed_dispatcher {
+                    Some(dispatch) => dispatch().build(),
+                    None => fixed_dispatcher().build(),
+                };
+
+                dispatchers.insert(self.id.to_owned(), (self.fixed_generation, dispatcher));
+            }
+
+            dispatchers.get_mut(&self.id).unwrap().1.dispatch(&self.ecs);
+        });
+
+        self.write_resource::<Stats>().delta = delta;
+        self.fixed_ticks += 1;
+    }
+
+    /// Remove the clients that messages have failed to reach too many times in a row, as their
+    /// connections are gone. Their messages would otherwise keep piling up.
+    fn remove_dead_clients(&mut self) {
+        let max_send_failures = self.config().max_send_failures;
+        let dead = self
+            .clients()
+            .values()
+            .filter(|client| client.failed_sends >= max_send_failures)
+            .map(|client| client.id.to_owned())
+            .collect::<Vec<_>>();
+
+        for id in dead {
+            warn!("Could not reach client {}, removing it.", id);
+            self.remove_client(&id);
+        }
+    }
+
     /// Handler for `Peer` type messages.
     fn on_peer(&mut self, client_id: &str, data: Message) {
         let client_ent = if let Some(client) = self.clients().get(client_id) {
@@ -1339,6 +1831,12 @@ impl World {
         }
     }
 
+    /// Handler for `Ack` type messages.
+    fn on_ack(&mut self, client_id: &str, data: Message) {
+        self.write_resource::<ReliableMessages>()
+            .ack(client_id, data.ack);
+    }
+
     /// Handler for `Event` type messages.
     fn on_event(&mut self, client_id: &str, data: Message) {
         let client_ent = if let Some(client) = self.clients().get(client_id) {
@@ -1381,10 +1879,22 @@ impl World {
             let command_symbol = self.config().command_symbol.to_owned();
 
             if body.starts_with(&command_symbol) {
-                if let Some(handle) = self.command_handle.to_owned() {
-                    handle(self, id, body.strip_prefix(&command_symbol).unwrap());
+                let command = body.strip_prefix(&command_symbol).unwrap();
+                let (name, args) = CommandRegistry::parse(command).unwrap_or_default();
+
+                if let Some(handle) = self.commands.get(&name) {
+                    handle(self, id, &args);
+                } else if let Some(handle) = self.command_handle.to_owned() {
+                    handle(self, id, command);
                 } else {
-                    warn!("Clients are sending commands, but no command handler set.");
+                    let reply = Message::new(&MessageType::Chat)
+                        .chat(ChatMessageProtocol {
+                            r#type: "error".to_owned(),
+                            sender: String::new(),
+                            body: format!("Unknown command: {}{}", command_symbol, name),
+                        })
+                        .build();
+                    self.broadcast(reply, ClientFilter::Direct(id.to_owned()));
                 }
             } else {
                 self.broadcast(data, ClientFilter::All);
@@ -1453,17 +1963,16 @@ impl World {
         }
     }
 
-    fn generate_init_message(&self, id: &str) -> Message {
-        let config = (*self.config()).to_owned();
-        let mut json = HashMap::new();
+    /// Generate the `Init` message sent to a client as it joins, carrying the `WorldInitData` of
+    /// this world along with its peers and entities.
+    pub fn generate_init_message(&self, id: &str) -> Message {
+        self.init_message(id, None)
+    }
 
-        json.insert("id".to_owned(), json!(id));
-        json.insert("blocks".to_owned(), json!(self.registry().blocks_by_name));
-        json.insert("options".to_owned(), json!(config));
-        json.insert(
-            "stats".to_owned(),
-            json!(self.read_resource::<Stats>().get_stats()),
-        );
+    /// Generate the `Init` message of a client, along with the token it can resume its session with.
+    fn init_message(&self, id: &str, session: Option<&str>) -> Message {
+        let mut json = WorldInitData::new(self, id);
+        json.session = session.map(str::to_owned);
 
         /* ------------------------ Loading other the clients ----------------------- */
         let ids = self.read_component::<IDComp>();
@@ -1488,7 +1997,10 @@ impl World {
         let mut entities = vec![];
 
         for (id, etype, metadata) in (&ids, &etypes, &metadatas).join() {
-            if !etype.0.starts_with("block::") && metadata.is_empty() {
+            // Entities with a position are sent as they enter the client's range instead.
+            if !etype.0.starts_with("block::")
+                && (metadata.is_empty() || metadata.map.contains_key("position"))
+            {
                 continue;
             }
 
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::BlockFace;
+use crate::{BlockFace, BlockRotation};
 
 /// Axis-aligned Bounding Box.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -124,6 +124,11 @@ impl AABB {
         }
     }
 
+    /// Rotate this AABB within its unit voxel to follow a block's rotation, including its y-rotation.
+    pub fn rotate(&self, rotation: &BlockRotation) -> AABB {
+        rotation.rotate_aabb(self, true, true)
+    }
+
     /// The width of this AABB, max_x - min_x.
     #[inline]
     pub fn width(&self) -> f32 {
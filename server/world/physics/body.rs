@@ -0,0 +1,36 @@
+use crate::{Vec3, AABB};
+
+/// A lightweight physical body that can be stepped directly against the chunks, without
+/// going through the full rigid body simulation.
+#[derive(Debug, Clone, Default)]
+pub struct PhysicsBody {
+    /// Position of the body, which is where the AABB's minimum corner sits.
+    pub position: Vec3<f32>,
+
+    /// Velocity of the body, in blocks per second.
+    pub velocity: Vec3<f32>,
+
+    /// The collision box of this body, relative to its position.
+    pub aabb: AABB,
+
+    /// Whether or not the body's last downward move was blocked.
+    pub on_ground: bool,
+
+    /// The fraction of velocity lost to air per second.
+    pub air_drag: f32,
+
+    /// The maximum speed this body can fall at.
+    pub terminal_velocity: f32,
+}
+
+impl PhysicsBody {
+    /// Create a new body at a position, with no drag and a terminal velocity of 50 blocks per second.
+    pub fn new(aabb: &AABB, position: &Vec3<f32>) -> Self {
+        Self {
+            position: position.to_owned(),
+            aabb: aabb.to_owned(),
+            terminal_velocity: 50.0,
+            ..Default::default()
+        }
+    }
+}
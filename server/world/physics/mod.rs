@@ -19,11 +19,13 @@ use crate::{approx_equals, BlockRotation, Vec3, VoxelAccess};
 use super::{registry::Registry, WorldConfig};
 
 mod aabb;
+mod body;
 mod raycast;
 mod rigidbody;
 mod sweep;
 
 pub use aabb::*;
+pub use body::*;
 pub use raycast::*;
 pub use rigidbody::*;
 pub use sweep::*;
@@ -200,7 +200,7 @@ pub fn sweep(
                 }
 
                 aabbs.iter().for_each(|aabb| {
-                    let mut block_aabb = rotation.rotate_aabb(aabb, true, true);
+                    let mut block_aabb = aabb.rotate(&rotation);
                     block_aabb.translate(vx as f32, vy as f32, vz as f32);
                     let result = sweep_aabb(target, &block_aabb, &velocity);
 
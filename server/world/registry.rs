@@ -4,10 +4,20 @@ use hashbrown::{HashMap, HashSet};
 use log::info;
 use serde::{Deserialize, Serialize};
 
-use crate::{BlockFace, Vec3, VoxelAccess, VoxelUpdate};
+use crate::{BlockFace, BlockRotation, Vec3, VoxelAccess, VoxelUpdate};
 
 use super::voxels::Block;
 
+/// The directions of `block.is_transparent`, in order.
+const TRANSPARENCY_DIRS: [Vec3<i32>; 6] = [
+    Vec3(1, 0, 0),
+    Vec3(0, 1, 0),
+    Vec3(0, 0, 1),
+    Vec3(-1, 0, 0),
+    Vec3(0, -1, 0),
+    Vec3(0, 0, -1),
+];
+
 /// Serializable struct representing a UV coordinate.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -282,7 +292,8 @@ impl Registry {
         &self.get_block_by_name(name).faces
     }
 
-    /// Check if block is air by id.
+    /// Check if block is the registry's own "Air" block by id. Worlds configured with another
+    /// `WorldConfig::air_id` should compare against it instead.
     pub fn is_air(&self, id: u32) -> bool {
         self.get_block_by_id(id).name == "Air"
     }
@@ -292,6 +303,47 @@ impl Registry {
         self.get_block_by_id(id).is_fluid
     }
 
+    /// Get a block reference by block ID, or `None` if the ID isn't registered.
+    pub fn get(&self, id: u32) -> Option<&Block> {
+        self.blocks_by_id.get(&id)
+    }
+
+    /// Get a block reference by block name, or `None` if the name isn't registered.
+    pub fn get_by_name(&self, name: &str) -> Option<&Block> {
+        self.blocks_by_name.get(&name.to_lowercase())
+    }
+
+    /// Check if block is passable by id.
+    pub fn is_passable(&self, id: u32) -> bool {
+        self.get_block_by_id(id).is_passable
+    }
+
+    /// Check if block is transparent by id, looking from an axis-aligned direction such as
+    /// `Vec3(0, 1, 0)`. The direction is in the block's own, unrotated, frame.
+    pub fn is_transparent(&self, id: u32, dir: &Vec3<i32>) -> bool {
+        Registry::transparency_index(dir)
+            .is_some_and(|index| self.get_block_by_id(id).is_transparent[index])
+    }
+
+    /// Check if a rotated block is transparent by id, looking from an axis-aligned direction in the
+    /// world, such as the side of a neighboring voxel. The block's transparency is rotated the same
+    /// way light propagation rotates it, and the side that ends up facing `dir` is looked up.
+    pub fn is_transparent_world(&self, id: u32, rotation: &BlockRotation, dir: &Vec3<i32>) -> bool {
+        let block = self.get_block_by_id(id);
+
+        if !block.rotatable && !block.y_rotatable {
+            return self.is_transparent(id, dir);
+        }
+
+        Registry::transparency_index(dir)
+            .is_some_and(|index| block.get_rotated_transparency(rotation)[index])
+    }
+
+    /// Get the index of an axis-aligned direction into `block.is_transparent`.
+    fn transparency_index(dir: &Vec3<i32>) -> Option<usize> {
+        TRANSPARENCY_DIRS.iter().position(|other| other == dir)
+    }
+
     /// Get type map of all blocks.
     pub fn get_type_map(&self, blocks: &[&str]) -> HashMap<String, u32> {
         let mut type_map = HashMap::new();
@@ -308,11 +360,20 @@ impl Registry {
         type_map
     }
 
-    /// Logic for checking max height, returning true if id counts as valid max height.
+    /// Logic for checking max height, returning true if id counts as valid max height. This assumes
+    /// the default air ID of 0; chunks compare against their `air_id` instead.
     pub fn check_height(&self, id: u32) -> bool {
         id != 0
     }
 
+    /// Get the fluid that waterlogged voxels are filled with, which is the fluid with the lowest ID.
+    pub fn get_waterlog_fluid(&self) -> Option<&Block> {
+        self.blocks_by_id
+            .values()
+            .filter(|block| block.is_fluid)
+            .min_by_key(|block| block.id)
+    }
+
     /// Check if registry contains type.
     pub fn has_type(&self, id: u32) -> bool {
         self.blocks_by_id.contains_key(&id)
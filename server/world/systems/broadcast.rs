@@ -1,10 +1,10 @@
+use hashbrown::HashMap;
 use specs::{ReadExpect, System, WriteExpect};
 
 use crate::{
-    common::ClientFilter,
-    server::encode_message,
-    world::{profiler::Profiler, Clients, MessageQueue},
-    EncodedMessage, EncodedMessageQueue, MessageType, Transports,
+    server::batch_messages,
+    world::{Clients, MessageQueue, ReliableMessages},
+    EncodedMessage, EncodedMessageQueue, Transports,
 };
 
 pub struct BroadcastSystem;
@@ -12,14 +12,25 @@ pub struct BroadcastSystem;
 impl<'a> System<'a> for BroadcastSystem {
     type SystemData = (
         ReadExpect<'a, Transports>,
-        ReadExpect<'a, Clients>,
+        WriteExpect<'a, Clients>,
         WriteExpect<'a, MessageQueue>,
         WriteExpect<'a, EncodedMessageQueue>,
-        WriteExpect<'a, Profiler>,
+        WriteExpect<'a, ReliableMessages>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (transports, clients, mut queue, mut encoded_queue, mut profiler) = data;
+        let (transports, mut clients, mut queue, mut encoded_queue, mut reliable) = data;
+
+        let mut resent = reliable.resend();
+
+        for (message, filter) in queue.iter().filter(|(message, _)| message.ack != 0) {
+            clients
+                .keys()
+                .filter(|id| filter.includes(id))
+                .for_each(|id| reliable.track(id, message));
+        }
+
+        queue.append(&mut resent);
 
         encoded_queue.append(queue.drain(..).collect());
         encoded_queue.process();
@@ -30,37 +41,29 @@ impl<'a> System<'a> for BroadcastSystem {
             return;
         }
 
+        // Messages for each client are batched into a single frame per tick.
+        let mut batches: HashMap<String, Vec<EncodedMessage>> = HashMap::new();
+
         for (encoded, filter) in done_messages {
             transports.values().for_each(|recipient| {
                 recipient.do_send(encoded.to_owned());
             });
 
-            if let ClientFilter::Direct(id) = &filter {
-                if let Some(client) = clients.get(id) {
-                    client.addr.do_send(encoded);
-                }
+            clients
+                .keys()
+                .filter(|id| filter.includes(id))
+                .for_each(|id| {
+                    batches
+                        .entry(id.to_owned())
+                        .or_default()
+                        .push(encoded.to_owned())
+                });
+        }
 
-                continue;
+        for (id, messages) in batches {
+            if let Some(client) = clients.get_mut(&id) {
+                let _ = client.send(batch_messages(messages));
             }
-
-            clients.iter().for_each(|(id, client)| {
-                match &filter {
-                    ClientFilter::All => {}
-                    ClientFilter::Include(ids) => {
-                        if !ids.iter().any(|i| *i == *id) {
-                            return;
-                        }
-                    }
-                    ClientFilter::Exclude(ids) => {
-                        if ids.iter().any(|i| *i == *id) {
-                            return;
-                        }
-                    }
-                    _ => {}
-                };
-
-                client.addr.do_send(encoded.to_owned());
-            })
         }
     }
 }
@@ -71,7 +74,7 @@ impl<'a> System<'a> for BroadcastSystem {
 // use crate::{
 //     common::ClientFilter,
 //     server::encode_message,
-//     world::{profiler::Profiler, Clients, MessageQueue},
+//     world::{profiler::Profiler, Clients, MessageQueue, ReliableMessages},
 //     EncodedMessage, EncodedMessageQueue, MessageType, Transports,
 // };
 
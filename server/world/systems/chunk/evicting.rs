@@ -0,0 +1,37 @@
+use hashbrown::HashSet;
+use specs::{Join, ReadExpect, ReadStorage, System, WriteExpect};
+
+use crate::{ChunkInterests, Chunks, ClientFlag, CurrentChunkComp, WorldConfig};
+
+/// Keeps the number of loaded chunks within `config.max_loaded_chunks`, evicting the least recently
+/// accessed ones. Chunks a client is standing in or has an interest in are never evicted.
+pub struct ChunkEvictingSystem;
+
+impl<'a> System<'a> for ChunkEvictingSystem {
+    type SystemData = (
+        ReadExpect<'a, WorldConfig>,
+        ReadExpect<'a, ChunkInterests>,
+        WriteExpect<'a, Chunks>,
+        ReadStorage<'a, ClientFlag>,
+        ReadStorage<'a, CurrentChunkComp>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (config, interests, mut chunks, flags, curr_chunks) = data;
+
+        if config.max_loaded_chunks == 0
+            || !config.saving
+            || chunks.loaded_count() <= config.max_loaded_chunks
+        {
+            return;
+        }
+
+        let mut protected = interests.map.keys().cloned().collect::<HashSet<_>>();
+
+        for (curr_chunk, _) in (&curr_chunks, &flags).join() {
+            protected.insert(curr_chunk.coords.to_owned());
+        }
+
+        chunks.evict(&protected);
+    }
+}
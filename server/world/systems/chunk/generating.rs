@@ -2,18 +2,91 @@ use std::time::Instant;
 use std::{cmp::Ordering, collections::VecDeque};
 
 use hashbrown::{HashMap, HashSet};
-use log::info;
+use log::{error, warn};
 use nanoid::nanoid;
 use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use specs::{ReadExpect, ReadStorage, System, WriteExpect};
 
 use crate::world::profiler::Profiler;
 use crate::{
-    BlockUtils, Chunk, ChunkInterests, ChunkOptions, ChunkRequestsComp, ChunkStatus, ChunkUtils,
-    Chunks, Clients, Mesher, MessageType, Pipeline, PositionComp, Registry, Stats, Vec2, Vec3,
-    VoxelAccess, WorldConfig,
+    Chunk, ChunkInterests, ChunkOptions, ChunkProtocol, ChunkRequestsComp, ChunkStatus, ChunkUtils,
+    Chunks, ClientFilter, Clients, Mesher, Message, MessageQueue, MessageType, Pipeline, Registry,
+    Stats, StatusError, Vec2, Vec3, VoxelAccess, WorldConfig, MAX_GENERATION_ATTEMPTS,
 };
 
+/// Tell the clients interested in a chunk that it has moved on to a new status, so they can show
+/// how far along its generation is.
+fn send_status(
+    queue: &mut MessageQueue,
+    interests: &ChunkInterests,
+    coords: &Vec2<i32>,
+    status: &ChunkStatus,
+) {
+    let ids = match interests.get_interests(coords) {
+        Some(ids) if !ids.is_empty() => ids,
+        _ => return,
+    };
+
+    let chunk = ChunkProtocol {
+        x: coords.0,
+        z: coords.1,
+        status: status.name().to_owned(),
+        ..Default::default()
+    };
+
+    let message = Message::new(&MessageType::Status).chunks(&[chunk]).build();
+    queue.push((
+        message,
+        ClientFilter::Include(ids.iter().cloned().collect()),
+    ));
+}
+
+/// Add the chunks waiting on a chunk back to the pipeline or the mesher, depending on how far along
+/// they are, so that they check on it again.
+fn wake_listeners(
+    chunks: &mut Chunks,
+    pipeline: &mut Pipeline,
+    mesher: &mut Mesher,
+    coords: &Vec2<i32>,
+) {
+    if let Some(listeners) = chunks.listeners.remove(coords) {
+        for n_coords in listeners {
+            match chunks.raw(&n_coords).map(|chunk| &chunk.status) {
+                None | Some(ChunkStatus::Generating(_)) => pipeline.add_chunk(&n_coords, true),
+                Some(ChunkStatus::Meshing) => mesher.add_chunk(&n_coords, true),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Create an empty chunk at the given coordinates, shaped by the world's config.
+fn empty_chunk(coords: &Vec2<i32>, config: &WorldConfig) -> Chunk {
+    Chunk::new(
+        &nanoid!(),
+        coords.0,
+        coords.1,
+        &ChunkOptions {
+            max_height: config.max_height,
+            sub_chunks: config.sub_chunks,
+            size: config.chunk_size,
+            min_y: config.min_y,
+            air_id: config.air_id,
+        },
+    )
+}
+
+/// Report a job result that can't move its chunk on to its next status, which should never happen.
+/// The result is thrown away by the caller, leaving the chunk's live data where it is.
+fn reject_result(coords: &Vec2<i32>, error: &StatusError) {
+    error!("Chunk {:?} result was thrown away: {}", coords, error);
+    debug_assert!(
+        false,
+        "chunk {:?} result was thrown away: {}",
+        coords, error
+    );
+}
+
 #[derive(Default)]
 pub struct ChunkGeneratingSystem;
 
@@ -28,6 +101,7 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
         WriteExpect<'a, Pipeline>,
         WriteExpect<'a, Mesher>,
         WriteExpect<'a, Profiler>,
+        WriteExpect<'a, MessageQueue>,
         ReadStorage<'a, ChunkRequestsComp>,
     );
 
@@ -42,6 +116,7 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
             mut pipeline,
             mut mesher,
             mut profiler,
+            mut queue,
             requests,
         ) = data;
 
@@ -106,35 +181,70 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
                 let next_stage = curr_stage + 1;
 
                 if next_stage >= pipeline.stages.len() {
-                    chunk.status = ChunkStatus::Meshing;
+                    if let Err(error) = chunk
+                        .advance_status(ChunkStatus::Generated)
+                        .and_then(|_| chunk.advance_status(ChunkStatus::Meshing))
+                    {
+                        reject_result(&chunk.coords, &error);
+                        continue;
+                    }
+
                     mesher.add_chunk(&chunk.coords, false);
                     pipeline.remove_chunk(&chunk.coords);
+                    pipeline.failures.remove(&chunk.coords);
+                    send_status(&mut queue, &interests, &chunk.coords, &chunk.status);
                 } else {
-                    chunk.status = ChunkStatus::Generating(next_stage);
+                    if let Err(error) = chunk.advance_status(ChunkStatus::Generating(next_stage)) {
+                        reject_result(&chunk.coords, &error);
+                        continue;
+                    }
+
                     pipeline.add_chunk(&chunk.coords, false);
                 }
 
-                if let Some(listeners) = chunks.listeners.remove(&chunk.coords) {
-                    for n_coords in listeners {
-                        if !chunks.map.contains_key(&n_coords)
-                            || matches!(
-                                chunks.raw(&n_coords).unwrap().status,
-                                ChunkStatus::Generating(_)
-                            )
-                        {
-                            pipeline.add_chunk(&n_coords, true);
-                        } else if let Some(chunk) = chunks.raw(&n_coords) {
-                            if matches!(chunk.status, ChunkStatus::Meshing) {
-                                mesher.add_chunk(&n_coords, true);
-                            }
-                        }
-                    }
-                }
+                wake_listeners(&mut chunks, &mut pipeline, &mut mesher, &chunk.coords);
 
                 chunks.renew(chunk, false);
             }
         }
 
+        // Chunks whose stage panicked are thrown away and generated again from the first stage,
+        // with the chunks waiting on them woken up to wait on the new attempt instead. After too
+        // many attempts, the chunk is left empty and meshed as is.
+        for (coords, error) in pipeline.failed_chunks() {
+            chunks.remove(&coords);
+
+            let attempts = pipeline.failures.entry(coords.clone()).or_default();
+            *attempts += 1;
+
+            if *attempts < MAX_GENERATION_ATTEMPTS {
+                warn!(
+                    "Chunk {:?} failed to generate, regenerating: {}",
+                    coords, error
+                );
+                wake_listeners(&mut chunks, &mut pipeline, &mut mesher, &coords);
+                pipeline.add_chunk(&coords, true);
+                continue;
+            }
+
+            error!(
+                "Chunk {:?} failed to generate {} times, leaving it empty: {}",
+                coords, attempts, error
+            );
+            pipeline.failures.remove(&coords);
+
+            let mut chunk = empty_chunk(&coords, &config);
+            chunk
+                .advance_status(ChunkStatus::Generated)
+                .and_then(|_| chunk.advance_status(ChunkStatus::Meshing))
+                .unwrap();
+
+            mesher.add_chunk(&coords, false);
+            send_status(&mut queue, &interests, &coords, &chunk.status);
+            chunks.renew(chunk, false);
+            wake_listeners(&mut chunks, &mut pipeline, &mut mesher, &coords);
+        }
+
         /* -------------------------------------------------------------------------- */
         /*                       PUSHING CHUNKS TO BE PROCESSED                       */
         /* -------------------------------------------------------------------------- */
@@ -161,17 +271,12 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
                     continue;
                 }
 
-                let new_chunk = Chunk::new(
-                    &nanoid!(),
-                    coords.0,
-                    coords.1,
-                    &ChunkOptions {
-                        max_height: config.max_height,
-                        sub_chunks: config.sub_chunks,
-                        size: config.chunk_size,
-                    },
-                );
+                let mut new_chunk = empty_chunk(&coords, &config);
 
+                new_chunk
+                    .advance_status(ChunkStatus::Generating(0))
+                    .unwrap();
+                send_status(&mut queue, &interests, &coords, &new_chunk.status);
                 chunks.renew(new_chunk, false);
             }
 
@@ -251,7 +356,7 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
         // parallelize loading
         let loaded_chunks: Vec<(Vec2<i32>, Option<Chunk>)> = to_load
             .into_par_iter()
-            .map(|coords| (coords.to_owned(), chunks.try_load(&coords, &registry)))
+            .map(|coords| (coords.to_owned(), chunks.try_load(&coords)))
             .collect();
 
         for (coords, loaded_chunk) in loaded_chunks.into_iter() {
@@ -272,29 +377,20 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
 
         for (mut chunk, r#type) in mesher.results() {
             if r#type == MessageType::Load {
-                if let Some(listeners) = chunks.listeners.remove(&chunk.coords) {
-                    for n_coords in listeners {
-                        if !chunks.map.contains_key(&n_coords)
-                            || matches!(
-                                chunks.raw(&n_coords).unwrap().status,
-                                ChunkStatus::Generating(_)
-                            )
-                        {
-                            pipeline.add_chunk(&n_coords, true);
-                        } else if let Some(chunk) = chunks.raw(&n_coords) {
-                            if matches!(chunk.status, ChunkStatus::Meshing) {
-                                mesher.add_chunk(&n_coords, true);
-                            }
-                        }
-                    }
-                }
+                wake_listeners(&mut chunks, &mut pipeline, &mut mesher, &chunk.coords);
             }
 
-            chunk.status = ChunkStatus::Ready;
             let is_updating = r#type == MessageType::Update;
 
+            // Remeshed chunks are already ready.
             if !is_updating {
+                if let Err(error) = chunk.advance_status(ChunkStatus::Ready) {
+                    reject_result(&chunk.coords, &error);
+                    continue;
+                }
+
                 chunks.add_chunk_to_send(&chunk.coords, &r#type, false);
+                send_status(&mut queue, &interests, &chunk.coords, &chunk.status);
             }
 
             chunks.renew(chunk, is_updating);
@@ -312,64 +408,51 @@ impl<'a> System<'a> for ChunkGeneratingSystem {
 
         let mut ready_chunks = vec![];
 
+        let centers = clients
+            .values()
+            .filter_map(|client| requests.get(client.entity))
+            .map(|request| request.center.to_owned())
+            .collect::<Vec<_>>();
+
         while !mesher.queue.is_empty() {
             let coords = mesher.get().unwrap();
-            let mut ready = true;
 
-            for (i, n_coords) in chunks
-                .light_traversed_chunks(&coords)
-                .into_iter()
-                .enumerate()
-            {
-                if !chunks.map.contains_key(&n_coords) {
-                    ready = false;
-                    break;
-                }
+            // Wait for the neighbors on their way through the pipeline to be generated, to be added
+            // back by their listeners. Chunks with a neighbor that was never requested are left
+            // unmeshed, to be added back once a client requests them.
+            let ungenerated = chunks.ungenerated_neighbors(&coords);
+
+            if let Some(n_coords) = ungenerated.iter().find(|n_coords| {
+                chunks.map.contains_key(*n_coords)
+                    || pipeline.has_chunk(n_coords)
+                    || pipeline.queue.contains(n_coords)
+            }) {
+                chunks.add_listener(n_coords, &coords);
+                continue;
+            }
 
-                if let Some(n_chunk) = chunks.raw(&n_coords) {
-                    if matches!(n_chunk.status, ChunkStatus::Generating(_)) {
-                        ready = false;
-                        chunks.add_listener(&n_coords, &coords);
-                        break;
-                    }
-                }
+            if !ungenerated.is_empty() {
+                continue;
+            }
 
+            for n_coords in chunks.light_traversed_chunks(&coords) {
                 if let Some(blocks) = pipeline.leftovers.get(&n_coords) {
-                    for (j, (voxel, val)) in blocks.iter().enumerate() {
+                    // The chunks keep their height maps up to date as the voxels are set.
+                    for (voxel, val) in blocks.iter() {
                         let Vec3(vx, vy, vz) = *voxel;
                         chunks.set_raw_voxel(vx, vy, vz, *val);
-
-                        let height = chunks.get_max_height(vx, vz);
-                        let id = BlockUtils::extract_id(*val);
-
-                        if registry.is_air(id) {
-                            if vy == height as i32 {
-                                for y in (0..vy - 1).rev() {
-                                    if y == 0 || registry.check_height(chunks.get_voxel(vx, y, vz))
-                                    {
-                                        chunks.set_max_height(vx, vz, y as u32);
-                                        break;
-                                    }
-                                }
-                            }
-                        } else if height < vy as u32 {
-                            chunks.set_max_height(vx, vz, vy as u32);
-                        }
                     }
                 }
             }
 
-            if !ready {
-                continue;
-            }
-
             pipeline.leftovers.remove(&coords);
 
             if config.saving {
                 chunks.add_chunk_to_save(&coords, false);
             }
 
-            let chunk = chunks.raw(&coords).unwrap().clone();
+            let mut chunk = chunks.raw(&coords).unwrap().clone();
+            chunk.lod = chunks.get_lod(&coords, &centers);
             ready_chunks.push((coords, chunk));
         }
 
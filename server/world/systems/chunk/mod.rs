@@ -1,13 +1,17 @@
 mod current;
+mod evicting;
 mod generating;
 mod requests;
 mod saving;
 mod sending;
+mod streaming;
 mod updating;
 
 pub use current::CurrentChunkSystem;
+pub use evicting::ChunkEvictingSystem;
 pub use generating::ChunkGeneratingSystem;
 pub use requests::ChunkRequestsSystem;
 pub use saving::ChunkSavingSystem;
 pub use sending::ChunkSendingSystem;
+pub use streaming::ChunkStreamingSystem;
 pub use updating::ChunkUpdatingSystem;
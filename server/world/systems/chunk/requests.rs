@@ -41,6 +41,7 @@ impl<'a> System<'a> for ChunkRequestsSystem {
                     }
 
                     clients_to_send.insert(coords.clone());
+                    requests.loaded.insert(coords.clone());
                     interests.add(&id.0, &coords);
                 } else {
                     if !interests.has_interests(&coords) {
@@ -66,6 +67,7 @@ impl<'a> System<'a> for ChunkRequestsSystem {
             let chunks: Vec<ChunkProtocol> = coords
                 .into_iter()
                 .filter_map(|coords| {
+                    chunks.touch(&coords);
                     chunks
                         .get(&coords)
                         .map(|chunk| chunk.to_model(true, true, 0..config.sub_chunks as u32))
@@ -1,10 +1,11 @@
+use hashbrown::HashMap;
 use log::info;
-use specs::{Join, ReadExpect, ReadStorage, System, WriteExpect};
+use specs::{Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
 use std::collections::VecDeque;
 
 use crate::{
     ChunkInterests, ChunkRequestsComp, Chunks, ClientFilter, IDComp, Message, MessageQueue,
-    MessageType, WorldConfig,
+    MessageType, Vec2, WorldConfig,
 };
 
 #[derive(Default)]
@@ -23,11 +24,11 @@ impl<'a> System<'a> for ChunkSendingSystem {
         WriteExpect<'a, Chunks>,
         WriteExpect<'a, MessageQueue>,
         ReadStorage<'a, IDComp>,
-        ReadStorage<'a, ChunkRequestsComp>,
+        WriteStorage<'a, ChunkRequestsComp>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (config, interests, mut chunks, mut queue, ids, requests) = data;
+        let (config, interests, mut chunks, mut queue, ids, mut requests) = data;
 
         if chunks.to_send.is_empty() {
             return;
@@ -36,7 +37,12 @@ impl<'a> System<'a> for ChunkSendingSystem {
         let mut to_send = VecDeque::new();
         std::mem::swap(&mut chunks.to_send, &mut to_send);
 
+        // Client ID -> chunks loaded to the client, so they aren't requested again.
+        let mut loaded: HashMap<String, Vec<Vec2<i32>>> = HashMap::new();
+
         while let Some((coords, r#type)) = to_send.pop_front() {
+            chunks.touch(&coords);
+
             if let Some(chunk) = chunks.get_mut(&coords) {
                 for [mesh, data] in [[true, false], [false, true]] {
                     let mut messages = vec![];
@@ -76,6 +82,13 @@ impl<'a> System<'a> for ChunkSendingSystem {
                             for message in &messages {
                                 queue.push((message.clone(), ClientFilter::Direct(id.to_owned())));
                             }
+
+                            if r#type == MessageType::Load && data {
+                                loaded
+                                    .entry(id.to_owned())
+                                    .or_default()
+                                    .push(coords.to_owned());
+                            }
                         }
                     }
                 }
@@ -83,5 +96,15 @@ impl<'a> System<'a> for ChunkSendingSystem {
                 panic!("Something went wrong with sending chunks...");
             }
         }
+
+        if loaded.is_empty() {
+            return;
+        }
+
+        for (id, requests) in (&ids, &mut requests).join() {
+            if let Some(coords) = loaded.remove(&id.0) {
+                requests.loaded.extend(coords);
+            }
+        }
     }
 }
@@ -0,0 +1,135 @@
+use specs::{Entities, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
+
+use crate::{
+    ChunkInterests, ChunkProtocol, ChunkRequestsComp, Chunks, ClientFilter, CurrentChunkComp,
+    DirectionComp, IDComp, Mesher, Message, MessageQueue, MessageType, Pipeline, Vec2, WorldConfig,
+};
+
+/// Streams chunks to clients as they move, if `config.stream_radius` is set. Chunks coming into
+/// range are requested on behalf of the client, to be sent by the `ChunkRequestsSystem` once
+/// they're ready, and loaded chunks going out of range are unloaded with an `Unload` message.
+/// Clients may ask for a smaller radius through their `view_radius`, and chunks in the direction a
+/// client is facing are requested before the ones behind it.
+pub struct ChunkStreamingSystem;
+
+impl<'a> System<'a> for ChunkStreamingSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Chunks>,
+        ReadExpect<'a, WorldConfig>,
+        WriteExpect<'a, ChunkInterests>,
+        WriteExpect<'a, Pipeline>,
+        WriteExpect<'a, Mesher>,
+        WriteExpect<'a, MessageQueue>,
+        ReadStorage<'a, IDComp>,
+        ReadStorage<'a, CurrentChunkComp>,
+        ReadStorage<'a, DirectionComp>,
+        WriteStorage<'a, ChunkRequestsComp>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            chunks,
+            config,
+            mut interests,
+            mut pipeline,
+            mut mesher,
+            mut queue,
+            ids,
+            curr_chunks,
+            directions,
+            mut requests,
+        ) = data;
+
+        let max_radius = config.stream_radius;
+
+        if max_radius == 0 {
+            return;
+        }
+
+        for (entity, id, curr_chunk, requests) in
+            (&entities, &ids, &curr_chunks, &mut requests).join()
+        {
+            let radius = requests
+                .view_radius
+                .map_or(max_radius, |radius| radius.min(max_radius))
+                as i32;
+            let center = curr_chunk.coords.to_owned();
+            let in_range = |coords: &Vec2<i32>| {
+                let dx = coords.0 - center.0;
+                let dz = coords.1 - center.1;
+                dx * dx + dz * dz <= radius * radius
+            };
+
+            requests.requests.retain(|coords| in_range(coords));
+
+            // Chunks requested but not yet sent that are now out of range are no longer waited on.
+            // They're left to finish generating, as their neighbors may be waiting on them.
+            let abandoned = interests
+                .map
+                .iter()
+                .filter(|(coords, ids)| {
+                    ids.contains(&id.0) && !in_range(coords) && !requests.loaded.contains(*coords)
+                })
+                .map(|(coords, _)| coords.to_owned())
+                .collect::<Vec<_>>();
+
+            for coords in &abandoned {
+                interests.remove(&id.0, coords);
+            }
+
+            let mut unloaded = requests
+                .loaded
+                .iter()
+                .filter(|coords| !in_range(coords))
+                .cloned()
+                .collect::<Vec<_>>();
+            unloaded.sort_by_key(|coords| (coords.0, coords.1));
+
+            for coords in &unloaded {
+                requests.loaded.remove(coords);
+                interests.remove(&id.0, coords);
+
+                if !interests.has_interests(coords) {
+                    pipeline.remove_chunk(coords);
+                    mesher.remove_chunk(coords);
+                }
+            }
+
+            if !unloaded.is_empty() {
+                let chunks = unloaded
+                    .into_iter()
+                    .map(|Vec2(x, z)| ChunkProtocol {
+                        x,
+                        z,
+                        ..Default::default()
+                    })
+                    .collect::<Vec<_>>();
+
+                let message = Message::new(&MessageType::Unload).chunks(&chunks).build();
+                queue.push((message, ClientFilter::Direct(id.0.to_owned())));
+            }
+
+            for x in -radius..=radius {
+                for z in -radius..=radius {
+                    let coords = Vec2(center.0 + x, center.1 + z);
+
+                    if in_range(&coords)
+                        && chunks.is_within_world(&coords)
+                        && !requests.loaded.contains(&coords)
+                    {
+                        requests.add(&coords);
+                    }
+                }
+            }
+
+            if let Some(DirectionComp(direction)) = directions.get(entity) {
+                requests.set_direction(&Vec2(direction.0, direction.2));
+            }
+
+            requests.set_center(&center);
+            requests.sort();
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 
+use hashbrown::HashSet;
+
 use log::info;
 use nanoid::nanoid;
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
@@ -67,14 +69,72 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
         let mut blue_flood = VecDeque::default();
         let mut sun_flood = VecDeque::default();
 
-        if !chunks.updates.is_empty() {
-            let mut updates = VecDeque::default();
-            let total_updates = chunks.updates.len();
-
-            for _ in 0..max_updates_per_tick.min(total_updates) {
-                updates.push_back(chunks.updates.pop_front().unwrap());
+        let mut updates = VecDeque::default();
+
+        // Changes are taken in the order they were queued. A change to a chunk that's meshing, or
+        // whose neighbors aren't ready, is held back along with every later change to that chunk,
+        // so that an earlier change never lands on top of a later one.
+        let mut held = VecDeque::new();
+        let mut held_chunks = HashSet::new();
+        let mut taken = 0;
+
+        while taken < max_updates_per_tick {
+            let update = match chunks.updates.pop_front() {
+                Some(update) => update,
+                None => break,
+            };
+
+            let touched = update
+                .voxels()
+                .iter()
+                .map(|(Vec3(vx, vy, vz), _)| {
+                    ChunkUtils::map_voxel_to_chunk(*vx, *vy, *vz, config.chunk_size)
+                })
+                .filter(|coords| chunks.raw(coords).is_some())
+                .collect::<HashSet<_>>();
+
+            let ready = |coords: &Vec2<i32>| {
+                chunks.is_chunk_ready(coords)
+                    && !mesher.map.contains(coords)
+                    && chunks
+                        .light_traversed_chunks(coords)
+                        .iter()
+                        .all(|neighbor| chunks.is_chunk_ready(neighbor))
+            };
+
+            if touched
+                .iter()
+                .any(|coords| held_chunks.contains(coords) || !ready(coords))
+            {
+                held_chunks.extend(touched);
+                held.push_back(update);
+                continue;
             }
 
+            taken += 1;
+
+            // Updates to chunks that aren't loaded can never be applied, and are dropped.
+            updates.extend(
+                update
+                    .voxels()
+                    .iter()
+                    .filter(|(voxel, _)| {
+                        touched.contains(&ChunkUtils::map_voxel_to_chunk(
+                            voxel.0,
+                            voxel.1,
+                            voxel.2,
+                            config.chunk_size,
+                        ))
+                    })
+                    .cloned(),
+            );
+        }
+
+        while let Some(update) = held.pop_back() {
+            chunks.updates.push_front(update);
+        }
+
+        if !updates.is_empty() {
             while let Some((voxel, raw)) = updates.pop_front() {
                 let Vec3(vx, vy, vz) = voxel;
 
@@ -83,40 +143,19 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
                 let stage = BlockUtils::extract_stage(raw);
                 let coords = ChunkUtils::map_voxel_to_chunk(vx, vy, vz, config.chunk_size);
 
-                if vy < 0 || vy >= config.max_height as i32 || !registry.has_type(updated_id) {
-                    continue;
-                }
-
-                if !chunks.is_chunk_ready(&coords) {
-                    continue;
-                }
-
-                if mesher.map.contains(&coords) {
-                    chunks.update_voxel(&voxel, raw);
-                    continue;
-                }
-
-                let mut ready = true;
-
-                for neighbor in chunks.light_traversed_chunks(&coords) {
-                    if ready && !chunks.is_chunk_ready(&neighbor) {
-                        ready = false;
-                    }
-                }
-
-                if !ready {
-                    chunks.update_voxel(&voxel, raw);
+                if vy < config.min_y
+                    || vy >= config.max_height as i32
+                    || !registry.has_type(updated_id)
+                {
                     continue;
                 }
 
                 let current_id = chunks.get_voxel(vx, vy, vz);
-                if registry.is_air(updated_id) && registry.is_air(current_id) {
+                if updated_id == config.air_id && current_id == config.air_id {
                     continue;
                 }
 
                 // Actually updating the voxel.
-                let height = chunks.get_max_height(vx, vz);
-
                 let current_type = registry.get_block_by_id(current_id);
                 let updated_type = registry.get_block_by_id(updated_id);
 
@@ -162,9 +201,16 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
                 };
 
                 chunks.set_voxel(vx, vy, vz, updated_id);
+                chunks.touch(&coords);
+
+                if current_id != updated_id {
+                    chunks
+                        .block_changes
+                        .push((voxel.to_owned(), current_id, updated_id));
+                }
 
                 if stage != 0 {
-                    chunks.set_voxel_stage(vx, vy, vz, stage);
+                    chunks.set_voxel_stage(vx, vy, vz, updated_type.clamp_stage(stage));
                 }
 
                 if updated_type.is_active {
@@ -176,27 +222,35 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
                     chunks.mark_voxel_active(&Vec3(vx, vy, vz), ticks + current_tick);
                 }
 
-                if updated_type.rotatable || updated_type.y_rotatable {
-                    chunks.set_voxel_rotation(vx, vy, vz, &rotation);
-                }
-
-                // updating the height map
-                if registry.is_air(updated_id) {
-                    if vy == height as i32 {
-                        // on max height, should set max height to lower
-                        for y in (0..vy).rev() {
-                            if y == 0 || registry.check_height(chunks.get_voxel(vx, y, vz)) {
-                                chunks.set_max_height(vx, vz, y as u32);
-                                break;
-                            }
+                // Fluids resting against a removed block wake up to flow into the gap.
+                if updated_type.is_empty {
+                    for [dx, dy, dz] in [[0, 1, 0], [-1, 0, 0], [1, 0, 0], [0, 0, -1], [0, 0, 1]] {
+                        let neighbor = Vec3(vx + dx, vy + dy, vz + dz);
+                        let neighbor_type = registry
+                            .get_block_by_id(chunks.get_voxel(neighbor.0, neighbor.1, neighbor.2));
+
+                        if neighbor_type.is_fluid && neighbor_type.is_active {
+                            let ticks = neighbor_type.active_ticker.as_ref().unwrap()(
+                                neighbor.clone(),
+                                &*chunks,
+                                &registry,
+                            );
+                            chunks.mark_voxel_active(&neighbor, ticks + current_tick);
                         }
                     }
-                } else if height < vy as u32 {
-                    chunks.set_max_height(vx, vz, vy as u32);
+                }
+
+                if updated_type.rotatable || updated_type.y_rotatable {
+                    chunks.set_voxel_rotation(vx, vy, vz, &rotation);
                 }
 
                 // Updating light levels...
 
+                // Blocks that filter torch light without blocking it leave sunlight alone.
+                let filters_only = updated_type.has_light_filter()
+                    && !updated_type.is_opaque
+                    && !updated_type.light_reduce;
+
                 // Straight up updating to a solid opaque block, remove all lights.
                 if updated_type.is_opaque || updated_type.light_reduce {
                     if chunks.get_sunlight(vx, vy, vz) != 0 {
@@ -212,6 +266,19 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
                         Lights::remove_light(&mut *chunks, &voxel, &BLUE, &config, &registry);
                     }
                 }
+                // Filtering blocks only filter torch light, so only those channels are removed, to
+                // be flooded back in filtered.
+                else if filters_only {
+                    if chunks.get_torch_light(vx, vy, vz, &RED) != 0 {
+                        Lights::remove_light(&mut *chunks, &voxel, &RED, &config, &registry);
+                    }
+                    if chunks.get_torch_light(vx, vy, vz, &GREEN) != 0 {
+                        Lights::remove_light(&mut *chunks, &voxel, &GREEN, &config, &registry);
+                    }
+                    if chunks.get_torch_light(vx, vy, vz, &BLUE) != 0 {
+                        Lights::remove_light(&mut *chunks, &voxel, &BLUE, &config, &registry);
+                    }
+                }
                 // Otherwise, check if light could originally go from source to neighbor, but not in the updated block. Also, check
                 // to see if neighbor light source is not zero and is less than the block itself, or if its sunlight and its going
                 // downwards and both are max light levels.
@@ -227,7 +294,7 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
 
                     VOXEL_NEIGHBORS.iter().for_each(|&[ox, oy, oz]| {
                         let nvy = vy + oy;
-                        if nvy < 0 || nvy >= max_height {
+                        if nvy < config.min_y || nvy >= max_height {
                             return;
                         }
 
@@ -332,20 +399,22 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
                     VOXEL_NEIGHBORS.iter().for_each(|&[ox, oy, oz]| {
                         let nvy = vy + oy;
 
-                        if nvy < 0 {
+                        if nvy < config.min_y {
                             return;
                         }
 
                         // Sunlight should propagate downwards here.
                         if nvy >= max_height {
                             // Light can go downwards into this block.
-                            if Lights::can_enter(
-                                &ALL_TRANSPARENT,
-                                &updated_transparency,
-                                ox,
-                                -1,
-                                oz,
-                            ) {
+                            if !filters_only
+                                && Lights::can_enter(
+                                    &ALL_TRANSPARENT,
+                                    &updated_transparency,
+                                    ox,
+                                    -1,
+                                    oz,
+                                )
+                            {
                                 sun_flood.push_back(LightNode {
                                     voxel: [vx + ox, vy, vz + oz],
                                     level: max_light_level,
@@ -385,7 +454,7 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
 
                         let level = chunks.get_sunlight(nvx, nvy, nvz)
                             - if updated_type.light_reduce { 1 } else { 0 };
-                        if level != 0 {
+                        if level != 0 && !filters_only {
                             sun_flood.push_back(LightNode {
                                 voxel: n_voxel,
                                 level,
@@ -490,6 +559,15 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
 
                 cache.iter().for_each(|coords| {
                     chunks.add_chunk_to_save(coords, true);
+
+                    // Coarse cells reach further than the updated levels and the ring of voxels the
+                    // mesh keys cover, so level of detail meshes are rebuilt whole.
+                    if let Some(chunk) = chunks.raw_mut(coords) {
+                        if chunk.lod > 0 {
+                            chunk.mesh_keys.clear();
+                            chunk.updated_levels.extend(0..config.sub_chunks as u32);
+                        }
+                    }
                 });
 
                 let processes = cache
@@ -521,7 +599,11 @@ impl<'a> System<'a> for ChunkUpdatingSystem {
                 .collect::<Vec<UpdateProtocol>>();
 
             if !results.is_empty() {
-                let new_message = Message::new(&MessageType::Update).updates(&results).build();
+                // Block edits must reach every client, so they're resent until acknowledged.
+                let new_message = Message::new(&MessageType::Update)
+                    .updates(&results)
+                    .reliable()
+                    .build();
                 message_queue.push((new_message, ClientFilter::All));
             }
         }
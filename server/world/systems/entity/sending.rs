@@ -1,19 +1,33 @@
 use hashbrown::{HashMap, HashSet};
 use log::{info, trace};
-use specs::{Entities, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
+use specs::{Entities, Entity, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
 
 use crate::{
-    Bookkeeping, ClientFilter, ETypeComp, EntitiesSaver, EntityFlag, EntityOperation,
-    EntityProtocol, IDComp, InteractorComp, Message, MessageQueue, MessageType, MetadataComp,
-    Physics, Stats,
+    Bookkeeping, ChunkInterests, ChunkUtils, ClientFilter, ClientFlag, ETypeComp, EntitiesSaver,
+    EntityFlag, EntityInterests, EntityOperation, EntityProtocol, IDComp, InteractorComp, Message,
+    MessageQueue, MessageType, MetadataComp, Physics, PositionComp, Vec2, Vec3, WorldConfig,
 };
 
+/// The position of an entity, going by its metadata.
+fn position_of(metadata: &MetadataComp) -> Option<Vec3<f32>> {
+    metadata
+        .get::<PositionComp>("position")
+        .map(|position| position.0)
+}
+
+/// Sends the entities created, updated and deleted this tick to the clients. Entities with a
+/// position are only sent to the clients they're in range of, within `config.entity_radius` or in
+/// a chunk the client has loaded, and each client is sent an `Enter` or a `Leave` as an entity
+/// comes into or goes out of its range. Entities without a position are sent to all clients.
 pub struct EntitiesSendingSystem;
 
 impl<'a> System<'a> for EntitiesSendingSystem {
     type SystemData = (
         Entities<'a>,
         ReadExpect<'a, EntitiesSaver>,
+        ReadExpect<'a, ChunkInterests>,
+        ReadExpect<'a, WorldConfig>,
+        WriteExpect<'a, EntityInterests>,
         WriteExpect<'a, MessageQueue>,
         WriteExpect<'a, Bookkeeping>,
         WriteExpect<'a, Physics>,
@@ -21,6 +35,8 @@ impl<'a> System<'a> for EntitiesSendingSystem {
         ReadStorage<'a, IDComp>,
         ReadStorage<'a, ETypeComp>,
         ReadStorage<'a, InteractorComp>,
+        ReadStorage<'a, ClientFlag>,
+        ReadStorage<'a, PositionComp>,
         WriteStorage<'a, MetadataComp>,
     );
 
@@ -28,6 +44,9 @@ impl<'a> System<'a> for EntitiesSendingSystem {
         let (
             entities,
             entities_saver,
+            interests,
+            config,
+            mut entity_interests,
             mut queue,
             mut bookkeeping,
             mut physics,
@@ -35,6 +54,8 @@ impl<'a> System<'a> for EntitiesSendingSystem {
             ids,
             etypes,
             interactors,
+            client_flags,
+            positions,
             mut metadatas,
         ) = data;
 
@@ -91,12 +112,15 @@ impl<'a> System<'a> for EntitiesSendingSystem {
                     physics.unregister(body_handle, collider_handle);
                 }
 
-                entity_updates.push(EntityProtocol {
-                    operation: EntityOperation::Delete,
-                    id: id.to_owned(),
-                    r#type: etype.to_owned(),
-                    metadata: Some(metadata.to_string()),
-                });
+                entity_updates.push((
+                    EntityProtocol {
+                        operation: EntityOperation::Delete,
+                        id: id.to_owned(),
+                        r#type: etype.to_owned(),
+                        metadata: Some(metadata.to_string()),
+                    },
+                    position_of(metadata),
+                ));
             });
 
         physics.entity_to_handlers = new_entity_handlers;
@@ -121,6 +145,9 @@ impl<'a> System<'a> for EntitiesSendingSystem {
 
         let mut new_bookkeeping_records = HashMap::new();
 
+        // Every entity with a position this tick, checked against the range of each client.
+        let mut positioned = vec![];
+
         for (ent, id, metadata, etype, _) in
             (&entities, &ids, &mut metadatas, &etypes, &flags).join()
         {
@@ -134,13 +161,27 @@ impl<'a> System<'a> for EntitiesSendingSystem {
                 (etype.0.to_owned(), ent, metadata.to_owned()),
             );
 
+            let position = position_of(metadata);
+
+            if let Some(position) = &position {
+                positioned.push((
+                    id.0.to_owned(),
+                    etype.0.to_owned(),
+                    position.to_owned(),
+                    ent,
+                ));
+            }
+
             if new_entity_ids.contains(&id.0) {
-                entity_updates.push(EntityProtocol {
-                    operation: EntityOperation::Create,
-                    id: id.0.to_owned(),
-                    r#type: etype.0.to_owned(),
-                    metadata: Some(metadata.to_string()),
-                });
+                entity_updates.push((
+                    EntityProtocol {
+                        operation: EntityOperation::Create,
+                        id: id.0.to_owned(),
+                        r#type: etype.0.to_owned(),
+                        metadata: Some(metadata.to_string()),
+                    },
+                    position,
+                ));
 
                 continue;
             }
@@ -151,25 +192,196 @@ impl<'a> System<'a> for EntitiesSendingSystem {
                 continue;
             }
 
-            entity_updates.push(EntityProtocol {
-                operation: EntityOperation::Update,
-                id: id.0.to_owned(),
-                r#type: etype.0.to_owned(),
-                metadata: Some(json_str),
-            });
+            entity_updates.push((
+                EntityProtocol {
+                    operation: EntityOperation::Update,
+                    id: id.0.to_owned(),
+                    r#type: etype.0.to_owned(),
+                    metadata: Some(json_str),
+                },
+                position,
+            ));
 
             metadata.reset();
         }
 
         bookkeeping.entities = new_bookkeeping_records;
 
-        if !entity_updates.is_empty() {
+        let mut broadcast = vec![];
+        let mut deleted = vec![];
+        let mut changes = HashMap::new();
+
+        for (update, position) in entity_updates {
+            match (position, update.operation) {
+                (None, _) => broadcast.push(update),
+                (Some(_), EntityOperation::Delete) => deleted.push(update),
+                (Some(_), _) => {
+                    changes.insert(update.id.to_owned(), update);
+                }
+            }
+        }
+
+        if !broadcast.is_empty() {
             queue.push((
                 Message::new(&MessageType::Entity)
-                    .entities(&entity_updates)
+                    .entities(&broadcast)
                     .build(),
                 ClientFilter::All,
             ));
         }
+
+        let mut clients = (&ids, &positions, &client_flags)
+            .join()
+            .map(|(id, position, _)| (id.0.to_owned(), position.0.to_owned()))
+            .collect::<Vec<_>>();
+        clients.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let radius = config.entity_radius;
+        let chunk_of = |Vec3(px, py, pz): &Vec3<f32>| {
+            ChunkUtils::map_voxel_to_chunk(*px as i32, *py as i32, *pz as i32, config.chunk_size)
+        };
+
+        // Positioned entities by the chunk they're in, so that each client only checks the ones in
+        // the chunks around it instead of every entity in the world.
+        let mut by_chunk: HashMap<Vec2<i32>, Vec<usize>> = HashMap::new();
+        let mut by_id = HashMap::new();
+
+        for (index, (id, _, position, _)) in positioned.iter().enumerate() {
+            by_chunk.entry(chunk_of(position)).or_default().push(index);
+            by_id.insert(id.as_str(), index);
+        }
+
+        // Without a radius, a client's range is the chunks it has loaded.
+        let mut loaded: HashMap<&str, Vec<usize>> = HashMap::new();
+
+        if radius <= 0.0 {
+            for (coords, indices) in by_chunk.iter() {
+                if let Some(clients) = interests.get_interests(coords) {
+                    for client_id in clients {
+                        loaded.entry(client_id).or_default().extend(indices);
+                    }
+                }
+            }
+        }
+
+        // The full metadata of the entities entering or leaving a client's range, serialized once
+        // per tick at most. Entities changed this tick already have theirs in the change.
+        let mut serialized = HashMap::new();
+        let mut metadata_of = |id: &String, ent: Entity| {
+            serialized
+                .entry(id.to_owned())
+                .or_insert_with(|| match changes.get(id) {
+                    Some(change) => change.metadata.to_owned().unwrap_or_default(),
+                    None => metadatas
+                        .get(ent)
+                        .map(|metadata| metadata.to_string())
+                        .unwrap_or_default(),
+                })
+                .to_owned()
+        };
+
+        for (client_id, client_position) in clients {
+            let in_view = if radius > 0.0 {
+                let Vec3(cx, _, cz) = client_position;
+                let Vec2(ccx, ccz) = chunk_of(&client_position);
+
+                // One chunk of slack, as positions are truncated towards zero into voxels.
+                let reach = (radius / config.chunk_size as f32).ceil() as i32 + 1;
+                let mut in_view = HashSet::new();
+
+                for x in (ccx - reach)..=(ccx + reach) {
+                    for z in (ccz - reach)..=(ccz + reach) {
+                        for &index in by_chunk.get(&Vec2(x, z)).into_iter().flatten() {
+                            let Vec3(px, _, pz) = positioned[index].2;
+                            let (dx, dz) = (px - cx, pz - cz);
+
+                            if dx * dx + dz * dz <= radius * radius {
+                                in_view.insert(index);
+                            }
+                        }
+                    }
+                }
+
+                in_view
+            } else {
+                loaded
+                    .remove(client_id.as_str())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect::<HashSet<_>>()
+            };
+
+            let visible = entity_interests
+                .map
+                .entry(client_id.to_owned())
+                .or_default();
+            let mut updates = vec![];
+
+            for update in &deleted {
+                if visible.remove(&update.id) {
+                    updates.push(update.clone());
+                }
+            }
+
+            // The entities in range, and the ones in view that have left it, in join order.
+            let mut indices = in_view.iter().copied().collect::<Vec<_>>();
+            indices.extend(
+                visible
+                    .iter()
+                    .filter_map(|id| by_id.get(id.as_str()))
+                    .filter(|index| !in_view.contains(*index)),
+            );
+            indices.sort_unstable();
+
+            for index in indices {
+                let (id, etype, _, ent) = &positioned[index];
+                let change = changes.get(id);
+                let in_view = in_view.contains(&index);
+
+                // Entities staying in range only need their changes, if any.
+                if in_view && visible.contains(id) {
+                    if let Some(change) = change {
+                        updates.push(change.clone());
+                    }
+
+                    continue;
+                }
+
+                if !in_view {
+                    visible.remove(id);
+                    updates.push(EntityProtocol {
+                        operation: EntityOperation::Leave,
+                        id: id.to_owned(),
+                        r#type: etype.to_owned(),
+                        metadata: Some(metadata_of(id, *ent)),
+                    });
+
+                    continue;
+                }
+
+                visible.insert(id.to_owned());
+
+                match change {
+                    Some(change) if change.operation == EntityOperation::Create => {
+                        updates.push(change.clone())
+                    }
+                    _ => updates.push(EntityProtocol {
+                        operation: EntityOperation::Enter,
+                        id: id.to_owned(),
+                        r#type: etype.to_owned(),
+                        metadata: Some(metadata_of(id, *ent)),
+                    }),
+                }
+            }
+
+            if !updates.is_empty() {
+                queue.push((
+                    Message::new(&MessageType::Entity)
+                        .entities(&updates)
+                        .build(),
+                    ClientFilter::Direct(client_id),
+                ));
+            }
+        }
     }
 }
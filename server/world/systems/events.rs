@@ -3,8 +3,8 @@ use serde_json::Value;
 use specs::{Entity, ReadExpect, ReadStorage, System, WriteExpect};
 
 use crate::{
-    encode_message, ChunkInterests, ChunkRequestsComp, ClientFilter, Clients, EncodedMessage,
-    Event, EventProtocol, Events, IDComp, Message, MessageType, Transports, Vec2,
+    encode_message, ChunkInterests, ClientFilter, Clients, EncodedMessage, Event, EventProtocol,
+    Events, IDComp, Message, MessageType, Transports, Vec2,
 };
 
 pub struct EventsSystem;
@@ -12,15 +12,14 @@ pub struct EventsSystem;
 impl<'a> System<'a> for EventsSystem {
     type SystemData = (
         ReadExpect<'a, Transports>,
-        ReadExpect<'a, Clients>,
+        WriteExpect<'a, Clients>,
         ReadExpect<'a, ChunkInterests>,
         WriteExpect<'a, Events>,
         ReadStorage<'a, IDComp>,
-        ReadStorage<'a, ChunkRequestsComp>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (transports, clients, interests, mut events, ids, requests) = data;
+        let (transports, mut clients, interests, mut events, ids) = data;
 
         if events.queue.is_empty() {
             return;
@@ -125,7 +124,7 @@ impl<'a> System<'a> for EventsSystem {
                 return;
             }
 
-            let client = clients.get(&id);
+            let client = clients.get_mut(&id);
 
             if client.is_none() {
                 return;
@@ -135,7 +134,7 @@ impl<'a> System<'a> for EventsSystem {
             let message = Message::new(&MessageType::Event).events(&events).build();
             let encoded = EncodedMessage(encode_message(&message));
 
-            client.addr.do_send(encoded);
+            let _ = client.send(encoded);
         });
 
         if !transports.is_empty() {
@@ -56,7 +56,7 @@ impl<'a> System<'a> for PathFindingSystem {
 
         let get_standable_voxel = |voxel: &Vec3<i32>| -> Vec3<i32> {
             let mut voxel = voxel.clone();
-            let min_y = 0; // Ensure this is the absolute minimum Y value in your game world.
+            let min_y = config.min_y;
 
             // Ensure we start within the world bounds.
             if voxel.1 < min_y {
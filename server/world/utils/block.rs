@@ -1,4 +1,6 @@
-use crate::world::voxels::{BlockRotation, ROTATION_MASK, STAGE_MASK, Y_ROTATION_MASK};
+use crate::world::voxels::{
+    BlockRotation, ROTATION_MASK, STAGE_MASK, WATERLOGGED_MASK, Y_ROTATION_MASK,
+};
 
 /// A set of utility functions for block operations.
 pub struct BlockUtils;
@@ -39,6 +41,16 @@ impl BlockUtils {
 
         (voxel & STAGE_MASK) | (stage << 24)
     }
+
+    /// Extract the bit in voxel that stores whether the voxel is waterlogged.
+    pub fn extract_waterlogged(voxel: u32) -> bool {
+        (voxel >> 28) & 0x1 == 1
+    }
+
+    /// Insert whether a voxel is waterlogged into voxel value.
+    pub fn insert_waterlogged(voxel: u32, waterlogged: bool) -> u32 {
+        (voxel & WATERLOGGED_MASK) | ((waterlogged as u32) << 28)
+    }
 }
 
 #[derive(Default)]
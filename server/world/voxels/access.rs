@@ -1,4 +1,4 @@
-use crate::{BlockUtils, LightColor, LightUtils, Ndarray};
+use crate::{BlockUtils, LightColor, LightUtils, Ndarray, Registry};
 
 use super::block::BlockRotation;
 
@@ -55,12 +55,43 @@ pub trait VoxelAccess {
         BlockUtils::extract_stage(self.get_raw_voxel(vx, vy, vz))
     }
 
-    /// Set the voxel stage at a voxel coordinate. Does nothing if chunk isn't found.
+    /// Set the voxel stage at a voxel coordinate. Does nothing if chunk isn't found. The stage isn't
+    /// checked against the block's `max_stage`, use `set_block_stage` for that.
     fn set_voxel_stage(&mut self, vx: i32, vy: i32, vz: i32, stage: u32) -> bool {
         let value = BlockUtils::insert_stage(self.get_raw_voxel(vx, vy, vz), stage);
         self.set_raw_voxel(vx, vy, vz, value)
     }
 
+    /// Set the stage of the block at a voxel coordinate. Does nothing and returns false if the stage
+    /// is past the block's `max_stage`, the block isn't registered, or chunk isn't found.
+    fn set_block_stage(
+        &mut self,
+        registry: &Registry,
+        vx: i32,
+        vy: i32,
+        vz: i32,
+        stage: u32,
+    ) -> bool {
+        let id = self.get_voxel(vx, vy, vz);
+
+        if !registry.has_type(id) || stage > registry.get_block_by_id(id).max_stage {
+            return false;
+        }
+
+        self.set_voxel_stage(vx, vy, vz, stage)
+    }
+
+    /// Get whether a voxel also holds a fluid. Returns false if chunk isn't found.
+    fn is_waterlogged(&self, vx: i32, vy: i32, vz: i32) -> bool {
+        BlockUtils::extract_waterlogged(self.get_raw_voxel(vx, vy, vz))
+    }
+
+    /// Set whether a voxel also holds a fluid. Does nothing if chunk isn't found.
+    fn set_waterlogged(&mut self, vx: i32, vy: i32, vz: i32, waterlogged: bool) -> bool {
+        let value = BlockUtils::insert_waterlogged(self.get_raw_voxel(vx, vy, vz), waterlogged);
+        self.set_raw_voxel(vx, vy, vz, value)
+    }
+
     /// Get the sunlight level at a voxel position. Returns 0 if chunk does not exist.
     fn get_sunlight(&self, vx: i32, vy: i32, vz: i32) -> u32 {
         LightUtils::extract_sunlight(self.get_raw_light(vx, vy, vz))
@@ -148,16 +179,23 @@ pub trait VoxelAccess {
         }
     }
 
-    /// Get the max height at a voxel column. Returns 0 if column does not exist.
+    /// Get the max height at a voxel column, counted in voxels up from the world's `min_y`. Returns
+    /// 0 if column does not exist.
     fn get_max_height(&self, vx: i32, vz: i32) -> u32 {
         todo!("Voxel access `get_max_height` is not implemented.");
     }
 
-    /// Set the max height at a voxel column. Does nothing if column does not exist.
+    /// Set the max height at a voxel column, counted in voxels up from the world's `min_y`. Does
+    /// nothing if column does not exist.
     fn set_max_height(&mut self, vx: i32, vz: i32, height: u32) -> bool {
         todo!("Voxel access `set_max_height` is not implemented.");
     }
 
+    /// The lowest voxel y-coordinate, which max heights are counted from.
+    fn min_y(&self) -> i32 {
+        0
+    }
+
     /// Get a reference of voxel n-dimensional array.
     fn get_voxels(&self, cx: i32, cz: i32) -> Option<&Ndarray<u32>> {
         todo!("Voxel assess `get_voxels` is not implemented.");
@@ -1,7 +1,8 @@
 use std::{
-    f32,
+    f32, fs,
     marker::Sync,
     ops::{Deref, DerefMut},
+    path::Path,
     sync::Arc,
 };
 
@@ -9,7 +10,8 @@ use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    BlockUtils, LightColor, LightUtils, Registry, Vec2, Vec3, VoxelAccess, VoxelUpdate, AABB, UV,
+    BlockBuildError, BlockConflict, BlockUtils, ImportError, LightColor, LightUtils, Registry,
+    Vec3, VoxelAccess, VoxelPacker, VoxelUpdate, AABB, UV, WHITE,
 };
 
 /// Base class to extract voxel data from a single u32
@@ -18,8 +20,8 @@ use crate::{
 /// - `1 - 16 bits`: ID (0x0000FFFF)
 /// - `17 - 20 bit`: rotation (0x000F0000)
 /// - `21 - 24 bit`: y rotation (0x00F00000)
-/// - `25 - 32 bit`: stage (0x0F000000)
-
+/// - `25 - 28 bit`: stage (0x0F000000)
+/// - `29 bit`: waterlogged (0x10000000)
 pub const PY_ROTATION: u32 = 0;
 pub const NY_ROTATION: u32 = 1;
 pub const PX_ROTATION: u32 = 2;
@@ -32,6 +34,7 @@ pub const Y_ROT_SEGMENTS: u32 = 16;
 pub const ROTATION_MASK: u32 = 0xFFF0FFFF;
 pub const Y_ROTATION_MASK: u32 = 0xFF0FFFFF;
 pub const STAGE_MASK: u32 = 0xF0FFFFFF;
+pub const WATERLOGGED_MASK: u32 = 0xEFFFFFFF;
 
 /// Block rotation enumeration. There are 6 possible rotations: `(px, nx, py, ny, pz, nz)`. Default rotation is PY.
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -229,43 +232,21 @@ impl BlockRotation {
         self.rotate_node(&mut positive, true, false);
         self.rotate_node(&mut negative, true, false);
 
-        let p: Vec<bool> = positive
-            .into_iter()
-            .map(|n| {
-                if n == 1.0 {
-                    px
-                } else if n == 2.0 {
-                    py
-                } else if n == 3.0 {
-                    pz
-                } else if n == 4.0 {
-                    nx
-                } else if n == 5.0 {
-                    ny
-                } else {
-                    nz
-                }
-            })
-            .collect();
+        // A side that got flipped around ends up negated, facing the opposite way. Y rotations
+        // between the axes don't land exactly on a side, and are clamped to one of them.
+        let sides = [px, py, pz, nx, ny, nz];
+        let side = |n: f32| {
+            let index = (n.abs().round() as usize).clamp(1, 6) - 1;
 
-        let n: Vec<bool> = negative
-            .into_iter()
-            .map(|n| {
-                if n == 1.0 {
-                    px
-                } else if n == 2.0 {
-                    py
-                } else if n == 3.0 {
-                    pz
-                } else if n == 4.0 {
-                    nx
-                } else if n == 5.0 {
-                    ny
-                } else {
-                    nz
-                }
-            })
-            .collect();
+            if n > 0.0 {
+                sides[index]
+            } else {
+                sides[(index + 3) % 6]
+            }
+        };
+
+        let p: Vec<bool> = positive.into_iter().map(side).collect();
+        let n: Vec<bool> = negative.into_iter().map(side).collect();
 
         [p[0], p[1], p[2], n[0], n[1], n[2]]
     }
@@ -325,6 +306,8 @@ pub struct BlockFace {
     pub dir: [i32; 3],
     pub corners: [CornerData; 4],
     pub range: UV,
+    #[serde(skip)]
+    normal: [f32; 3],
 }
 
 impl BlockFace {
@@ -335,16 +318,192 @@ impl BlockFace {
         dir: [i32; 3],
         corners: [CornerData; 4],
     ) -> Self {
-        Self {
+        let mut face = Self {
             name,
             independent,
             isolated,
             dir,
             corners,
             range: UV::default(),
+            normal: [0.0; 3],
+        };
+
+        face.normal = face.compute_normal();
+        face
+    }
+
+    /// The outward normal of this face. Axis-aligned faces have unit axis normals, and the faces
+    /// of a diagonal cross have normals 45 degrees off the axes. Cached when the face is built,
+    /// and derived again for faces that were deserialized without one.
+    pub fn normal(&self) -> Vec3<f32> {
+        let [x, y, z] = if self.normal == [0.0; 3] {
+            self.compute_normal()
+        } else {
+            self.normal
+        };
+
+        Vec3(x, y, z)
+    }
+
+    /// Derive the normal from the first three corners, wound counter-clockwise.
+    fn compute_normal(&self) -> [f32; 3] {
+        let [x0, y0, z0] = self.corners[0].pos;
+        let [x1, y1, z1] = self.corners[1].pos;
+        let [x2, y2, z2] = self.corners[2].pos;
+
+        let (ax, ay, az) = (x1 - x0, y1 - y0, z1 - z0);
+        let (bx, by, bz) = (x2 - x0, y2 - y0, z2 - z0);
+
+        let normal = [ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx];
+        let length = (normal[0].powi(2) + normal[1].powi(2) + normal[2].powi(2)).sqrt();
+
+        if length > 0.0 {
+            [normal[0] / length, normal[1] / length, normal[2] / length]
+        } else {
+            [0.0; 3]
         }
     }
 
+    /// Import the faces of an OBJ model, such as stairs modeled in an external editor. Triangles
+    /// and quads become one face each, named after the object or group they're in. Every vertex
+    /// must be within the unit cube.
+    pub fn from_obj(path: &Path) -> Result<Vec<BlockFace>, ImportError> {
+        let source = fs::read_to_string(path).map_err(|e| ImportError::Io(e.to_string()))?;
+        Self::parse_obj(&source)
+    }
+
+    /// Parse the faces of an OBJ model from its source. See `BlockFace::from_obj`.
+    pub fn parse_obj(source: &str) -> Result<Vec<BlockFace>, ImportError> {
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        let mut faces = vec![];
+        let mut name = "obj".to_owned();
+
+        // OBJ indices start at 1, and negative indices count back from the latest element.
+        let resolve = |index: &str, len: usize, line: usize| -> Result<usize, ImportError> {
+            let index = index
+                .parse::<i64>()
+                .map_err(|_| ImportError::Parse(line, format!("invalid index `{index}`")))?;
+            let resolved = if index < 0 {
+                len as i64 + index
+            } else {
+                index - 1
+            };
+
+            if resolved < 0 || resolved >= len as i64 {
+                return Err(ImportError::Parse(
+                    line,
+                    format!("index {index} out of range"),
+                ));
+            }
+
+            Ok(resolved as usize)
+        };
+
+        for (number, text) in source.lines().enumerate() {
+            let line = number + 1;
+            let mut parts = text.split_whitespace();
+
+            let floats = |parts: std::str::SplitWhitespace| -> Result<Vec<f32>, ImportError> {
+                parts
+                    .map(|part| {
+                        part.parse::<f32>().map_err(|_| {
+                            ImportError::Parse(line, format!("invalid number `{part}`"))
+                        })
+                    })
+                    .collect()
+            };
+
+            match parts.next() {
+                Some("v") => {
+                    let values = floats(parts)?;
+                    if values.len() < 3 {
+                        return Err(ImportError::Parse(line, "vertex needs 3 values".to_owned()));
+                    }
+
+                    if values[..3]
+                        .iter()
+                        .any(|&value| !(0.0..=1.0).contains(&value))
+                    {
+                        return Err(ImportError::OutOfBounds(line));
+                    }
+
+                    positions.push([values[0], values[1], values[2]]);
+                }
+                Some("vt") => {
+                    let values = floats(parts)?;
+                    if values.len() < 2 {
+                        return Err(ImportError::Parse(
+                            line,
+                            "texture coordinate needs 2 values".to_owned(),
+                        ));
+                    }
+
+                    uvs.push([values[0], values[1]]);
+                }
+                Some("o") | Some("g") => {
+                    if let Some(group) = parts.next() {
+                        name = group.to_owned();
+                    }
+                }
+                Some("f") => {
+                    let mut corners = vec![];
+
+                    for vertex in parts {
+                        let mut indices = vertex.split('/');
+                        let pos =
+                            positions[resolve(indices.next().unwrap(), positions.len(), line)?];
+                        let uv = match indices.next() {
+                            Some(index) if !index.is_empty() => {
+                                uvs[resolve(index, uvs.len(), line)?]
+                            }
+                            _ => [0.0, 0.0],
+                        };
+
+                        corners.push(CornerData { pos, uv });
+                    }
+
+                    // Corners are laid out so that the mesher's (0, 1, 2), (2, 1, 3) triangles keep
+                    // the counter-clockwise winding of the model.
+                    let corners = match corners.len() {
+                        3 => [
+                            corners[0].clone(),
+                            corners[1].clone(),
+                            corners[2].clone(),
+                            corners[2].clone(),
+                        ],
+                        4 => [
+                            corners[0].clone(),
+                            corners[1].clone(),
+                            corners[3].clone(),
+                            corners[2].clone(),
+                        ],
+                        count => {
+                            return Err(ImportError::Parse(
+                                line,
+                                format!("faces need 3 or 4 vertices, found {count}"),
+                            ))
+                        }
+                    };
+
+                    let mut face =
+                        BlockFace::new(name.to_owned(), false, false, [0, 0, 0], corners);
+
+                    // Axis-aligned faces point towards their neighbor, so that they can be culled.
+                    let Vec3(x, y, z) = face.normal();
+                    if [x, y, z].iter().filter(|value| value.abs() > 0.999).count() == 1 {
+                        face.dir = [x.round() as i32, y.round() as i32, z.round() as i32];
+                    }
+
+                    faces.push(face);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(faces)
+    }
+
     pub fn into_independent(&mut self) {
         self.independent = true;
     }
@@ -564,13 +723,12 @@ impl DiagonalFacesBuilder {
 
         if to_four {
             BlockFaces::from_faces(vec![
-                BlockFace {
-                    name: make_name("one1"),
-                    dir: [0, 0, 0],
-                    independent: false,
-                    isolated: false,
-                    range: UV::default(),
-                    corners: [
+                BlockFace::new(
+                    make_name("one1"),
+                    false,
+                    false,
+                    [0, 0, 0],
+                    [
                         CornerData {
                             pos: [
                                 offset_x + h_min,
@@ -600,14 +758,13 @@ impl DiagonalFacesBuilder {
                             uv: [0.5, 0.0],
                         },
                     ],
-                },
-                BlockFace {
-                    name: make_name("one2"),
-                    dir: [0, 0, 0],
-                    independent: false,
-                    isolated: false,
-                    range: UV::default(),
-                    corners: [
+                ),
+                BlockFace::new(
+                    make_name("one2"),
+                    false,
+                    false,
+                    [0, 0, 0],
+                    [
                         CornerData {
                             pos: [
                                 offset_x + (h_min + h_max) / 2.0,
@@ -637,14 +794,13 @@ impl DiagonalFacesBuilder {
                             uv: [1.0, 0.0],
                         },
                     ],
-                },
-                BlockFace {
-                    name: make_name("two1"),
-                    dir: [0, 0, 0],
-                    independent: false,
-                    isolated: false,
-                    range: UV::default(),
-                    corners: [
+                ),
+                BlockFace::new(
+                    make_name("two1"),
+                    false,
+                    false,
+                    [0, 0, 0],
+                    [
                         CornerData {
                             pos: [
                                 offset_x + h_max,
@@ -674,14 +830,13 @@ impl DiagonalFacesBuilder {
                             uv: [0.5, 0.0],
                         },
                     ],
-                },
-                BlockFace {
-                    name: make_name("two2"),
-                    dir: [0, 0, 0],
-                    independent: false,
-                    isolated: false,
-                    range: UV::default(),
-                    corners: [
+                ),
+                BlockFace::new(
+                    make_name("two2"),
+                    false,
+                    false,
+                    [0, 0, 0],
+                    [
                         CornerData {
                             pos: [
                                 offset_x + (h_min + h_max) / 2.0,
@@ -711,17 +866,16 @@ impl DiagonalFacesBuilder {
                             uv: [1.0, 0.0],
                         },
                     ],
-                },
+                ),
             ])
         } else {
             BlockFaces::from_faces(vec![
-                BlockFace {
-                    name: make_name("one"),
-                    dir: [0, 0, 0],
-                    independent: false,
-                    isolated: false,
-                    range: UV::default(),
-                    corners: [
+                BlockFace::new(
+                    make_name("one"),
+                    false,
+                    false,
+                    [0, 0, 0],
+                    [
                         CornerData {
                             pos: [
                                 offset_x + h_min,
@@ -747,14 +901,13 @@ impl DiagonalFacesBuilder {
                             uv: [1.0, 0.0],
                         },
                     ],
-                },
-                BlockFace {
-                    name: make_name("two"),
-                    dir: [0, 0, 0],
-                    independent: false,
-                    isolated: false,
-                    range: UV::default(),
-                    corners: [
+                ),
+                BlockFace::new(
+                    make_name("two"),
+                    false,
+                    false,
+                    [0, 0, 0],
+                    [
                         CornerData {
                             pos: [
                                 offset_x + h_max,
@@ -780,7 +933,7 @@ impl DiagonalFacesBuilder {
                             uv: [1.0, 0.0],
                         },
                     ],
-                },
+                ),
             ])
         }
     }
@@ -793,18 +946,20 @@ pub struct SixFacesBuilder {
     offset_x: f32,
     offset_y: f32,
     offset_z: f32,
-    uv_scale_x: f32,
-    uv_scale_y: f32,
-    uv_scale_z: f32,
-    uv_offset_x: f32,
-    uv_offset_y: f32,
-    uv_offset_z: f32,
+    uv_scale_x: Option<f32>,
+    uv_scale_y: Option<f32>,
+    uv_scale_z: Option<f32>,
+    uv_offset_x: Option<f32>,
+    uv_offset_y: Option<f32>,
+    uv_offset_z: Option<f32>,
     prefix: String,
     suffix: String,
     concat: String,
     independence: [bool; 6],
     isolation: [bool; 6],
+    names: [Option<String>; 6],
     auto_uv_offset: bool,
+    auto_uv: bool,
     rotation: Option<BlockRotation>,
 }
 
@@ -818,18 +973,20 @@ impl SixFacesBuilder {
             offset_x: 0.0,
             offset_y: 0.0,
             offset_z: 0.0,
-            uv_scale_x: 1.0,
-            uv_scale_y: 1.0,
-            uv_scale_z: 1.0,
-            uv_offset_x: 0.0,
-            uv_offset_y: 0.0,
-            uv_offset_z: 0.0,
+            uv_scale_x: None,
+            uv_scale_y: None,
+            uv_scale_z: None,
+            uv_offset_x: None,
+            uv_offset_y: None,
+            uv_offset_z: None,
             prefix: "".to_owned(),
             suffix: "".to_owned(),
             concat: "".to_owned(),
             independence: [false, false, false, false, false, false],
             isolation: [false, false, false, false, false, false],
+            names: Default::default(),
             auto_uv_offset: false,
+            auto_uv: false,
             rotation: None,
         }
     }
@@ -872,37 +1029,37 @@ impl SixFacesBuilder {
 
     /// Configure the UV x scale of this six faces.
     pub fn uv_scale_x(mut self, uv_scale_x: f32) -> Self {
-        self.uv_scale_x = uv_scale_x;
+        self.uv_scale_x = Some(uv_scale_x);
         self
     }
 
     /// Configure the UV y scale of this six faces.
     pub fn uv_scale_y(mut self, uv_scale_y: f32) -> Self {
-        self.uv_scale_y = uv_scale_y;
+        self.uv_scale_y = Some(uv_scale_y);
         self
     }
 
     /// Configure the UV z scale of this six faces.
     pub fn uv_scale_z(mut self, uv_scale_z: f32) -> Self {
-        self.uv_scale_z = uv_scale_z;
+        self.uv_scale_z = Some(uv_scale_z);
         self
     }
 
     /// Configure the UV x offset of the six faces.
     pub fn uv_offset_x(mut self, uv_offset_x: f32) -> Self {
-        self.uv_offset_x = uv_offset_x;
+        self.uv_offset_x = Some(uv_offset_x);
         self
     }
 
     /// Configure the UV y offset of the six faces.
     pub fn uv_offset_y(mut self, uv_offset_y: f32) -> Self {
-        self.uv_offset_y = uv_offset_y;
+        self.uv_offset_y = Some(uv_offset_y);
         self
     }
 
     /// Configure the UV z offset of the six faces.
     pub fn uv_offset_z(mut self, uv_offset_z: f32) -> Self {
-        self.uv_offset_z = uv_offset_z;
+        self.uv_offset_z = Some(uv_offset_z);
         self
     }
 
@@ -924,11 +1081,59 @@ impl SixFacesBuilder {
         self
     }
 
+    /// Configure the texture name of the top (py) face, overriding the generated name.
+    pub fn top(mut self, name: &str) -> Self {
+        self.names[SIX_FACES_PY] = Some(name.to_owned());
+        self
+    }
+
+    /// Configure the texture name of the bottom (ny) face, overriding the generated name.
+    pub fn bottom(mut self, name: &str) -> Self {
+        self.names[SIX_FACES_NY] = Some(name.to_owned());
+        self
+    }
+
+    /// Configure the texture name of the px face, overriding the generated name.
+    pub fn px(mut self, name: &str) -> Self {
+        self.names[SIX_FACES_PX] = Some(name.to_owned());
+        self
+    }
+
+    /// Configure the texture name of the nx face, overriding the generated name.
+    pub fn nx(mut self, name: &str) -> Self {
+        self.names[SIX_FACES_NX] = Some(name.to_owned());
+        self
+    }
+
+    /// Configure the texture name of the pz face, overriding the generated name.
+    pub fn pz(mut self, name: &str) -> Self {
+        self.names[SIX_FACES_PZ] = Some(name.to_owned());
+        self
+    }
+
+    /// Configure the texture name of the nz face, overriding the generated name.
+    pub fn nz(mut self, name: &str) -> Self {
+        self.names[SIX_FACES_NZ] = Some(name.to_owned());
+        self
+    }
+
+    /// Configure the texture name of all four side faces at once.
+    pub fn sides(self, name: &str) -> Self {
+        self.px(name).nx(name).pz(name).nz(name)
+    }
+
     pub fn auto_uv_offset(mut self, auto_uv_offset: bool) -> Self {
         self.auto_uv_offset = auto_uv_offset;
         self
     }
 
+    /// Configure whether UV scales and offsets that aren't explicitly set follow the geometric
+    /// scales and offsets, so that textures map proportionally onto scaled faces. Default is false.
+    pub fn auto_uv(mut self, auto_uv: bool) -> Self {
+        self.auto_uv = auto_uv;
+        self
+    }
+
     pub fn with_rotation(mut self, rotation: &BlockRotation) -> Self {
         self.rotation = Some(rotation.to_owned());
         self
@@ -971,9 +1176,11 @@ impl SixFacesBuilder {
             suffix,
             concat,
             auto_uv_offset,
+            auto_uv,
             rotation,
             independence,
             isolation,
+            names,
         } = self;
 
         let make_name = |side: &str| {
@@ -994,24 +1201,28 @@ impl SixFacesBuilder {
             name
         };
 
-        let uv_offset_x: f32 = if auto_uv_offset {
-            offset_x
-        } else {
-            uv_offset_x
-        };
-        let uv_offset_y = if auto_uv_offset {
-            offset_y
-        } else {
-            uv_offset_y
-        };
-        let uv_offset_z = if auto_uv_offset {
-            offset_z
-        } else {
-            uv_offset_z
+        let face_name =
+            |index: usize, side: &str| names[index].to_owned().unwrap_or_else(|| make_name(side));
+
+        // `auto_uv_offset` overrides everything, while `auto_uv` only fills in the UVs not set.
+        let derive = |manual: Option<f32>, geometric: f32, default: f32| {
+            if auto_uv_offset {
+                geometric
+            } else if let Some(manual) = manual {
+                manual
+            } else if auto_uv {
+                geometric
+            } else {
+                default
+            }
         };
-        let uv_scale_x = if auto_uv_offset { scale_x } else { uv_scale_x };
-        let uv_scale_y = if auto_uv_offset { scale_y } else { uv_scale_y };
-        let uv_scale_z = if auto_uv_offset { scale_z } else { uv_scale_z };
+
+        let uv_offset_x = derive(uv_offset_x, offset_x, 0.0);
+        let uv_offset_y = derive(uv_offset_y, offset_y, 0.0);
+        let uv_offset_z = derive(uv_offset_z, offset_z, 0.0);
+        let uv_scale_x = derive(uv_scale_x, scale_x, 1.0);
+        let uv_scale_y = derive(uv_scale_y, scale_y, 1.0);
+        let uv_scale_z = derive(uv_scale_z, scale_z, 1.0);
 
         let is_px_independent = independence[SIX_FACES_PX];
         let is_nx_independent = independence[SIX_FACES_NX];
@@ -1028,13 +1239,12 @@ impl SixFacesBuilder {
         let is_nz_isolated = isolation[SIX_FACES_NZ];
 
         let mut results = BlockFaces::from_faces(vec![
-            BlockFace {
-                name: make_name("px"),
-                dir: [1, 0, 0],
-                independent: is_px_independent,
-                isolated: is_px_isolated,
-                range: UV::default(),
-                corners: [
+            BlockFace::new(
+                face_name(SIX_FACES_PX, "px"),
+                is_px_independent,
+                is_px_isolated,
+                [1, 0, 0],
+                [
                     CornerData {
                         pos: [
                             1.0 * scale_x + offset_x,
@@ -1075,14 +1285,13 @@ impl SixFacesBuilder {
                         },
                     },
                 ],
-            },
-            BlockFace {
-                name: make_name("py"),
-                dir: [0, 1, 0],
-                independent: is_py_independent,
-                isolated: is_py_isolated,
-                range: UV::default(),
-                corners: [
+            ),
+            BlockFace::new(
+                face_name(SIX_FACES_PY, "py"),
+                is_py_independent,
+                is_py_isolated,
+                [0, 1, 0],
+                [
                     CornerData {
                         pos: [offset_x, 1.0 * scale_y + offset_y, 1.0 * scale_z + offset_z],
                         uv: if is_py_independent || is_py_isolated {
@@ -1123,14 +1332,13 @@ impl SixFacesBuilder {
                         },
                     },
                 ],
-            },
-            BlockFace {
-                name: make_name("pz"),
-                dir: [0, 0, 1],
-                independent: is_pz_independent,
-                isolated: is_pz_isolated,
-                range: UV::default(),
-                corners: [
+            ),
+            BlockFace::new(
+                face_name(SIX_FACES_PZ, "pz"),
+                is_pz_independent,
+                is_pz_isolated,
+                [0, 0, 1],
+                [
                     CornerData {
                         pos: [offset_x, offset_y, 1.0 * scale_z + offset_z],
                         uv: if is_pz_independent || is_pz_isolated {
@@ -1171,14 +1379,13 @@ impl SixFacesBuilder {
                         },
                     },
                 ],
-            },
-            BlockFace {
-                name: make_name("nx"),
-                dir: [-1, 0, 0],
-                independent: is_nx_independent,
-                isolated: is_nx_isolated,
-                range: UV::default(),
-                corners: [
+            ),
+            BlockFace::new(
+                face_name(SIX_FACES_NX, "nx"),
+                is_nx_independent,
+                is_nx_isolated,
+                [-1, 0, 0],
+                [
                     CornerData {
                         pos: [offset_x, 1.0 * scale_y + offset_y, offset_z],
                         uv: if is_nx_independent || is_nx_isolated {
@@ -1215,14 +1422,13 @@ impl SixFacesBuilder {
                         },
                     },
                 ],
-            },
-            BlockFace {
-                name: make_name("ny"),
-                dir: [0, -1, 0],
-                independent: is_ny_independent,
-                isolated: is_ny_isolated,
-                range: UV::default(),
-                corners: [
+            ),
+            BlockFace::new(
+                face_name(SIX_FACES_NY, "ny"),
+                is_ny_independent,
+                is_ny_isolated,
+                [0, -1, 0],
+                [
                     CornerData {
                         pos: [1.0 * scale_x + offset_x, offset_y, 1.0 * scale_z + offset_z],
                         uv: if is_ny_independent || is_ny_isolated {
@@ -1259,14 +1465,13 @@ impl SixFacesBuilder {
                         },
                     },
                 ],
-            },
-            BlockFace {
-                name: make_name("nz"),
-                dir: [0, 0, -1],
-                independent: is_nz_independent,
-                isolated: is_nz_isolated,
-                range: UV::default(),
-                corners: [
+            ),
+            BlockFace::new(
+                face_name(SIX_FACES_NZ, "nz"),
+                is_nz_independent,
+                is_nz_isolated,
+                [0, 0, -1],
+                [
                     CornerData {
                         pos: [1.0 * scale_x + offset_x, offset_y, offset_z],
                         uv: if is_nz_independent || is_nz_isolated {
@@ -1303,7 +1508,7 @@ impl SixFacesBuilder {
                         },
                     },
                 ],
-            },
+            ),
         ]);
 
         if let Some(rotation) = rotation {
@@ -1311,6 +1516,8 @@ impl SixFacesBuilder {
                 for corner in face.corners.iter_mut() {
                     rotation.rotate_node(&mut corner.pos, true, true);
                 }
+
+                face.normal = face.compute_normal();
             }
         }
 
@@ -1484,6 +1691,21 @@ pub struct Block {
     /// Can this block be passed through?
     pub is_passable: bool,
 
+    /// Can this block share its voxel with a fluid, such as an underwater slab?
+    pub can_be_waterlogged: bool,
+
+    /// The highest stage this block can be at, such as a fully grown crop.
+    pub max_stage: u32,
+
+    /// Does this block receive random ticks, such as grass spreading or crops growing?
+    pub random_tick: bool,
+
+    /// How many voxels of an explosion's radius this block soaks up before it breaks.
+    pub blast_resistance: f32,
+
+    /// Can this block never be broken by explosions, such as bedrock?
+    pub is_unbreakable: bool,
+
     /// Is the block opaque?
     pub is_opaque: bool,
 
@@ -1496,7 +1718,9 @@ pub struct Block {
     /// Blue-light level of the block.
     pub blue_light_level: u32,
 
-    /// Do faces of this transparent block need to be rendered?
+    /// Does this transparent block still draw all of its own faces against identical neighbors? Dense
+    /// plants like grass set this so that they don't cull into nothing, while blocks like water leave
+    /// it off to cull the faces they share with each other.
     pub transparent_standalone: bool,
 
     /// The faces that this block has to render.
@@ -1505,16 +1729,30 @@ pub struct Block {
     /// Bounding boxes of this block.
     pub aabbs: Vec<AABB>,
 
-    /// Is the block overall see-through? Opacity equals 0.1 or something?
+    /// Is the block rendered in the transparent pass, like water and glass? See-through blocks cull
+    /// the faces they share with identical neighbors, unless `transparent_standalone` is set. This
+    /// has no effect on light, which only looks at `is_transparent`.
     pub is_see_through: bool,
 
-    /// Is this block transparent from looking from all 6 sides?
+    /// Does light pass through this block from each of its 6 sides? Transparent blocks that aren't
+    /// see-through, like leaves, are still rendered in the opaque pass.
     /// The order is: px, py, pz, nx, ny, nz.
     pub is_transparent: [bool; 6],
 
     /// Does light reduce when passing through this block?
     pub light_reduce: bool,
 
+    /// How many extra levels of red, green and blue torch light are lost entering this block, on
+    /// top of the usual one per voxel. Clear glass would filter `[1, 1, 1]`, red stained glass
+    /// something like `[1, 4, 4]`. Sunlight is only reduced through `light_reduce`.
+    pub light_filter: [u32; 3],
+
+    /// The RGBA color the vertices of this block are multiplied by. Default is white.
+    pub tint: [f32; 4],
+
+    /// Is this block also tinted by the color of the biome it's in, like grass and leaves?
+    pub biome_tint: bool,
+
     pub is_entity: bool,
 
     /// Whether or not this block has dynamic aabb and face generation. This is
@@ -1551,10 +1789,25 @@ impl Block {
         BlockBuilder::new(name)
     }
 
+    /// Clamp a stage to the highest stage this block can be at.
+    pub fn clamp_stage(&self, stage: u32) -> u32 {
+        stage.min(self.max_stage)
+    }
+
     pub fn has_torch_light(&self) -> bool {
         self.red_light_level > 0 || self.green_light_level > 0 || self.blue_light_level > 0
     }
 
+    /// Does this block filter any channel of torch light passing through it?
+    pub fn has_light_filter(&self) -> bool {
+        self.light_filter.iter().any(|&filter| filter > 0)
+    }
+
+    /// Does this block color its vertices, with a tint or by its biome?
+    pub fn is_tinted(&self) -> bool {
+        self.biome_tint || self.tint != WHITE
+    }
+
     pub fn get_aabbs(
         &self,
         pos: &Vec3<i32>,
@@ -1610,10 +1863,59 @@ impl Block {
         }
     }
 
+    /// Get the extra levels of a color of light lost entering this block, always none for sunlight.
+    pub fn get_light_filter(&self, color: &LightColor) -> u32 {
+        match *color {
+            LightColor::Red => self.light_filter[0],
+            LightColor::Green => self.light_filter[1],
+            LightColor::Blue => self.light_filter[2],
+            LightColor::Sunlight => 0,
+        }
+    }
+
     pub fn get_rotated_transparency(&self, rotation: &BlockRotation) -> [bool; 6] {
         rotation.rotate_transparency(self.is_transparent)
     }
 
+    /// The updates of a fluid voxel flowing once, used by `fluid_flow`.
+    fn flow_fluid(
+        voxel: &Vec3<i32>,
+        space: &dyn VoxelAccess,
+        registry: &Registry,
+        max_spread: u32,
+    ) -> Vec<VoxelUpdate> {
+        let &Vec3(vx, vy, vz) = voxel;
+        let id = space.get_voxel(vx, vy, vz);
+        let stage = space.get_voxel_stage(vx, vy, vz);
+
+        let pack = |stage: u32| VoxelPacker::new().with_id(id).with_stage(stage).pack();
+        let is_empty = |vx: i32, vy: i32, vz: i32| {
+            space.contains(vx, vy, vz)
+                && registry
+                    .get_block_by_id(space.get_voxel(vx, vy, vz))
+                    .is_empty
+        };
+
+        if is_empty(vx, vy - 1, vz) {
+            return vec![(Vec3(vx, vy - 1, vz), pack(0))];
+        }
+
+        if stage >= max_spread {
+            return vec![];
+        }
+
+        [[-1, 0], [1, 0], [0, -1], [0, 1]]
+            .into_iter()
+            .map(|[dx, dz]| Vec3(vx + dx, vy, vz + dz))
+            .filter(|&Vec3(nx, ny, nz)| {
+                is_empty(nx, ny, nz)
+                    || (space.get_voxel(nx, ny, nz) == id
+                        && space.get_voxel_stage(nx, ny, nz) > stage + 1)
+            })
+            .map(|neighbor| (neighbor, pack(stage + 1)))
+            .collect()
+    }
+
     /// Evaluate the dynamic pattern and return the combined faces and AABBs based on the rules.
     fn evaluate_dynamic_pattern(
         pattern: &BlockDynamicPattern,
@@ -1711,12 +2013,19 @@ pub struct BlockBuilder {
     is_empty: bool,
     is_fluid: bool,
     is_passable: bool,
+    can_be_waterlogged: bool,
+    max_stage: u32,
+    random_tick: bool,
+    blast_resistance: f32,
+    is_unbreakable: bool,
     red_light_level: u32,
     green_light_level: u32,
     blue_light_level: u32,
     transparent_standalone: bool,
     faces: Vec<BlockFace>,
     aabbs: Vec<AABB>,
+    custom_faces: bool,
+    custom_aabbs: bool,
     is_see_through: bool,
     is_px_transparent: bool,
     is_py_transparent: bool,
@@ -1726,6 +2035,9 @@ pub struct BlockBuilder {
     is_nz_transparent: bool,
     is_entity: bool,
     light_reduce: bool,
+    light_filter: [u32; 3],
+    tint: [f32; 4],
+    biome_tint: bool,
     dynamic_patterns: Option<Vec<BlockDynamicPattern>>,
     dynamic_fn: Option<
         Arc<
@@ -1747,6 +2059,8 @@ impl BlockBuilder {
             name: name.to_owned(),
             faces: BlockFaces::six_faces().build().to_vec(),
             aabbs: vec![AABB::new().build()],
+            max_stage: 15,
+            tint: WHITE,
             ..Default::default()
         }
     }
@@ -1796,6 +2110,37 @@ impl BlockBuilder {
         self
     }
 
+    /// Configure whether or not this block can share its voxel with a fluid. Default is false.
+    pub fn can_be_waterlogged(mut self, can_be_waterlogged: bool) -> Self {
+        self.can_be_waterlogged = can_be_waterlogged;
+        self
+    }
+
+    /// Configure the highest stage this block can be at. Default is 15, the most a voxel can store.
+    pub fn max_stage(mut self, max_stage: u8) -> Self {
+        self.max_stage = (max_stage as u32).min(15);
+        self
+    }
+
+    /// Configure whether or not this block receives random ticks. Default is false.
+    pub fn random_tick(mut self, random_tick: bool) -> Self {
+        self.random_tick = random_tick;
+        self
+    }
+
+    /// Configure how many voxels of an explosion's radius this block soaks up before it breaks.
+    /// Default is 0.
+    pub fn blast_resistance(mut self, blast_resistance: f32) -> Self {
+        self.blast_resistance = blast_resistance;
+        self
+    }
+
+    /// Configure whether or not this block can never be broken by explosions. Default is false.
+    pub fn is_unbreakable(mut self, is_unbreakable: bool) -> Self {
+        self.is_unbreakable = is_unbreakable;
+        self
+    }
+
     /// Configure the red light level of this block. Default is 0.
     pub fn red_light_level(mut self, red_light_level: u32) -> Self {
         self.red_light_level = red_light_level;
@@ -1822,7 +2167,8 @@ impl BlockBuilder {
         self
     }
 
-    /// Configure whether or not should transparent faces be rendered individually. Default is false.
+    /// Configure whether or not this transparent block draws its faces against identical neighbors,
+    /// instead of culling them. Default is false.
     pub fn transparent_standalone(mut self, transparent_standalone: bool) -> Self {
         self.transparent_standalone = transparent_standalone;
         self
@@ -1831,16 +2177,19 @@ impl BlockBuilder {
     /// Configure the faces that the block has. Default is `vec![]`.
     pub fn faces(mut self, faces: &[BlockFace]) -> Self {
         self.faces = faces.to_vec();
+        self.custom_faces = true;
         self
     }
 
     /// Configure the bounding boxes that the block has. Default is `vec![]`.
     pub fn aabbs(mut self, aabbs: &[AABB]) -> Self {
         self.aabbs = aabbs.to_vec();
+        self.custom_aabbs = true;
         self
     }
 
-    /// Is this block a see-through block? Should it be sorted to the transparent meshes?
+    /// Is this block a see-through block, rendered in the transparent pass? Light passing through is
+    /// configured separately with `is_transparent`. Default is false.
     pub fn is_see_through(mut self, is_see_through: bool) -> Self {
         self.is_see_through = is_see_through;
         self
@@ -1920,6 +2269,24 @@ impl BlockBuilder {
         self
     }
 
+    /// Configure the extra levels of red, green and blue light lost entering this block. Default is none.
+    pub fn light_filter(mut self, light_filter: [u32; 3]) -> Self {
+        self.light_filter = light_filter;
+        self
+    }
+
+    /// Configure the RGBA color this block's vertices are multiplied by. Default is white.
+    pub fn tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Configure whether this block is tinted by the color of its biome too. Default is false.
+    pub fn biome_tint(mut self, biome_tint: bool) -> Self {
+        self.biome_tint = biome_tint;
+        self
+    }
+
     pub fn dynamic_patterns(mut self, patterns: &[BlockDynamicPattern]) -> Self {
         self.dynamic_patterns = Some(patterns.to_vec());
         self
@@ -1939,6 +2306,19 @@ impl BlockBuilder {
         self
     }
 
+    /// Make this block a fluid that flows every `delay` ticks. A fluid falls into the empty voxel
+    /// below it first, and otherwise spreads sideways into empty voxels, one stage further each
+    /// voxel until it is `max_spread` voxels away from where it landed. Solid blocks are never
+    /// flowed into, and fluid that can be reached through a shorter path lowers its stage instead.
+    pub fn fluid_flow(self, max_spread: u8, delay: u64) -> Self {
+        let max_spread = (max_spread as u32).min(15);
+
+        self.is_fluid(true).max_stage(max_spread as u8).active_fn(
+            move |_, _, _| delay,
+            move |voxel, space, registry| Block::flow_fluid(&voxel, space, registry, max_spread),
+        )
+    }
+
     pub fn active_fn<
         F1: Fn(Vec3<i32>, &dyn VoxelAccess, &Registry) -> u64 + 'static + Send + Sync,
         F2: Fn(Vec3<i32>, &dyn VoxelAccess, &Registry) -> Vec<VoxelUpdate> + 'static + Send + Sync,
@@ -1957,6 +2337,34 @@ impl BlockBuilder {
         self
     }
 
+    /// Construct a block instance like `build`, but fail with every contradictory combination of
+    /// flags found on the block, such as a fluid with collision boxes. Only faces and AABBs set
+    /// through `faces` and `aabbs` count, not the default cube every block starts with.
+    pub fn try_build(self) -> Result<Block, BlockBuildError> {
+        let mut conflicts = vec![];
+
+        if self.is_fluid && self.custom_aabbs && !self.aabbs.is_empty() {
+            conflicts.push(BlockConflict::FluidWithAabbs);
+        }
+
+        if self.is_empty && self.custom_faces && !self.faces.is_empty() {
+            conflicts.push(BlockConflict::EmptyWithFaces);
+        }
+
+        if self.is_fluid && self.can_be_waterlogged {
+            conflicts.push(BlockConflict::WaterloggedFluid);
+        }
+
+        if !conflicts.is_empty() {
+            return Err(BlockBuildError {
+                name: self.name,
+                conflicts,
+            });
+        }
+
+        Ok(self.build())
+    }
+
     /// Construct a block instance, ready to be added into the registry.
     pub fn build(self) -> Block {
         Block {
@@ -1971,6 +2379,11 @@ impl BlockBuilder {
                 || self.green_light_level > 0
                 || self.blue_light_level > 0,
             is_passable: self.is_passable,
+            can_be_waterlogged: self.can_be_waterlogged,
+            max_stage: self.max_stage,
+            random_tick: self.random_tick,
+            blast_resistance: self.blast_resistance,
+            is_unbreakable: self.is_unbreakable,
             is_opaque: !self.is_px_transparent
                 && !self.is_py_transparent
                 && !self.is_pz_transparent
@@ -1993,6 +2406,9 @@ impl BlockBuilder {
                 self.is_nz_transparent,
             ],
             light_reduce: self.light_reduce,
+            light_filter: self.light_filter,
+            tint: self.tint,
+            biome_tint: self.biome_tint,
             is_dynamic: self.dynamic_fn.is_some() || self.dynamic_patterns.is_some(),
             dynamic_patterns: self.dynamic_patterns,
             dynamic_fn: self.dynamic_fn,
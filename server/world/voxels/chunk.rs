@@ -2,22 +2,60 @@ use std::ops::Range;
 
 use hashbrown::{HashMap, HashSet};
 
-use crate::{ChunkProtocol, ChunkUtils, MeshProtocol, Ndarray, Registry, Vec2, Vec3, VoxelUpdate};
+use crate::{
+    BlockUtils, ChunkOptionsError, ChunkProtocol, ChunkUtils, MeshProtocol, Ndarray, StatusError,
+    Vec2, Vec3, VoxelUpdate,
+};
 
 use super::access::VoxelAccess;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// Where a chunk is in its lifecycle. A chunk goes through the stages of the pipeline one after
+/// another, then gets meshed, and is ready once it has its meshes. See `chunk.advance_status`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub enum ChunkStatus {
+    /// Nothing has been done to the chunk yet.
+    #[default]
+    Empty,
+
+    /// The chunk is going through the pipeline, at the stage of this index.
     Generating(usize),
 
+    /// The chunk has gone through every stage of the pipeline.
+    Generated,
+
     Meshing,
 
     Ready,
 }
 
-impl Default for ChunkStatus {
-    fn default() -> Self {
-        Self::Generating(0)
+impl ChunkStatus {
+    /// The name of this status, as sent to clients in `Status` messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::Generating(_) => "generating",
+            Self::Generated => "generated",
+            Self::Meshing => "meshing",
+            Self::Ready => "ready",
+        }
+    }
+
+    /// Whether the chunk has made it through the pipeline, so its voxels are final.
+    pub fn is_generated(&self) -> bool {
+        matches!(self, Self::Generated | Self::Meshing | Self::Ready)
+    }
+
+    /// Whether a chunk can go from this status to another. Chunks generate stage by stage before
+    /// meshing, and chunks loaded from disk skip the pipeline as they're already generated.
+    pub fn can_advance(&self, to: &ChunkStatus) -> bool {
+        matches!(
+            (self, to),
+            (Self::Empty, Self::Generating(0))
+                | (Self::Empty, Self::Generated)
+                | (Self::Generating(_), Self::Generated)
+                | (Self::Generated, Self::Meshing)
+                | (Self::Meshing, Self::Ready)
+        ) || matches!((self, to), (Self::Generating(from), Self::Generating(to)) if *to == from + 1)
     }
 }
 
@@ -26,6 +64,125 @@ pub struct ChunkOptions {
     pub size: usize,
     pub max_height: usize,
     pub sub_chunks: usize,
+
+    /// The lowest voxel y-coordinate of the chunk, which may be negative. The chunk spans vertically
+    /// from `min_y` up to, but not including, `max_height`.
+    pub min_y: i32,
+
+    /// The ID of the block that fills an empty chunk, treated as empty when culling and looking for
+    /// the highest block of a column.
+    pub air_id: u32,
+}
+
+impl ChunkOptions {
+    /// Create chunk options of a size, max height and number of sub-chunks, starting at a y of 0 and
+    /// filled with the block of ID 0. Use `ChunkOptions::builder` to name each option instead.
+    pub fn new(size: usize, max_height: usize, sub_chunks: usize) -> Self {
+        Self {
+            size,
+            max_height,
+            sub_chunks,
+            min_y: 0,
+            air_id: 0,
+        }
+    }
+
+    /// Create chunk options using the Builder pattern, validated on `build`.
+    pub fn builder() -> ChunkOptionsBuilder {
+        ChunkOptionsBuilder::new()
+    }
+
+    /// The number of voxels the chunk spans vertically.
+    pub fn height(&self) -> usize {
+        (self.max_height as i32 - self.min_y) as usize
+    }
+}
+
+/// Builder for chunk options, with the same defaults as `WorldConfig`.
+#[derive(Debug, Clone)]
+pub struct ChunkOptionsBuilder {
+    size: usize,
+    max_height: usize,
+    sub_chunks: usize,
+    min_y: i32,
+    air_id: u32,
+}
+
+impl ChunkOptionsBuilder {
+    /// Create a new chunk options builder, of 16x256x16 chunks split into 8 sub-chunks.
+    pub fn new() -> Self {
+        Self {
+            size: 16,
+            max_height: 256,
+            sub_chunks: 8,
+            min_y: 0,
+            air_id: 0,
+        }
+    }
+
+    /// Configure the horizontal width of the chunk. Default is 16 voxels.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Configure the exclusive upper y-coordinate of the chunk. Default is 256.
+    pub fn max_height(mut self, max_height: usize) -> Self {
+        self.max_height = max_height;
+        self
+    }
+
+    /// Configure the number of sub-chunks the chunk is vertically split into. Default is 8.
+    pub fn sub_chunks(mut self, sub_chunks: usize) -> Self {
+        self.sub_chunks = sub_chunks;
+        self
+    }
+
+    /// Configure the lowest y-coordinate of the chunk. Default is 0.
+    pub fn min_y(mut self, min_y: i32) -> Self {
+        self.min_y = min_y;
+        self
+    }
+
+    /// Configure the ID of the block that fills an empty chunk. Default is 0.
+    pub fn air_id(mut self, air_id: u32) -> Self {
+        self.air_id = air_id;
+        self
+    }
+
+    /// Create chunk options, failing if they don't make sense.
+    pub fn build(self) -> Result<ChunkOptions, ChunkOptionsError> {
+        if self.size == 0 {
+            return Err(ChunkOptionsError::ZeroSize);
+        }
+
+        if self.min_y >= self.max_height as i32 {
+            return Err(ChunkOptionsError::EmptyHeight(self.min_y, self.max_height));
+        }
+
+        let options = ChunkOptions {
+            size: self.size,
+            max_height: self.max_height,
+            sub_chunks: self.sub_chunks,
+            min_y: self.min_y,
+            air_id: self.air_id,
+        };
+
+        if options.sub_chunks == 0 || !options.height().is_multiple_of(options.sub_chunks) {
+            return Err(ChunkOptionsError::UnevenSubChunks(
+                options.height(),
+                options.sub_chunks,
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+impl Default for ChunkOptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -38,6 +195,8 @@ pub struct Chunk {
 
     pub voxels: Ndarray<u32>,
     pub lights: Ndarray<u32>,
+
+    /// The highest non-air voxel of each column, counted in voxels up from `options.min_y`.
     pub height_map: Ndarray<u32>,
 
     pub meshes: Option<HashMap<u32, MeshProtocol>>,
@@ -49,6 +208,35 @@ pub struct Chunk {
 
     pub extra_changes: Vec<VoxelUpdate>,
     pub updated_levels: HashSet<u32>,
+
+    /// The level of detail this chunk is meshed at, 0 being full resolution.
+    pub lod: u8,
+
+    /// A hash of the voxels and lights of this chunk, kept up to date as they are set.
+    pub(crate) content_hash: u64,
+
+    /// The mesh key each sub-chunk level was last meshed with, level -> key.
+    pub(crate) mesh_keys: HashMap<u32, u64>,
+
+    /// The number of voxels in this chunk that aren't air.
+    pub(crate) solid_count: usize,
+}
+
+/// Salts to tell voxels and lights apart in a chunk's content hash.
+const VOXEL_SALT: u64 = 0x9E3779B97F4A7C15;
+const LIGHT_SALT: u64 = 0xC2B2AE3D27D4EB4F;
+
+/// Hash a single value of a chunk at an index. Zeroes hash to zero, so that an empty chunk has a
+/// content hash of zero, and XOR-ing the hashes of all values together gives the chunk's hash.
+fn hash_value(index: usize, value: u32, salt: u64) -> u64 {
+    if value == 0 {
+        return 0;
+    }
+
+    let mut hash = ((index as u64) << 32 | value as u64) ^ salt;
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D049BB133111EB);
+    hash ^ (hash >> 31)
 }
 
 impl Chunk {
@@ -57,20 +245,23 @@ impl Chunk {
             size,
             max_height,
             sub_chunks,
+            min_y,
+            air_id,
         } = *options;
+        let height = options.height();
 
-        let voxels = Ndarray::new(&[size, max_height, size], 0);
-        let lights = Ndarray::new(&[size, max_height, size], 0);
+        let voxels = Ndarray::new(&[size, height, size], air_id);
+        let lights = Ndarray::new(&[size, height, size], 0);
         let height_map = Ndarray::new(&[size, size], 0);
 
-        let min = Vec3(cx * size as i32, 0, cz * size as i32);
+        let min = Vec3(cx * size as i32, min_y, cz * size as i32);
         let max = Vec3(
             (cx + 1) * size as i32,
             max_height as i32,
             (cz + 1) * size as i32,
         );
 
-        Self {
+        let mut chunk = Self {
             id: id.to_owned(),
             name: ChunkUtils::get_chunk_name(cx, cz),
             coords: Vec2(cx, cz),
@@ -86,23 +277,77 @@ impl Chunk {
             updated_levels: (0..sub_chunks as u32).collect(),
 
             ..Default::default()
+        };
+
+        // Only a chunk full of zeroes has a content hash of zero.
+        if air_id != 0 {
+            chunk.rehash();
         }
+
+        chunk
+    }
+
+    /// Check if a voxel of this chunk is air, according to `options.air_id`.
+    pub fn is_air(&self, vx: i32, vy: i32, vz: i32) -> bool {
+        self.get_voxel(vx, vy, vz) == self.options.air_id
+    }
+
+    /// A hash of the voxels and lights in this chunk. Chunks with the same content share a hash.
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash
     }
 
-    /// Calculate the height map of this chunk.
-    pub fn calculate_max_height(&mut self, registry: &Registry) {
+    /// Check if this chunk has no voxels other than air, in which case it has nothing to mesh.
+    pub fn is_empty(&self) -> bool {
+        self.solid_count == 0
+    }
+
+    /// Recalculate the content hash and the count of solid voxels from scratch, after the voxels or
+    /// lights are replaced directly.
+    pub fn rehash(&mut self) {
+        let air_id = self.options.air_id;
+        self.solid_count = self
+            .voxels
+            .data
+            .iter()
+            .filter(|&&value| BlockUtils::extract_id(value) != air_id)
+            .count();
+
+        let voxels = self
+            .voxels
+            .data
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (index, &value)| {
+                hash ^ hash_value(index, value, VOXEL_SALT)
+            });
+
+        self.content_hash = self
+            .lights
+            .data
+            .iter()
+            .enumerate()
+            .fold(voxels, |hash, (index, &value)| {
+                hash ^ hash_value(index, value, LIGHT_SALT)
+            });
+    }
+
+    /// Calculate the height map of this chunk from scratch, such as after a generation stage. Heights
+    /// are otherwise kept up to date as voxels are set. Every block but `options.air_id` counts.
+    pub fn calculate_max_height(&mut self) {
         let Vec3(min_x, _, min_z) = self.min;
         let Vec3(max_x, _, max_z) = self.max;
 
+        let min_y = self.options.min_y;
         let max_height = self.options.max_height as i32;
 
         for vx in min_x..max_x {
             for vz in min_z..max_z {
-                for vy in (0..max_height).rev() {
+                for vy in (min_y..max_height).rev() {
                     let id = self.get_voxel(vx, vy, vz);
 
-                    if vy == 0 || registry.check_height(id) {
-                        self.set_max_height(vx, vz, vy as u32);
+                    if vy == min_y || id != self.options.air_id {
+                        self.set_max_height(vx, vz, (vy - min_y) as u32);
                         break;
                     }
                 }
@@ -110,6 +355,21 @@ impl Chunk {
         }
     }
 
+    /// Move the chunk on to a new status, erroring without changing it if the transition isn't
+    /// allowed by `ChunkStatus::can_advance`.
+    pub fn advance_status(&mut self, to: ChunkStatus) -> Result<(), StatusError> {
+        if !self.status.can_advance(&to) {
+            return Err(StatusError {
+                from: self.status.to_owned(),
+                to,
+            });
+        }
+
+        self.status = to;
+
+        Ok(())
+    }
+
     /// Convert chunk to protocol model.
     pub fn to_model(&self, mesh: bool, data: bool, levels: Range<u32>) -> ChunkProtocol {
         let mut meshes = vec![];
@@ -139,15 +399,44 @@ impl Chunk {
             } else {
                 None
             },
+            status: String::new(),
+        }
+    }
+
+    /// Get the y-coordinate of the highest solid voxel in a column. Columns without any solid voxel,
+    /// or outside of the chunk, are at `options.min_y`.
+    pub fn get_height(&self, vx: i32, vz: i32) -> i32 {
+        if !self.contains(vx, self.options.min_y, vz) {
+            return self.options.min_y;
+        }
+
+        self.options.min_y + self.get_max_height(vx, vz) as i32
+    }
+
+    /// Keep the height map up to date after a voxel in the chunk has changed.
+    fn update_height(&mut self, vx: i32, vy: i32, vz: i32, id: u32) {
+        let floor = self.options.min_y;
+        let height = self.get_height(vx, vz);
+
+        if id != self.options.air_id {
+            if vy > height {
+                self.set_max_height(vx, vz, (vy - floor) as u32);
+            }
+        } else if vy == height {
+            let height = (floor..vy)
+                .rev()
+                .find(|&y| y == floor || !self.is_air(vx, y, vz))
+                .unwrap_or(floor);
+            self.set_max_height(vx, vz, (height - floor) as u32);
         }
     }
 
     /// Flag a level of sub-chunk as dirty, waiting to be remeshed.
     pub fn add_updated_level(&mut self, vy: i32) {
-        let partition = (self.options.max_height / self.options.sub_chunks) as i32;
+        let partition = (self.options.height() / self.options.sub_chunks) as i32;
 
-        let level = vy / partition;
-        let remainder = vy % partition;
+        let level = (vy - self.options.min_y) / partition;
+        let remainder = (vy - self.options.min_y) % partition;
 
         if remainder == partition - 1 && (level) < (self.options.sub_chunks as i32) - 1 {
             self.updated_levels.insert(level as u32 + 1);
@@ -170,10 +459,10 @@ impl Chunk {
 impl VoxelAccess for Chunk {
     /// Get the raw value of voxel.
     ///
-    /// Returns 0 if it's outside of the chunk.
+    /// Returns `options.air_id` if it's outside of the chunk.
     fn get_raw_voxel(&self, vx: i32, vy: i32, vz: i32) -> u32 {
         if !self.contains(vx, vy, vz) {
-            return 0;
+            return self.options.air_id;
         }
 
         let Vec3(lx, ly, lz) = self.to_local(vx, vy, vz);
@@ -185,7 +474,7 @@ impl VoxelAccess for Chunk {
     /// Panics if the coordinates are outside of chunk.
     fn set_raw_voxel(&mut self, vx: i32, vy: i32, vz: i32, val: u32) -> bool {
         if !self.contains(vx, vy, vz) {
-            if vy >= 0 && vy < self.options.max_height as i32 {
+            if vy >= self.options.min_y && vy < self.options.max_height as i32 {
                 self.extra_changes.push((Vec3(vx, vy, vz), val));
             }
 
@@ -195,7 +484,21 @@ impl VoxelAccess for Chunk {
         self.add_updated_level(vy);
 
         let Vec3(lx, ly, lz) = self.to_local(vx, vy, vz);
-        self.voxels[&[lx, ly, lz]] = val;
+        let index = self.voxels.index(&[lx, ly, lz]);
+        let old_id = BlockUtils::extract_id(self.voxels.data[index]);
+        let id = BlockUtils::extract_id(val);
+
+        if old_id == self.options.air_id && id != self.options.air_id {
+            self.solid_count += 1;
+        } else if old_id != self.options.air_id && id == self.options.air_id {
+            self.solid_count -= 1;
+        }
+
+        self.content_hash ^= hash_value(index, self.voxels.data[index], VOXEL_SALT)
+            ^ hash_value(index, val, VOXEL_SALT);
+        self.voxels.data[index] = val;
+
+        self.update_height(vx, vy, vz, id);
 
         true
     }
@@ -223,20 +526,23 @@ impl VoxelAccess for Chunk {
         self.add_updated_level(vy);
 
         let Vec3(lx, ly, lz) = self.to_local(vx, vy, vz);
-        self.lights[&[lx, ly, lz]] = level;
+        let index = self.lights.index(&[lx, ly, lz]);
+        self.content_hash ^= hash_value(index, self.lights.data[index], LIGHT_SALT)
+            ^ hash_value(index, level, LIGHT_SALT);
+        self.lights.data[index] = level;
 
         true
     }
 
-    /// Get the max height of a voxel column.
+    /// Get the max height of a voxel column, counted in voxels up from `options.min_y`.
     ///
-    /// Returns `max_height` if it's not within the chunk.
+    /// Returns the height of the chunk if it's not within the chunk.
     fn get_max_height(&self, vx: i32, vz: i32) -> u32 {
-        if !self.contains(vx, 0, vz) {
-            return self.options.max_height as u32;
+        if !self.contains(vx, self.options.min_y, vz) {
+            return self.options.height() as u32;
         }
 
-        let Vec3(lx, _, lz) = self.to_local(vx, 0, vz);
+        let Vec3(lx, _, lz) = self.to_local(vx, self.options.min_y, vz);
         self.height_map[&[lx as usize, lz as usize]]
     }
 
@@ -244,11 +550,11 @@ impl VoxelAccess for Chunk {
     ///
     /// Panics if it's not within the chunk.
     fn set_max_height(&mut self, vx: i32, vz: i32, height: u32) -> bool {
-        if !self.contains(vx, 0, vz) {
+        if !self.contains(vx, self.options.min_y, vz) {
             return false;
         }
 
-        let Vec3(lx, _, lz) = self.to_local(vx, 0, vz);
+        let Vec3(lx, _, lz) = self.to_local(vx, self.options.min_y, vz);
         self.height_map[&[lx as usize, lz as usize]] = height;
 
         true
@@ -262,13 +568,16 @@ impl VoxelAccess for Chunk {
         Some(&self.voxels)
     }
 
+    fn min_y(&self) -> i32 {
+        self.options.min_y
+    }
+
     /// Check if chunk contains this voxel coordinate.
     fn contains(&self, vx: i32, vy: i32, vz: i32) -> bool {
-        let ChunkOptions {
-            size, max_height, ..
-        } = self.options;
+        let size = self.options.size;
+        let height = self.options.height();
         let Vec3(lx, ly, lz) = self.to_local(vx, vy, vz);
 
-        lx < size && ly < max_height && lz < size
+        lx < size && ly < height && lz < size
     }
 }
@@ -3,26 +3,39 @@ use byteorder::{ByteOrder, LittleEndian};
 use hashbrown::{HashMap, HashSet};
 use libflate::zlib::{Decoder, Encoder};
 use log::info;
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use specs::Entity;
 use std::{
+    cell::Cell,
     collections::VecDeque,
     fs::{self, File},
     io::{BufReader, Read, Write},
     path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
 };
 
 use crate::{
-    ChunkOptions, ChunkStatus, ChunkUtils, LightUtils, MessageType, Registry, Vec2, Vec3,
-    VoxelUpdate, WorldConfig,
+    sweep, AStar, BiomeMap, BlockUtils, ChunkOptions, ChunkStatus, ChunkUtils, EditHistory,
+    GeometryProtocol, LightUtils, Mesher, MessageType, PathNode, PhysicsBody, Registry, Schematic,
+    Vec2, Vec3, VoxelError, VoxelUpdate, WorldConfig, AABB,
 };
 
 use super::{
     access::VoxelAccess,
     chunk::Chunk,
-    space::{SpaceBuilder, SpaceOptions},
+    space::{Space, SpaceBuilder, SpaceOptions},
 };
 
+/// The coarsest level of detail a chunk can be meshed at, 4x4x4 voxels per cell.
+pub const MAX_LOD: u8 = 2;
+
+/// How far below a falling physics body to look for the ground it's resting on.
+const GROUND_PROBE: f32 = 1e-3;
+
 /// Prototype for chunk's internal data used to send to client
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -30,16 +43,40 @@ struct ChunkFileData {
     id: String,
     voxels: String,
     height_map: String,
+
+    /// The lowest y-coordinate of the chunk when it was saved, which its height map counts from.
+    #[serde(default)]
+    min_y: i32,
+}
+
+/// A queued change of voxels. Changes are applied in the order they were queued.
+pub(crate) enum QueuedUpdate {
+    /// A single voxel update, counting towards `config.max_updates_per_tick`.
+    Voxel(VoxelUpdate),
+
+    /// A bulk edit, applied all at once so that each chunk it touches is only remeshed once.
+    Batch(Vec<VoxelUpdate>),
+}
+
+impl QueuedUpdate {
+    /// The voxel updates of this change.
+    pub(crate) fn voxels(&self) -> &[VoxelUpdate] {
+        match self {
+            QueuedUpdate::Voxel(update) => std::slice::from_ref(update),
+            QueuedUpdate::Batch(updates) => updates,
+        }
+    }
 }
 
 /// A manager for all chunks in the Voxelize world.
 #[derive(Default)]
 pub struct Chunks {
-    /// A map of all the chunks, coords -> Chunk.
-    pub map: HashMap<Vec2<i32>, Chunk>,
+    /// A map of all the chunks, coords -> Chunk. Accessed through `chunks.get_chunk` and friends, so
+    /// that the storage can change without breaking users.
+    pub(crate) map: HashMap<Vec2<i32>, Chunk>,
 
-    /// Voxel updates waiting to be processed.
-    pub(crate) updates: VecDeque<VoxelUpdate>,
+    /// Voxel updates and bulk edits waiting to be processed, in the order they were queued.
+    pub(crate) updates: VecDeque<QueuedUpdate>,
 
     /// A list of chunks that are done meshing and ready to be sent.
     pub(crate) to_send: VecDeque<(Vec2<i32>, MessageType)>,
@@ -49,6 +86,12 @@ pub struct Chunks {
 
     pub(crate) active_voxels: Vec<(u64, Vec3<i32>)>,
 
+    /// Block updates scheduled by game logic, tick -> voxel.
+    pub(crate) scheduled_updates: Vec<(u64, Vec3<i32>)>,
+
+    /// Blocks that have changed since the last tick, voxel -> old ID -> new ID.
+    pub(crate) block_changes: Vec<(Vec3<i32>, u32, u32)>,
+
     /// A listener for when a chunk is done generating or meshing.
     pub(crate) listeners: HashMap<Vec2<i32>, Vec<Vec2<i32>>>,
 
@@ -57,6 +100,15 @@ pub struct Chunks {
 
     pub block_entities: HashMap<Vec3<i32>, Entity>,
 
+    /// The edits made through `chunks.edit_voxel`, `chunks.fill` and `chunks.replace`, to be undone.
+    pub history: EditHistory,
+
+    /// When each chunk was last accessed, by the value of `clock` at the time.
+    accessed: Mutex<HashMap<Vec2<i32>, u64>>,
+
+    /// A counter bumped on every access, to order the accesses of `accessed`.
+    clock: AtomicU64,
+
     /// A copy of the world's config.
     config: WorldConfig,
 
@@ -80,6 +132,7 @@ impl Chunks {
 
         Self {
             folder,
+            history: EditHistory::with_limit(config.max_history),
             config: config.to_owned(),
             ..Default::default()
         }
@@ -91,7 +144,7 @@ impl Chunks {
     }
 
     // Try to load the data of a chunk, returns whether successful or not.
-    pub fn try_load(&self, coords: &Vec2<i32>, registry: &Registry) -> Option<Chunk> {
+    pub fn try_load(&self, coords: &Vec2<i32>) -> Option<Chunk> {
         if !self.config.saving {
             return None;
         }
@@ -131,18 +184,27 @@ impl Chunks {
                 max_height: self.config.max_height,
                 sub_chunks: self.config.sub_chunks,
                 size: self.config.chunk_size,
+                min_y: self.config.min_y,
+                air_id: self.config.air_id,
             },
         );
 
+        // Chunks saved with a different vertical range can't be loaded into this one.
+        if data.min_y != self.config.min_y || voxels.len() != chunk.voxels.data.len() {
+            return None;
+        }
+
         chunk.voxels.data = voxels;
+        chunk.rehash();
 
         if height_map.len() > 0 {
             chunk.height_map.data = height_map;
         } else {
-            chunk.calculate_max_height(registry);
+            chunk.calculate_max_height();
         }
 
-        chunk.status = ChunkStatus::Meshing;
+        chunk.advance_status(ChunkStatus::Generated).unwrap();
+        chunk.advance_status(ChunkStatus::Meshing).unwrap();
 
         Some(chunk)
     }
@@ -176,6 +238,7 @@ impl Chunks {
             id: chunk.id.to_owned(),
             voxels: to_base_64(&chunk.voxels.data),
             height_map: to_base_64(&chunk.height_map.data),
+            min_y: chunk.options.min_y,
         };
 
         let j = serde_json::to_string(&data).unwrap();
@@ -186,11 +249,24 @@ impl Chunks {
         true
     }
 
+    /// Save every ready chunk, clearing the save queue. Returns the number of chunks saved.
+    pub fn save_all(&mut self) -> usize {
+        self.to_save.clear();
+
+        self.map.keys().filter(|coords| self.save(coords)).count()
+    }
+
+    /// Check to see if there are chunks waiting to be saved.
+    pub fn has_pending_saves(&self) -> bool {
+        !self.to_save.is_empty()
+    }
+
     /// Update a chunk, removing the old chunk instance and updating with a new one.
     pub fn renew(&mut self, chunk: Chunk, renew_mesh_only: bool) {
         if renew_mesh_only {
             if let Some(mut old_chunk) = self.map.remove(&chunk.coords) {
                 old_chunk.meshes = chunk.meshes;
+                old_chunk.mesh_keys = chunk.mesh_keys;
                 old_chunk.status = chunk.status;
                 self.map.insert(chunk.coords.to_owned(), old_chunk);
             }
@@ -198,8 +274,9 @@ impl Chunks {
             return;
         }
 
-        self.map.remove(&chunk.coords);
-        self.map.insert(chunk.coords.to_owned(), chunk);
+        let coords = chunk.coords.to_owned();
+        self.map.insert(coords.to_owned(), chunk);
+        self.touch(&coords);
     }
 
     /// Add a new chunk, synonym for `chunks.renew`
@@ -207,6 +284,121 @@ impl Chunks {
         self.renew(chunk, false);
     }
 
+    /// Remove a chunk from the manager, returning it if it was there.
+    pub fn remove(&mut self, coords: &Vec2<i32>) -> Option<Chunk> {
+        self.accessed.get_mut().unwrap().remove(coords);
+        self.map.remove(coords)
+    }
+
+    /// Get a chunk at a chunk coordinate, no matter what stage it's in.
+    pub fn get_chunk(&self, coords: &Vec2<i32>) -> Option<&Chunk> {
+        self.touch(coords);
+        self.map.get(coords)
+    }
+
+    /// Get a mutable chunk at a chunk coordinate, no matter what stage it's in.
+    pub fn get_chunk_mut(&mut self, coords: &Vec2<i32>) -> Option<&mut Chunk> {
+        self.touch(coords);
+        self.cache.insert(coords.to_owned());
+        self.map.get_mut(coords)
+    }
+
+    /// Mark a chunk as just accessed, making it the last to be evicted by `chunks.evict`. Chunks that
+    /// aren't loaded are ignored. The chunks sent to clients and the voxels updated are touched by the
+    /// world's systems.
+    pub fn touch(&self, coords: &Vec2<i32>) {
+        if !self.map.contains_key(coords) {
+            return;
+        }
+
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        self.accessed.lock().unwrap().insert(coords.to_owned(), now);
+    }
+
+    /// Unload the least recently accessed chunks until there are no more than
+    /// `config.max_loaded_chunks`, saving each one first. Only ready chunks outside of `protected`,
+    /// the chunks clients are using, are evicted. Nothing is evicted unless saving is on, as the
+    /// edits made to an evicted chunk would otherwise be lost. The updates still queued for an
+    /// evicted chunk and the listeners waiting on it are dropped along with it. Returns the evicted
+    /// coordinates, coldest first.
+    pub fn evict(&mut self, protected: &HashSet<Vec2<i32>>) -> Vec<Vec2<i32>> {
+        let max = self.config.max_loaded_chunks;
+
+        if max == 0 || !self.config.saving || self.map.len() <= max {
+            return vec![];
+        }
+
+        let accessed = self.accessed.get_mut().unwrap();
+        let mut candidates = self
+            .map
+            .iter()
+            .filter(|(coords, chunk)| {
+                chunk.status == ChunkStatus::Ready && !protected.contains(*coords)
+            })
+            .map(|(coords, _)| {
+                (
+                    accessed.get(coords).copied().unwrap_or(0),
+                    coords.to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(accessed, coords)| (*accessed, coords.0, coords.1));
+
+        let evicted = candidates
+            .into_iter()
+            .take(self.map.len() - max)
+            .map(|(_, coords)| coords)
+            .collect::<Vec<_>>();
+
+        for coords in &evicted {
+            self.save(coords);
+            self.remove(coords);
+            self.listeners.remove(coords);
+        }
+
+        let size = self.config.chunk_size;
+        let is_evicted = |Vec3(vx, vy, vz): &Vec3<i32>| {
+            evicted.contains(&ChunkUtils::map_voxel_to_chunk(*vx, *vy, *vz, size))
+        };
+
+        self.updates = self
+            .updates
+            .drain(..)
+            .filter_map(|update| match update {
+                QueuedUpdate::Voxel((voxel, _)) if is_evicted(&voxel) => None,
+                QueuedUpdate::Batch(updates) => {
+                    let updates = updates
+                        .into_iter()
+                        .filter(|(voxel, _)| !is_evicted(voxel))
+                        .collect::<Vec<_>>();
+                    (!updates.is_empty()).then_some(QueuedUpdate::Batch(updates))
+                }
+                update => Some(update),
+            })
+            .collect();
+        self.active_voxels.retain(|(_, voxel)| !is_evicted(voxel));
+        self.scheduled_updates
+            .retain(|(_, voxel)| !is_evicted(voxel));
+        self.to_send.retain(|(coords, _)| !evicted.contains(coords));
+
+        for listeners in self.listeners.values_mut() {
+            listeners.retain(|coords| !evicted.contains(coords));
+        }
+        self.listeners.retain(|_, listeners| !listeners.is_empty());
+
+        evicted
+    }
+
+    /// Iterate over every chunk the manager holds, in no particular order.
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = (&Vec2<i32>, &Chunk)> {
+        self.map.iter()
+    }
+
+    /// The number of chunks the manager holds.
+    pub fn loaded_count(&self) -> usize {
+        self.map.len()
+    }
+
     /// Get raw chunk data.
     pub fn raw(&self, coords: &Vec2<i32>) -> Option<&Chunk> {
         if !self.is_within_world(coords) {
@@ -259,6 +451,40 @@ impl Chunks {
         self.raw_mut(&coords)
     }
 
+    /// Get the block ID at a voxel coordinate, from whichever chunk it's in. Returns `None` if that
+    /// chunk isn't loaded or the voxel is out of the world's height.
+    pub fn get_block_id(&self, vx: i32, vy: i32, vz: i32) -> Option<u32> {
+        self.touch(&ChunkUtils::map_voxel_to_chunk(
+            vx,
+            vy,
+            vz,
+            self.config.chunk_size,
+        ));
+
+        self.raw_chunk_by_voxel(vx, vy, vz)
+            .filter(|chunk| chunk.contains(vx, vy, vz))
+            .map(|chunk| chunk.get_voxel(vx, vy, vz))
+    }
+
+    /// Set the block ID at a voxel coordinate, in whichever chunk it's in, clearing its rotation and
+    /// stage. The change is queued through `chunks.update_voxel`, so it's relit, remeshed and sent to
+    /// the clients like any other update, once the updating system gets to it.
+    pub fn set_block_id(&mut self, vx: i32, vy: i32, vz: i32, id: u32) -> Result<(), VoxelError> {
+        let chunk = self
+            .raw_chunk_by_voxel(vx, vy, vz)
+            .ok_or(VoxelError::Unloaded(Vec3(vx, vy, vz)))?;
+
+        if !chunk.contains(vx, vy, vz) {
+            return Err(VoxelError::OutOfHeight(Vec3(vx, vy, vz)));
+        }
+
+        let coords = chunk.coords.to_owned();
+        self.touch(&coords);
+        self.update_voxel(&Vec3(vx, vy, vz), BlockUtils::insert_id(0, id));
+
+        Ok(())
+    }
+
     /// Get neighboring coords of a voxel coordinate.
     pub fn voxel_affected_chunks(&self, vx: i32, vy: i32, vz: i32) -> Vec<Vec2<i32>> {
         let mut neighbors = vec![];
@@ -325,6 +551,20 @@ impl Chunks {
         list
     }
 
+    /// Get the chunks around a chunk, within the reach of its light, that haven't made it through
+    /// the pipeline yet. A chunk meshed before these are generated would cull its border faces
+    /// against air that might not stay air.
+    pub fn ungenerated_neighbors(&self, coords: &Vec2<i32>) -> Vec<Vec2<i32>> {
+        self.light_traversed_chunks(coords)
+            .into_iter()
+            .filter(|n_coords| {
+                !self
+                    .raw(n_coords)
+                    .is_some_and(|chunk| chunk.status.is_generated())
+            })
+            .collect()
+    }
+
     /// Create a voxel querying space around a chunk coordinate.
     pub fn make_space<'a>(&'a self, coords: &Vec2<i32>, margin: usize) -> SpaceBuilder<'a> {
         SpaceBuilder {
@@ -336,14 +576,492 @@ impl Chunks {
                 sub_chunks: self.config.sub_chunks,
                 max_height: self.config.max_height,
                 max_light_level: self.config.max_light_level,
+                min_y: self.config.min_y,
+                air_id: self.config.air_id,
             },
             needs_voxels: false,
             needs_lights: false,
             needs_height_maps: false,
             strict: false,
+            radius: None,
+            default_voxel: self.config.air_id,
+        }
+    }
+
+    /// Write the voxels that were set through a space back into the chunks they belong to, flagging
+    /// the affected sub-chunks to be remeshed.
+    pub fn apply_space(&mut self, space: &Space) {
+        for (voxel, val) in space.writes.iter() {
+            self.set_raw_voxel(voxel.0, voxel.1, voxel.2, *val);
         }
     }
 
+    /// Get the level of detail a chunk should be meshed at, based on its distance to the closest
+    /// of the given client chunk centers. Returns 0 (full resolution) if LOD is disabled.
+    pub fn get_lod(&self, coords: &Vec2<i32>, centers: &[Vec2<i32>]) -> u8 {
+        let lod_distance = self.config.lod_distance;
+
+        if lod_distance == 0 || centers.is_empty() {
+            return 0;
+        }
+
+        let closest = centers
+            .iter()
+            .map(|center| (coords.0 - center.0).abs().max((coords.1 - center.1).abs()) as usize)
+            .min()
+            .unwrap();
+
+        (closest / lod_distance).min(MAX_LOD as usize) as u8
+    }
+
+    /// Move an entity's bounding box, placed at `position`, by `velocity` through the loaded voxels.
+    /// Blocked axes are zeroed so the entity slides along walls instead of stopping dead. Returns the
+    /// displacement the entity can actually make. Passable blocks are ignored, and blocks collide
+    /// using their registered AABBs.
+    pub fn resolve_collision(
+        &self,
+        registry: &Registry,
+        aabb: &AABB,
+        position: &Vec3<f32>,
+        velocity: &Vec3<f32>,
+    ) -> Vec3<f32> {
+        self.sweep_body(registry, aabb, position, velocity).0
+    }
+
+    /// Step a physics body by `dt` seconds, applying `gravity` along the y axis, the body's air drag
+    /// and terminal velocity, then resolving its movement against the voxels. Velocity along blocked
+    /// axes is zeroed, and `on_ground` is set if the body is falling onto something.
+    pub fn step_body(&self, registry: &Registry, body: &mut PhysicsBody, dt: f32, gravity: f32) {
+        body.velocity.1 += gravity * dt;
+        body.velocity *= (1.0 - body.air_drag * dt).max(0.0);
+        body.velocity.1 = body.velocity.1.max(-body.terminal_velocity);
+
+        let mut delta = &body.velocity * dt;
+
+        // Sweeps stop a hair above the floor and skip moves too small to register, so a small step
+        // down wouldn't be blocked. Probe a fixed distance below instead, to keep a resting body
+        // grounded no matter how small `dt` is.
+        let falling = delta.1 < 0.0;
+        let grounded = falling
+            && self
+                .sweep_body(
+                    registry,
+                    &body.aabb,
+                    &body.position,
+                    &Vec3(0.0, -GROUND_PROBE, 0.0),
+                )
+                .1[1];
+
+        if grounded {
+            delta.1 = 0.0;
+        }
+
+        let (moved, mut blocked) = self.sweep_body(registry, &body.aabb, &body.position, &delta);
+        blocked[1] |= grounded;
+
+        for (axis, &hit) in blocked.iter().enumerate() {
+            if hit {
+                body.velocity[axis] = 0.0;
+            }
+        }
+
+        body.on_ground = falling && blocked[1];
+        body.position += moved;
+    }
+
+    /// Sweep a bounding box placed at `position` by `velocity`, returning the displacement it can
+    /// make and which axes the sweep hit something along.
+    fn sweep_body(
+        &self,
+        registry: &Registry,
+        aabb: &AABB,
+        position: &Vec3<f32>,
+        velocity: &Vec3<f32>,
+    ) -> (Vec3<f32>, [bool; 3]) {
+        let mut target = aabb.clone();
+        target.translate(position.0, position.1, position.2);
+
+        let (start_x, start_y, start_z) = (target.min_x, target.min_y, target.min_z);
+        let mut blocked = [false; 3];
+
+        sweep(
+            self,
+            registry,
+            &mut target,
+            velocity,
+            &mut |_, axis, _, leftover| {
+                blocked[axis] = true;
+                leftover[axis] = 0.0;
+                false
+            },
+            true,
+            10,
+        );
+
+        (
+            Vec3(
+                target.min_x - start_x,
+                target.min_y - start_y,
+                target.min_z - start_z,
+            ),
+            blocked,
+        )
+    }
+
+    /// Get every voxel that isn't air within an inclusive box, along with its block ID, in x, z, then
+    /// y order. Voxels in chunks that haven't been loaded are skipped.
+    pub fn blocks_in_aabb(&self, min: &Vec3<i32>, max: &Vec3<i32>) -> Vec<(Vec3<i32>, u32)> {
+        let mut blocks = vec![];
+
+        for vx in min.0..=max.0 {
+            for vz in min.2..=max.2 {
+                let chunk = match self.raw_chunk_by_voxel(vx, 0, vz) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+
+                for vy in min.1.max(chunk.min.1)..=max.1.min(chunk.max.1 - 1) {
+                    if !chunk.is_air(vx, vy, vz) {
+                        blocks.push((Vec3(vx, vy, vz), chunk.get_voxel(vx, vy, vz)));
+                    }
+                }
+            }
+        }
+
+        blocks
+    }
+
+    /// Blow up every voxel whose center lies within a radius of a point, returning the voxels that
+    /// were destroyed. The voxels are queued to be set to air like any other update, so clients get
+    /// their updates and the affected chunks are remeshed. When `resistance_aware`, unbreakable blocks
+    /// survive, and blocks only break within the radius shrunk by their blast resistance.
+    pub fn explode(
+        &mut self,
+        registry: &Registry,
+        center: &Vec3<f32>,
+        radius: f32,
+        resistance_aware: bool,
+    ) -> Vec<Vec3<i32>> {
+        let &Vec3(cx, cy, cz) = center;
+        let min = Vec3(
+            (cx - radius).floor() as i32,
+            (cy - radius).floor() as i32,
+            (cz - radius).floor() as i32,
+        );
+        let max = Vec3(
+            (cx + radius).floor() as i32,
+            (cy + radius).floor() as i32,
+            (cz + radius).floor() as i32,
+        );
+
+        let destroyed = self
+            .blocks_in_aabb(&min, &max)
+            .into_iter()
+            .filter(|&(Vec3(vx, vy, vz), id)| {
+                let dx = vx as f32 + 0.5 - cx;
+                let dy = vy as f32 + 0.5 - cy;
+                let dz = vz as f32 + 0.5 - cz;
+                let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                if !resistance_aware {
+                    return distance <= radius;
+                }
+
+                let block = registry.get_block_by_id(id);
+                !block.is_unbreakable && distance <= radius - block.blast_resistance
+            })
+            .map(|(voxel, _)| voxel)
+            .collect::<Vec<_>>();
+
+        let air_id = self.config.air_id;
+        for voxel in destroyed.iter() {
+            self.update_voxel(voxel, air_id);
+        }
+
+        destroyed
+    }
+
+    /// Find a walking path between two voxels with A*, including both ends. A voxel can be stood in
+    /// if it and the voxel above are passable and the voxel below isn't, and each step moves to one
+    /// of the four horizontal neighbors, stepping up or down by at most one block. Gives up with
+    /// `None` once `max_nodes` voxels have been expanded without reaching the goal.
+    pub fn find_path(
+        &self,
+        registry: &Registry,
+        start: &Vec3<i32>,
+        goal: &Vec3<i32>,
+        max_nodes: usize,
+    ) -> Option<Vec<Vec3<i32>>> {
+        let passable = |vx: i32, vy: i32, vz: i32| registry.is_passable(self.get_voxel(vx, vy, vz));
+        let standable = |vx: i32, vy: i32, vz: i32| {
+            passable(vx, vy, vz) && passable(vx, vy + 1, vz) && !passable(vx, vy - 1, vz)
+        };
+
+        if !standable(start.0, start.1, start.2) || !standable(goal.0, goal.1, goal.2) {
+            return None;
+        }
+
+        let expanded = Cell::new(0);
+        let goal_node = PathNode::from_vec3(goal);
+
+        let (nodes, _) = AStar::calculate(
+            start,
+            goal,
+            &|&PathNode(vx, vy, vz)| {
+                let mut successors = vec![];
+
+                expanded.set(expanded.get() + 1);
+                if expanded.get() > max_nodes {
+                    return successors;
+                }
+
+                for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    let (nx, nz) = (vx + dx, vz + dz);
+
+                    if standable(nx, vy, nz) {
+                        successors.push((PathNode(nx, vy, nz), 1));
+                    } else if passable(vx, vy + 2, vz) && standable(nx, vy + 1, nz) {
+                        successors.push((PathNode(nx, vy + 1, nz), 2));
+                    } else if passable(nx, vy + 1, nz) && standable(nx, vy - 1, nz) {
+                        successors.push((PathNode(nx, vy - 1, nz), 2));
+                    }
+                }
+
+                successors
+            },
+            &|node| node.distance(&goal_node),
+        )?;
+
+        Some(
+            nodes
+                .into_iter()
+                .map(|PathNode(vx, vy, vz)| Vec3(vx, vy, vz))
+                .collect(),
+        )
+    }
+
+    /// Select up to `max` voxels connected to `start` through their faces that share its block ID,
+    /// such as a wall of stone, starting with `start` itself and spreading out breadth-first. The
+    /// selection crosses chunk boundaries, but stops at chunks that haven't been loaded.
+    pub fn flood_select(&self, start: &Vec3<i32>, max: usize) -> Vec<Vec3<i32>> {
+        let id = self.get_voxel(start.0, start.1, start.2);
+
+        let mut selected = vec![];
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+
+        visited.insert(start.to_owned());
+        queue.push_back(start.to_owned());
+
+        while let Some(voxel) = queue.pop_front() {
+            if selected.len() >= max {
+                break;
+            }
+
+            let Vec3(vx, vy, vz) = voxel;
+            selected.push(voxel);
+
+            for [ox, oy, oz] in [
+                [1, 0, 0],
+                [-1, 0, 0],
+                [0, 1, 0],
+                [0, -1, 0],
+                [0, 0, 1],
+                [0, 0, -1],
+            ] {
+                let neighbor = Vec3(vx + ox, vy + oy, vz + oz);
+
+                let in_chunk = self
+                    .raw_chunk_by_voxel(neighbor.0, neighbor.1, neighbor.2)
+                    .is_some_and(|chunk| neighbor.1 >= chunk.min.1 && neighbor.1 < chunk.max.1);
+
+                if in_chunk
+                    && self.get_voxel(neighbor.0, neighbor.1, neighbor.2) == id
+                    && visited.insert(neighbor.clone())
+                {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        selected
+    }
+
+    /// Set every voxel within an inclusive box to a block, returning the number of voxels that will
+    /// change. The edits are queued as a single batch, applied all at once so that each chunk they
+    /// touch, including neighbors across chunk borders, is only remeshed once. The fill is recorded
+    /// as a single entry of `chunks.history`.
+    pub fn fill(&mut self, min: &Vec3<i32>, max: &Vec3<i32>, id: u32) -> usize {
+        self.bulk_edit(min, max, |_, current| {
+            (BlockUtils::extract_id(current) != id).then_some(id)
+        })
+    }
+
+    /// Replace every voxel of the block `from_id` within an inclusive box with `to_id`, returning the
+    /// number of voxels that will change. Batched and recorded like `chunks.fill`.
+    pub fn replace(&mut self, min: &Vec3<i32>, max: &Vec3<i32>, from_id: u32, to_id: u32) -> usize {
+        self.bulk_edit(min, max, |_, current| {
+            (BlockUtils::extract_id(current) == from_id).then_some(to_id)
+        })
+    }
+
+    /// Update a voxel like `chunks.update_voxel`, recording the edit in `chunks.history`.
+    pub fn edit_voxel(&mut self, voxel: &Vec3<i32>, val: u32) {
+        let old = self
+            .pending_voxel(voxel)
+            .unwrap_or_else(|| self.get_raw_voxel(voxel.0, voxel.1, voxel.2));
+        self.history.record(vec![(voxel.to_owned(), old, val)]);
+        self.update_voxel(voxel, val);
+    }
+
+    /// Revert the latest entry of `chunks.history`, returning whether there was anything to undo.
+    /// The reverting updates are batched like `chunks.fill`.
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo() {
+            Some(updates) => {
+                self.updates.push_back(QueuedUpdate::Batch(updates));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replay the latest undone entry of `chunks.history`, returning whether there was anything to
+    /// redo. The replaying updates are batched like `chunks.fill`.
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo() {
+            Some(updates) => {
+                self.updates.push_back(QueuedUpdate::Batch(updates));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy the raw voxels, with their rotations and stages, within an inclusive box into a
+    /// schematic. Voxels in chunks that haven't been loaded are copied as `config.air_id`.
+    pub fn capture(&self, min: &Vec3<i32>, max: &Vec3<i32>) -> Schematic {
+        let size = Vec3(
+            (max.0 - min.0 + 1).max(0) as usize,
+            (max.1 - min.1 + 1).max(0) as usize,
+            (max.2 - min.2 + 1).max(0) as usize,
+        );
+        let mut schematic = Schematic::new(&size);
+
+        for x in 0..size.0 {
+            for y in 0..size.1 {
+                for z in 0..size.2 {
+                    let raw =
+                        self.get_raw_voxel(min.0 + x as i32, min.1 + y as i32, min.2 + z as i32);
+                    schematic.set(x, y, z, raw);
+                }
+            }
+        }
+
+        schematic
+    }
+
+    /// Paste a schematic with its minimum corner at `origin`, returning the number of voxels that
+    /// will change. Batched and recorded like `chunks.fill`. Voxels landing in chunks that haven't
+    /// been loaded are skipped.
+    pub fn paste(&mut self, origin: &Vec3<i32>, schematic: &Schematic) -> usize {
+        let Vec3(width, height, depth) = schematic.size;
+
+        if width == 0 || height == 0 || depth == 0 {
+            return 0;
+        }
+
+        let max = Vec3(
+            origin.0 + width as i32 - 1,
+            origin.1 + height as i32 - 1,
+            origin.2 + depth as i32 - 1,
+        );
+
+        self.bulk_edit(origin, &max, |voxel, current| {
+            let raw = schematic.get(
+                (voxel.0 - origin.0) as usize,
+                (voxel.1 - origin.1) as usize,
+                (voxel.2 - origin.2) as usize,
+            );
+            (raw != current).then_some(raw)
+        })
+    }
+
+    /// Queue an edit of every loaded voxel within an inclusive box as a single batch and history
+    /// entry. `edit` is given each voxel and its pending raw value, and returns its new raw value.
+    fn bulk_edit<F: Fn(&Vec3<i32>, u32) -> Option<u32>>(
+        &mut self,
+        min: &Vec3<i32>,
+        max: &Vec3<i32>,
+        edit: F,
+    ) -> usize {
+        let pending = self.pending_voxels(min, max);
+        let mut edits = vec![];
+
+        for vx in min.0..=max.0 {
+            for vz in min.2..=max.2 {
+                let chunk = match self.raw_chunk_by_voxel(vx, 0, vz) {
+                    Some(chunk) => chunk,
+                    None => continue,
+                };
+
+                for vy in min.1.max(chunk.min.1)..=max.1.min(chunk.max.1 - 1) {
+                    let voxel = Vec3(vx, vy, vz);
+                    let current = pending
+                        .get(&voxel)
+                        .copied()
+                        .unwrap_or_else(|| self.get_raw_voxel(vx, vy, vz));
+
+                    if let Some(raw) = edit(&voxel, current) {
+                        if raw != current {
+                            edits.push((voxel, current, raw));
+                        }
+                    }
+                }
+            }
+        }
+
+        let count = edits.len();
+
+        if count > 0 {
+            self.updates.push_back(QueuedUpdate::Batch(
+                edits
+                    .iter()
+                    .map(|(voxel, _, id)| (voxel.to_owned(), *id))
+                    .collect(),
+            ));
+            self.history.record(edits);
+        }
+
+        count
+    }
+
+    /// The raw values that queued updates will set the voxels within an inclusive box to.
+    fn pending_voxels(&self, min: &Vec3<i32>, max: &Vec3<i32>) -> HashMap<Vec3<i32>, u32> {
+        self.updates
+            .iter()
+            .flat_map(|update| update.voxels())
+            .filter(|(Vec3(vx, vy, vz), _)| {
+                (min.0..=max.0).contains(vx)
+                    && (min.1..=max.1).contains(vy)
+                    && (min.2..=max.2).contains(vz)
+            })
+            .map(|(voxel, val)| (voxel.to_owned(), *val))
+            .collect()
+    }
+
+    /// The raw value that the latest queued update will set a voxel to, if any.
+    fn pending_voxel(&self, voxel: &Vec3<i32>) -> Option<u32> {
+        self.updates.iter().rev().find_map(|update| {
+            update
+                .voxels()
+                .iter()
+                .rev()
+                .find(|(queued, _)| queued == voxel)
+                .map(|(_, val)| *val)
+        })
+    }
+
     /// Check to see if chunk is within the world's min/max chunk.
     pub fn is_within_world(&self, coords: &Vec2<i32>) -> bool {
         coords.0 >= self.config.min_chunk[0]
@@ -361,6 +1079,67 @@ impl Chunks {
         false
     }
 
+    /// Mesh many chunks in parallel at their own level of detail, sharing the registry across
+    /// threads. Chunks that aren't ready are skipped. Geometries of each chunk are sorted by their
+    /// block, face and position, so the results never depend on how the threads interleave.
+    pub fn mesh_chunks(
+        &self,
+        coords: &[Vec2<i32>],
+        registry: &Registry,
+    ) -> HashMap<Vec2<i32>, Vec<GeometryProtocol>> {
+        let biomes = BiomeMap::new(self.config.seed);
+
+        coords
+            .par_iter()
+            .filter(|coords| self.is_chunk_ready(coords))
+            .map(|coords| {
+                let chunk = self.raw(coords).unwrap();
+
+                if chunk.is_empty() {
+                    return (coords.to_owned(), vec![]);
+                }
+
+                let space = self
+                    .make_space(coords, 1)
+                    .needs_height_maps()
+                    .needs_voxels()
+                    .needs_lights()
+                    .build();
+
+                let mut geometries = Mesher::mesh_space_lod(
+                    &chunk.min,
+                    &chunk.max,
+                    &space,
+                    registry,
+                    chunk.lod,
+                    Some(&biomes),
+                );
+                geometries.sort_by(|a, b| {
+                    (a.voxel, &a.face_name, &a.at).cmp(&(b.voxel, &b.face_name, &b.at))
+                });
+
+                (coords.to_owned(), geometries)
+            })
+            .collect()
+    }
+
+    /// Reassemble the geometries of a chunk from the meshes of its sub-chunks, from the bottom up.
+    /// Sub-chunks are meshed on their own, so an edit only remeshes the sub-chunks it touches.
+    pub fn get_geometries(&self, coords: &Vec2<i32>) -> Vec<GeometryProtocol> {
+        let meshes = match self.raw(coords).and_then(|chunk| chunk.meshes.as_ref()) {
+            Some(meshes) => meshes,
+            None => return vec![],
+        };
+
+        let mut levels = meshes.keys().collect::<Vec<_>>();
+        levels.sort();
+
+        levels
+            .into_iter()
+            .flat_map(|level| meshes[level].geometries.to_owned())
+            .collect()
+    }
+
     /// Clear the mutable chunk borrowing list.
     pub fn clear_cache(&mut self) {
         self.cache.clear();
@@ -371,9 +1150,10 @@ impl Chunks {
     /// be done in the background.
     pub fn update_voxel(&mut self, voxel: &Vec3<i32>, val: u32) {
         self.updates
-            .retain(|(v, _)| !(v.0 == voxel.0 && v.1 == voxel.1 && v.2 == voxel.2));
+            .retain(|update| !matches!(update, QueuedUpdate::Voxel((v, _)) if v == voxel));
 
-        self.updates.push_back((voxel.to_owned(), val));
+        self.updates
+            .push_back(QueuedUpdate::Voxel((voxel.to_owned(), val)));
     }
 
     pub fn update_voxels(&mut self, voxels: &[(Vec3<i32>, u32)]) {
@@ -391,6 +1171,77 @@ impl Chunks {
         self.active_voxels.push((active_at, voxel.to_owned()));
     }
 
+    /// Schedule a block update at a voxel to be fired at a certain tick.
+    pub fn schedule_block_update(&mut self, voxel: &Vec3<i32>, fire_at: u64) {
+        self.scheduled_updates.push((fire_at, voxel.to_owned()));
+    }
+
+    /// Take all the scheduled block updates that are due by `tick`. Updates in chunks that are not
+    /// loaded are discarded, and updates in chunks that are still being generated are deferred.
+    pub fn take_due_updates(&mut self, tick: u64) -> Vec<Vec3<i32>> {
+        let mut due = vec![];
+        let size = self.config.chunk_size;
+
+        let scheduled = std::mem::take(&mut self.scheduled_updates);
+
+        for (fire_at, voxel) in scheduled {
+            if fire_at > tick {
+                self.scheduled_updates.push((fire_at, voxel));
+                continue;
+            }
+
+            let coords = ChunkUtils::map_voxel_to_chunk(voxel.0, voxel.1, voxel.2, size);
+
+            if self.is_chunk_ready(&coords) {
+                due.push(voxel);
+            } else if self.raw(&coords).is_some() {
+                self.scheduled_updates.push((fire_at, voxel));
+            }
+        }
+
+        due
+    }
+
+    /// Pick `count` random voxels in each ready chunk, returning the ones that are random-tickable.
+    /// Chunks are visited in order of their coordinates, so that a seeded `rng` is reproducible.
+    pub fn pick_random_ticks(
+        &self,
+        registry: &Registry,
+        rng: &mut fastrand::Rng,
+        count: usize,
+    ) -> Vec<Vec3<i32>> {
+        let mut coords = self
+            .map
+            .iter()
+            .filter(|(_, chunk)| chunk.status == ChunkStatus::Ready)
+            .map(|(coords, _)| coords.to_owned())
+            .collect::<Vec<_>>();
+        coords.sort_by_key(|coords| (coords.0, coords.1));
+
+        let mut picked = vec![];
+
+        for coords in coords {
+            let chunk = self.raw(&coords).unwrap();
+            let Vec3(min_x, min_y, min_z) = chunk.min;
+            let Vec3(max_x, max_y, max_z) = chunk.max;
+
+            for _ in 0..count {
+                let vx = rng.i32(min_x..max_x);
+                let vy = rng.i32(min_y..max_y);
+                let vz = rng.i32(min_z..max_z);
+
+                if registry
+                    .get_block_by_id(chunk.get_voxel(vx, vy, vz))
+                    .random_tick
+                {
+                    picked.push(Vec3(vx, vy, vz));
+                }
+            }
+        }
+
+        picked
+    }
+
     /// Add a chunk to be saved.
     pub fn add_chunk_to_save(&mut self, coords: &Vec2<i32>, prioritized: bool) {
         if !self.to_save.contains(coords) {
@@ -445,12 +1296,12 @@ impl Chunks {
 }
 
 impl VoxelAccess for Chunks {
-    /// Get the raw voxel value at a voxel coordinate. If chunk not found, 0 is returned.
+    /// Get the raw voxel value at a voxel coordinate. If chunk not found, `config.air_id` is returned.
     fn get_raw_voxel(&self, vx: i32, vy: i32, vz: i32) -> u32 {
         if let Some(chunk) = self.raw_chunk_by_voxel(vx, vy, vz) {
             chunk.get_raw_voxel(vx, vy, vz)
         } else {
-            0
+            self.config.air_id
         }
     }
 
@@ -499,12 +1350,10 @@ impl VoxelAccess for Chunks {
 
         if let Some(chunk) = self.raw_chunk_by_voxel(vx, vy, vz) {
             chunk.get_sunlight(vx, vy, vz)
+        } else if vy < self.config.min_y {
+            0
         } else {
-            return if vy < 0 {
-                0
-            } else {
-                self.config.max_light_level
-            };
+            self.config.max_light_level
         }
     }
 
@@ -527,6 +1376,10 @@ impl VoxelAccess for Chunks {
         false
     }
 
+    fn min_y(&self) -> i32 {
+        self.config.min_y
+    }
+
     fn contains(&self, vx: i32, vy: i32, vz: i32) -> bool {
         self.raw_chunk_by_voxel(vx, vy, vz).is_some()
     }
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use crate::{Vec3, VoxelUpdate};
+
+/// A recorded change of a voxel, voxel -> old raw value -> new raw value.
+pub type BlockEdit = (Vec3<i32>, u32, u32);
+
+/// A history of block edits to be undone and redone, such as for a creative editor. Each entry is
+/// a group of edits undone together, so a bulk edit like `chunks.fill` is a single entry.
+#[derive(Default, Clone)]
+pub struct EditHistory {
+    /// Entries that can be undone, latest last.
+    undos: VecDeque<Vec<BlockEdit>>,
+
+    /// Entries that have been undone and can be redone, latest last.
+    redos: Vec<Vec<BlockEdit>>,
+
+    /// The most entries kept to be undone, if any.
+    limit: Option<usize>,
+}
+
+impl EditHistory {
+    /// Create an empty edit history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty edit history that keeps at most `limit` entries to be undone, dropping the
+    /// oldest ones past it.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    /// Record a group of edits as a single entry. Recording anything new drops the entries that
+    /// could have been redone.
+    pub fn record(&mut self, edits: Vec<BlockEdit>) {
+        if edits.is_empty() {
+            return;
+        }
+
+        self.push_undo(edits);
+        self.redos.clear();
+    }
+
+    /// Take the latest entry off of the history, returning the updates that revert it.
+    pub fn undo(&mut self) -> Option<Vec<VoxelUpdate>> {
+        let edits = self.undos.pop_back()?;
+        let updates = edits
+            .iter()
+            .rev()
+            .map(|(voxel, old, _)| (voxel.to_owned(), *old))
+            .collect();
+
+        self.redos.push(edits);
+        Some(updates)
+    }
+
+    /// Put the latest undone entry back onto the history, returning the updates that replay it.
+    pub fn redo(&mut self) -> Option<Vec<VoxelUpdate>> {
+        let edits = self.redos.pop()?;
+        let updates = edits
+            .iter()
+            .map(|(voxel, _, new)| (voxel.to_owned(), *new))
+            .collect();
+
+        self.push_undo(edits);
+        Some(updates)
+    }
+
+    /// Check if there's anything to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undos.is_empty()
+    }
+
+    /// Check if there's anything to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redos.is_empty()
+    }
+
+    /// Forget every recorded edit.
+    pub fn clear(&mut self) {
+        self.undos.clear();
+        self.redos.clear();
+    }
+
+    fn push_undo(&mut self, edits: Vec<BlockEdit>) {
+        self.undos.push_back(edits);
+
+        if let Some(limit) = self.limit {
+            while self.undos.len() > limit {
+                self.undos.pop_front();
+            }
+        }
+    }
+}
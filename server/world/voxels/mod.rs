@@ -2,10 +2,14 @@ mod access;
 mod block;
 mod chunk;
 mod chunks;
+mod history;
+mod schematic;
 mod space;
 
 pub use access::VoxelAccess;
 pub use block::*;
 pub use chunk::*;
-pub use chunks::Chunks;
+pub use chunks::{Chunks, MAX_LOD};
+pub use history::*;
+pub use schematic::Schematic;
 pub use space::*;
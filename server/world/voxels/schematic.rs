@@ -0,0 +1,108 @@
+use std::io::{ErrorKind, Read, Write};
+
+use byteorder::{ByteOrder, LittleEndian};
+use libflate::zlib::{Decoder, Encoder};
+
+use crate::{SchematicError, Vec3};
+
+/// A copy of a box of voxels, captured with `chunks.capture` and placed with `chunks.paste`. Voxels
+/// are kept raw, so their rotations and stages come along with their IDs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    /// Width, height and depth of the schematic.
+    pub size: Vec3<usize>,
+
+    /// Raw voxel values of the schematic, in x, z, then y order.
+    voxels: Vec<u32>,
+}
+
+impl Schematic {
+    /// Create a schematic of a certain size, filled with zeros.
+    pub fn new(size: &Vec3<usize>) -> Self {
+        Self {
+            size: size.to_owned(),
+            voxels: vec![0; size.0 * size.1 * size.2],
+        }
+    }
+
+    /// Get the raw voxel value at a position within the schematic.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.voxels[self.index(x, y, z)]
+    }
+
+    /// Set the raw voxel value at a position within the schematic.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, raw: u32) {
+        let index = self.index(x, y, z);
+        self.voxels[index] = raw;
+    }
+
+    /// Encode the schematic into zlib-compressed bytes, its size followed by its voxels as
+    /// little-endian u32's.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![self.size.0 as u32, self.size.1 as u32, self.size.2 as u32];
+        data.extend_from_slice(&self.voxels);
+
+        let mut bytes = vec![0; data.len() * 4];
+        LittleEndian::write_u32_into(&data, &mut bytes);
+
+        let mut encoder = Encoder::new(vec![]).unwrap();
+        encoder.write_all(&bytes).unwrap();
+        encoder.finish().into_result().unwrap()
+    }
+
+    /// Decode a schematic from the bytes of `schematic.to_bytes`. No more voxels are inflated than
+    /// the size of the schematic calls for.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SchematicError> {
+        let decompress_error =
+            |error: std::io::Error| SchematicError::Decompress(error.to_string());
+        let mut decoder = Decoder::new(bytes).map_err(decompress_error)?;
+
+        let mut header = [0; 12];
+        decoder
+            .read_exact(&mut header)
+            .map_err(|error| match error.kind() {
+                ErrorKind::UnexpectedEof => SchematicError::MissingSize,
+                _ => decompress_error(error),
+            })?;
+
+        let mut dimensions = [0; 3];
+        LittleEndian::read_u32_into(&header, &mut dimensions);
+        let size = Vec3(
+            dimensions[0] as usize,
+            dimensions[1] as usize,
+            dimensions[2] as usize,
+        );
+
+        let expected = size
+            .0
+            .checked_mul(size.1)
+            .and_then(|area| area.checked_mul(size.2))
+            .ok_or(SchematicError::SizeOverflow)?;
+        let limit = expected
+            .checked_mul(4)
+            .ok_or(SchematicError::SizeOverflow)?;
+
+        // Read one byte past the voxels, to tell if there are more than the size calls for.
+        let mut buf = vec![];
+        decoder
+            .take(limit as u64 + 1)
+            .read_to_end(&mut buf)
+            .map_err(decompress_error)?;
+
+        if buf.len() != limit {
+            return Err(SchematicError::SizeMismatch(
+                expected,
+                buf.len().div_ceil(4),
+            ));
+        }
+
+        let mut voxels = vec![0; expected];
+        LittleEndian::read_u32_into(&buf, &mut voxels);
+
+        Ok(Self { size, voxels })
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + z * self.size.0 + y * self.size.0 * self.size.2
+    }
+}
@@ -1,7 +1,7 @@
 use hashbrown::{HashMap, HashSet};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{ndarray, BlockUtils, ChunkUtils, LightUtils, Ndarray, Vec2, Vec3};
+use crate::{ndarray, BlockUtils, ChunkUtils, LightUtils, Ndarray, Vec2, Vec3, VoxelUpdate};
 
 use super::{
     access::VoxelAccess,
@@ -32,8 +32,14 @@ pub struct SpaceOptions {
     /// Maximum height of the chunk/space.
     pub max_height: usize,
 
+    /// The lowest voxel y-coordinate of the chunk/space.
+    pub min_y: i32,
+
     /// Maximum light of the voxelize world.
     pub max_light_level: u32,
+
+    /// The ID of the air block, read outside of the space's height.
+    pub air_id: u32,
 }
 
 /// A data structure used in Voxelize to access voxel data of multiple chunks at
@@ -61,6 +67,12 @@ pub struct Space {
     /// A set of sub-chunks that have been updated.
     pub updated_levels: HashSet<u32>,
 
+    /// The voxel read from chunks that aren't loaded into this space. Defaults to air.
+    pub default_voxel: u32,
+
+    /// Voxels set through this space, waiting to be written back with `chunks.apply_space`.
+    pub(crate) writes: Vec<VoxelUpdate>,
+
     /// A map of voxels, chunk coordinates -> n-dims array of voxels.
     voxels: HashMap<Vec2<i32>, Ndarray<u32>>,
 
@@ -74,13 +86,21 @@ pub struct Space {
 impl Space {
     /// Converts a voxel position to a chunk coordinate and a chunk local coordinate.
     fn to_local(&self, vx: i32, vy: i32, vz: i32) -> (Vec2<i32>, Vec3<usize>) {
-        let SpaceOptions { chunk_size, .. } = self.options;
+        let SpaceOptions {
+            chunk_size, min_y, ..
+        } = self.options;
 
         let coords = ChunkUtils::map_voxel_to_chunk(vx, vy, vz, chunk_size);
-        let local = ChunkUtils::map_voxel_to_chunk_local(vx, vy, vz, chunk_size);
+        let local = ChunkUtils::map_voxel_to_chunk_local(vx, vy - min_y, vz, chunk_size);
 
         (coords, local)
     }
+
+    /// The number of voxels each sub-chunk spans vertically.
+    fn level_height(&self) -> u32 {
+        let height = self.options.max_height as i32 - self.options.min_y;
+        (height as usize / self.options.sub_chunks) as u32
+    }
 }
 
 /// A data structure to build a space.
@@ -94,6 +114,13 @@ pub struct SpaceBuilder<'a> {
     pub needs_height_maps: bool,
 
     pub strict: bool,
+
+    /// If set, the space loads every chunk within this many chunks of the center instead of the
+    /// chunks that light could traverse, and extends its margin to match.
+    pub radius: Option<usize>,
+
+    /// The voxel read from chunks that aren't loaded into the space.
+    pub default_voxel: u32,
 }
 
 impl SpaceBuilder<'_> {
@@ -129,12 +156,27 @@ impl SpaceBuilder<'_> {
         self
     }
 
+    /// Set this space to load every chunk within a radius of chunks around the center, measured
+    /// as the Chebyshev distance.
+    pub fn radius(mut self, radius: usize) -> Self {
+        self.radius = Some(radius);
+        self.options.margin = radius * self.options.chunk_size;
+        self
+    }
+
+    /// Set the voxel that is read from chunks that aren't loaded into the space. Defaults to air.
+    pub fn default_voxel(mut self, id: u32) -> Self {
+        self.default_voxel = id;
+        self
+    }
+
     /// Create a `Space` instance with the instructed data loaded in.
     pub fn build(self) -> Space {
         let SpaceOptions {
             margin,
             chunk_size,
             max_height,
+            min_y,
             ..
         } = self.options;
 
@@ -146,9 +188,22 @@ impl SpaceBuilder<'_> {
 
         let width = chunk_size + margin * 2;
 
-        let (voxels, lights, height_maps): (HashMap<_, _>, HashMap<_, _>, HashMap<_, _>) = self
-            .chunks
-            .light_traversed_chunks(&self.coords)
+        let members = if let Some(radius) = self.radius {
+            let radius = radius as i32;
+            let mut members = vec![];
+
+            for x in -radius..=radius {
+                for z in -radius..=radius {
+                    members.push(Vec2(cx + x, cz + z));
+                }
+            }
+
+            members
+        } else {
+            self.chunks.light_traversed_chunks(&self.coords)
+        };
+
+        let (voxels, lights, height_maps): (HashMap<_, _>, HashMap<_, _>, HashMap<_, _>) = members
             .into_par_iter()
             .filter_map(|n_coords| {
                 if !self.chunks.is_within_world(&n_coords) {
@@ -210,11 +265,11 @@ impl SpaceBuilder<'_> {
 
         let min = Vec3(
             cx * chunk_size as i32 - margin as i32,
-            0,
+            min_y,
             cz * chunk_size as i32 - margin as i32,
         );
 
-        let shape = Vec3(width, max_height, width);
+        let shape = Vec3(width, (max_height as i32 - min_y) as usize, width);
 
         Space {
             coords: self.coords,
@@ -228,13 +283,15 @@ impl SpaceBuilder<'_> {
             lights,
             height_maps,
 
+            default_voxel: self.default_voxel,
+
             ..Default::default()
         }
     }
 }
 
 impl VoxelAccess for Space {
-    /// Get the raw voxel data at the voxel position. Zero is returned if chunk doesn't exist.
+    /// Get the raw voxel data at the voxel position. The default voxel is returned if chunk doesn't exist.
     /// Panics if space does not contain voxel data.
     fn get_raw_voxel(&self, vx: i32, vy: i32, vz: i32) -> u32 {
         if self.voxels.is_empty() {
@@ -245,20 +302,47 @@ impl VoxelAccess for Space {
 
         if let Some(voxels) = self.voxels.get(&coords) {
             if !voxels.contains(&[lx, ly, lz]) {
-                return 0;
+                return self.options.air_id;
             }
 
             return voxels[&[lx, ly, lz]];
         }
 
-        0
+        self.default_voxel
     }
 
-    /// Get the voxel type at the voxel position. Zero is returned if chunk doesn't exist.
-    /// Panics if space does not contain voxel data.
+    /// Set the raw voxel data at the voxel position, to be written back to its chunk later on. Returns
+    /// false if the chunk isn't part of this space. Panics if space does not contain voxel data.
+    fn set_raw_voxel(&mut self, vx: i32, vy: i32, vz: i32, val: u32) -> bool {
+        if self.voxels.is_empty() {
+            panic!("Space does not contain voxel data.");
+        }
+
+        if vy < self.options.min_y || vy >= self.options.max_height as i32 {
+            return false;
+        }
+
+        let (coords, Vec3(lx, ly, lz)) = self.to_local(vx, vy, vz);
+
+        let chunk_level = ly as u32 / self.level_height();
+
+        if let Some(voxels) = self.voxels.get_mut(&coords) {
+            self.updated_levels.insert(chunk_level);
+
+            voxels[&[lx, ly, lz]] = val;
+            self.writes.push((Vec3(vx, vy, vz), val));
+            return true;
+        }
+
+        false
+    }
+
+    /// Get the voxel type at the voxel position. The default voxel is returned if chunk doesn't exist,
+    /// and `options.air_id` outside of the space's height. Panics if space does not contain voxel
+    /// data.
     fn get_voxel(&self, vx: i32, vy: i32, vz: i32) -> u32 {
-        if !self.contains(vx, vy, vz) {
-            return 0;
+        if vy < self.options.min_y || vy >= self.options.max_height as i32 {
+            return self.options.air_id;
         }
 
         BlockUtils::extract_id(self.get_raw_voxel(vx, vy, vz))
@@ -291,9 +375,9 @@ impl VoxelAccess for Space {
             panic!("Space does not contain light data.");
         }
 
-        if vy > 0 && vy as usize >= self.options.max_height {
+        if vy >= self.options.max_height as i32 {
             return LightUtils::insert_sunlight(0, self.options.max_light_level);
-        } else if vy < 0 {
+        } else if vy < self.options.min_y {
             return 0;
         }
 
@@ -323,9 +407,9 @@ impl VoxelAccess for Space {
 
         let (coords, Vec3(lx, ly, lz)) = self.to_local(vx, vy, vz);
 
+        let chunk_level = ly as u32 / self.level_height();
+
         if let Some(lights) = self.lights.get_mut(&coords) {
-            let chunk_level =
-                vy as u32 / (self.options.max_height / self.options.sub_chunks) as u32;
             self.updated_levels.insert(chunk_level);
 
             lights[&[lx, ly, lz]] = level;
@@ -338,7 +422,7 @@ impl VoxelAccess for Space {
     /// Get the sunlight level at the voxel position. Zero is returned if chunk doesn't exist.
     fn get_sunlight(&self, vx: i32, vy: i32, vz: i32) -> u32 {
         if !self.contains(vx, vy, vz) {
-            return if vy < 0 {
+            return if vy < self.options.min_y {
                 0
             } else {
                 self.options.max_light_level
@@ -354,11 +438,11 @@ impl VoxelAccess for Space {
             panic!("Space does not contain height map data.");
         }
 
-        if !self.contains(vx, 0, vz) {
+        if !self.contains(vx, self.options.min_y, vz) {
             return 0;
         }
 
-        let (coords, Vec3(lx, _, lz)) = self.to_local(vx, 0, vz);
+        let (coords, Vec3(lx, _, lz)) = self.to_local(vx, self.options.min_y, vz);
 
         if let Some(height_map) = self.height_maps.get(&coords) {
             return height_map[&[lx, lz]];
@@ -372,11 +456,15 @@ impl VoxelAccess for Space {
         self.lights.get(&Vec2(cx, cz))
     }
 
+    fn min_y(&self) -> i32 {
+        self.options.min_y
+    }
+
     /// Check if space contains this coordinate
     fn contains(&self, vx: i32, vy: i32, vz: i32) -> bool {
         let (coords, _) = self.to_local(vx, vy, vz);
 
-        vy >= 0
+        vy >= self.options.min_y
             && vy < self.options.max_height as i32
             && (self.lights.contains_key(&coords)
                 || self.voxels.contains_key(&coords)
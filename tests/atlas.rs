@@ -0,0 +1,518 @@
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read};
+
+    use libflate::zlib::Decoder;
+    use voxelize::{AtlasError, Block, BlockFaces, Face, Registry, TextureAtlas, UvRegion};
+
+    fn colored_atlas(tile_size: u32) -> (TextureAtlas, Vec<[u8; 4]>) {
+        let colors = vec![
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [255, 255, 0, 255],
+            [0, 255, 255, 255],
+        ];
+
+        let mut atlas = TextureAtlas::new(tile_size);
+
+        for (index, color) in colors.iter().enumerate() {
+            atlas.add_faces(
+                &format!("block{index}"),
+                &[Face::color("all", tile_size, *color)],
+            );
+        }
+
+        (atlas, colors)
+    }
+
+    #[test]
+    fn mipmap_generation() {
+        let levels = 3;
+        let (mut atlas, colors) = colored_atlas(16);
+
+        atlas.generate_with_mipmaps(levels).unwrap();
+
+        assert_eq!(atlas.mip_levels(), levels + 1);
+
+        for level in 1..=levels {
+            assert_eq!(
+                atlas.mip_dimension(level) * 2,
+                atlas.mip_dimension(level - 1)
+            );
+            assert_eq!(
+                atlas.mipmap(level).unwrap().len(),
+                (atlas.mip_dimension(level).pow(2) * 4) as usize
+            );
+        }
+
+        // Every pixel of a tile, edges included, should only ever contain that tile's color.
+        for level in 0..=levels {
+            let dimension = atlas.mip_dimension(level);
+            let pixels = atlas.mipmap(level).unwrap();
+
+            for (index, color) in colors.iter().enumerate() {
+                let group = atlas.get_group(&format!("block{index}")).unwrap();
+                let range = &group.ranges[0];
+
+                let start_x = (range.start_u * dimension as f32).floor() as u32;
+                let end_x = (range.end_u * dimension as f32).ceil() as u32;
+                let start_y = (range.start_v * dimension as f32).floor() as u32;
+                let end_y = (range.end_v * dimension as f32).ceil() as u32;
+
+                for y in start_y..end_y {
+                    for x in start_x..end_x {
+                        let index = ((y * dimension + x) * 4) as usize;
+                        assert_eq!(&pixels[index..index + 4], color);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn animated_faces() {
+        let (mut atlas, _) = colored_atlas(16);
+
+        let frames = (0..4)
+            .map(|frame| Face::color("water", 16, [0, 0, frame * 60, 255]))
+            .collect::<Vec<_>>();
+
+        atlas.add_animated_faces("water", &frames, 8.0);
+        atlas.generate().unwrap();
+
+        let water = atlas.get_group("water").unwrap();
+        assert_eq!(water.frames, 4);
+        assert_eq!(water.fps, Some(8.0));
+        assert_eq!(water.ranges.len(), 4);
+
+        let still = atlas.get_group("block0").unwrap();
+        assert_eq!(still.frames, 1);
+        assert_eq!(still.fps, None);
+        assert_eq!(still.ranges.len(), 1);
+    }
+
+    #[test]
+    fn deterministic_packing() {
+        let faces = [
+            ("dirt", [120, 80, 40, 255]),
+            ("stone", [128, 128, 128, 255]),
+            ("grass", [0, 200, 0, 255]),
+            ("sand", [220, 200, 120, 255]),
+            ("water", [0, 0, 255, 255]),
+        ];
+
+        let mut forwards = TextureAtlas::new(16);
+        for (name, color) in faces.iter() {
+            forwards.add_faces(name, &[Face::color("all", 16, *color)]);
+        }
+
+        let mut backwards = TextureAtlas::new(16);
+        for (name, color) in faces.iter().rev() {
+            backwards.add_faces(name, &[Face::color("all", 16, *color)]);
+        }
+
+        forwards.generate().unwrap();
+        backwards.generate().unwrap();
+
+        assert!(forwards.dimension().is_power_of_two());
+        assert_eq!(forwards.dimension(), backwards.dimension());
+
+        for (name, _) in faces.iter() {
+            assert_eq!(
+                forwards.get_group(name).unwrap().ranges,
+                backwards.get_group(name).unwrap().ranges
+            );
+        }
+    }
+
+    #[test]
+    fn png_export() {
+        let (mut atlas, _) = colored_atlas(8);
+
+        let path = std::env::temp_dir().join("voxelize-atlas-test.png");
+        assert!(atlas.save_png(&path).is_err());
+
+        atlas.generate().unwrap();
+        atlas.save_png(&path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[1..4], b"PNG");
+        assert_eq!(&bytes[12..16], b"IHDR");
+
+        let width = u32::from_be_bytes(bytes[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(bytes[20..24].try_into().unwrap());
+        assert_eq!(width, atlas.dimension());
+        assert_eq!(height, atlas.dimension());
+
+        // IDAT directly follows the 13-byte IHDR and its CRC.
+        let length = u32::from_be_bytes(bytes[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[37..41], b"IDAT");
+
+        let mut decoder = Decoder::new(&bytes[41..41 + length]).unwrap();
+        let mut scanlines = vec![];
+        decoder.read_to_end(&mut scanlines).unwrap();
+
+        let pixels = scanlines
+            .chunks(width as usize * 4 + 1)
+            .flat_map(|line| line[1..].to_vec())
+            .collect::<Vec<_>>();
+
+        assert_eq!(pixels, atlas.to_rgba_bytes());
+    }
+
+    #[test]
+    fn per_face_textures() {
+        let grass = Block::new("Grass Block")
+            .faces(
+                &BlockFaces::six_faces()
+                    .top("grass_top")
+                    .bottom("dirt")
+                    .sides("grass_side")
+                    .build(),
+            )
+            .build();
+
+        let mut atlas = TextureAtlas::new(16);
+        atlas.add_block(
+            &grass,
+            &[
+                Face::color("grass_top", 16, [0, 200, 0, 255]),
+                Face::color("dirt", 16, [120, 80, 40, 255]),
+                Face::color("grass_side", 16, [80, 160, 40, 255]),
+            ],
+        );
+        atlas.generate().unwrap();
+
+        let group = atlas.get_group("Grass Block").unwrap();
+        let top = group.range("grass_top").unwrap();
+        let bottom = group.range("dirt").unwrap();
+        let side = group.range("grass_side").unwrap();
+
+        assert_ne!(top, bottom);
+        assert_ne!(top, side);
+        assert_ne!(bottom, side);
+
+        for face in grass.faces.iter() {
+            assert!(group.range(&face.name).is_some());
+        }
+    }
+
+    #[test]
+    fn uv_lookup() {
+        let grass = Block::new("Grass Block")
+            .faces(
+                &BlockFaces::six_faces()
+                    .top("grass_top")
+                    .bottom("dirt")
+                    .sides("grass_side")
+                    .build(),
+            )
+            .build();
+
+        let mut atlas = TextureAtlas::new(16);
+        atlas.add_block(
+            &grass,
+            &[
+                Face::color("grass_top", 16, [0, 200, 0, 255]),
+                Face::color("dirt", 16, [120, 80, 40, 255]),
+                Face::color("grass_side", 16, [80, 160, 40, 255]),
+            ],
+        );
+        assert!(atlas.get_uv("Grass Block", 0).is_none());
+
+        atlas.generate().unwrap();
+
+        let group = atlas.get_group("Grass Block").unwrap();
+
+        for (index, face) in grass.faces.iter().enumerate() {
+            let (page, uv) = atlas.get_uv("Grass Block", index).unwrap();
+
+            assert_eq!(page, 0);
+            assert!(0.0 <= uv.u0 && uv.u0 < uv.u1 && uv.u1 <= 1.0);
+            assert!(0.0 <= uv.v0 && uv.v0 < uv.v1 && uv.v1 <= 1.0);
+            assert_eq!(uv, UvRegion::from(group.range(&face.name).unwrap()));
+        }
+
+        assert_eq!(
+            atlas.get_uv(" grass block ", 0),
+            atlas.get_uv("Grass Block", 0)
+        );
+        assert_eq!(
+            atlas.get_group("GRASS BLOCK").unwrap().range(" Grass_Top "),
+            group.range("grass_top")
+        );
+
+        let missing = atlas.missing_uv();
+        assert!(missing.is_some());
+        assert_eq!(atlas.get_uv("Grass Block", grass.faces.len()), missing);
+        assert_eq!(atlas.get_uv("missing", 0), missing);
+    }
+
+    #[test]
+    fn missing_textures() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone")
+                .id(1)
+                .faces(&BlockFaces::six_faces().build())
+                .build(),
+            Block::new("Grass Block")
+                .id(2)
+                .faces(
+                    &BlockFaces::six_faces()
+                        .top("grass_top")
+                        .bottom("dirt")
+                        .sides("grass_side")
+                        .build(),
+                )
+                .build(),
+        ]);
+        registry.generate();
+
+        let stone = registry.get_block_by_name("Stone");
+        let grass = registry.get_block_by_name("Grass Block");
+
+        // Grass is missing its top texture.
+        let mut atlas = TextureAtlas::new(16);
+        let textures = stone
+            .faces
+            .iter()
+            .map(|face| Face::color(&face.name, 16, [128, 128, 128, 255]))
+            .collect::<Vec<_>>();
+        atlas.add_block(stone, &textures);
+        atlas.add_block(
+            grass,
+            &[
+                Face::color("dirt", 16, [120, 80, 40, 255]),
+                Face::color("grass_side", 16, [80, 160, 40, 255]),
+            ],
+        );
+        atlas.generate().unwrap();
+
+        assert_eq!(atlas.validate(&registry), vec!["Grass Block".to_owned()]);
+
+        let (page, uv) = atlas.missing_uv().unwrap();
+        let top = grass.faces.iter().position(|face| face.name == "grass_top");
+        assert_eq!(atlas.get_uv("Grass Block", top.unwrap()), Some((page, uv)));
+        assert_ne!(atlas.get_uv("Stone", 0), Some((page, uv)));
+
+        let dimension = atlas.dimension() as usize;
+        let x = (uv.u0 * dimension as f32) as usize;
+        let y = (uv.v0 * dimension as f32) as usize;
+        let index = (y * dimension + x) * 4;
+        assert_eq!(
+            &atlas.page_rgba_bytes(page)[index..index + 4],
+            &[255, 0, 255, 255]
+        );
+    }
+
+    #[test]
+    fn atlas_pages() {
+        let (mut atlas, colors) = colored_atlas(16);
+
+        // 20-pixel slots only fit a single 2x2 grid of tiles on a 40-pixel page.
+        atlas.max_dimension = 40;
+        atlas.generate().unwrap();
+
+        assert_eq!(atlas.page_count(), 2);
+        assert_eq!(atlas.dimension(), 40);
+
+        for (index, color) in colors.iter().enumerate() {
+            let (page, uv) = atlas.get_uv(&format!("block{index}"), 0).unwrap();
+            assert_eq!(page, index / 4);
+
+            let dimension = atlas.dimension() as usize;
+            let x = (uv.u0 * dimension as f32) as usize;
+            let y = (uv.v0 * dimension as f32) as usize;
+            let offset = (y * dimension + x) * 4;
+            assert_eq!(&atlas.page_rgba_bytes(page)[offset..offset + 4], color);
+        }
+
+        // Every page is exported, one after the other.
+        let mut pixels = atlas.page_rgba_bytes(0);
+        pixels.extend(atlas.page_rgba_bytes(1));
+        assert_eq!(atlas.to_rgba_bytes(), pixels);
+
+        // With the default max dimension, everything fits back onto a single page.
+        atlas.max_dimension = 4096;
+        atlas.generate().unwrap();
+        assert_eq!(atlas.page_count(), 1);
+    }
+
+    #[test]
+    fn animated_pages() {
+        let mut atlas = TextureAtlas::new(16);
+        atlas.max_dimension = 40;
+
+        for name in ["a", "b", "c"] {
+            atlas.add_faces(name, &[Face::color("all", 16, [255, 0, 0, 255])]);
+        }
+
+        let frames = (0..2)
+            .map(|frame| Face::color("water", 16, [0, 0, frame * 60, 255]))
+            .collect::<Vec<_>>();
+        atlas.add_animated_faces("d", &frames, 8.0);
+
+        // The first frame would take the last slot of the first page, so both start a new one.
+        atlas.generate().unwrap();
+        assert_eq!(atlas.page_count(), 2);
+        assert_eq!(atlas.get_group("d").unwrap().pages, vec![1, 1]);
+    }
+
+    #[test]
+    fn oversized_tiles() {
+        let (mut atlas, _) = colored_atlas(16);
+        atlas.generate().unwrap();
+
+        // A 16-pixel tile with its gutter doesn't fit on a 16-pixel page.
+        atlas.max_dimension = 16;
+        assert_eq!(
+            atlas.generate(),
+            Err(AtlasError::TileTooLarge(
+                "block0".to_owned(),
+                "all".to_owned()
+            ))
+        );
+        assert_eq!(atlas.page_count(), 1);
+
+        // Five frames can't share a page that only fits four tiles.
+        atlas.max_dimension = 40;
+        let frames = (0..5)
+            .map(|frame| Face::color("water", 16, [0, 0, frame * 50, 255]))
+            .collect::<Vec<_>>();
+        atlas.add_animated_faces("water", &frames, 8.0);
+        assert_eq!(
+            atlas.generate(),
+            Err(AtlasError::AnimationTooLarge("water".to_owned()))
+        );
+    }
+
+    #[test]
+    fn mixed_resolutions() {
+        let mut atlas = TextureAtlas::new(16);
+
+        // Added out of name order, as groups are laid out sorted by name.
+        for index in (0..6).rev() {
+            let size = if index % 3 == 0 { 32 } else { 16 };
+            atlas.add_faces(
+                &format!("block{index}"),
+                &[Face::color("all", size, [index * 40, 0, 0, 255])],
+            );
+        }
+
+        atlas.generate_with_mipmaps(1).unwrap();
+
+        let dimension = atlas.dimension() as f32;
+        let mut tiles = vec![];
+
+        for index in 0..6 {
+            let group = atlas.get_group(&format!("block{index}")).unwrap();
+            let (page, uv) = atlas.get_uv(&group.name, 0).unwrap();
+
+            // The UVs span the face's own resolution, half a texel in on each side.
+            let size = group.faces[0].width as f32;
+            assert_eq!((uv.u1 - uv.u0) * dimension + 1.0, size);
+            assert_eq!((uv.v1 - uv.v0) * dimension + 1.0, size);
+
+            tiles.push((page, uv.u0 * dimension - 0.5, uv.v0 * dimension - 0.5, size));
+        }
+
+        for (i, a) in tiles.iter().enumerate() {
+            for b in tiles.iter().skip(i + 1) {
+                let overlaps = a.0 == b.0
+                    && a.1 < b.1 + b.3
+                    && b.1 < a.1 + a.3
+                    && a.2 < b.2 + b.3
+                    && b.2 < a.2 + a.3;
+                assert!(!overlaps);
+            }
+        }
+
+        // A detailed face can only be replaced by one of the same resolution.
+        let replacement = Face::color("all", 16, [255, 255, 255, 255]);
+        assert_eq!(
+            atlas.replace_face_texture("block0", &replacement),
+            Err(AtlasError::ResolutionMismatch(
+                "block0".to_owned(),
+                "all".to_owned()
+            ))
+        );
+
+        // Scaling is opt-in, squeezing every face into the tile size.
+        atlas.scale_faces = true;
+        atlas.generate().unwrap();
+
+        let dimension = atlas.dimension() as f32;
+        let (_, uv) = atlas.get_uv("block0", 0).unwrap();
+        assert_eq!((uv.u1 - uv.u0) * dimension + 1.0, 16.0);
+    }
+
+    #[test]
+    fn uv_padding() {
+        let (mut atlas, colors) = colored_atlas(16);
+        atlas.padding = 4;
+        atlas.generate().unwrap();
+
+        let dimension = atlas.dimension() as f32;
+        let pixels = atlas.to_rgba_bytes();
+
+        for (index, color) in colors.iter().enumerate() {
+            let range = &atlas.get_group(&format!("block{index}")).unwrap().ranges[0];
+
+            // The tile starts half a texel before its UVs, and spans the tile size in pixels.
+            let start_x = range.start_u * dimension - 0.5;
+            let start_y = range.start_v * dimension - 0.5;
+            assert_eq!(start_x.fract(), 0.0);
+            assert_eq!(start_y.fract(), 0.0);
+            assert_eq!(range.end_u * dimension + 0.5 - start_x, 16.0);
+            assert_eq!(range.end_v * dimension + 0.5 - start_y, 16.0);
+
+            // The gutter right outside of the tile duplicates its edge pixels.
+            let x = start_x as usize - atlas.padding as usize;
+            let y = start_y as usize;
+            let index = (y * dimension as usize + x) * 4;
+            assert_eq!(&pixels[index..index + 4], color);
+        }
+    }
+
+    #[test]
+    fn hot_reload() {
+        let (mut atlas, _) = colored_atlas(16);
+
+        let replacement = Face::color("all", 16, [255, 0, 255, 255]);
+        assert_eq!(
+            atlas.replace_face_texture("block0", &replacement),
+            Err(AtlasError::NotGenerated)
+        );
+
+        atlas.generate().unwrap();
+        atlas.mark_clean();
+
+        let version = atlas.version();
+        let ranges = atlas.get_group("block0").unwrap().ranges.clone();
+
+        atlas.replace_face_texture("block0", &replacement).unwrap();
+
+        assert_eq!(atlas.version(), version + 1);
+        assert!(atlas.is_dirty());
+        assert_eq!(atlas.get_group("block0").unwrap().ranges, ranges);
+
+        let dimension = atlas.dimension() as usize;
+        let x = (ranges[0].start_u * dimension as f32) as usize;
+        let y = (ranges[0].start_v * dimension as f32) as usize;
+        let index = (y * dimension + x) * 4;
+        assert_eq!(
+            &atlas.to_rgba_bytes()[index..index + 4],
+            &[255, 0, 255, 255]
+        );
+
+        assert_eq!(
+            atlas.replace_face_texture("missing", &replacement),
+            Err(AtlasError::GroupNotFound("missing".to_owned()))
+        );
+        assert!(atlas.get_group("missing").is_none());
+    }
+}
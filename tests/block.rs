@@ -1,6 +1,12 @@
 #[cfg(test)]
 mod tests {
-    use voxelize::{BlockRotation, BlockUtils};
+    use std::fs;
+
+    use hashbrown::HashMap;
+    use voxelize::{
+        Block, BlockConflict, BlockFace, BlockFaces, BlockRotation, BlockUtils, Chunk,
+        ChunkOptions, ChunkStatus, ImportError, Mesher, Registry, Vec3, VoxelAccess, AABB,
+    };
 
     #[test]
     fn id_insertion() {
@@ -70,4 +76,410 @@ mod tests {
 
         assert_eq!(BlockUtils::extract_id(voxel), id);
     }
+
+    #[test]
+    fn waterlogging() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Water")
+                .id(1)
+                .is_fluid(true)
+                .is_see_through(true)
+                .is_transparent(true)
+                .faces(&BlockFaces::six_faces().build())
+                .build(),
+            Block::new("Oak Slab")
+                .id(2)
+                .is_transparent(true)
+                .can_be_waterlogged(true)
+                .faces(&BlockFaces::six_faces().scale_y(0.5).build())
+                .aabbs(&[AABB::new().scale_y(0.5).build()])
+                .build(),
+        ]);
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Ready;
+        chunk.meshes = Some(HashMap::new());
+
+        chunk.set_voxel(4, 2, 4, 2);
+        chunk.set_voxel_rotation(4, 2, 4, &BlockRotation::PX(0.0));
+        assert!(!chunk.is_waterlogged(4, 2, 4));
+
+        chunk.set_waterlogged(4, 2, 4, true);
+        assert_eq!(chunk.get_voxel(4, 2, 4), 2);
+        assert!(chunk.is_waterlogged(4, 2, 4));
+        assert_eq!(chunk.get_voxel_rotation(4, 2, 4), BlockRotation::PX(0.0));
+
+        chunk.calculate_max_height();
+
+        let voxels = |chunk: &Chunk| {
+            Mesher::mesh_space(&Vec3(0, 0, 0), &Vec3(16, 16, 16), chunk, &registry, None)
+                .iter()
+                .map(|geometry| geometry.voxel)
+                .collect::<Vec<_>>()
+        };
+
+        let meshed = voxels(&chunk);
+        assert!(meshed.contains(&1));
+        assert!(meshed.contains(&2));
+
+        chunk.set_waterlogged(4, 2, 4, false);
+        assert_eq!(chunk.get_voxel(4, 2, 4), 2);
+        assert!(!voxels(&chunk).contains(&1));
+    }
+
+    #[test]
+    fn per_voxel_rotation() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Log")
+                .id(1)
+                .rotatable(true)
+                .is_transparent(true)
+                .faces(&BlockFaces::six_faces().scale_y(0.5).build())
+                .build(),
+            Block::new("Slab")
+                .id(2)
+                .is_transparent(true)
+                .faces(&BlockFaces::six_faces().scale_y(0.5).build())
+                .build(),
+        ]);
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Ready;
+        chunk.meshes = Some(HashMap::new());
+
+        // Height of the meshed block at (4, 2, 4), relative to its voxel.
+        let height = |chunk: &Chunk| {
+            let geometries =
+                Mesher::mesh_space(&Vec3(0, 0, 0), &Vec3(16, 16, 16), chunk, &registry, None);
+            let ys = geometries[0]
+                .positions
+                .chunks(3)
+                .map(|position| position[1])
+                .collect::<Vec<_>>();
+            ys.iter().cloned().fold(f32::MIN, f32::max)
+                - ys.iter().cloned().fold(f32::MAX, f32::min)
+        };
+
+        for id in [1, 2] {
+            chunk.set_voxel(4, 2, 4, id);
+            chunk.calculate_max_height();
+            assert!((height(&chunk) - 0.5).abs() < 1e-3);
+
+            chunk.set_voxel_rotation(4, 2, 4, &BlockRotation::PX(0.0));
+            assert_eq!(chunk.get_voxel(4, 2, 4), id);
+            assert_eq!(chunk.get_voxel_rotation(4, 2, 4), BlockRotation::PX(0.0));
+
+            // Only rotatable blocks are meshed on their side.
+            let expected = if id == 1 { 1.0 } else { 0.5 };
+            assert!((height(&chunk) - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn stage_limits() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Wheat").id(1).max_stage(7).build(),
+            Block::new("Stone").id(2).build(),
+        ]);
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.set_voxel(1, 1, 1, 1);
+        chunk.set_voxel(2, 1, 1, 2);
+
+        for stage in 0..=7 {
+            assert!(chunk.set_block_stage(&registry, 1, 1, 1, stage));
+            assert_eq!(chunk.get_voxel_stage(1, 1, 1), stage);
+            assert_eq!(chunk.get_voxel(1, 1, 1), 1);
+        }
+
+        // Stages past the block's last are refused, leaving the voxel as it was.
+        assert!(!chunk.set_block_stage(&registry, 1, 1, 1, 8));
+        assert_eq!(chunk.get_voxel_stage(1, 1, 1), 7);
+
+        assert!(chunk.set_block_stage(&registry, 2, 1, 1, 12));
+        assert_eq!(chunk.get_voxel_stage(2, 1, 1), 12);
+
+        // Unregistered blocks have no stages to check against.
+        chunk.set_voxel(3, 1, 1, 9);
+        assert!(!chunk.set_block_stage(&registry, 3, 1, 1, 1));
+
+        assert_eq!(Block::new("Overflow").max_stage(200).build().max_stage, 15);
+    }
+
+    #[test]
+    fn face_normals() {
+        let six = BlockFaces::six_faces().build();
+        let py = six.iter().find(|face| face.name == "py").unwrap();
+        assert_eq!(py.normal(), Vec3(0.0, 1.0, 0.0));
+
+        for face in six.iter() {
+            let Vec3(x, y, z) = face.normal();
+            assert_eq!(
+                [x, y, z],
+                [face.dir[0] as f32, face.dir[1] as f32, face.dir[2] as f32]
+            );
+        }
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+
+        for face in BlockFaces::diagonal_faces().build().iter() {
+            let Vec3(x, y, z) = face.normal();
+            assert!(y.abs() < 1e-6);
+            assert!((x.abs() - half).abs() < 1e-6);
+            assert!((z.abs() - half).abs() < 1e-6);
+        }
+
+        // Faces sent over the wire drop the cached normal, and derive it again.
+        let json = serde_json::to_string(py).unwrap();
+        let parsed: BlockFace = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.normal(), Vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn obj_import() {
+        let path = std::env::temp_dir().join("voxelize-obj-test.obj");
+        fs::write(
+            &path,
+            "o top\n\
+             v 0 1 0\nv 0 1 1\nv 1 1 1\nv 1 1 0\n\
+             vt 0 0\nvt 0 1\nvt 1 1\nvt 1 0\n\
+             f 1/1 2/2 3/3 4/4\n",
+        )
+        .unwrap();
+
+        let faces = BlockFace::from_obj(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].name, "top");
+        assert_eq!(faces[0].dir, [0, 1, 0]);
+        assert_eq!(faces[0].normal(), Vec3(0.0, 1.0, 0.0));
+
+        let corners = faces[0]
+            .corners
+            .iter()
+            .map(|corner| (corner.pos, corner.uv))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            corners,
+            vec![
+                ([0.0, 1.0, 0.0], [0.0, 0.0]),
+                ([0.0, 1.0, 1.0], [0.0, 1.0]),
+                ([1.0, 1.0, 0.0], [1.0, 0.0]),
+                ([1.0, 1.0, 1.0], [1.0, 1.0]),
+            ]
+        );
+
+        assert_eq!(
+            BlockFace::parse_obj("v 0 0 0\nv 2 0 0\n"),
+            Err(ImportError::OutOfBounds(2))
+        );
+        assert!(matches!(
+            BlockFace::parse_obj("v 0 0 0\nf 1 2 3\n"),
+            Err(ImportError::Parse(2, _))
+        ));
+    }
+
+    #[test]
+    fn auto_uvs() {
+        let range = |values: Vec<f32>| {
+            let min = values.iter().cloned().fold(f32::MAX, f32::min);
+            let max = values.iter().cloned().fold(f32::MIN, f32::max);
+            ((min * 1e4).round() / 1e4, (max * 1e4).round() / 1e4)
+        };
+
+        let faces = BlockFaces::six_faces()
+            .scale_x(0.4)
+            .offset_x(0.3)
+            .auto_uv(true)
+            .build();
+
+        // The top face's texture spans the same part of the tile as its geometry.
+        let py = faces.iter().find(|face| face.name == "py").unwrap();
+        let us = range(py.corners.iter().map(|corner| corner.uv[0]).collect());
+        let xs = range(py.corners.iter().map(|corner| corner.pos[0]).collect());
+        let vs = range(py.corners.iter().map(|corner| corner.uv[1]).collect());
+        let zs = range(py.corners.iter().map(|corner| corner.pos[2]).collect());
+        assert_eq!(us, (0.3, 0.7));
+        assert_eq!(us, xs);
+        assert_eq!(vs, zs);
+
+        // Explicit UVs still win over the derived ones.
+        let faces = BlockFaces::six_faces()
+            .scale_x(0.4)
+            .offset_x(0.3)
+            .uv_scale_x(1.0)
+            .auto_uv(true)
+            .build();
+
+        let py = faces.iter().find(|face| face.name == "py").unwrap();
+        let us = range(py.corners.iter().map(|corner| corner.uv[0]).collect());
+        assert_eq!(us, (0.3, 1.3));
+    }
+
+    #[test]
+    fn shared_getters() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Dirt").id(1).build(),
+            Block::new("Water")
+                .id(2)
+                .is_fluid(true)
+                .is_passable(true)
+                .is_transparent(true)
+                .build(),
+            Block::new("Grass").id(3).is_passable(true).build(),
+            Block::new("Slab")
+                .id(4)
+                .is_x_transparent(true)
+                .is_z_transparent(true)
+                .build(),
+        ]);
+        registry.generate();
+
+        for id in [0, 1, 2, 3, 4] {
+            let block = registry.get(id).unwrap();
+
+            assert_eq!(registry.get_by_name(&block.name).unwrap().id, id);
+            assert_eq!(
+                registry.get_by_name(&block.name.to_uppercase()).unwrap().id,
+                id
+            );
+            assert_eq!(registry.get_block_by_id(id).name, block.name);
+            assert_eq!(registry.is_fluid(id), block.is_fluid);
+            assert_eq!(registry.is_passable(id), block.is_passable);
+        }
+
+        assert!(registry.get(5).is_none());
+        assert!(registry.get_by_name("Lava").is_none());
+
+        assert!(registry.is_air(0) && registry.is_passable(0));
+        assert!(registry.is_fluid(2) && !registry.is_fluid(1));
+        assert!(registry.is_passable(3) && !registry.is_passable(1));
+
+        assert!(registry.is_transparent(2, &Vec3(0, 1, 0)));
+        assert!(!registry.is_transparent(1, &Vec3(0, 1, 0)));
+        assert!(registry.is_transparent(4, &Vec3(-1, 0, 0)));
+        assert!(registry.is_transparent(4, &Vec3(0, 0, 1)));
+        assert!(!registry.is_transparent(4, &Vec3(0, -1, 0)));
+        assert!(!registry.is_transparent(2, &Vec3(1, 1, 0)));
+    }
+
+    #[test]
+    fn rotated_transparency() {
+        let mut registry = Registry::new();
+        registry.register_block(
+            &Block::new("Slab")
+                .id(1)
+                .rotatable(true)
+                .is_x_transparent(true)
+                .is_z_transparent(true)
+                .is_py_transparent(true)
+                .build(),
+        );
+        registry.generate();
+
+        // Upright, the slab's solid face points down.
+        let upright = BlockRotation::PY(0.0);
+        assert!(!registry.is_transparent_world(1, &upright, &Vec3(0, -1, 0)));
+        assert!(registry.is_transparent_world(1, &upright, &Vec3(0, 1, 0)));
+
+        // Upside down, the solid face points up instead.
+        let flipped = BlockRotation::NY(0.0);
+        assert!(!registry.is_transparent_world(1, &flipped, &Vec3(0, 1, 0)));
+        assert!(registry.is_transparent_world(1, &flipped, &Vec3(0, -1, 0)));
+        assert!(registry.is_transparent_world(1, &flipped, &Vec3(1, 0, 0)));
+
+        // Lying on its side, the solid face points sideways.
+        let sideways = BlockRotation::PX(0.0);
+        let solid = [
+            Vec3(1, 0, 0),
+            Vec3(-1, 0, 0),
+            Vec3(0, 1, 0),
+            Vec3(0, -1, 0),
+            Vec3(0, 0, 1),
+            Vec3(0, 0, -1),
+        ]
+        .into_iter()
+        .filter(|dir| !registry.is_transparent_world(1, &sideways, dir))
+        .collect::<Vec<_>>();
+        assert_eq!(solid, vec![Vec3(-1, 0, 0)]);
+    }
+
+    #[test]
+    fn build_validation() {
+        let error = Block::new("Water")
+            .is_fluid(true)
+            .aabbs(&[AABB::new().build()])
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(error.name, "Water");
+        assert_eq!(error.conflicts, vec![BlockConflict::FluidWithAabbs]);
+
+        let error = Block::new("Lava")
+            .is_fluid(true)
+            .can_be_waterlogged(true)
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(error.conflicts, vec![BlockConflict::WaterloggedFluid]);
+
+        let error = Block::new("Ghost")
+            .is_empty(true)
+            .faces(&BlockFaces::six_faces().build())
+            .try_build()
+            .err()
+            .unwrap();
+        assert_eq!(error.conflicts, vec![BlockConflict::EmptyWithFaces]);
+
+        // The default cube of faces and AABBs doesn't count as a conflict.
+        let water = Block::new("Water").is_fluid(true).try_build().unwrap();
+        assert!(water.is_fluid);
+
+        let air = Block::new("Air").is_empty(true).try_build().unwrap();
+        assert!(air.is_empty);
+
+        let stone = Block::new("Stone").faces(&BlockFaces::six_faces().build());
+        assert!(stone.try_build().is_ok());
+    }
 }
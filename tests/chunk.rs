@@ -0,0 +1,281 @@
+#[cfg(test)]
+mod tests {
+    use voxelize::{
+        Block, Chunk, ChunkOptions, ChunkOptionsError, ChunkStatus, Chunks, Mesher, Registry,
+        StatusError, Vec2, Vec3, VoxelAccess, VoxelError, WorldConfig,
+    };
+
+    fn chunk() -> Chunk {
+        Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 64,
+                sub_chunks: 4,
+                min_y: 0,
+                air_id: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn incremental_height_map() {
+        let mut chunk = chunk();
+        assert_eq!(chunk.get_height(3, 5), 0);
+
+        for vy in 0..10 {
+            chunk.set_voxel(3, vy, 5, 1);
+        }
+        assert_eq!(chunk.get_height(3, 5), 9);
+        assert_eq!(chunk.get_height(4, 5), 0);
+
+        chunk.set_voxel(3, 9, 5, 0);
+        assert_eq!(chunk.get_height(3, 5), 8);
+
+        // Removing a voxel under the top leaves the height alone.
+        chunk.set_voxel(3, 4, 5, 0);
+        assert_eq!(chunk.get_height(3, 5), 8);
+
+        chunk.set_voxel(3, 20, 5, 1);
+        assert_eq!(chunk.get_height(3, 5), 20);
+        chunk.set_voxel(3, 20, 5, 0);
+        assert_eq!(chunk.get_height(3, 5), 8);
+    }
+
+    #[test]
+    fn negative_min_y() {
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 64,
+                sub_chunks: 4,
+                min_y: -64,
+                air_id: 0,
+            },
+        );
+
+        assert_eq!(chunk.voxels.shape, vec![16, 128, 16]);
+        assert!(chunk.contains(2, -64, 3));
+        assert!(!chunk.contains(2, -65, 3));
+        assert!(!chunk.contains(2, 64, 3));
+
+        chunk.updated_levels.clear();
+        chunk.set_voxel(2, -40, 3, 7);
+        chunk.set_voxel(2, 63, 3, 8);
+
+        assert_eq!(chunk.get_voxel(2, -40, 3), 7);
+        assert_eq!(chunk.get_voxel(2, 63, 3), 8);
+        assert_eq!(chunk.get_voxel(2, 0, 3), 0);
+        assert!(chunk.updated_levels.contains(&0));
+        assert!(chunk.updated_levels.contains(&3));
+
+        // Voxels below the vertical range are dropped instead of deferred to a neighbor.
+        assert!(!chunk.set_raw_voxel(2, -65, 3, 1));
+        assert!(chunk.extra_changes.is_empty());
+
+        // Heights are stored counting up from min y, so that they can go below 0.
+        assert_eq!(chunk.get_height(2, 3), 63);
+        assert_eq!(chunk.get_max_height(2, 3), 127);
+
+        chunk.set_voxel(2, 63, 3, 0);
+        assert_eq!(chunk.get_height(2, 3), -40);
+        assert_eq!(chunk.get_max_height(2, 3), 24);
+
+        chunk.set_voxel(2, -40, 3, 0);
+        assert_eq!(chunk.get_height(2, 3), -64);
+    }
+
+    #[test]
+    fn nonzero_air_id() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Void")
+                .id(7)
+                .is_empty(true)
+                .is_passable(true)
+                .is_x_transparent(true)
+                .is_y_transparent(true)
+                .is_z_transparent(true)
+                .build(),
+        ]);
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 7,
+            },
+        );
+
+        assert!(chunk.is_air(3, 3, 3));
+        assert_eq!(chunk.get_voxel(3, 3, 3), 7);
+
+        chunk.set_voxel(4, 4, 4, 1);
+        chunk.set_voxel(5, 4, 4, 1);
+        chunk.calculate_max_height();
+
+        assert!(!chunk.is_air(4, 4, 4));
+        assert_eq!(chunk.get_max_height(4, 4), 4);
+        assert_eq!(chunk.get_max_height(6, 6), 0);
+
+        // Two stones next to each other cull their shared faces, and the air is never meshed.
+        let geometries =
+            Mesher::mesh_space(&Vec3(0, 0, 0), &Vec3(16, 16, 16), &chunk, &registry, None);
+        assert!(geometries.iter().all(|geometry| geometry.voxel == 1));
+        assert_eq!(
+            geometries
+                .iter()
+                .map(|geometry| geometry.indices.len() / 6)
+                .sum::<usize>(),
+            10
+        );
+
+        chunk.set_voxel(4, 4, 4, 7);
+        assert_eq!(chunk.get_max_height(4, 4), 0);
+    }
+
+    #[test]
+    fn empty_chunks() {
+        let mut chunk = chunk();
+        assert!(chunk.is_empty());
+
+        chunk.set_voxel(3, 10, 3, 1);
+        assert!(!chunk.is_empty());
+
+        // Replacing a solid block with another one keeps a single solid voxel.
+        chunk.set_voxel(3, 10, 3, 2);
+        chunk.set_voxel(3, 10, 3, 0);
+        assert!(chunk.is_empty());
+
+        chunk.voxels.data[0] = 1;
+        chunk.rehash();
+        assert!(!chunk.is_empty());
+    }
+
+    #[test]
+    fn options_builder() {
+        let options = ChunkOptions::builder()
+            .chunk_size(16)
+            .max_height(256)
+            .sub_chunks(4)
+            .build()
+            .unwrap();
+        let expected = ChunkOptions::new(16, 256, 4);
+        assert_eq!(options.size, expected.size);
+        assert_eq!(options.max_height, expected.max_height);
+        assert_eq!(options.sub_chunks, expected.sub_chunks);
+        assert_eq!(options.min_y, expected.min_y);
+        assert_eq!(options.air_id, expected.air_id);
+
+        assert_eq!(
+            ChunkOptions::builder()
+                .max_height(100)
+                .sub_chunks(8)
+                .build()
+                .err(),
+            Some(ChunkOptionsError::UnevenSubChunks(100, 8))
+        );
+        assert_eq!(
+            ChunkOptions::builder()
+                .min_y(-64)
+                .max_height(64)
+                .sub_chunks(8)
+                .build()
+                .map(|options| options.height()),
+            Ok(128)
+        );
+        assert!(ChunkOptions::builder().chunk_size(0).build().is_err());
+        assert!(ChunkOptions::builder().min_y(256).build().is_err());
+    }
+
+    #[test]
+    fn status_transitions() {
+        let mut chunk = chunk();
+        assert_eq!(chunk.status, ChunkStatus::Empty);
+
+        assert_eq!(
+            chunk.advance_status(ChunkStatus::Ready),
+            Err(StatusError {
+                from: ChunkStatus::Empty,
+                to: ChunkStatus::Ready,
+            })
+        );
+        assert_eq!(chunk.status, ChunkStatus::Empty);
+
+        for status in [
+            ChunkStatus::Generating(0),
+            ChunkStatus::Generating(1),
+            ChunkStatus::Generated,
+            ChunkStatus::Meshing,
+            ChunkStatus::Ready,
+        ] {
+            chunk.advance_status(status.clone()).unwrap();
+            assert_eq!(chunk.status, status);
+        }
+
+        // Stages can't be skipped, and a chunk can't be meshed before it's generated.
+        let mut chunk = self::chunk();
+        chunk.advance_status(ChunkStatus::Generating(0)).unwrap();
+        assert!(chunk.advance_status(ChunkStatus::Generating(2)).is_err());
+        assert!(chunk.advance_status(ChunkStatus::Meshing).is_err());
+        assert_eq!(chunk.status, ChunkStatus::Generating(0));
+    }
+
+    #[test]
+    fn world_space_block_ids() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut chunks = Chunks::new(&config);
+
+        for cx in -1..=0 {
+            chunks.add(Chunk::new(
+                &format!("{cx}:0"),
+                cx,
+                0,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 32,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            ));
+        }
+
+        // A negative x lands in the chunk to the west of the origin.
+        chunks.raw_mut(&Vec2(-1, 0)).unwrap().set_voxel(-3, 5, 7, 2);
+        assert_eq!(chunks.get_block_id(-3, 5, 7), Some(2));
+        assert_eq!(chunks.get_block_id(3, 5, 7), Some(0));
+
+        // Setting a block only queues the update.
+        assert_eq!(chunks.set_block_id(3, 5, 7, 2), Ok(()));
+        assert_eq!(chunks.get_block_id(3, 5, 7), Some(0));
+
+        assert_eq!(chunks.get_block_id(40, 5, 7), None);
+        assert_eq!(
+            chunks.set_block_id(40, 5, 7, 2),
+            Err(VoxelError::Unloaded(Vec3(40, 5, 7)))
+        );
+
+        assert_eq!(chunks.get_block_id(3, 32, 7), None);
+        assert_eq!(
+            chunks.set_block_id(3, -1, 7, 2),
+            Err(VoxelError::OutOfHeight(Vec3(3, -1, 7)))
+        );
+    }
+}
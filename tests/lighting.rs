@@ -1,7 +1,98 @@
 #[cfg(test)]
 mod lighting_tests {
+    use std::collections::VecDeque;
+
+    use voxelize::{
+        Block, Chunk, ChunkOptions, Chunks, LightColor, LightNode, Lights, Registry, VoxelAccess,
+        WorldConfig,
+    };
+
     #[test]
     fn test_addition() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn filtered_light() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Glass")
+                .id(2)
+                .is_transparent(true)
+                .is_see_through(true)
+                .light_filter([1, 1, 1])
+                .build(),
+            Block::new("Red Glass")
+                .id(3)
+                .is_transparent(true)
+                .is_see_through(true)
+                .light_filter([1, 4, 4])
+                .build(),
+        ]);
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(16)
+            .sub_chunks(1)
+            .build();
+
+        // A torch at the end of a tunnel through stone, looking through a pane of glass.
+        let flood = |glass: u32| {
+            let mut chunks = Chunks::new(&config);
+            chunks.add(Chunk::new(
+                "test",
+                0,
+                0,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 16,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            ));
+
+            for vx in 0..16 {
+                for vy in 0..16 {
+                    for vz in 0..16 {
+                        let tunnel = vy == 8 && vz == 8 && (2..12).contains(&vx);
+                        chunks.set_voxel(vx, vy, vz, if tunnel { 0 } else { 1 });
+                    }
+                }
+            }
+            chunks.set_voxel(5, 8, 8, glass);
+
+            for color in [LightColor::Red, LightColor::Green, LightColor::Blue] {
+                chunks.set_torch_light(2, 8, 8, 15, &color);
+                let queue = VecDeque::from(vec![LightNode {
+                    voxel: [2, 8, 8],
+                    level: 15,
+                }]);
+                Lights::flood_light(&mut chunks, queue, &color, &registry, &config, None, None);
+            }
+
+            chunks
+        };
+
+        // Clear glass takes one more level off of every channel.
+        let chunks = flood(2);
+        assert_eq!(chunks.get_red_light(4, 8, 8), 13);
+        assert_eq!(chunks.get_red_light(5, 8, 8), 11);
+        assert_eq!(chunks.get_green_light(5, 8, 8), 11);
+        assert_eq!(chunks.get_blue_light(6, 8, 8), 10);
+
+        // Stained glass lets its own color through and filters the rest.
+        let chunks = flood(3);
+        assert_eq!(chunks.get_red_light(5, 8, 8), 11);
+        assert_eq!(chunks.get_green_light(5, 8, 8), 8);
+        assert_eq!(chunks.get_blue_light(5, 8, 8), 8);
+        assert_eq!(chunks.get_red_light(6, 8, 8), 10);
+        assert_eq!(chunks.get_green_light(6, 8, 8), 7);
+
+        // Without any glass, light only falls off by one per voxel.
+        let chunks = flood(0);
+        assert_eq!(chunks.get_green_light(6, 8, 8), 11);
+    }
 }
@@ -0,0 +1,78 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Mutex,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use voxelize::{
+        Block, Chunk, ChunkOptions, ChunkStatus, DebugStage, Pipeline, Registry, Vec2, WorldConfig,
+    };
+
+    struct Capture(Mutex<Vec<(Level, String)>>);
+
+    impl Log for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: Capture = Capture(Mutex::new(vec![]));
+
+    #[test]
+    fn chunk_trace_logs() {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut pipeline = Pipeline::new();
+        pipeline.add_stage(DebugStage::new(1));
+        pipeline.add_chunk(&Vec2(3, 4), false);
+
+        let mut chunk = Chunk::new(
+            "3:4",
+            3,
+            4,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Generating(0);
+        pipeline.get();
+        pipeline.process(vec![(chunk, None)], &registry, &config);
+
+        let start = Instant::now();
+        while pipeline.results().is_empty() && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let logs = LOGGER.0.lock().unwrap();
+        assert!(logs.iter().any(|(level, message)| {
+            *level == Level::Trace && message.starts_with("Chunk Vec2(3, 4) went through stage")
+        }));
+    }
+}
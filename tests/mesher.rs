@@ -0,0 +1,557 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use voxelize::{
+        BiomeMap, Block, BlockFaces, BlockRotation, Chunk, ChunkOptions, ChunkStatus, Chunks,
+        Mesher, MessageType, Registry, Vec2, Vec3, VoxelAccess, WorldConfig, WHITE,
+    };
+
+    #[test]
+    fn lod_meshing() {
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+
+        for vx in 0..16 {
+            for vz in 0..16 {
+                for vy in 0..2 {
+                    chunk.set_voxel(vx, vy, vz, 1);
+                }
+            }
+        }
+
+        chunk.calculate_max_height();
+
+        let count_quads = |lod: u8| {
+            Mesher::mesh_space_lod(
+                &Vec3(0, 0, 0),
+                &Vec3(16, 32, 16),
+                &chunk,
+                &registry,
+                lod,
+                None,
+            )
+            .iter()
+            .map(|geometry| geometry.indices.len() / 6)
+            .sum::<usize>()
+        };
+
+        let full = count_quads(0);
+        let half = count_quads(1);
+
+        assert_eq!(full, 16 * 16 * 2 + 16 * 2 * 4);
+        assert_eq!(half * 4, full);
+    }
+
+    #[test]
+    fn rotated_diagonal_faces() {
+        let mut registry = Registry::new();
+        registry.register_block(
+            &Block::new("Grass Plant")
+                .id(1)
+                .faces(&BlockFaces::diagonal_faces().build())
+                .is_passable(true)
+                .is_transparent(true)
+                .rotatable(true)
+                .build(),
+        );
+        registry.generate();
+
+        let mesh = |rotation: &BlockRotation| {
+            let mut chunk = Chunk::new(
+                "test",
+                0,
+                0,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 32,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            );
+            chunk.set_voxel(4, 4, 4, 1);
+            chunk.set_voxel_rotation(4, 4, 4, rotation);
+            chunk.calculate_max_height();
+
+            Mesher::mesh_space(&Vec3(0, 0, 0), &Vec3(16, 32, 16), &chunk, &registry, None)
+                .into_iter()
+                .flat_map(|geometry| geometry.positions)
+                .collect::<Vec<_>>()
+        };
+
+        let upright = mesh(&BlockRotation::PY(0.0));
+        let sideways = mesh(&BlockRotation::PX(0.0));
+
+        assert_eq!(upright.len(), 2 * 4 * 3);
+        assert_eq!(upright.len(), sideways.len());
+        assert_ne!(upright, sideways);
+
+        // Upright, the cross is diagonal on the xz plane. Rotated onto its side, it's diagonal
+        // on the yz plane instead.
+        for vertex in upright.chunks(3) {
+            assert!(vertex[0] == vertex[2] || vertex[0] + vertex[2] == 9.0);
+        }
+        for vertex in sideways.chunks(3) {
+            assert!(vertex[1] == vertex[2] || vertex[1] + vertex[2] == 9.0);
+        }
+    }
+
+    #[test]
+    fn cached_meshes() {
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(2)
+            .build();
+        let mut chunks = Chunks::new(&config);
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 2,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.set_voxel(4, 4, 4, 1);
+        chunk.set_voxel(4, 20, 4, 1);
+        chunk.calculate_max_height();
+        chunks.add(chunk);
+
+        let mut mesher = Mesher::new();
+
+        let mesh = |chunks: &Chunks, mesher: &mut Mesher| {
+            let process = (
+                chunks.raw(&Vec2(0, 0)).unwrap().to_owned(),
+                chunks
+                    .make_space(&Vec2(0, 0), 15)
+                    .needs_height_maps()
+                    .needs_voxels()
+                    .needs_lights()
+                    .build(),
+            );
+            mesher.add_chunk(&Vec2(0, 0), false);
+            mesher.get();
+            mesher.process(vec![process], &MessageType::Update, &registry, &config);
+
+            let start = Instant::now();
+            loop {
+                if let Some((chunk, _)) = mesher.results().pop() {
+                    return chunk;
+                }
+                assert!(start.elapsed() < Duration::from_secs(5));
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        let geometries = |chunk: &Chunk| {
+            let meshes = chunk.meshes.as_ref().unwrap();
+            (0..2)
+                .map(|level| meshes[&level].geometries.to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let first = mesh(&chunks, &mut mesher);
+        assert_eq!(mesher.cache_hits(), 0);
+        chunks.renew(first.to_owned(), false);
+
+        // Nothing has changed, so both sub-chunks reuse their meshes.
+        let second = mesh(&chunks, &mut mesher);
+        assert_eq!(mesher.cache_hits(), 2);
+        assert_eq!(geometries(&first), geometries(&second));
+        chunks.renew(second.to_owned(), false);
+
+        // Editing a voxel remeshes instead.
+        chunks.set_voxel(5, 4, 4, 1);
+        let third = mesh(&chunks, &mut mesher);
+        assert_eq!(mesher.cache_hits(), 2);
+        assert_ne!(geometries(&second), geometries(&third));
+    }
+
+    #[test]
+    fn parallel_meshing() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Dirt").id(2).build(),
+        ]);
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(8)
+            .max_height(16)
+            .sub_chunks(1)
+            .build();
+        let mut chunks = Chunks::new(&config);
+
+        let mut coords = vec![];
+
+        for cx in -2..=2 {
+            for cz in -2..=2 {
+                let mut chunk = Chunk::new(
+                    "test",
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 8,
+                        max_height: 16,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunks.add(chunk);
+                coords.push(Vec2(cx, cz));
+            }
+        }
+
+        // Uneven terrain so that faces cross chunk borders.
+        for vx in -16..24i32 {
+            for vz in -16..24 {
+                let height = (vx * 3 + vz * 5).rem_euclid(7) + 1;
+                for vy in 0..height {
+                    chunks.set_voxel(vx, vy, vz, if vy == height - 1 { 2 } else { 1 });
+                }
+            }
+        }
+
+        let parallel = chunks.mesh_chunks(&coords, &registry);
+        assert_eq!(parallel.len(), coords.len());
+        assert_eq!(parallel, chunks.mesh_chunks(&coords, &registry));
+
+        for coords in coords.iter() {
+            let chunk = chunks.raw(coords).unwrap();
+            let space = chunks
+                .make_space(coords, 1)
+                .needs_height_maps()
+                .needs_voxels()
+                .needs_lights()
+                .build();
+
+            let mut serial = Mesher::mesh_space(&chunk.min, &chunk.max, &space, &registry, None);
+            serial.sort_by(|a, b| {
+                (a.voxel, &a.face_name, &a.at).cmp(&(b.voxel, &b.face_name, &b.at))
+            });
+
+            assert!(!serial.is_empty());
+            assert_eq!(parallel[coords], serial);
+        }
+    }
+
+    #[test]
+    fn sub_chunk_remeshing() {
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(64)
+            .sub_chunks(4)
+            .build();
+        let mut chunks = Chunks::new(&config);
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 64,
+                sub_chunks: 4,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        for level in 0..4 {
+            chunk.set_voxel(4, level * 16 + 4, 4, 1);
+        }
+        chunk.calculate_max_height();
+        chunks.add(chunk);
+
+        let mut mesher = Mesher::new();
+
+        let mut mesh = |chunks: &mut Chunks| {
+            let process = (
+                chunks.raw(&Vec2(0, 0)).unwrap().to_owned(),
+                chunks
+                    .make_space(&Vec2(0, 0), 15)
+                    .needs_height_maps()
+                    .needs_voxels()
+                    .needs_lights()
+                    .build(),
+            );
+            mesher.add_chunk(&Vec2(0, 0), false);
+            mesher.get();
+            mesher.process(vec![process], &MessageType::Update, &registry, &config);
+
+            let start = Instant::now();
+            let mut chunk = loop {
+                if let Some((chunk, _)) = mesher.results().pop() {
+                    break chunk;
+                }
+                assert!(start.elapsed() < Duration::from_secs(5));
+                thread::sleep(Duration::from_millis(5));
+            };
+
+            // Sending a chunk clears its dirty levels.
+            let levels = chunk.updated_levels.drain().collect::<Vec<_>>();
+            chunks.renew(chunk, false);
+            levels
+        };
+
+        assert_eq!(mesh(&mut chunks).len(), 4);
+        let before = chunks.raw(&Vec2(0, 0)).unwrap().meshes.clone().unwrap();
+        assert_eq!(chunks.get_geometries(&Vec2(0, 0)).len(), 4);
+
+        // An edit in the top sub-chunk only remeshes the top sub-chunk.
+        chunks.set_voxel(8, 60, 8, 1);
+        assert_eq!(mesh(&mut chunks), vec![3]);
+
+        let after = chunks.raw(&Vec2(0, 0)).unwrap().meshes.clone().unwrap();
+        for level in 0..3 {
+            assert_eq!(before[&level].geometries, after[&level].geometries);
+        }
+        assert_ne!(before[&3].geometries, after[&3].geometries);
+
+        // An edit on the boundary of two sub-chunks remeshes both.
+        chunks.set_voxel(8, 48, 8, 1);
+        let mut levels = mesh(&mut chunks);
+        levels.sort();
+        assert_eq!(levels, vec![2, 3]);
+    }
+
+    #[test]
+    fn standalone_transparency() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Grass")
+                .id(1)
+                .is_transparent(true)
+                .is_see_through(true)
+                .transparent_standalone(true)
+                .build(),
+            Block::new("Water")
+                .id(2)
+                .is_transparent(true)
+                .is_see_through(true)
+                .build(),
+        ]);
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+
+        chunk.set_voxel(2, 2, 2, 1);
+        chunk.set_voxel(3, 2, 2, 1);
+        chunk.set_voxel(8, 2, 8, 2);
+        chunk.set_voxel(9, 2, 8, 2);
+        chunk.calculate_max_height();
+
+        let geometries =
+            Mesher::mesh_space(&Vec3(0, 0, 0), &Vec3(16, 16, 16), &chunk, &registry, None);
+        let quads = |id: u32| {
+            geometries
+                .iter()
+                .filter(|geometry| geometry.voxel == id)
+                .map(|geometry| geometry.indices.len() / 6)
+                .sum::<usize>()
+        };
+
+        // Both grass blocks keep all of their faces, while the water blocks cull the one in between.
+        assert_eq!(quads(1), 12);
+        assert_eq!(quads(2), 10);
+    }
+
+    #[test]
+    fn see_through_and_transparency() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Glass")
+                .id(2)
+                .is_transparent(true)
+                .is_see_through(true)
+                .build(),
+            Block::new("Leaves").id(3).is_transparent(true).build(),
+            Block::new("Oak Leaves").id(4).is_transparent(true).build(),
+        ]);
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(16)
+            .sub_chunks(1)
+            .quantize_geometry(true)
+            .build();
+        let mut chunks = Chunks::new(&config);
+        chunks.add(Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        ));
+
+        // A roof of leaves over one corner and stone everywhere else.
+        for vx in 0..16 {
+            for vz in 0..16 {
+                chunks.set_voxel(vx, 8, vz, if vx < 2 && vz < 2 { 4 } else { 1 });
+            }
+        }
+        chunks.set_voxel(6, 2, 6, 2);
+        chunks.set_voxel(7, 2, 6, 2);
+        chunks.set_voxel(10, 2, 6, 3);
+        chunks.set_voxel(11, 2, 6, 3);
+
+        let mut mesher = Mesher::new();
+        mesher.add_chunk(&Vec2(0, 0), false);
+        mesher.get();
+        mesher.process(
+            vec![(
+                chunks.raw(&Vec2(0, 0)).unwrap().to_owned(),
+                chunks
+                    .make_space(&Vec2(0, 0), 15)
+                    .needs_height_maps()
+                    .needs_voxels()
+                    .needs_lights()
+                    .build(),
+            )],
+            &MessageType::Load,
+            &registry,
+            &config,
+        );
+
+        let start = Instant::now();
+        let chunk = loop {
+            if let Some((chunk, _)) = mesher.results().pop() {
+                break chunk;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        // The meshes are flagged to be sent quantized.
+        assert!(chunk.meshes.as_ref().unwrap()[&0].quantized);
+
+        let geometries = &chunk.meshes.as_ref().unwrap()[&0].geometries;
+        let quads = |id: u32| {
+            geometries
+                .iter()
+                .filter(|geometry| geometry.voxel == id)
+                .map(|geometry| geometry.indices.len() / 6)
+                .sum::<usize>()
+        };
+
+        // Glass goes to the transparent pass and culls against itself, while leaves go to the
+        // opaque pass and keep every face.
+        assert!(registry.get_block_by_id(2).is_see_through);
+        assert!(!registry.get_block_by_id(3).is_see_through);
+        assert_eq!(quads(2), 10);
+        assert_eq!(quads(3), 12);
+
+        // Sunlight falls straight through the leaves, but not through the stone.
+        assert_eq!(chunk.get_sunlight(0, 4, 0), config.max_light_level);
+        assert!(chunk.get_sunlight(12, 4, 12) < config.max_light_level);
+    }
+
+    #[test]
+    fn tinted_blocks() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Moss").id(2).tint([0.5, 0.8, 0.2, 1.0]).build(),
+            Block::new("Grass").id(3).biome_tint(true).build(),
+        ]);
+        registry.generate();
+
+        let mut chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.set_voxel(2, 2, 2, 1);
+        chunk.set_voxel(6, 2, 6, 2);
+        chunk.set_voxel(10, 2, 10, 3);
+        chunk.calculate_max_height();
+
+        let biomes = BiomeMap::new(42);
+        let geometries = Mesher::mesh_space(
+            &Vec3(0, 0, 0),
+            &Vec3(16, 16, 16),
+            &chunk,
+            &registry,
+            Some(&biomes),
+        );
+        let geometry_of = |id: u32| {
+            geometries
+                .iter()
+                .find(|geometry| geometry.voxel == id)
+                .unwrap()
+        };
+
+        // Untinted blocks send no colors at all, which reads as white.
+        let stone = geometry_of(1);
+        assert!(stone.colors.is_empty());
+        assert_eq!(stone.color(0), WHITE);
+
+        // Tinted blocks color every vertex.
+        let moss = geometry_of(2);
+        assert_eq!(moss.colors.len(), moss.positions.len() / 3 * 4);
+        assert_eq!(moss.color(0), [0.5, 0.8, 0.2, 1.0]);
+
+        // Biome tinted blocks take on the color of the biome they sit in.
+        let grass = geometry_of(3);
+        let [r, g, b] = biomes.biome_at(10, 10).color();
+        assert_eq!(grass.color(0), [r, g, b, 1.0]);
+    }
+}
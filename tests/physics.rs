@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests {
+    use voxelize::{
+        Block, BlockRotation, Chunk, ChunkOptions, Chunks, PhysicsBody, Registry, Vec3,
+        VoxelAccess, WorldConfig, AABB,
+    };
+
+    fn setup() -> (Chunks, Registry) {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Slab")
+                .id(2)
+                .aabbs(&[AABB::new().scale_y(0.5).build()])
+                .build(),
+            Block::new("Grass").id(3).is_passable(true).build(),
+            Block::new("Log")
+                .id(4)
+                .rotatable(true)
+                .aabbs(&[AABB::new()
+                    .scale_x(0.5)
+                    .scale_z(0.5)
+                    .offset_x(0.25)
+                    .offset_z(0.25)
+                    .build()])
+                .build(),
+        ]);
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut chunks = Chunks::new(&config);
+
+        chunks.add(Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        ));
+
+        for vx in 0..16 {
+            for vz in 0..16 {
+                chunks.set_voxel(vx, 0, vz, 1);
+            }
+        }
+
+        chunks.set_voxel(8, 1, 4, 1);
+        chunks.set_voxel(8, 1, 10, 2);
+        chunks.set_voxel(4, 1, 12, 3);
+
+        (chunks, registry)
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn collision_resolution() {
+        let (chunks, registry) = setup();
+        let aabb = AABB::new().scale_x(0.8).scale_y(1.8).scale_z(0.8).build();
+
+        // Walking into a full block stops on the x axis, but keeps sliding along z.
+        let moved = chunks.resolve_collision(
+            &registry,
+            &aabb,
+            &Vec3(6.0, 1.01, 4.1),
+            &Vec3(2.0, 0.0, 0.5),
+        );
+        assert!(approx(moved.0, 8.0 - 6.8));
+        assert!(approx(moved.1, 0.0));
+        assert!(approx(moved.2, 0.5));
+
+        // Falling onto a slab lands on its top half instead of the full voxel.
+        let moved = chunks.resolve_collision(
+            &registry,
+            &aabb,
+            &Vec3(8.1, 1.6, 10.1),
+            &Vec3(0.0, -1.0, 0.0),
+        );
+        assert!(approx(1.6 + moved.1, 1.5));
+
+        // Passable blocks do not collide at all.
+        let moved = chunks.resolve_collision(
+            &registry,
+            &aabb,
+            &Vec3(2.0, 1.01, 12.1),
+            &Vec3(4.0, 0.0, 0.0),
+        );
+        assert!(approx(moved.0, 4.0));
+    }
+
+    #[test]
+    fn body_settles_on_floor() {
+        let (chunks, registry) = setup();
+        let aabb = AABB::new().scale_x(0.8).scale_y(1.8).scale_z(0.8).build();
+
+        let mut body = PhysicsBody::new(&aabb, &Vec3(2.1, 10.0, 2.1));
+        body.air_drag = 0.1;
+
+        for _ in 0..200 {
+            chunks.step_body(&registry, &mut body, 1.0 / 60.0, -9.8);
+        }
+
+        assert!(body.on_ground);
+        assert!(approx(body.position.1, 1.0));
+        assert_eq!(body.velocity.1, 0.0);
+    }
+
+    #[test]
+    fn body_stays_grounded_at_small_steps() {
+        let (chunks, registry) = setup();
+        let aabb = AABB::new().scale_x(0.8).scale_y(1.8).scale_z(0.8).build();
+
+        let mut body = PhysicsBody::new(&aabb, &Vec3(2.1, 1.0, 2.1));
+
+        // Each step only moves the body a tiny bit into the floor, which should still ground it.
+        for _ in 0..100 {
+            chunks.step_body(&registry, &mut body, 1.0 / 1000.0, -9.8);
+            assert!(body.on_ground);
+            assert_eq!(body.velocity.1, 0.0);
+        }
+
+        assert!(approx(body.position.1, 1.0));
+    }
+
+    #[test]
+    fn rotated_collision_boxes() {
+        let (mut chunks, registry) = setup();
+
+        let log = &registry.get_block_by_id(4).aabbs[0];
+        let sideways = log.rotate(&BlockRotation::PX(0.0));
+        assert!(approx(sideways.width(), 1.0));
+        assert!(approx(sideways.height(), 0.5));
+        assert!(approx(sideways.depth(), 0.5));
+        assert!(approx(sideways.min_y, 0.25));
+
+        chunks.set_voxel(12, 1, 2, 4);
+        chunks.set_voxel_rotation(12, 1, 2, &BlockRotation::PX(0.0));
+
+        // A body falling onto the log lands on its side, not on top of an upright pole.
+        let aabb = AABB::new().scale_x(0.2).scale_y(0.2).scale_z(0.2).build();
+        let moved = chunks.resolve_collision(
+            &registry,
+            &aabb,
+            &Vec3(12.1, 2.0, 2.4),
+            &Vec3(0.0, -1.0, 0.0),
+        );
+        assert!(approx(2.0 + moved.1, 1.75));
+    }
+
+    #[test]
+    fn path_finding() {
+        let (mut chunks, registry) = setup();
+
+        // A two-block high wall, too tall to step over, between the start and the goal.
+        for vz in 2..=6 {
+            chunks.set_voxel(8, 1, vz, 1);
+            chunks.set_voxel(8, 2, vz, 1);
+        }
+
+        let start = Vec3(6, 1, 4);
+        let goal = Vec3(10, 1, 4);
+
+        let path = chunks.find_path(&registry, &start, &goal, 1000).unwrap();
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.len() > 5);
+        assert!(path
+            .iter()
+            .all(|voxel| voxel.0 != 8 || !(2..=6).contains(&voxel.2)));
+
+        // Too few nodes to go around the wall.
+        assert!(chunks.find_path(&registry, &start, &goal, 4).is_none());
+
+        // Once the wall spans the whole chunk, the goal can't be reached.
+        for vz in 0..16 {
+            chunks.set_voxel(8, 1, vz, 1);
+            chunks.set_voxel(8, 2, vz, 1);
+        }
+        assert!(chunks.find_path(&registry, &start, &goal, 1000).is_none());
+    }
+}
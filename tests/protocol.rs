@@ -0,0 +1,434 @@
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use voxelize::{
+        batch_messages, compress, compress_message, decode_message, encode_message, is_compressed,
+        ChunkProtocol, EncodedMessage, EntityOperation, EntityProtocol, GeometryProtocol,
+        MeshProtocol, Message, MessageType, Ndarray, QuantizedGeometry, ReliableMessages,
+        UpdateProtocol, Vec3, DEFAULT_COMPRESSION_THRESHOLD, MAX_DECOMPRESSED_SIZE,
+    };
+
+    #[test]
+    fn binary_geometry() {
+        let geometry = GeometryProtocol {
+            voxel: 12,
+            at: vec![1, 2, 3],
+            face_name: Some("py".to_owned()),
+            positions: vec![0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+            indices: vec![0, 1, 2, 2, 1, 3],
+            uvs: vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            lights: vec![15 << 12; 4],
+            colors: vec![],
+        };
+
+        let message = Message::new(&MessageType::Load)
+            .binary(geometry.encode())
+            .build();
+
+        let decoded = decode_message(&encode_message(&message)).unwrap();
+
+        assert_eq!(GeometryProtocol::decode(&decoded.binary).unwrap(), geometry);
+    }
+
+    #[test]
+    fn compression() {
+        let chunk = ChunkProtocol {
+            x: 1,
+            z: -2,
+            id: "chunk".to_owned(),
+            voxels: Some(Ndarray::new(&[16, 64, 16], 3)),
+            lights: Some(Ndarray::new(&[16, 64, 16], 15)),
+            ..Default::default()
+        };
+
+        let load = Message::new(&MessageType::Load).chunks(&[chunk]).build();
+        let encoded = compress_message(encode_message(&load), DEFAULT_COMPRESSION_THRESHOLD);
+
+        assert!(is_compressed(&encoded));
+        assert!(encoded.len() < DEFAULT_COMPRESSION_THRESHOLD);
+        assert_eq!(decode_message(&encoded).unwrap(), load);
+
+        let tiny = Message::new(&MessageType::Chat).text("hi").build();
+        let encoded = compress_message(encode_message(&tiny), DEFAULT_COMPRESSION_THRESHOLD);
+
+        assert!(!is_compressed(&encoded));
+        assert_eq!(decode_message(&encoded).unwrap(), tiny);
+
+        // The threshold is up to whoever sends the message.
+        let encoded = compress_message(encode_message(&tiny), 0);
+
+        assert!(is_compressed(&encoded));
+        assert_eq!(decode_message(&encoded).unwrap(), tiny);
+    }
+
+    #[test]
+    fn decompression_limit() {
+        // A small frame that would inflate past the limit is rejected instead of being inflated.
+        let bomb = compress(&vec![0; MAX_DECOMPRESSED_SIZE + 1]);
+
+        assert!(bomb.len() < 64 * 1024);
+        assert!(decode_message(&bomb).is_err());
+
+        assert!(decode_message(&compress(&[0xff; 64])).is_err());
+    }
+
+    #[test]
+    fn entity_updates() {
+        let mut extra = serde_json::Map::new();
+        extra.insert("health".to_owned(), serde_json::json!(20));
+
+        let entity = EntityProtocol::new(EntityOperation::Update, "zombie-1", "zombie")
+            .position(&Vec3(1.5, 64.0, -3.25))
+            .direction(&Vec3(0.0, 0.0, 1.0))
+            .metadata(&extra);
+
+        let message = Message::new(&MessageType::Entity)
+            .entities(&[entity])
+            .build();
+
+        let decoded = decode_message(&encode_message(&message)).unwrap();
+        let entity = &decoded.entities[0];
+
+        assert_eq!(entity.id, "zombie-1");
+        assert_eq!(entity.r#type, "zombie");
+        assert_eq!(entity.operation, EntityOperation::Update as i32);
+
+        let received = EntityProtocol {
+            metadata: Some(entity.metadata.to_owned()),
+            ..Default::default()
+        };
+
+        assert_eq!(received.get_position(), Some(Vec3(1.5, 64.0, -3.25)));
+        assert_eq!(received.get_direction(), Some(Vec3(0.0, 0.0, 1.0)));
+        assert!(entity.metadata.contains("\"health\":20"));
+    }
+
+    #[test]
+    fn batching() {
+        let messages = ["a", "b", "c"]
+            .iter()
+            .map(|text| {
+                EncodedMessage(encode_message(
+                    &Message::new(&MessageType::Chat).text(text).build(),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let batch = decode_message(&batch_messages(messages.clone()).0).unwrap();
+
+        assert_eq!(batch.r#type, MessageType::Batch as i32);
+        assert_eq!(
+            batch
+                .batch
+                .iter()
+                .map(|buf| decode_message(buf).unwrap().text)
+                .collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
+        );
+
+        assert_eq!(batch_messages(messages[..1].to_vec()).0, messages[0].0);
+    }
+
+    #[test]
+    fn reliable_delivery() {
+        let edit = Message::new(&MessageType::Update)
+            .updates(&[UpdateProtocol {
+                vx: 1,
+                vy: 2,
+                vz: 3,
+                voxel: 4,
+                light: 0,
+            }])
+            .reliable()
+            .build();
+        let position = Message::new(&MessageType::Peer).build();
+
+        assert_ne!(edit.ack, 0);
+        assert_eq!(position.ack, 0);
+        assert_ne!(
+            Message::new(&MessageType::Update).reliable().build().ack,
+            edit.ack
+        );
+
+        let mut reliable = ReliableMessages::new(Duration::from_millis(20));
+        reliable.track("alice", &edit);
+        reliable.track("alice", &position);
+        assert_eq!(reliable.pending("alice"), 1);
+
+        // Nothing is resent within the window.
+        assert!(reliable.resend().is_empty());
+
+        thread::sleep(Duration::from_millis(30));
+        let resent = reliable.resend();
+        assert_eq!(resent.len(), 1);
+        assert_eq!(resent[0].0, edit);
+        assert!(resent[0].1.includes("alice"));
+        assert!(!resent[0].1.includes("bob"));
+
+        // Resending restarts the wait.
+        assert!(reliable.resend().is_empty());
+
+        reliable.ack("alice", edit.ack);
+        assert_eq!(reliable.pending("alice"), 0);
+
+        thread::sleep(Duration::from_millis(30));
+        assert!(reliable.resend().is_empty());
+    }
+
+    fn voxel_update(vx: i32, voxel: u32) -> Message {
+        Message::new(&MessageType::Update)
+            .updates(&[UpdateProtocol {
+                vx,
+                vy: 0,
+                vz: 0,
+                voxel,
+                light: 0,
+            }])
+            .reliable()
+            .build()
+    }
+
+    #[test]
+    fn reliable_limits() {
+        let mut reliable = ReliableMessages::new(Duration::from_millis(10));
+        reliable.max_retries = 2;
+        reliable.track("alice", &voxel_update(0, 1));
+
+        // A client that never acknowledges is only resent a message so many times.
+        let mut resends = 0;
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(15));
+            resends += reliable.resend().len();
+        }
+        assert_eq!(resends, 2);
+        assert_eq!(reliable.pending("alice"), 0);
+
+        // Past the cap, the oldest messages are given up on.
+        reliable.max_pending = 3;
+        let updates = (0..5).map(|vx| voxel_update(vx, 1)).collect::<Vec<_>>();
+        updates
+            .iter()
+            .for_each(|update| reliable.track("alice", update));
+        assert_eq!(reliable.pending("alice"), 3);
+
+        thread::sleep(Duration::from_millis(15));
+        let resent = reliable
+            .resend()
+            .into_iter()
+            .map(|(message, _)| message.ack)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            resent,
+            updates[2..].iter().map(|m| m.ack).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn superseded_updates() {
+        let mut reliable = ReliableMessages::new(Duration::from_millis(10));
+
+        let stale = Message::new(&MessageType::Update)
+            .updates(&[
+                UpdateProtocol {
+                    vx: 0,
+                    voxel: 1,
+                    ..Default::default()
+                },
+                UpdateProtocol {
+                    vx: 1,
+                    voxel: 1,
+                    ..Default::default()
+                },
+            ])
+            .reliable()
+            .build();
+        reliable.track("alice", &stale);
+        reliable.track("alice", &voxel_update(0, 2));
+        reliable.track("alice", &voxel_update(1, 3));
+
+        // Only the newest update of each voxel is ever resent.
+        thread::sleep(Duration::from_millis(15));
+        let resent = reliable.resend();
+        let updates = resent
+            .iter()
+            .flat_map(|(message, _)| message.updates.iter())
+            .map(|update| (update.vx, update.voxel))
+            .collect::<Vec<_>>();
+
+        assert_eq!(updates, vec![(0, 2), (1, 3)]);
+        assert_eq!(reliable.pending("alice"), 2);
+    }
+
+    #[test]
+    fn geometry_optimize() {
+        // Two quads side by side, sharing the edge at x = 1.
+        let mut geometry = GeometryProtocol {
+            voxel: 1,
+            positions: vec![
+                0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, //
+                1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 1.0, 1.0, 0.0, 2.0, 1.0, 0.0,
+            ],
+            uvs: vec![
+                0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, //
+                1.0, 0.0, 2.0, 0.0, 1.0, 1.0, 2.0, 1.0,
+            ],
+            lights: vec![15; 8],
+            indices: vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7],
+            ..Default::default()
+        };
+
+        let vertex = |geometry: &GeometryProtocol, index: i32| {
+            let i = index as usize;
+            (
+                geometry.positions[i * 3..i * 3 + 3].to_vec(),
+                geometry.uvs[i * 2..i * 2 + 2].to_vec(),
+                geometry.lights[i],
+            )
+        };
+        let triangles = |geometry: &GeometryProtocol| {
+            geometry
+                .indices
+                .iter()
+                .map(|&index| vertex(geometry, index))
+                .collect::<Vec<_>>()
+        };
+
+        let before = triangles(&geometry);
+        geometry.optimize();
+
+        assert_eq!(geometry.positions.len(), 6 * 3);
+        assert_eq!(geometry.uvs.len(), 6 * 2);
+        assert_eq!(geometry.lights.len(), 6);
+        assert_eq!(geometry.indices, vec![0, 1, 2, 2, 1, 3, 1, 4, 3, 3, 4, 5]);
+        assert_eq!(triangles(&geometry), before);
+
+        // Vertices that only share a position stay apart.
+        let mut lit = GeometryProtocol {
+            positions: vec![0.0; 6],
+            uvs: vec![0.0; 4],
+            lights: vec![15, 0],
+            indices: vec![0, 1],
+            ..Default::default()
+        };
+        lit.optimize();
+        assert_eq!(lit.lights, vec![15, 0]);
+        assert_eq!(lit.indices, vec![0, 1]);
+
+        // Malformed geometry is left alone instead of panicking.
+        let mut out_of_range = GeometryProtocol {
+            positions: vec![0.0; 6],
+            indices: vec![0, 1, 2],
+            ..Default::default()
+        };
+        let expected = out_of_range.clone();
+        out_of_range.optimize();
+        assert_eq!(out_of_range, expected);
+
+        let mut mismatched = GeometryProtocol {
+            positions: vec![0.0; 6],
+            uvs: vec![0.0; 2],
+            lights: vec![15],
+            indices: vec![0, 1],
+            ..Default::default()
+        };
+        let expected = mismatched.clone();
+        mismatched.optimize();
+        assert_eq!(mismatched, expected);
+    }
+
+    #[test]
+    fn quantized_geometry() {
+        let geometry = GeometryProtocol {
+            voxel: 3,
+            at: vec![1, 2, 3],
+            face_name: Some("py".to_owned()),
+            positions: vec![16.0, 0.0, 32.0, 31.99, 255.5, 47.25, 20.123, 100.0, 40.001],
+            indices: vec![0, 1, 2],
+            uvs: vec![0.0, 0.0, 0.5, 0.25, 1.0, 0.333],
+            lights: vec![15 << 12; 3],
+            colors: vec![],
+        };
+
+        let offset = Vec3(16.0, 0.0, 32.0);
+        let quantized = geometry.quantize(&offset, 256.0);
+        assert_eq!(quantized.offset, offset);
+        assert_eq!(quantized.scale, 256.0 / 65535.0);
+
+        let check = |restored: &GeometryProtocol| {
+            assert_eq!(restored.indices, geometry.indices);
+            assert_eq!(restored.lights, geometry.lights);
+            assert_eq!(restored.face_name, geometry.face_name);
+
+            for (a, b) in restored.positions.iter().zip(&geometry.positions) {
+                assert!((a - b).abs() <= quantized.scale / 2.0 + 1e-4);
+            }
+            for (a, b) in restored.uvs.iter().zip(&geometry.uvs) {
+                assert!((a - b).abs() <= 0.5 / 65535.0 + 1e-6);
+            }
+        };
+
+        check(&quantized.dequantize());
+
+        // Decoding a quantized geometry dequantizes it, in fewer bytes than the floats.
+        let bytes = quantized.encode();
+        check(&GeometryProtocol::decode(&bytes).unwrap());
+        assert!(bytes.len() < geometry.encode().len());
+
+        // UVs are quantized over their own range, so ones outside of 0 to 1 survive.
+        let tiled = GeometryProtocol {
+            uvs: vec![-0.5, 0.0, 2.5, 3.0, 1.0, 0.25],
+            ..geometry.clone()
+        };
+        let restored = tiled.quantize(&offset, 256.0).dequantize();
+        for (a, b) in restored.uvs.iter().zip(&tiled.uvs) {
+            assert!((a - b).abs() <= 3.5 / 65535.0);
+        }
+    }
+
+    #[test]
+    fn quantized_chunk_meshes() {
+        let geometry = GeometryProtocol {
+            voxel: 1,
+            positions: vec![0.0, 0.0, 0.0, 16.0, 4.5, 0.0, 0.0, 4.5, 16.0],
+            indices: vec![0, 1, 2],
+            uvs: vec![0.0, 0.0, 0.5, 0.5, 1.0, 1.0],
+            ..Default::default()
+        };
+        let chunk = |quantized: bool| ChunkProtocol {
+            id: "chunk".to_owned(),
+            meshes: vec![MeshProtocol {
+                level: 0,
+                geometries: vec![geometry.clone()],
+                quantized,
+            }],
+            ..Default::default()
+        };
+
+        let floats = encode_message(
+            &Message::new(&MessageType::Load)
+                .chunks(&[chunk(false)])
+                .build(),
+        );
+        let quantized = encode_message(
+            &Message::new(&MessageType::Load)
+                .chunks(&[chunk(true)])
+                .build(),
+        );
+        assert!(quantized.len() < floats.len());
+
+        // Quantized meshes are sent fitted to their own bounds, in place of the floats.
+        let decoded = decode_message(&quantized).unwrap();
+        let sent = decoded.chunks[0].meshes[0].geometries[0].to_owned();
+        assert!(sent.positions.is_empty());
+        assert!(sent.scale > 0.0);
+
+        let restored = QuantizedGeometry::from(sent).dequantize();
+        for (a, b) in restored.positions.iter().zip(&geometry.positions) {
+            assert!((a - b).abs() <= 16.0 / 65535.0);
+        }
+        for (a, b) in restored.uvs.iter().zip(&geometry.uvs) {
+            assert!((a - b).abs() <= 1.0 / 65535.0);
+        }
+    }
+}
@@ -0,0 +1,859 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::poll_fn,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context as TaskContext, Poll},
+        time::Duration,
+    };
+
+    use actix::{prelude::Stream, Actor, ActorContext, Addr, Context, Handler};
+    use actix_web::{error::PayloadError, web::Bytes};
+    use actix_web_actors::ws::WebsocketContext;
+    use hashbrown::HashMap;
+    use serde_json::Value;
+    use specs::{Builder, RunNow, WorldExt};
+    use voxelize::{
+        decode_message, AddWorld, BroadcastSystem, ChatMessageProtocol, Client, ClientFilter,
+        ClientMessage, Connect, Disconnect, EncodedMessage, GetInfo, Message, MessageType,
+        MoveClient, PositionComp, RateLimiter, Server, ServerError, SyncWorld, Vec3, World,
+        WorldConfig, WsSession,
+    };
+
+    /// A websocket connection that never sends anything.
+    struct Silent;
+
+    impl Stream for Silent {
+        type Item = Result<Bytes, PayloadError>;
+
+        fn poll_next(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
+        }
+    }
+
+    struct Sink;
+
+    impl Actor for Sink {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<EncodedMessage> for Sink {
+        type Result = ();
+
+        fn handle(&mut self, _: EncodedMessage, _: &mut Context<Self>) {}
+    }
+
+    /// An actor that keeps every `Init` message sent to it.
+    struct Inits(Arc<Mutex<Vec<Message>>>);
+
+    impl Actor for Inits {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<EncodedMessage> for Inits {
+        type Result = ();
+
+        fn handle(&mut self, msg: EncodedMessage, _: &mut Context<Self>) {
+            let message = decode_message(&msg.0).unwrap();
+
+            if message.r#type == MessageType::Init as i32 {
+                self.0.lock().unwrap().push(message);
+            }
+        }
+    }
+
+    /// An actor that keeps the body of every chat message sent to it.
+    struct Chats(Arc<Mutex<Vec<String>>>);
+
+    impl Actor for Chats {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<EncodedMessage> for Chats {
+        type Result = ();
+
+        fn handle(&mut self, msg: EncodedMessage, _: &mut Context<Self>) {
+            let message = decode_message(&msg.0).unwrap();
+            let mut messages = vec![message.to_owned()];
+
+            // Each tick's messages come batched into one.
+            for encoded in &message.batch {
+                messages.push(decode_message(encoded).unwrap());
+            }
+
+            for message in messages {
+                if let Some(chat) = message.chat {
+                    self.0.lock().unwrap().push(chat.body);
+                }
+            }
+        }
+    }
+
+    /// An actor that stops as soon as it starts, so that every message sent to it fails.
+    struct Dead;
+
+    impl Actor for Dead {
+        type Context = Context<Self>;
+
+        fn started(&mut self, ctx: &mut Context<Self>) {
+            ctx.stop();
+        }
+    }
+
+    impl Handler<EncodedMessage> for Dead {
+        type Result = ();
+
+        fn handle(&mut self, _: EncodedMessage, _: &mut Context<Self>) {}
+    }
+
+    async fn wait_for_client(world: &Addr<SyncWorld>, id: &str, present: bool) -> bool {
+        for _ in 0..100 {
+            let clients = world.send(GetInfo).await.unwrap().clients;
+
+            if clients.iter().any(|client| client == id) == present {
+                return true;
+            }
+
+            actix::clock::sleep(Duration::from_millis(10)).await;
+        }
+
+        false
+    }
+
+    #[test]
+    fn heartbeat_timeout() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).heartbeat_timeout(100).build();
+            let config = WorldConfig::new().preload_radius(1).build();
+            let world = server
+                .add_world(World::new("world", &config))
+                .unwrap()
+                .clone();
+
+            let timeout = Duration::from_millis(server.heartbeat_timeout);
+            let mut session = WsSession::new("client", false, timeout, server.start());
+            session.world = Some("world".to_owned());
+
+            // Run the session over a connection that never sends anything, not even pongs.
+            let mut output = Box::pin(WebsocketContext::create(session, Silent));
+            actix::spawn(async move {
+                while poll_fn(|cx| output.as_mut().poll_next(cx)).await.is_some() {}
+            });
+
+            assert!(wait_for_client(&world, "client", true).await);
+
+            // The client is dropped from its world once the timeout has elapsed.
+            actix::clock::sleep(timeout).await;
+            assert!(wait_for_client(&world, "client", false).await);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Heartbeat timeout should be above zero.")]
+    fn zero_heartbeat_timeout() {
+        Server::new().heartbeat_timeout(0).build();
+    }
+
+    #[test]
+    fn client_spawn() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).build();
+            let config = WorldConfig::new()
+                .preload_radius(1)
+                .spawn([0.5, 40.0, -3.5])
+                .build();
+
+            // Keep the position a client is created at, before any system moves it.
+            let spawned = Arc::new(Mutex::new(None));
+            let mut world = World::new("world", &config);
+            let position = spawned.clone();
+            world.set_client_modifier(move |world, ent| {
+                *position.lock().unwrap() = world
+                    .read_component::<PositionComp>()
+                    .get(ent)
+                    .map(|position| position.0.clone());
+            });
+            let world = server.add_world(world).unwrap().clone();
+
+            let timeout = Duration::from_secs(10);
+            let mut session = WsSession::new("client", false, timeout, server.start());
+            session.world = Some("world".to_owned());
+
+            let mut output = Box::pin(WebsocketContext::create(session, Silent));
+            actix::spawn(async move {
+                while poll_fn(|cx| output.as_mut().poll_next(cx)).await.is_some() {}
+            });
+
+            assert!(wait_for_client(&world, "client", true).await);
+            assert_eq!(*spawned.lock().unwrap(), Some(Vec3(0.5, 40.0, -3.5)));
+        });
+    }
+
+    #[test]
+    fn per_world_tick_rates() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).interval(1000).build();
+
+            let mut add_world = |name: &str, tick_interval: u64| {
+                let config = WorldConfig::new()
+                    .preload_radius(1)
+                    .tick_interval(tick_interval)
+                    .build();
+                server.add_world(World::new(name, &config)).unwrap().clone()
+            };
+
+            let fast = add_world("fast", 10);
+            let slow = add_world("slow", 50);
+
+            let _server = server.start();
+            actix::clock::sleep(Duration::from_millis(300)).await;
+
+            let fast_ticks = fast.send(GetInfo).await.unwrap().ticks;
+            let slow_ticks = slow.send(GetInfo).await.unwrap().ticks;
+
+            assert!(slow_ticks > 0);
+            assert!(fast_ticks > slow_ticks * 2);
+        });
+    }
+
+    #[test]
+    fn world_added_after_start() {
+        actix::System::new().block_on(async {
+            let server = Server::new().debug(false).build().start();
+            let config = WorldConfig::new()
+                .preload_radius(1)
+                .tick_interval(10)
+                .build();
+
+            let world = server
+                .send(AddWorld(World::new("late", &config)))
+                .await
+                .unwrap()
+                .unwrap();
+            actix::clock::sleep(Duration::from_millis(200)).await;
+
+            assert!(world.send(GetInfo).await.unwrap().ticks > 0);
+
+            // A world can't be added twice under the same name.
+            let duplicate = server
+                .send(AddWorld(World::new("late", &config)))
+                .await
+                .unwrap();
+            assert!(duplicate.is_err());
+        });
+    }
+
+    #[test]
+    fn rate_limiting() {
+        actix::System::new().block_on(async {
+            let server = Server::new().debug(false).build().start();
+            let timeout = Duration::from_secs(10);
+
+            let mut flooder = WsSession::new("flooder", false, timeout, server.clone());
+            flooder.limiter = Some(RateLimiter::new(10, 20.0));
+
+            let mut polite = WsSession::new("polite", false, timeout, server.clone());
+            polite.limiter = Some(RateLimiter::new(10, 20.0));
+
+            let start = flooder.last_heartbeat.max(polite.last_heartbeat);
+
+            // Against a limit of twenty messages a second, a thousand messages in a tenth of a
+            // second from the flooder, and ten messages a second from the polite client.
+            let flooded = (0..1000)
+                .filter(|i| {
+                    flooder.allow_message(start + Duration::from_micros(i * 100)) == Some(true)
+                })
+                .count();
+            let polite_sent = (0..30)
+                .filter(|i| {
+                    polite.allow_message(start + Duration::from_millis(i * 100)) == Some(true)
+                })
+                .count();
+
+            assert!(flooded <= 12);
+            assert_eq!(polite_sent, 30);
+
+            let mut offender = WsSession::new("offender", false, timeout, server);
+            offender.limiter = Some(RateLimiter::new(1, 1.0));
+            offender.kick_after = Some(3);
+
+            let now = offender.last_heartbeat;
+            let results = (0..5)
+                .map(|_| offender.allow_message(now))
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                results,
+                vec![Some(true), Some(false), Some(false), None, None]
+            );
+        });
+    }
+
+    #[test]
+    fn handshake_versions() {
+        let server = Server::new().debug(false).protocol_versions(2, 3).build();
+
+        let handshake = |version: Option<&str>| {
+            let mut metadata = HashMap::new();
+            metadata.insert("token".to_owned(), "secret".to_owned());
+            if let Some(version) = version {
+                metadata.insert("version".to_owned(), version.to_owned());
+            }
+            server.validate_handshake(&metadata)
+        };
+
+        assert!(handshake(Some("1")).is_some());
+        assert!(handshake(Some("4")).is_some());
+        assert!(handshake(None).is_some());
+        assert!(handshake(Some("2")).is_none());
+        assert!(handshake(Some("3")).is_none());
+
+        let open = Server::new().debug(false).build();
+        assert!(open.validate_handshake(&HashMap::new()).is_none());
+    }
+
+    #[test]
+    fn move_client_between_worlds() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).build();
+            let config = WorldConfig::new().preload_radius(1).build();
+
+            let world1 = server
+                .add_world(World::new("world1", &config))
+                .unwrap()
+                .clone();
+            let world2 = server
+                .add_world(World::new("world2", &config))
+                .unwrap()
+                .clone();
+
+            let server = server.start();
+
+            let id = server
+                .send(Connect {
+                    id: None,
+                    is_transport: false,
+                    addr: Sink.start().recipient(),
+                })
+                .await
+                .unwrap();
+
+            let join = Message::new(&MessageType::Join)
+                .json(r#"{"world":"world1","username":"alice"}"#)
+                .build();
+            let error = server
+                .send(ClientMessage {
+                    id: id.clone(),
+                    data: join,
+                })
+                .await
+                .unwrap();
+            assert!(error.is_none());
+            assert!(wait_for_client(&world1, &id, true).await);
+
+            let moved = server
+                .send(MoveClient {
+                    id: id.clone(),
+                    world: "world2".to_owned(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(moved, Ok(()));
+
+            assert!(wait_for_client(&world1, &id, false).await);
+            assert!(wait_for_client(&world2, &id, true).await);
+
+            let missing = server
+                .send(MoveClient {
+                    id,
+                    world: "world3".to_owned(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(
+                missing,
+                Err(ServerError::WorldNotFound("world3".to_owned()))
+            );
+        });
+    }
+
+    #[test]
+    fn session_resumption() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).session_grace(200).build();
+            let config = WorldConfig::new().preload_radius(1).build();
+
+            let world1 = server
+                .add_world(World::new("world1", &config))
+                .unwrap()
+                .clone();
+            let world2 = server
+                .add_world(World::new("world2", &config))
+                .unwrap()
+                .clone();
+
+            let server = server.start();
+
+            // Connect a new client, joining world1 as it would by default, and return the client's
+            // ID, the token handed to it in its `Init` message, and all the `Init`s it receives.
+            let join = |server: Addr<Server>, username: &'static str, token: Option<String>| async move {
+                let inits = Arc::new(Mutex::new(vec![]));
+
+                let id = server
+                    .send(Connect {
+                        id: None,
+                        is_transport: false,
+                        addr: Inits(inits.clone()).start().recipient(),
+                    })
+                    .await
+                    .unwrap();
+
+                let metadata = match token {
+                    Some(token) => serde_json::json!({ "session": token }),
+                    None => serde_json::json!({}),
+                };
+                let join = Message::new(&MessageType::Join)
+                    .json(
+                        &serde_json::json!({
+                            "world": "world1",
+                            "username": username,
+                            "metadata": metadata,
+                        })
+                        .to_string(),
+                    )
+                    .build();
+                let error = server
+                    .send(ClientMessage {
+                        id: id.clone(),
+                        data: join,
+                    })
+                    .await
+                    .unwrap();
+                assert!(error.is_none());
+
+                let mut token = None;
+                for _ in 0..100 {
+                    if let Some(init) = inits.lock().unwrap().first() {
+                        let json: Value = serde_json::from_str(&init.json).unwrap();
+                        token = json["session"].as_str().map(str::to_owned);
+                        break;
+                    }
+
+                    actix::clock::sleep(Duration::from_millis(10)).await;
+                }
+
+                (id, token.expect("no session token in the Init message"), inits)
+            };
+
+            let (id, token, _) = join(server.clone(), "alice", None).await;
+            assert!(wait_for_client(&world1, &id, true).await);
+
+            server
+                .send(MoveClient {
+                    id: id.clone(),
+                    world: "world2".to_owned(),
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(wait_for_client(&world2, &id, true).await);
+
+            server.send(Disconnect { id: id.clone() }).await.unwrap();
+            assert!(wait_for_client(&world2, &id, false).await);
+
+            // A token the server never handed out resumes nothing.
+            let (guessed, _, guessed_inits) =
+                join(server.clone(), "mallory", Some("token".to_owned())).await;
+            assert!(wait_for_client(&world1, &guessed, true).await);
+
+            // Reconnecting with the token within the grace period goes back to world2, under the
+            // old username, and hands out a fresh token.
+            let (id, new_token, _) = join(server.clone(), "bob", Some(token.clone())).await;
+            assert!(wait_for_client(&world2, &id, true).await);
+            assert_ne!(new_token, token);
+
+            server
+                .send(MoveClient {
+                    id: guessed.clone(),
+                    world: "world2".to_owned(),
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(wait_for_client(&world2, &guessed, true).await);
+
+            // The init for world2 is delivered after the client is added, so wait for it.
+            let mut peer = None;
+            for _ in 0..100 {
+                peer = guessed_inits
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .flat_map(|init| init.peers.iter())
+                    .find(|peer| peer.id == id)
+                    .cloned();
+
+                if peer.is_some() {
+                    break;
+                }
+
+                actix::clock::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(peer.unwrap().username, "alice");
+
+            // A used token can't be used again.
+            server.send(Disconnect { id: id.clone() }).await.unwrap();
+            assert!(wait_for_client(&world2, &id, false).await);
+
+            let (id, _, _) = join(server.clone(), "alice", Some(token)).await;
+            assert!(wait_for_client(&world1, &id, true).await);
+
+            // Once the grace period is over, the client starts fresh.
+            actix::clock::sleep(Duration::from_millis(300)).await;
+
+            let (id, _, _) = join(server.clone(), "alice", Some(new_token)).await;
+            assert!(wait_for_client(&world1, &id, true).await);
+            assert!(!wait_for_client(&world2, &id, true).await);
+        });
+    }
+
+    #[test]
+    fn chat_stays_in_world() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).build();
+            let config = WorldConfig::new().preload_radius(1).build();
+
+            let world1 = server
+                .add_world(World::new("world1", &config))
+                .unwrap()
+                .clone();
+            let world2 = server
+                .add_world(World::new("world2", &config))
+                .unwrap()
+                .clone();
+
+            let server = server.start();
+
+            let join = |world: &'static str, username: &'static str| {
+                let server = server.clone();
+
+                async move {
+                    let chats = Arc::new(Mutex::new(vec![]));
+                    let id = server
+                        .send(Connect {
+                            id: None,
+                            is_transport: false,
+                            addr: Chats(chats.clone()).start().recipient(),
+                        })
+                        .await
+                        .unwrap();
+
+                    let json = format!(r#"{{"world":"{world}","username":"{username}"}}"#);
+                    let join = Message::new(&MessageType::Join).json(&json).build();
+                    let error = server
+                        .send(ClientMessage {
+                            id: id.clone(),
+                            data: join,
+                        })
+                        .await
+                        .unwrap();
+                    assert!(error.is_none());
+
+                    (id, chats)
+                }
+            };
+
+            let (alice, alice_chats) = join("world1", "alice").await;
+            let (bob, bob_chats) = join("world2", "bob").await;
+            assert!(wait_for_client(&world1, &alice, true).await);
+            assert!(wait_for_client(&world2, &bob, true).await);
+
+            let chat = |body: &str| {
+                Message::new(&MessageType::Chat)
+                    .chat(ChatMessageProtocol {
+                        r#type: "chat".to_owned(),
+                        sender: String::new(),
+                        body: body.to_owned(),
+                    })
+                    .build()
+            };
+
+            for (id, body) in [(&alice, "hello"), (&bob, "hi")] {
+                let error = server
+                    .send(ClientMessage {
+                        id: id.to_owned(),
+                        data: chat(body),
+                    })
+                    .await
+                    .unwrap();
+                assert!(error.is_none());
+            }
+
+            // Once each client has heard its own world's chat, neither has heard the other's.
+            for _ in 0..100 {
+                if !alice_chats.lock().unwrap().is_empty() && !bob_chats.lock().unwrap().is_empty()
+                {
+                    break;
+                }
+
+                actix::clock::sleep(Duration::from_millis(10)).await;
+            }
+
+            assert_eq!(*alice_chats.lock().unwrap(), vec!["hello"]);
+            assert_eq!(*bob_chats.lock().unwrap(), vec!["hi"]);
+        });
+    }
+
+    #[test]
+    fn broadcast_recipients() {
+        actix::System::new().block_on(async {
+            let mut world = World::new("world", &WorldConfig::new().build());
+            let mut received = HashMap::new();
+
+            for id in ["alice", "bob", "carol"] {
+                let chats = Arc::new(Mutex::new(vec![]));
+                let entity = world.ecs_mut().create_entity().build();
+
+                world.clients_mut().insert(
+                    id.to_owned(),
+                    Client {
+                        id: id.to_owned(),
+                        username: id.to_owned(),
+                        entity,
+                        addr: Chats(chats.clone()).start().recipient(),
+                        metadata: HashMap::new(),
+                        failed_sends: 0,
+                    },
+                );
+                received.insert(id, chats);
+            }
+
+            let chat = |body: &str| {
+                Message::new(&MessageType::Chat)
+                    .chat(ChatMessageProtocol {
+                        r#type: "chat".to_owned(),
+                        sender: String::new(),
+                        body: body.to_owned(),
+                    })
+                    .build()
+            };
+
+            world.broadcast(chat("everyone"), ClientFilter::All);
+            world.broadcast_except("alice", chat("not alice"));
+            world.broadcast(chat("bob"), ClientFilter::Direct("bob".to_owned()));
+            world.broadcast(
+                chat("alice and carol"),
+                ClientFilter::Include(vec!["alice".to_owned(), "carol".to_owned()]),
+            );
+
+            // Messages are encoded off the main thread, so run the system until all have gone out.
+            for _ in 0..100 {
+                BroadcastSystem.run_now(world.ecs());
+                actix::clock::sleep(Duration::from_millis(10)).await;
+
+                let count = received
+                    .values()
+                    .map(|chats| chats.lock().unwrap().len())
+                    .sum::<usize>();
+                if count == 8 {
+                    break;
+                }
+            }
+
+            let heard = |id: &str| {
+                let mut chats = received[id].lock().unwrap().clone();
+                chats.sort();
+                chats
+            };
+
+            assert_eq!(heard("alice"), vec!["alice and carol", "everyone"]);
+            assert_eq!(heard("bob"), vec!["bob", "everyone", "not alice"]);
+            assert_eq!(
+                heard("carol"),
+                vec!["alice and carol", "everyone", "not alice"]
+            );
+        });
+    }
+
+    #[test]
+    fn dead_clients_removed() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).interval(10).build();
+
+            let mut add_world = |name: &str, max_send_failures: u32| {
+                let config = WorldConfig::new()
+                    .preload_radius(1)
+                    .max_send_failures(max_send_failures)
+                    .build();
+                server.add_world(World::new(name, &config)).unwrap().clone()
+            };
+
+            let patient = add_world("patient", 1000);
+            let strict = add_world("strict", 2);
+
+            let server = server.start();
+
+            let join = |world: &str| {
+                let server = server.clone();
+                let json = format!(r#"{{"world":"{}","username":"ghost"}}"#, world);
+
+                async move {
+                    let addr = Dead.start();
+                    actix::clock::sleep(Duration::from_millis(10)).await;
+                    assert!(!addr.connected());
+
+                    let id = server
+                        .send(Connect {
+                            id: None,
+                            is_transport: false,
+                            addr: addr.recipient(),
+                        })
+                        .await
+                        .unwrap();
+
+                    let join = Message::new(&MessageType::Join).json(&json).build();
+                    let error = server
+                        .send(ClientMessage {
+                            id: id.clone(),
+                            data: join,
+                        })
+                        .await
+                        .unwrap();
+                    assert!(error.is_none());
+
+                    id
+                }
+            };
+
+            let lingering = join("patient").await;
+            let reaped = join("strict").await;
+
+            // Both the init and the join messages fail, which is enough to be removed from strict.
+            actix::clock::sleep(Duration::from_millis(300)).await;
+            assert!(wait_for_client(&patient, &lingering, true).await);
+            assert!(wait_for_client(&strict, &reaped, false).await);
+        });
+    }
+
+    #[test]
+    fn world_selection_at_connect() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).build();
+            let config = WorldConfig::new().preload_radius(1).build();
+
+            let world1 = server
+                .add_world(World::new("world1", &config))
+                .unwrap()
+                .clone();
+            let world2 = server
+                .add_world(World::new("world2", &config))
+                .unwrap()
+                .clone();
+
+            let server = server.start();
+            let timeout = Duration::from_secs(10);
+
+            // What the session does once connected with `/ws/?world=...`.
+            let connect = |world: &str| {
+                let server = server.clone();
+                let mut session = WsSession::new("", false, timeout, server.clone());
+                session.world = Some(world.to_owned());
+
+                async move {
+                    session.id = server
+                        .send(Connect {
+                            id: None,
+                            is_transport: false,
+                            addr: Sink.start().recipient(),
+                        })
+                        .await
+                        .unwrap();
+
+                    let error = server
+                        .send(ClientMessage {
+                            id: session.id.clone(),
+                            data: session.join_message().unwrap(),
+                        })
+                        .await
+                        .unwrap();
+
+                    (session.id, error)
+                }
+            };
+
+            let (id, error) = connect("world2").await;
+            assert!(error.is_none());
+            assert!(wait_for_client(&world2, &id, true).await);
+            assert!(!world1.send(GetInfo).await.unwrap().clients.contains(&id));
+
+            // An unknown world is refused, which closes the connection.
+            let (_, error) = connect("world3").await;
+            assert!(error.is_some());
+
+            let session = WsSession::new("bob", false, timeout, server);
+            assert!(session.join_message().is_none());
+        });
+    }
+
+    #[test]
+    fn versioned_world_selection_at_connect() {
+        actix::System::new().block_on(async {
+            let mut server = Server::new().debug(false).protocol_versions(2, 3).build();
+            let config = WorldConfig::new().preload_radius(1).build();
+
+            let world = server
+                .add_world(World::new("world", &config))
+                .unwrap()
+                .clone();
+
+            let server = server.start();
+            let timeout = Duration::from_secs(10);
+
+            // What the session does once connected with `/ws/?world=world&version=...`.
+            let connect = |version: Option<&str>| {
+                let server = server.clone();
+                let mut session = WsSession::new("", false, timeout, server.clone());
+                session.world = Some("world".to_owned());
+
+                if let Some(version) = version {
+                    session
+                        .metadata
+                        .insert("version".to_owned(), version.to_owned());
+                }
+
+                async move {
+                    session.id = server
+                        .send(Connect {
+                            id: None,
+                            is_transport: false,
+                            addr: Sink.start().recipient(),
+                        })
+                        .await
+                        .unwrap();
+
+                    let error = server
+                        .send(ClientMessage {
+                            id: session.id.clone(),
+                            data: session.join_message().unwrap(),
+                        })
+                        .await
+                        .unwrap();
+
+                    (session.id, error)
+                }
+            };
+
+            // The protocol version of the connect query is checked like that of a join.
+            let (id, error) = connect(Some("2")).await;
+            assert!(error.is_none());
+            assert!(wait_for_client(&world, &id, true).await);
+
+            let (_, error) = connect(Some("1")).await;
+            assert!(error.is_some());
+
+            let (_, error) = connect(None).await;
+            assert!(error.is_some());
+        });
+    }
+}
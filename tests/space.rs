@@ -0,0 +1,289 @@
+#[cfg(test)]
+mod tests {
+    use voxelize::{
+        Block, Chunk, ChunkOptions, ChunkStatus, Chunks, Registry, Vec2, Vec3, VoxelAccess,
+        WorldConfig,
+    };
+
+    fn chunks() -> Chunks {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut chunks = Chunks::new(&config);
+
+        for cx in -1..=1 {
+            for cz in -1..=1 {
+                let mut chunk = Chunk::new(
+                    "test",
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.set_voxel(
+                    cx * 16 + 8,
+                    4,
+                    cz * 16 + 8,
+                    (cx + 1 + (cz + 1) * 3 + 1) as u32,
+                );
+                chunks.add(chunk);
+            }
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn space_radius() {
+        let mut chunks = chunks();
+        chunks.remove(&Vec2(1, 1));
+
+        let space = chunks
+            .make_space(&Vec2(0, 0), 1)
+            .radius(1)
+            .needs_voxels()
+            .default_voxel(99)
+            .build();
+
+        assert_eq!(space.width, 48);
+
+        for cx in -1..=1 {
+            for cz in -1..=1 {
+                let expected = if cx == 1 && cz == 1 {
+                    99
+                } else {
+                    (cx + 1 + (cz + 1) * 3 + 1) as u32
+                };
+                assert_eq!(space.get_voxel(cx * 16 + 8, 4, cz * 16 + 8), expected);
+            }
+        }
+
+        // Chunks beyond the radius read as the default, and outside the height as air.
+        assert_eq!(space.get_voxel(40, 4, 8), 99);
+        assert_eq!(space.get_voxel(8, -1, 8), 0);
+        assert_eq!(space.get_voxel(8, 32, 8), 0);
+    }
+
+    #[test]
+    fn space_write_back() {
+        let mut chunks = chunks();
+        chunks.remove(&Vec2(1, 1));
+
+        let mut space = chunks
+            .make_space(&Vec2(0, 0), 1)
+            .radius(1)
+            .needs_voxels()
+            .build();
+
+        assert!(space.set_voxel(-3, 10, 20, 42));
+        assert_eq!(space.get_voxel(-3, 10, 20), 42);
+
+        // Writes to chunks outside of the space are ignored.
+        assert!(!space.set_voxel(20, 10, 20, 42));
+        assert!(!space.set_voxel(40, 10, 8, 42));
+
+        assert_eq!(chunks.get_voxel(-3, 10, 20), 0);
+        chunks.apply_space(&space);
+
+        let neighbor = chunks.raw(&Vec2(-1, 1)).unwrap();
+        assert_eq!(neighbor.get_voxel(-3, 10, 20), 42);
+        assert_eq!(neighbor.get_height(-3, 20), 10);
+        assert!(chunks.raw(&Vec2(1, 1)).is_none());
+    }
+
+    #[test]
+    fn blocks_in_aabb() {
+        let mut chunks = chunks();
+        chunks.set_voxel(15, 4, 8, 7);
+        chunks.set_voxel(16, 5, 8, 8);
+
+        assert_eq!(
+            chunks.blocks_in_aabb(&Vec3(8, 0, 0), &Vec3(20, 10, 15)),
+            vec![(Vec3(8, 4, 8), 5), (Vec3(15, 4, 8), 7), (Vec3(16, 5, 8), 8)]
+        );
+
+        // Voxels past the loaded chunks are skipped, and heights are clamped to the chunks.
+        assert_eq!(
+            chunks.blocks_in_aabb(&Vec3(16, -10, 8), &Vec3(80, 100, 8)),
+            vec![(Vec3(16, 5, 8), 8), (Vec3(24, 4, 8), 6)]
+        );
+    }
+
+    #[test]
+    fn flood_select() {
+        let mut chunks = chunks();
+
+        // A slab of one block type straddling the border between two chunks.
+        for vx in 12..20 {
+            for vz in 0..3 {
+                chunks.set_voxel(vx, 10, vz, 20);
+            }
+        }
+        chunks.set_voxel(12, 11, 0, 21);
+
+        let selected = chunks.flood_select(&Vec3(12, 10, 0), 100);
+        assert_eq!(selected.len(), 24);
+        assert_eq!(selected[0], Vec3(12, 10, 0));
+        assert!(selected.iter().all(|voxel| voxel.1 == 10));
+        assert!(!selected.contains(&Vec3(12, 11, 0)));
+
+        assert_eq!(chunks.flood_select(&Vec3(12, 10, 0), 5).len(), 5);
+    }
+
+    #[test]
+    fn chunk_accessors() {
+        let mut chunks = chunks();
+
+        let chunk = chunks.get_chunk(&Vec2(1, 0)).unwrap();
+        assert_eq!(chunk.coords, Vec2(1, 0));
+        assert_eq!(chunk.get_voxel(24, 4, 8), 6);
+        assert!(chunks.get_chunk(&Vec2(5, 5)).is_none());
+
+        // Unlike `chunks.get`, chunks are returned even before they're ready.
+        assert!(chunks.get(&Vec2(1, 0)).is_none());
+
+        chunks
+            .get_chunk_mut(&Vec2(1, 0))
+            .unwrap()
+            .set_voxel(24, 5, 8, 9);
+        assert_eq!(chunks.get_voxel(24, 5, 8), 9);
+
+        assert_eq!(chunks.remove(&Vec2(1, 0)).unwrap().coords, Vec2(1, 0));
+        assert!(chunks.get_chunk(&Vec2(1, 0)).is_none());
+    }
+
+    #[test]
+    fn loaded_chunks() {
+        let mut chunks = Chunks::new(&WorldConfig::new().chunk_size(16).build());
+        assert_eq!(chunks.loaded_count(), 0);
+
+        for cx in 0..3 {
+            chunks.add(Chunk::new(
+                "test",
+                cx,
+                0,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 32,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            ));
+        }
+
+        assert_eq!(chunks.loaded_count(), 3);
+
+        let mut coords = chunks
+            .loaded_chunks()
+            .map(|(coords, chunk)| {
+                assert_eq!(coords, &chunk.coords);
+                coords.0
+            })
+            .collect::<Vec<_>>();
+        coords.sort();
+        assert_eq!(coords, vec![0, 1, 2]);
+    }
+
+    fn below_zero(config: &WorldConfig) -> Chunks {
+        let mut chunks = Chunks::new(config);
+
+        for cx in -1..=1 {
+            for cz in -1..=1 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 2,
+                        min_y: -32,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunks.add(chunk);
+            }
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn space_below_zero() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .min_y(-32)
+            .sub_chunks(2)
+            .build();
+        let mut chunks = below_zero(&config);
+        chunks.set_voxel(8, -20, 8, 5);
+
+        let mut space = chunks
+            .make_space(&Vec2(0, 0), 1)
+            .needs_voxels()
+            .needs_height_maps()
+            .build();
+
+        assert_eq!(space.min.1, -32);
+        assert_eq!(space.shape.1, 64);
+        assert_eq!(space.get_voxel(8, -20, 8), 5);
+        assert_eq!(space.get_voxel(8, -33, 8), 0);
+        assert_eq!(space.min_y() + space.get_max_height(8, 8) as i32, -20);
+
+        space.updated_levels.clear();
+        assert!(space.set_voxel(8, -30, 8, 5));
+        assert_eq!(space.updated_levels.iter().collect::<Vec<_>>(), vec![&0]);
+    }
+
+    #[test]
+    fn saved_min_y() {
+        let save_dir = std::env::temp_dir().join("voxelize-min-y-test");
+        let _ = std::fs::remove_dir_all(&save_dir);
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .min_y(-32)
+            .sub_chunks(2)
+            .saving(true)
+            .save_dir(save_dir.to_str().unwrap())
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut chunks = below_zero(&config);
+        chunks.set_voxel(8, -20, 8, 1);
+        assert!(chunks.save(&Vec2(0, 0)));
+
+        let loaded = chunks.try_load(&Vec2(0, 0)).unwrap();
+        assert_eq!(loaded.options.min_y, -32);
+        assert_eq!(loaded.get_voxel(8, -20, 8), 1);
+        assert_eq!(loaded.get_height(8, 8), -20);
+        assert_eq!(loaded.status, ChunkStatus::Meshing);
+
+        // Chunks saved with another min y are generated again instead of loaded.
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(48)
+            .min_y(-16)
+            .sub_chunks(2)
+            .saving(true)
+            .save_dir(save_dir.to_str().unwrap())
+            .build();
+        assert!(Chunks::new(&config).try_load(&Vec2(0, 0)).is_none());
+
+        let _ = std::fs::remove_dir_all(&save_dir);
+    }
+}
@@ -1,6 +1,65 @@
 #[cfg(test)]
 mod tests {
-    use voxelize::{NoiseOptions, TerrainLayer};
+    use hashbrown::{HashMap, HashSet};
+    use voxelize::{
+        BiomeKind, BiomeMap, Block, CaveStage, Chunk, ChunkOptions, ChunkStage, ChunkUtils,
+        FlatlandStage, NoiseOptions, OreStage, Registry, Resources, Space, StructureStage,
+        StructureTemplate, TerrainLayer, TerrainStage, Vec2, Vec3, VoxelAccess, WorldConfig,
+    };
+
+    fn generate(seed: u32) -> Vec<u32> {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Grass").id(2).build(),
+            Block::new("Water").id(3).is_fluid(true).build(),
+        ]);
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(64)
+            .sub_chunks(1)
+            .seed(seed)
+            .build();
+
+        let chunk = Chunk::new(
+            "test",
+            3,
+            -2,
+            &ChunkOptions {
+                size: 16,
+                max_height: 64,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+
+        let mut stage = TerrainStage::new(1, 2, 3);
+        stage.set_amplitude(16.0);
+        stage.set_frequency(0.05);
+        stage.set_sea_level(30);
+
+        let chunk = stage.process(
+            chunk,
+            Resources {
+                registry: &registry,
+                config: &config,
+            },
+            None,
+        );
+
+        let mut voxels = vec![];
+        for vx in chunk.min.0..chunk.max.0 {
+            for vz in chunk.min.2..chunk.max.2 {
+                for vy in 0..64 {
+                    voxels.push(chunk.get_voxel(vx, vy, vz));
+                }
+            }
+        }
+        voxels
+    }
 
     #[test]
     fn terrain_layer_remap() {
@@ -20,4 +79,449 @@ mod tests {
             -1.0 + 2.0 * (5.0 - 3.0) / (8.5 - 3.0)
         );
     }
+
+    #[test]
+    fn seeded_terrain() {
+        let first = generate(42);
+
+        assert_eq!(first, generate(42));
+        assert_ne!(first, generate(1337));
+        assert!(first.contains(&1));
+        assert!(first.contains(&2) || first.contains(&3));
+    }
+
+    #[test]
+    fn seeded_stages() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Grass").id(2).build(),
+            Block::new("Water").id(3).is_fluid(true).build(),
+            Block::new("Ore").id(4).build(),
+            Block::new("Log").id(5).build(),
+        ]);
+        registry.generate();
+
+        let options = ChunkOptions {
+            size: 16,
+            max_height: 64,
+            sub_chunks: 1,
+            min_y: 0,
+            air_id: 0,
+        };
+        let pillar = StructureTemplate::new("Pillar", &Vec3(1, 3, 1), &Vec3(0, 0, 0)).fill(
+            &Vec3(0, 0, 0),
+            &Vec3(0, 2, 0),
+            5,
+        );
+
+        // Every stage reads the seed from the world config passed through `Resources`.
+        let generate = |seed: u32| {
+            let config = WorldConfig::new().max_height(64).seed(seed).build();
+            let resources = Resources {
+                registry: &registry,
+                config: &config,
+            };
+
+            let mut terrain = TerrainStage::new(1, 2, 3);
+            terrain.set_amplitude(16.0);
+            terrain.set_sea_level(30);
+
+            let mut chunk = Chunk::new("test", -4, 7, &options);
+            chunk = terrain.process(chunk, resources.clone(), None);
+            chunk = CaveStage::default().process(chunk, resources.clone(), None);
+            chunk =
+                OreStage::new(1)
+                    .add_ore(4, 20.0, 1, 40, 6)
+                    .process(chunk, resources.clone(), None);
+            chunk = StructureStage::new()
+                .add_structure(pillar.clone(), 3.0)
+                .process(chunk, resources, None);
+
+            chunk.voxels.data
+        };
+
+        let first = generate(2024);
+
+        assert_eq!(first, generate(2024));
+        assert_ne!(first, generate(2025));
+    }
+
+    #[test]
+    fn continuous_caves() {
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let config = WorldConfig::new().max_height(64).seed(7).build();
+        let resources = Resources {
+            registry: &registry,
+            config: &config,
+        };
+
+        let flatland = FlatlandStage::new().add_soiling(1, 40);
+        let caves = CaveStage::new();
+
+        let carve = |cx: i32, size: usize| {
+            let chunk = Chunk::new(
+                "test",
+                cx,
+                0,
+                &ChunkOptions {
+                    size,
+                    max_height: 64,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            );
+            let mut chunk = flatland.process(chunk, resources.clone(), None);
+            chunk.calculate_max_height();
+            caves.process(chunk, resources.clone(), None)
+        };
+
+        let left = carve(0, 16);
+        let right = carve(1, 16);
+        let whole = carve(0, 32);
+
+        let mut carved = 0;
+
+        for vx in 0..32 {
+            for vz in 0..16 {
+                let half = if vx < 16 { &left } else { &right };
+
+                assert_ne!(whole.get_voxel(vx, 0, vz), 0);
+
+                for vy in 0..64 {
+                    assert_eq!(half.get_voxel(vx, vy, vz), whole.get_voxel(vx, vy, vz));
+
+                    if vy < 36 && whole.get_voxel(vx, vy, vz) == 0 {
+                        carved += 1;
+                    }
+                }
+
+                assert_eq!(whole.get_voxel(vx, 37, vz), 1);
+            }
+        }
+
+        assert!(carved > 0);
+    }
+
+    #[test]
+    fn ore_distribution() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Iron Ore").id(2).build(),
+        ]);
+        registry.generate();
+
+        let config = WorldConfig::new().max_height(64).build();
+        let resources = Resources {
+            registry: &registry,
+            config: &config,
+        };
+
+        let generate = || {
+            let chunk = Chunk::new(
+                "test",
+                0,
+                0,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 64,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            );
+            let chunk =
+                FlatlandStage::new()
+                    .add_soiling(1, 30)
+                    .process(chunk, resources.clone(), None);
+            OreStage::new(1)
+                .add_ore(2, 40.0, 5, 20, 8)
+                .process(chunk, resources.clone(), None)
+        };
+
+        let chunk = generate();
+        let again = generate();
+
+        let mut ores = 0;
+
+        for vx in 0..16 {
+            for vz in 0..16 {
+                for vy in 0..64 {
+                    let id = chunk.get_voxel(vx, vy, vz);
+                    assert_eq!(id, again.get_voxel(vx, vy, vz));
+
+                    if id == 2 {
+                        assert!((5..=20).contains(&vy));
+                        ores += 1;
+                    } else if vy < 30 {
+                        assert_eq!(id, 1);
+                    } else {
+                        assert_eq!(id, 0);
+                    }
+                }
+            }
+        }
+
+        assert!(ores > 0);
+    }
+
+    fn structure_world(
+        stage: &StructureStage,
+        order: &[Vec2<i32>],
+        registry: &Registry,
+    ) -> HashMap<Vec2<i32>, Chunk> {
+        let config = WorldConfig::new().chunk_size(4).max_height(16).build();
+        let resources = Resources {
+            registry,
+            config: &config,
+        };
+        let options = ChunkOptions {
+            size: 4,
+            max_height: 16,
+            sub_chunks: 1,
+            min_y: 0,
+            air_id: 0,
+        };
+
+        let mut chunks = HashMap::new();
+        let mut extra_changes = vec![];
+
+        for &Vec2(cx, cz) in order {
+            let mut chunk = FlatlandStage::new().add_soiling(1, 1).process(
+                Chunk::new("test", cx, cz, &options),
+                resources.clone(),
+                None,
+            );
+            chunk.calculate_max_height();
+
+            let mut chunk = stage.process(chunk, resources.clone(), None);
+            extra_changes.append(&mut chunk.extra_changes);
+            chunks.insert(chunk.coords.to_owned(), chunk);
+        }
+
+        // Apply the voxels deferred to chunks that already had their structures placed.
+        for (Vec3(vx, vy, vz), id) in extra_changes {
+            let coords = ChunkUtils::map_voxel_to_chunk(vx, vy, vz, 4);
+
+            if let Some(chunk) = chunks.get_mut(&coords) {
+                chunk.set_voxel(vx, vy, vz, id);
+            }
+        }
+
+        chunks
+    }
+
+    fn structure_registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Grass").id(1).build(),
+            Block::new("Log").id(2).build(),
+            Block::new("Leaves").id(3).build(),
+        ]);
+        registry.generate();
+        registry
+    }
+
+    fn around(cx: i32, cz: i32) -> Vec<Vec2<i32>> {
+        let mut coords = vec![Vec2(cx, cz)];
+
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                if dx != 0 || dz != 0 {
+                    coords.push(Vec2(cx + dx, cz + dz));
+                }
+            }
+        }
+
+        coords
+    }
+
+    /// Every chunk from (min, min) to (max, max), row by row.
+    fn grid(min: i32, max: i32) -> Vec<Vec2<i32>> {
+        (min..=max)
+            .flat_map(|cx| (min..=max).map(move |cz| Vec2(cx, cz)))
+            .collect()
+    }
+
+    fn voxel_at(chunks: &HashMap<Vec2<i32>, Chunk>, vx: i32, vy: i32, vz: i32) -> u32 {
+        chunks[&ChunkUtils::map_voxel_to_chunk(vx, vy, vz, 4)].get_voxel(vx, vy, vz)
+    }
+
+    #[test]
+    fn structure_overhang() {
+        let registry = structure_registry();
+
+        let tree = StructureTemplate::new("Tree", &Vec3(3, 4, 3), &Vec3(1, 0, 1))
+            .fill(&Vec3(1, 0, 1), &Vec3(1, 2, 1), 2)
+            .fill(&Vec3(0, 3, 0), &Vec3(2, 3, 2), 3);
+        let stage = StructureStage::new().add_structure(tree, 4.0);
+
+        let chunks = structure_world(&stage, &around(0, 0), &registry);
+
+        let mut spilled = 0;
+
+        // Every trunk in the center chunk has its whole canopy, even where it reaches into the
+        // neighbors generated after it.
+        for vx in 0..4 {
+            for vz in 0..4 {
+                if voxel_at(&chunks, vx, 1, vz) != 2 {
+                    continue;
+                }
+
+                for x in vx - 1..=vx + 1 {
+                    for z in vz - 1..=vz + 1 {
+                        assert_eq!(voxel_at(&chunks, x, 4, z), 3);
+
+                        if !chunks[&Vec2(0, 0)].contains(x, 4, z) {
+                            spilled += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        assert!(spilled > 0);
+    }
+
+    #[test]
+    fn structure_priority() {
+        let registry = structure_registry();
+
+        let post =
+            || StructureTemplate::new("Post", &Vec3(1, 1, 1), &Vec3(0, 0, 0)).voxel(0, 0, 0, 2);
+        let slab = StructureTemplate::new("Slab", &Vec3(5, 1, 5), &Vec3(2, 0, 2)).fill(
+            &Vec3(0, 0, 0),
+            &Vec3(4, 0, 4),
+            3,
+        );
+
+        // The posts land where they would without the slabs, as they're attempted first.
+        let posts = structure_world(
+            &StructureStage::new().add_structure(post().priority(1), 2.0),
+            &grid(-2, 3),
+            &registry,
+        );
+
+        // The grid is wide enough for the chunks in its middle to be forgotten by the stage.
+        let mut order = grid(-2, 3);
+        let forward = structure_world(
+            &StructureStage::new()
+                .add_structure(post().priority(1), 2.0)
+                .add_structure(slab.clone(), 2.0),
+            &order,
+            &registry,
+        );
+
+        order.reverse();
+        let backward = structure_world(
+            &StructureStage::new()
+                .add_structure(post().priority(1), 2.0)
+                .add_structure(slab, 2.0),
+            &order,
+            &registry,
+        );
+
+        let mut slabs = 0;
+
+        for vx in -8..16 {
+            for vz in -8..16 {
+                let id = voxel_at(&forward, vx, 1, vz);
+                assert_eq!(id, voxel_at(&backward, vx, 1, vz));
+
+                // Slabs from any chunk never cover a post, whichever chunk was generated first.
+                if voxel_at(&posts, vx, 1, vz) == 2 {
+                    assert_eq!(id, 2);
+                } else if id == 3 {
+                    slabs += 1;
+                }
+            }
+        }
+
+        assert!(slabs > 0);
+    }
+
+    #[test]
+    fn biome_map() {
+        let biomes = BiomeMap::new(42);
+        let same = BiomeMap::new(42);
+
+        let mut kinds = HashSet::new();
+
+        for vx in (-4000..4000).step_by(100) {
+            for vz in (-4000..4000).step_by(100) {
+                let kind = biomes.biome_at(vx, vz);
+                assert_eq!(kind, biomes.biome_at(vx, vz));
+                assert_eq!(kind, same.biome_at(vx, vz));
+                kinds.insert(kind);
+            }
+        }
+
+        assert!(kinds.len() > 1);
+        assert!(kinds.contains(&BiomeKind::Plains));
+    }
+
+    struct MarbleFloorStage;
+
+    impl ChunkStage for MarbleFloorStage {
+        fn name(&self) -> String {
+            "Marble Floor".to_owned()
+        }
+
+        fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
+            let marble = resources.registry.get_id_by_name("marble");
+
+            for vx in chunk.min.0..chunk.max.0 {
+                for vz in chunk.min.2..chunk.max.2 {
+                    chunk.set_voxel(vx, 0, vz, marble);
+                }
+            }
+
+            chunk
+        }
+    }
+
+    #[test]
+    fn registry_stage() {
+        let mut registry = Registry::new();
+        registry.register_blocks(&[Block::new("Stone").build(), Block::new("Marble").build()]);
+        registry.generate();
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(16)
+            .sub_chunks(1)
+            .build();
+
+        let chunk = Chunk::new(
+            "test",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 16,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+
+        let chunk = MarbleFloorStage.process(
+            chunk,
+            Resources {
+                registry: &registry,
+                config: &config,
+            },
+            None,
+        );
+
+        let marble = registry.get_block_by_name("Marble").id;
+        assert_ne!(marble, 0);
+        assert_eq!(chunk.get_voxel(5, 0, 7), marble);
+        assert_eq!(chunk.get_voxel(5, 1, 7), 0);
+    }
 }
@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod tests {
+    use voxelize::{Vec3, VecLengthError};
+
+    #[test]
+    fn reflect_and_project() {
+        let velocity = Vec3(3.0, -2.0, 1.5);
+
+        // Bouncing off of a wall facing +x only flips the x component.
+        assert_eq!(
+            velocity.reflect(&Vec3(1.0, 0.0, 0.0)),
+            Vec3(-3.0, -2.0, 1.5)
+        );
+        assert_eq!(velocity.reflect(&Vec3(0.0, 1.0, 0.0)), Vec3(3.0, 2.0, 1.5));
+
+        assert_eq!(
+            velocity.project_onto(&Vec3(0.0, 0.0, 4.0)),
+            Vec3(0.0, 0.0, 1.5)
+        );
+        assert_eq!(
+            velocity.project_onto(&Vec3(0.0, 0.0, 0.0)),
+            Vec3(0.0, 0.0, 0.0)
+        );
+        assert_eq!(velocity.dot(&Vec3(1.0, 1.0, 2.0)), 4.0);
+    }
+
+    #[test]
+    fn approx_eq_and_finite() {
+        let a = Vec3(1.0, 2.0, 3.0);
+        assert!(a.approx_eq(&Vec3(1.0005, 1.9995, 3.0), 1e-3));
+        assert!(!a.approx_eq(&Vec3(1.01, 2.0, 3.0), 1e-3));
+
+        assert!(a.is_finite());
+        assert!(!Vec3(0.0, f32::NAN, 0.0).is_finite());
+        assert!(!Vec3(f32::INFINITY, 0.0, 0.0).is_finite());
+    }
+
+    #[test]
+    fn iteration_and_conversion() {
+        let components = Vec3(1, 2, 3).into_iter().collect::<Vec<_>>();
+        assert_eq!(components, vec![1, 2, 3]);
+        assert_eq!(Vec3(1, 2, 3).to_arr(), [1, 2, 3]);
+
+        assert_eq!(Vec3::try_from(&components[..]), Ok(Vec3(1, 2, 3)));
+        assert_eq!(Vec3::try_from(&components[..2]), Err(VecLengthError(2)));
+        assert_eq!(Vec3::<i32>::try_from(&[][..]), Err(VecLengthError(0)));
+    }
+}
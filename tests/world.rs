@@ -0,0 +1,2505 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::Write,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use hashbrown::{HashMap, HashSet};
+    use libflate::zlib::Encoder;
+    use specs::{Builder, DispatcherBuilder, Join, ReadExpect, RunNow, System, WorldExt};
+    use voxelize::{
+        Block, BlockRotation, BlockUtils, ChatMessageProtocol, Chunk, ChunkEvictingSystem,
+        ChunkGeneratingSystem, ChunkInterests, ChunkOptions, ChunkRequestsComp,
+        ChunkRequestsSystem, ChunkSendingSystem, ChunkStage, ChunkStatus, ChunkStreamingSystem,
+        ChunkUpdatingSystem, ClientFilter, ClientFlag, CurrentChunkComp, DebugStage, DirectionComp,
+        EntitiesMetaSystem, EntitiesSendingSystem, EntityOperation, GeometryProtocol, IDComp,
+        LightColor, MeshProtocol, Mesher, Message, MessageQueue, MessageType, MethodProtocol,
+        Pipeline, PositionComp, Registry, Resources, Schematic, SchematicError, Space, Stats, Vec2,
+        Vec3, VoxelAccess, World, WorldConfig, WorldInitData, MAX_GENERATION_ATTEMPTS,
+    };
+
+    fn chat(body: &str) -> Message {
+        Message::new(&MessageType::Chat)
+            .chat(ChatMessageProtocol {
+                r#type: "chat".to_owned(),
+                sender: "alice".to_owned(),
+                body: body.to_owned(),
+            })
+            .build()
+    }
+
+    /// A world with the chunks around the origin already generated and meshed, so that edits only
+    /// remesh them instead of propagating lights again.
+    fn ready_world(config: &WorldConfig, mut registry: Registry) -> World {
+        let mut world = World::new("world", config);
+
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        for cx in -1..=1 {
+            for cz in -1..=1 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: config.chunk_size,
+                        max_height: config.max_height,
+                        sub_chunks: config.sub_chunks,
+                        min_y: config.min_y,
+                        air_id: config.air_id,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.meshes = Some(HashMap::new());
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        world
+    }
+
+    /// Wait for the chunks being meshed without draining the mesher, which would stop it meshing.
+    fn finish_meshing(world: &mut World) {
+        while !world.read_resource::<Mesher>().is_empty() {
+            world.write_resource::<Mesher>().results();
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn queued_chats(world: &World) -> Vec<String> {
+        world
+            .read_resource::<MessageQueue>()
+            .iter()
+            .filter(|(message, filter)| {
+                message.r#type == MessageType::Chat as i32 && matches!(filter, ClientFilter::All)
+            })
+            .map(|(message, _)| message.chat.as_ref().unwrap().body.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn chat_routing() {
+        let config = WorldConfig::new().build();
+        let mut world = World::new("world", &config);
+
+        world.set_command_handle(|world, _, command| {
+            let reply = chat(&format!("ran {command}"));
+            world.broadcast(reply, ClientFilter::All);
+        });
+
+        world.on_request("alice", chat("hello"));
+        world.on_request("alice", chat("/time 0"));
+
+        assert_eq!(queued_chats(&world), vec!["hello", "ran time 0"]);
+    }
+
+    #[test]
+    fn delta_updates() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        let mut world = ready_world(&config, registry);
+
+        // A voxel on the positive x border of chunk (0, 0).
+        world.chunks_mut().update_voxel(&Vec3(15, 4, 8), 1);
+        ChunkUpdatingSystem.run_now(world.ecs());
+
+        let queue = world.read_resource::<MessageQueue>();
+        let updates = queue
+            .iter()
+            .filter(|(message, _)| message.r#type == MessageType::Update as i32)
+            .flat_map(|(message, _)| message.updates.iter())
+            .collect::<Vec<_>>();
+
+        assert_eq!(updates.len(), 1);
+        assert_eq!((updates[0].vx, updates[0].vy, updates[0].vz), (15, 4, 8));
+        assert_eq!(updates[0].voxel, 1);
+        drop(queue);
+
+        let mut remeshed = HashSet::new();
+        let start = Instant::now();
+
+        while start.elapsed() < Duration::from_millis(500) {
+            remeshed.extend(
+                world
+                    .write_resource::<Mesher>()
+                    .results()
+                    .into_iter()
+                    .map(|(chunk, _)| chunk.coords),
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(remeshed, HashSet::from_iter([Vec2(0, 0), Vec2(1, 0)]));
+    }
+
+    #[test]
+    fn broadcast_filters() {
+        let config = WorldConfig::new().build();
+        let mut world = World::new("world", &config);
+        let ids = ["alice", "bob", "carol"];
+
+        world.broadcast(chat("everyone"), ClientFilter::All);
+        world.broadcast_except("alice", chat("not alice"));
+
+        let queue = world.read_resource::<MessageQueue>();
+        let recipients = queue
+            .iter()
+            .map(|(_, filter)| {
+                ids.iter()
+                    .filter(|id| filter.includes(id))
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(recipients[0], ids);
+        assert_eq!(recipients[1], ["bob", "carol"]);
+    }
+
+    #[test]
+    fn graceful_shutdown() {
+        let save_dir = std::env::temp_dir().join("voxelize-shutdown-test");
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .saving(true)
+            .save_dir(save_dir.to_str().unwrap())
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry.clone());
+
+        let coords = [Vec2(0, 0), Vec2(1, 0)];
+
+        for &Vec2(cx, cz) in coords.iter() {
+            let mut chunk = Chunk::new(
+                &format!("{cx}:{cz}"),
+                cx,
+                cz,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 32,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            );
+            chunk.status = ChunkStatus::Ready;
+            chunk.meshes = Some(HashMap::new());
+            world.chunks_mut().add(chunk);
+            world.chunks_mut().add_chunk_to_save(&Vec2(cx, cz), false);
+        }
+
+        // One chunk waiting to be generated, and one being meshed.
+        world.pipeline_mut().add_chunk(&Vec2(5, 5), false);
+
+        let process = {
+            let chunks = world.chunks();
+            let space = chunks
+                .make_space(&coords[0], 15)
+                .needs_height_maps()
+                .needs_voxels()
+                .needs_lights()
+                .build();
+            (chunks.raw(&coords[0]).unwrap().to_owned(), space)
+        };
+        world.mesher_mut().process(
+            vec![process.clone()],
+            &MessageType::Update,
+            &registry,
+            &config,
+        );
+
+        world.shutdown();
+
+        // Jobs handed over after the drain are dropped instead of started.
+        world.mesher_mut().process(
+            vec![process.clone()],
+            &MessageType::Update,
+            &registry,
+            &config,
+        );
+        let (chunk, _) = process;
+        world
+            .pipeline_mut()
+            .process(vec![(chunk, None)], &registry, &config);
+
+        assert!(world.pipeline().is_empty());
+        assert!(world.mesher().is_empty());
+        assert!(!world.chunks().has_pending_saves());
+
+        for coords in coords.iter() {
+            assert!(world.chunks().test_load(coords));
+        }
+
+        fs::remove_dir_all(&save_dir).unwrap();
+    }
+
+    #[test]
+    fn scheduled_block_updates() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut chunk = Chunk::new(
+            "0:0",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Ready;
+        world.chunks_mut().add(chunk);
+
+        let fired = Arc::new(Mutex::new(vec![]));
+        let recorder = fired.clone();
+        world.set_block_update_handle(move |world, voxel| {
+            recorder.lock().unwrap().push((world.stats().tick, voxel));
+        });
+
+        world.schedule_block_update(&Vec3(3, 4, 5), 2);
+        // Updates in chunks that aren't loaded are discarded.
+        world.schedule_block_update(&Vec3(100, 4, 5), 2);
+
+        for _ in 0..4 {
+            world.process_block_updates();
+            world.stats_mut().tick += 1;
+        }
+
+        assert_eq!(*fired.lock().unwrap(), vec![(2, Vec3(3, 4, 5))]);
+    }
+
+    struct DeltaRecorder(Arc<Mutex<Vec<f32>>>);
+
+    impl<'a> System<'a> for DeltaRecorder {
+        type SystemData = ReadExpect<'a, Stats>;
+
+        fn run(&mut self, stats: Self::SystemData) {
+            self.0.lock().unwrap().push(stats.delta);
+        }
+    }
+
+    #[test]
+    fn fixed_updates() {
+        let deltas = Arc::new(Mutex::new(vec![]));
+        let builds = Arc::new(AtomicUsize::new(0));
+        let fixed_world = || {
+            let config = WorldConfig::new().fixed_timestep(10).build();
+            let mut world = World::new("world", &config);
+
+            let recorder = deltas.clone();
+            let builds = builds.clone();
+            world.set_fixed_dispatcher(move || {
+                builds.fetch_add(1, Ordering::SeqCst);
+                DispatcherBuilder::new().with(
+                    DeltaRecorder(recorder.clone()),
+                    "delta-recorder",
+                    &[],
+                )
+            });
+
+            world.stats_mut().delta = 0.5;
+            world
+        };
+
+        // Irregular ticks add up to the same number of fixed updates as steady ones.
+        let mut irregular = fixed_world();
+        let steps = [0.003, 0.025, 0.0, 0.012, 0.031, 0.004, 0.03]
+            .iter()
+            .map(|&elapsed| irregular.advance_fixed(elapsed))
+            .sum::<u32>();
+        assert_eq!(steps, 10);
+        assert_eq!(irregular.fixed_ticks, 10);
+
+        let mut steady = fixed_world();
+        let steps = (0..21).map(|_| steady.advance_fixed(0.005)).sum::<u32>();
+        assert_eq!(steps, 10);
+
+        // Fixed systems always see the fixed timestep, and the regular delta is left alone.
+        {
+            let deltas = deltas.lock().unwrap();
+            assert_eq!(deltas.len(), 20);
+            assert!(deltas.iter().all(|&delta| (delta - 0.01).abs() < 1e-6));
+        }
+        assert_eq!(steady.stats().delta, 0.5);
+
+        // A long stall only catches up a limited number of steps.
+        assert_eq!(steady.advance_fixed(10.0), 8);
+        assert_eq!(steady.advance_fixed(0.0), 0);
+
+        // Each world builds its fixed dispatcher once, and again when its systems are set.
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+        steady.set_fixed_dispatcher(DispatcherBuilder::new);
+        steady.advance_fixed(0.01);
+        assert_eq!(deltas.lock().unwrap().len(), 28);
+    }
+
+    #[test]
+    fn random_ticks() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .random_tick_speed(3)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Grass").id(2).random_tick(true).build(),
+        ]);
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        let mut chunk = Chunk::new(
+            "0:0",
+            0,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Ready;
+
+        // The bottom half is grass, the top quarter is stone, and the rest is air.
+        for vx in 0..16 {
+            for vz in 0..16 {
+                for vy in 0..24 {
+                    chunk.set_voxel(vx, vy, vz, if vy < 16 { 2 } else { 1 });
+                }
+            }
+        }
+        world.chunks_mut().add(chunk);
+
+        let ticked = Arc::new(Mutex::new(vec![]));
+        let recorder = ticked.clone();
+        world.set_random_tick_handle(move |world, voxel| {
+            let id = world.chunks().get_voxel(voxel.0, voxel.1, voxel.2);
+            recorder.lock().unwrap().push(id);
+        });
+
+        let ticks = 1000;
+        for _ in 0..ticks {
+            world.process_random_ticks();
+        }
+
+        let ticked = ticked.lock().unwrap();
+        assert!(ticked.iter().all(|&id| id == 2));
+
+        // Half of all the 3 voxels picked per tick should be grass.
+        let expected = ticks as f32 * 3.0 * 0.5;
+        assert!((ticked.len() as f32 - expected).abs() < expected * 0.1);
+    }
+
+    #[test]
+    fn block_set_hook() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        let mut world = ready_world(&config, registry);
+
+        let changes = Arc::new(Mutex::new(vec![]));
+        let recorder = changes.clone();
+        world.set_block_set_handle(move |_, voxel, old_id, new_id| {
+            recorder.lock().unwrap().push((voxel, old_id, new_id));
+        });
+
+        world.chunks_mut().update_voxel(&Vec3(3, 4, 5), 1);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        world.process_block_changes();
+
+        // The chunk is being remeshed, so the second update waits until the mesher is done.
+        world.chunks_mut().update_voxel(&Vec3(3, 4, 5), 0);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        world.process_block_changes();
+        assert_eq!(*changes.lock().unwrap(), vec![(Vec3(3, 4, 5), 0, 1)]);
+
+        world.write_resource::<Mesher>().drain();
+        ChunkUpdatingSystem.run_now(world.ecs());
+        world.process_block_changes();
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![(Vec3(3, 4, 5), 0, 1), (Vec3(3, 4, 5), 1, 0)]
+        );
+    }
+
+    #[test]
+    fn set_block_id_updates() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        let mut world = ready_world(&config, registry);
+
+        let changes = Arc::new(Mutex::new(vec![]));
+        let recorder = changes.clone();
+        world.set_block_set_handle(move |_, voxel, old_id, new_id| {
+            recorder.lock().unwrap().push((voxel, old_id, new_id));
+        });
+
+        world.chunks_mut().set_block_id(3, 4, 5, 1).unwrap();
+        ChunkUpdatingSystem.run_now(world.ecs());
+        world.process_block_changes();
+
+        // The block goes through the same path as `update_voxel`.
+        assert_eq!(world.chunks().get_block_id(3, 4, 5), Some(1));
+        assert_eq!(world.chunks().get_max_height(3, 5), 4);
+        assert_eq!(*changes.lock().unwrap(), vec![(Vec3(3, 4, 5), 0, 1)]);
+
+        let queue = world.read_resource::<MessageQueue>();
+        assert!(queue
+            .iter()
+            .filter(|(message, _)| message.r#type == MessageType::Update as i32)
+            .flat_map(|(message, _)| message.updates.iter())
+            .any(|update| (update.vx, update.vy, update.vz, update.voxel) == (3, 4, 5, 1)));
+    }
+
+    #[test]
+    fn update_stage_clamped() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Wheat").id(1).max_stage(7).build());
+        let mut world = ready_world(&config, registry);
+
+        // A stage past the wheat's last is clamped to it, while one within is kept as is.
+        world
+            .chunks_mut()
+            .update_voxel(&Vec3(3, 4, 5), BlockUtils::insert_stage(1, 12));
+        world
+            .chunks_mut()
+            .update_voxel(&Vec3(6, 4, 5), BlockUtils::insert_stage(1, 3));
+        ChunkUpdatingSystem.run_now(world.ecs());
+
+        assert_eq!(world.chunks().get_voxel(3, 4, 5), 1);
+        assert_eq!(world.chunks().get_voxel_stage(3, 4, 5), 7);
+        assert_eq!(world.chunks().get_voxel_stage(6, 4, 5), 3);
+    }
+
+    #[test]
+    fn lod_meshes_rebuilt_on_update() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(2)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        // A mesh left over from before, which no edit would produce.
+        let stale = MeshProtocol {
+            level: 0,
+            geometries: vec![GeometryProtocol {
+                voxel: 99,
+                ..Default::default()
+            }],
+            quantized: false,
+        };
+
+        for cx in -1..=1 {
+            for cz in -1..=1 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 2,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.lod = 1;
+                chunk.meshes = Some(HashMap::from([(0, stale.clone()), (1, stale.clone())]));
+                chunk.updated_levels.clear();
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        // An edit low in the first level, far from the second.
+        world.chunks_mut().update_voxel(&Vec3(3, 4, 5), 1);
+        ChunkUpdatingSystem.run_now(world.ecs());
+
+        let start = Instant::now();
+        let chunk = loop {
+            let results = world.write_resource::<Mesher>().results();
+            if let Some((chunk, _)) = results
+                .into_iter()
+                .find(|(chunk, _)| chunk.coords == Vec2(0, 0))
+            {
+                break chunk;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        };
+        world.chunks_mut().renew(chunk, true);
+
+        // Both levels are rebuilt at the chunk's level of detail, and both are sent.
+        let chunks = world.chunks();
+        let chunk = chunks.raw(&Vec2(0, 0)).unwrap();
+        for level in 0..2 {
+            let mesh = &chunk.meshes.as_ref().unwrap()[&level];
+            assert!(mesh.geometries.iter().all(|geometry| geometry.voxel != 99));
+        }
+        assert_eq!(chunk.updated_levels, HashSet::from([0, 1]));
+    }
+
+    #[test]
+    fn command_registry() {
+        let config = WorldConfig::new().build();
+        let mut world = World::new("world", &config);
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let recorder = calls.clone();
+        world
+            .commands_mut()
+            .register("tp", move |_, sender, args: &[String]| {
+                recorder
+                    .lock()
+                    .unwrap()
+                    .push((sender.to_owned(), args.to_vec()));
+            });
+
+        world.on_request("alice", chat("/TP 0  64 -12"));
+        world.on_request("alice", chat("/fly"));
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![(
+                "alice".to_owned(),
+                vec!["0".to_owned(), "64".to_owned(), "-12".to_owned()]
+            )]
+        );
+
+        let queue = world.read_resource::<MessageQueue>();
+        let replies = queue
+            .iter()
+            .filter(|(message, _)| message.r#type == MessageType::Chat as i32)
+            .collect::<Vec<_>>();
+
+        assert_eq!(replies.len(), 1);
+        assert!(matches!(&replies[0].1, ClientFilter::Direct(id) if id == "alice"));
+
+        let reply = replies[0].0.chat.as_ref().unwrap();
+        assert_eq!(reply.r#type, "error");
+        assert_eq!(reply.body, "Unknown command: /fly");
+    }
+
+    #[test]
+    fn limited_results() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut pipeline = Pipeline::new();
+        pipeline.add_stage(DebugStage::new(1));
+
+        let processes = (0..100)
+            .map(|index| {
+                let mut chunk = Chunk::new(
+                    &format!("{index}"),
+                    index,
+                    0,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Generating(0);
+                (chunk, None)
+            })
+            .collect();
+        pipeline.process(processes, &registry, &config);
+
+        let start = Instant::now();
+        while pipeline.done_count() < 100 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let results = pipeline.results_limited(10);
+        assert_eq!(results.len(), 10);
+        assert_eq!(pipeline.done_count(), 90);
+
+        // Only the retrieved chunks leave the pipeline.
+        for (chunk, _) in results.iter() {
+            assert!(!pipeline.has_chunk(&chunk.coords));
+        }
+        assert_eq!(
+            (0..100)
+                .filter(|&index| pipeline.has_chunk(&Vec2(index, 0)))
+                .count(),
+            90
+        );
+
+        assert_eq!(pipeline.results().len(), 90);
+        assert!(pipeline.is_empty());
+    }
+
+    #[test]
+    fn cancelled_chunks() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut pipeline = Pipeline::new();
+        pipeline.add_stage(DebugStage::new(1));
+
+        // A queued chunk is dropped before being picked up.
+        pipeline.add_chunk(&Vec2(0, 0), false);
+        assert!(pipeline.cancel_chunk(&Vec2(0, 0)));
+        assert!(!pipeline.cancel_chunk(&Vec2(0, 0)));
+        assert!(pipeline.get().is_none());
+
+        // A chunk being processed has its result discarded.
+        let mut chunk = Chunk::new(
+            "1:0",
+            1,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Generating(0);
+        pipeline.process(vec![(chunk, None)], &registry, &config);
+        assert!(pipeline.cancel_chunk(&Vec2(1, 0)));
+
+        let start = Instant::now();
+        while pipeline.done_count() < 1 && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(pipeline.results().is_empty());
+        assert!(!pipeline.cancel_chunk(&Vec2(5, 5)));
+    }
+
+    struct FaultyStage;
+
+    impl ChunkStage for FaultyStage {
+        fn name(&self) -> String {
+            "Faulty".to_owned()
+        }
+
+        fn process(&self, chunk: Chunk, _: Resources, _: Option<Space>) -> Chunk {
+            if chunk.coords == Vec2(2, 0) {
+                panic!("bad chunk");
+            }
+
+            chunk
+        }
+    }
+
+    #[test]
+    fn failed_chunks() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut pipeline = Pipeline::new();
+        pipeline.add_stage(FaultyStage);
+
+        let processes = (0..5)
+            .map(|index| {
+                let mut chunk = Chunk::new(
+                    &format!("{index}"),
+                    index,
+                    0,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Generating(0);
+                (chunk, None)
+            })
+            .collect();
+        pipeline.process(processes, &registry, &config);
+
+        let mut done = vec![];
+        let mut failed = vec![];
+        let start = Instant::now();
+
+        while done.len() + failed.len() < 5 && start.elapsed() < Duration::from_secs(5) {
+            done.extend(
+                pipeline
+                    .results()
+                    .into_iter()
+                    .map(|(chunk, _)| chunk.coords),
+            );
+            failed.extend(pipeline.failed_chunks());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(done.len(), 4);
+        assert!(!done.contains(&Vec2(2, 0)));
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, Vec2(2, 0));
+        assert!(failed[0].1.contains("Faulty"));
+        assert!(failed[0].1.contains("bad chunk"));
+
+        // The failed chunk can be added back to retry.
+        assert!(!pipeline.has_chunk(&Vec2(2, 0)));
+        pipeline.add_chunk(&Vec2(2, 0), false);
+        assert_eq!(pipeline.get(), Some(Vec2(2, 0)));
+    }
+
+    /// A stage that panics the first time it processes the chunk at the origin.
+    struct FlakyStage(AtomicBool);
+
+    impl ChunkStage for FlakyStage {
+        fn name(&self) -> String {
+            "Flaky".to_owned()
+        }
+
+        fn process(&self, chunk: Chunk, _: Resources, _: Option<Space>) -> Chunk {
+            if chunk.coords == Vec2(0, 0) && !self.0.swap(true, Ordering::SeqCst) {
+                panic!("flaky chunk");
+            }
+
+            chunk
+        }
+    }
+
+    #[test]
+    fn failed_chunks_regenerate() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+        world
+            .pipeline_mut()
+            .add_stage(FlakyStage(AtomicBool::new(false)));
+
+        let mut requests = ChunkRequestsComp::default();
+        requests.add(&Vec2(0, 0));
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(requests)
+            .build();
+
+        ChunkRequestsSystem.run_now(world.ecs());
+
+        let start = Instant::now();
+
+        while !world.chunks().is_chunk_ready(&Vec2(0, 0))
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            ChunkGeneratingSystem.run_now(world.ecs());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The chunk is generated again from its first stage after the stage panicked on it.
+        assert!(world.chunks().is_chunk_ready(&Vec2(0, 0)));
+        assert!(!world.pipeline().has_chunk(&Vec2(0, 0)));
+    }
+
+    /// A stage that always panics on the chunk at the origin, counting how many times it tried.
+    struct BrokenStage(Arc<AtomicUsize>);
+
+    impl ChunkStage for BrokenStage {
+        fn name(&self) -> String {
+            "Broken".to_owned()
+        }
+
+        fn process(&self, chunk: Chunk, _: Resources, _: Option<Space>) -> Chunk {
+            if chunk.coords == Vec2(0, 0) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                panic!("broken chunk");
+            }
+
+            chunk
+        }
+    }
+
+    #[test]
+    fn failed_chunks_give_up() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        world
+            .pipeline_mut()
+            .add_stage(BrokenStage(attempts.clone()));
+
+        let mut requests = ChunkRequestsComp::default();
+        requests.add(&Vec2(0, 0));
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(requests)
+            .build();
+
+        ChunkRequestsSystem.run_now(world.ecs());
+
+        let start = Instant::now();
+
+        while !world.chunks().is_chunk_ready(&Vec2(0, 0))
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            ChunkGeneratingSystem.run_now(world.ecs());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The chunk is given up on after a few attempts, and left empty instead of being stuck.
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_GENERATION_ATTEMPTS);
+        assert!(world.chunks().is_chunk_ready(&Vec2(0, 0)));
+        assert!(!world.pipeline().has_chunk(&Vec2(0, 0)));
+        assert_eq!(world.chunks().get_voxel(0, 0, 0), 0);
+    }
+
+    struct ScatterStage;
+
+    impl ChunkStage for ScatterStage {
+        fn name(&self) -> String {
+            "Scatter".to_owned()
+        }
+
+        fn process(&self, mut chunk: Chunk, resources: Resources, _: Option<Space>) -> Chunk {
+            let mut rng = resources.rng(&chunk.coords, &self.name());
+
+            for _ in 0..32 {
+                let vx = rng.i32(chunk.min.0..chunk.max.0);
+                let vy = rng.i32(0..32);
+                let vz = rng.i32(chunk.min.2..chunk.max.2);
+                chunk.set_voxel(vx, vy, vz, 1);
+            }
+
+            chunk
+        }
+    }
+
+    #[test]
+    fn seeded_stage_rng() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .seed(42)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let generate = |coords: &[Vec2<i32>]| {
+            let mut pipeline = Pipeline::new();
+            pipeline.add_stage(ScatterStage);
+
+            let processes = coords
+                .iter()
+                .map(|&Vec2(cx, cz)| {
+                    let mut chunk = Chunk::new(
+                        &format!("{cx}:{cz}"),
+                        cx,
+                        cz,
+                        &ChunkOptions {
+                            size: 16,
+                            max_height: 32,
+                            sub_chunks: 1,
+                            min_y: 0,
+                            air_id: 0,
+                        },
+                    );
+                    chunk.status = ChunkStatus::Generating(0);
+                    (chunk, None)
+                })
+                .collect();
+            pipeline.process(processes, &registry, &config);
+
+            let mut voxels = HashMap::new();
+            let start = Instant::now();
+
+            while voxels.len() < coords.len() && start.elapsed() < Duration::from_secs(5) {
+                for (chunk, _) in pipeline.results() {
+                    let mut placed = vec![];
+                    for vx in chunk.min.0..chunk.max.0 {
+                        for vz in chunk.min.2..chunk.max.2 {
+                            for vy in 0..32 {
+                                placed.push(chunk.get_voxel(vx, vy, vz));
+                            }
+                        }
+                    }
+                    voxels.insert(chunk.coords.to_owned(), placed);
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            voxels
+        };
+
+        let coords = (-3..3)
+            .flat_map(|cx| (-3..3).map(move |cz| Vec2(cx, cz)))
+            .collect::<Vec<_>>();
+        let mut shuffled = coords.clone();
+        fastrand::Rng::with_seed(7).shuffle(&mut shuffled);
+
+        let ordered = generate(&coords);
+        let reordered = generate(&shuffled);
+        let single = generate(&[Vec2(1, -2)]);
+
+        assert_eq!(ordered.len(), coords.len());
+        assert_eq!(ordered, reordered);
+        assert_eq!(ordered[&Vec2(1, -2)], single[&Vec2(1, -2)]);
+        assert_ne!(ordered[&Vec2(0, 0)], ordered[&Vec2(0, 1)]);
+
+        // Different stages get different streams for the same chunk.
+        let resources = Resources {
+            registry: &registry,
+            config: &config,
+        };
+        assert_eq!(
+            resources.rng(&Vec2(0, 0), "Scatter").u64(..),
+            resources.rng(&Vec2(0, 0), "Scatter").u64(..)
+        );
+        assert_ne!(
+            resources.rng(&Vec2(0, 0), "Scatter").u64(..),
+            resources.rng(&Vec2(0, 0), "Ores").u64(..)
+        );
+    }
+
+    #[test]
+    fn chunk_status() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .min_chunk([-1, -1])
+            .max_chunk([1, 1])
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+        world.pipeline_mut().add_stage(DebugStage::new(1));
+
+        let mut requests = ChunkRequestsComp::default();
+        requests.add(&Vec2(0, 0));
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(requests)
+            .build();
+
+        ChunkRequestsSystem.run_now(world.ecs());
+
+        let mut received = vec![];
+        let start = Instant::now();
+
+        while !received.contains(&"load".to_owned()) && start.elapsed() < Duration::from_secs(5) {
+            ChunkGeneratingSystem.run_now(world.ecs());
+            ChunkSendingSystem.run_now(world.ecs());
+
+            for (message, filter) in world.write_resource::<MessageQueue>().drain(..) {
+                if !filter.includes("alice") {
+                    continue;
+                }
+
+                if message.r#type == MessageType::Status as i32 {
+                    let chunk = &message.chunks[0];
+                    assert_eq!((chunk.x, chunk.z), (0, 0));
+                    received.push(chunk.status.to_owned());
+                } else if message.r#type == MessageType::Load as i32 {
+                    received.push("load".to_owned());
+                }
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // The chunk's meshes and data are loaded in separate messages.
+        received.dedup();
+        assert_eq!(received, vec!["generating", "meshing", "ready", "load"]);
+    }
+
+    struct SlowFloorStage;
+
+    impl ChunkStage for SlowFloorStage {
+        fn name(&self) -> String {
+            "Slow Floor".to_owned()
+        }
+
+        fn process(&self, mut chunk: Chunk, _: Resources, _: Option<Space>) -> Chunk {
+            if chunk.coords == Vec2(1, 0) {
+                thread::sleep(Duration::from_millis(300));
+            }
+
+            for vx in chunk.min.0..chunk.max.0 {
+                for vz in chunk.min.2..chunk.max.2 {
+                    for vy in 0..2 {
+                        chunk.set_voxel(vx, vy, vz, 1);
+                    }
+                }
+            }
+
+            chunk
+        }
+    }
+
+    #[test]
+    fn neighbor_gated_meshing() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .min_chunk([0, 0])
+            .max_chunk([1, 0])
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+        world.pipeline_mut().add_stage(SlowFloorStage);
+
+        let mut requests = ChunkRequestsComp::default();
+        requests.add(&Vec2(0, 0));
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(requests)
+            .build();
+
+        ChunkRequestsSystem.run_now(world.ecs());
+
+        let start = Instant::now();
+
+        while !world.chunks().is_chunk_ready(&Vec2(0, 0))
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            ChunkGeneratingSystem.run_now(world.ecs());
+
+            // The chunk is never done meshing while its slow neighbor is still generating.
+            let chunks = world.chunks();
+            if !chunks.raw(&Vec2(1, 0)).unwrap().status.is_generated() {
+                assert!(chunks.raw(&Vec2(0, 0)).unwrap().meshes.is_none());
+            }
+            drop(chunks);
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let chunks = world.chunks();
+        let chunk = chunks.raw(&Vec2(0, 0)).unwrap();
+        assert_eq!(chunk.status, ChunkStatus::Ready);
+
+        // Nothing faces the generated neighbor, but the other three sides face out of the world.
+        let quads = chunk.meshes.as_ref().unwrap()[&0]
+            .geometries
+            .iter()
+            .map(|geometry| geometry.indices.len() / 6)
+            .sum::<usize>();
+        assert_eq!(quads, 16 * 16 * 2 + 16 * 2 * 3);
+    }
+
+    #[test]
+    fn meshing_stays_within_requests() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+        world.pipeline_mut().add_stage(DebugStage::new(1));
+
+        let mut requests = ChunkRequestsComp::default();
+        requests.add(&Vec2(0, 0));
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(requests)
+            .build();
+
+        ChunkRequestsSystem.run_now(world.ecs());
+
+        let start = Instant::now();
+
+        while !world.chunks().is_chunk_ready(&Vec2(0, 0))
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            ChunkGeneratingSystem.run_now(world.ecs());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(world.chunks().is_chunk_ready(&Vec2(0, 0)));
+
+        for _ in 0..30 {
+            ChunkGeneratingSystem.run_now(world.ecs());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Only the requested chunk and the ring of neighbors its light reaches are ever generated.
+        let chunks = world.chunks();
+        let loaded = chunks
+            .loaded_chunks()
+            .map(|(coords, _)| coords.to_owned())
+            .collect::<HashSet<_>>();
+        assert_eq!(loaded, chunks.light_traversed_chunks(&Vec2(0, 0)).into_iter().collect());
+    }
+
+    #[test]
+    fn pipeline_stats() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+
+        let mut pipeline = Pipeline::new();
+        pipeline.add_stage(DebugStage::new(1));
+        pipeline.add_stage(DebugStage::new(2));
+
+        // Runs every chunk through one stage, returning the number of results.
+        let run_stage = |pipeline: &mut Pipeline, stage: usize| {
+            for index in 0..20 {
+                pipeline.add_chunk(&Vec2(index, 0), false);
+            }
+
+            let stats = pipeline.stats();
+            assert_eq!(stats.pending, 20);
+            assert_eq!(stats.in_flight, 0);
+
+            let mut processes = vec![];
+            while let Some(Vec2(cx, cz)) = pipeline.get() {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Generating(stage);
+                processes.push((chunk, None));
+            }
+            pipeline.process(processes, &registry, &config);
+
+            let stats = pipeline.stats();
+            assert_eq!(stats.pending, 0);
+            assert_eq!(stats.in_flight, 20);
+
+            let mut done = 0;
+            let start = Instant::now();
+            while done < 20 && start.elapsed() < Duration::from_secs(5) {
+                done += pipeline.results().len();
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            let stats = pipeline.stats();
+            assert_eq!(stats.pending, 0);
+            assert_eq!(stats.in_flight, 0);
+            done
+        };
+
+        assert_eq!(pipeline.stats().completed, 0);
+
+        // Chunks only count as completed once they're through the last stage.
+        assert_eq!(run_stage(&mut pipeline, 0), 20);
+        assert_eq!(pipeline.stats().completed, 0);
+
+        assert_eq!(run_stage(&mut pipeline, 1), 20);
+        assert_eq!(pipeline.stats().completed, 20);
+        assert!(pipeline.stats().average_time > Duration::ZERO);
+    }
+
+    #[test]
+    fn fluid_spreading() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Water")
+                .id(2)
+                .aabbs(&[])
+                .is_passable(true)
+                .is_transparent(true)
+                .fluid_flow(2, 1)
+                .build(),
+        ]);
+        let mut world = ready_world(&config, registry);
+
+        // A flat floor, with a wall standing in the way of the water.
+        for vx in 0..16 {
+            for vz in 0..16 {
+                world.chunks_mut().set_voxel(vx, 3, vz, 1);
+            }
+        }
+        world.chunks_mut().set_voxel(10, 4, 8, 1);
+
+        let mut expected = HashSet::from_iter([Vec3(8, 6, 8), Vec3(8, 5, 8)]);
+        for vx in 6..=10 {
+            for vz in 6..=10 {
+                if (vx - 8i32).abs() + (vz - 8i32).abs() <= 2 && (vx, vz) != (10, 8) {
+                    expected.insert(Vec3(vx, 4, vz));
+                }
+            }
+        }
+
+        let water = |world: &World| {
+            let chunks = world.chunks();
+            let mut water = HashSet::new();
+            for vx in 0..16 {
+                for vy in 4..8 {
+                    for vz in 0..16 {
+                        if chunks.get_voxel(vx, vy, vz) == 2 {
+                            water.insert(Vec3(vx, vy, vz));
+                        }
+                    }
+                }
+            }
+            water
+        };
+
+        let step = |world: &mut World| {
+            world.write_resource::<Stats>().tick += 1;
+            world.write_resource::<Mesher>().results();
+            ChunkUpdatingSystem.run_now(world.ecs());
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        world.chunks_mut().update_voxel(&Vec3(8, 6, 8), 2);
+        let start = Instant::now();
+
+        while water(&world) != expected && start.elapsed() < Duration::from_secs(5) {
+            step(&mut world);
+        }
+        assert_eq!(water(&world), expected);
+
+        // Once settled, the water neither spreads any further nor into the wall.
+        for _ in 0..20 {
+            step(&mut world);
+        }
+        assert_eq!(water(&world), expected);
+        assert_eq!(world.chunks().get_voxel_stage(8, 4, 8), 0);
+        assert_eq!(world.chunks().get_voxel_stage(7, 4, 8), 1);
+        assert_eq!(world.chunks().get_voxel_stage(6, 4, 8), 2);
+
+        // Knocking the wall down lets the settled water flow into the gap.
+        world.chunks_mut().update_voxel(&Vec3(10, 4, 8), 0);
+        expected.insert(Vec3(10, 4, 8));
+        let start = Instant::now();
+
+        while water(&world) != expected && start.elapsed() < Duration::from_secs(5) {
+            step(&mut world);
+        }
+        assert_eq!(water(&world), expected);
+        assert_eq!(world.chunks().get_voxel_stage(10, 4, 8), 2);
+    }
+
+    #[test]
+    fn explosions() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Bedrock").id(2).is_unbreakable(true).build(),
+        ]);
+        registry.generate();
+
+        for cx in -1..=2 {
+            for cz in -1..=1 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.meshes = Some(HashMap::new());
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        // A slab of stone across the border of chunks (0, 0) and (1, 0), with bedrock in the middle.
+        for vx in 10..22 {
+            for vy in 2..14 {
+                for vz in 2..14 {
+                    world.chunks_mut().set_voxel(vx, vy, vz, 1);
+                }
+            }
+        }
+        world.chunks_mut().set_voxel(16, 8, 8, 2);
+
+        let center = Vec3(16.0, 8.0, 8.0);
+        let radius = 2.5;
+
+        let mut expected = HashSet::new();
+        for vx in 10..22 {
+            for vy in 2..14 {
+                for vz in 2..14 {
+                    let dx = vx as f32 + 0.5 - center.0;
+                    let dy = vy as f32 + 0.5 - center.1;
+                    let dz = vz as f32 + 0.5 - center.2;
+                    if (dx * dx + dy * dy + dz * dz).sqrt() <= radius && (vx, vy, vz) != (16, 8, 8)
+                    {
+                        expected.insert(Vec3(vx, vy, vz));
+                    }
+                }
+            }
+        }
+
+        let destroyed = world.chunks_mut().explode(&registry, &center, radius, true);
+        assert_eq!(destroyed.len(), expected.len());
+        assert_eq!(HashSet::from_iter(destroyed), expected);
+        assert!(expected.iter().any(|voxel| voxel.0 < 16));
+        assert!(expected.iter().any(|voxel| voxel.0 >= 16));
+
+        world.ecs_mut().insert(registry);
+
+        // Updates to chunks that are still remeshing are retried on the next run.
+        let cleared = |world: &World| {
+            let chunks = world.chunks();
+            expected
+                .iter()
+                .all(|voxel| chunks.get_voxel(voxel.0, voxel.1, voxel.2) == 0)
+        };
+        let start = Instant::now();
+
+        while !cleared(&world) && start.elapsed() < Duration::from_millis(500) {
+            world.write_resource::<Mesher>().results();
+            ChunkUpdatingSystem.run_now(world.ecs());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let chunks = world.chunks();
+        assert!(expected
+            .iter()
+            .all(|voxel| chunks.get_voxel(voxel.0, voxel.1, voxel.2) == 0));
+        assert_eq!(chunks.get_voxel(16, 8, 8), 2);
+        assert_eq!(chunks.get_voxel(16, 8, 12), 1);
+        drop(chunks);
+
+        let queue = world.read_resource::<MessageQueue>();
+        let updates = queue
+            .iter()
+            .filter(|(message, _)| message.r#type == MessageType::Update as i32)
+            .map(|(message, _)| message.updates.len())
+            .sum::<usize>();
+        assert_eq!(updates, expected.len());
+    }
+
+    #[test]
+    fn bulk_fill() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .max_updates_per_tick(16)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Dirt").id(2).build(),
+        ]);
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        for cx in -2..=1 {
+            for cz in -2..=1 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.meshes = Some(HashMap::new());
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        // A box across the corner shared by chunks (-1, -1), (0, -1), (-1, 0) and (0, 0).
+        let min = Vec3(-4, 1, -4);
+        let max = Vec3(3, 3, 3);
+        assert_eq!(world.chunks_mut().fill(&min, &max, 1), 192);
+
+        // Far more voxels than `max_updates_per_tick`, yet applied in a single run.
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.read_resource::<Mesher>().processed(), 4);
+
+        finish_meshing(&mut world);
+
+        assert_eq!(world.chunks_mut().replace(&min, &Vec3(3, 1, 3), 1, 2), 64);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.read_resource::<Mesher>().processed(), 8);
+        world.write_resource::<Mesher>().drain();
+
+        let chunks = world.chunks();
+        assert_eq!(chunks.get_voxel(-4, 1, -4), 2);
+        assert_eq!(chunks.get_voxel(3, 2, 3), 1);
+        assert_eq!(chunks.get_voxel(4, 3, 3), 0);
+    }
+
+    #[test]
+    fn bulk_edit_order() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Dirt").id(2).build(),
+        ]);
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        for cx in -2..=2 {
+            for cz in -2..=2 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.meshes = Some(HashMap::new());
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        // A fill queued after an update of the same voxel lands on top of it.
+        world.chunks_mut().update_voxel(&Vec3(2, 2, 2), 1);
+        world.chunks_mut().fill(&Vec3(1, 1, 1), &Vec3(3, 3, 3), 2);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_voxel(2, 2, 2), 2);
+
+        // While the chunk is being remeshed, both wait and keep their order.
+        world.chunks_mut().fill(&Vec3(1, 1, 1), &Vec3(3, 3, 3), 1);
+        world.chunks_mut().update_voxel(&Vec3(2, 2, 2), 0);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_voxel(2, 2, 2), 2);
+
+        world.write_resource::<Mesher>().drain();
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_voxel(1, 1, 1), 1);
+        assert_eq!(world.chunks().get_voxel(2, 2, 2), 0);
+        world.write_resource::<Mesher>().drain();
+
+        // A batch touching a chunk that isn't ready is kept until the chunk is.
+        world.chunks_mut().raw_mut(&Vec2(1, 0)).unwrap().status = ChunkStatus::Meshing;
+        world.chunks_mut().fill(&Vec3(15, 1, 1), &Vec3(16, 1, 1), 1);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_voxel(15, 1, 1), 0);
+
+        world.chunks_mut().raw_mut(&Vec2(1, 0)).unwrap().status = ChunkStatus::Ready;
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_voxel(15, 1, 1), 1);
+        assert_eq!(world.chunks().get_voxel(16, 1, 1), 1);
+    }
+
+    #[test]
+    fn filtered_light_updates() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(16)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Torch").id(2).torch_light_level(15).build(),
+            Block::new("Red Glass")
+                .id(3)
+                .is_transparent(true)
+                .is_see_through(true)
+                .light_filter([1, 4, 4])
+                .build(),
+        ]);
+        let mut world = ready_world(&config, registry);
+
+        // A tunnel through stone, lit by a torch at one end.
+        for vx in 0..16 {
+            for vy in 0..16 {
+                for vz in 0..16 {
+                    let tunnel = vy == 8 && vz == 8 && (2..12).contains(&vx);
+                    world
+                        .chunks_mut()
+                        .set_voxel(vx, vy, vz, if tunnel { 0 } else { 1 });
+                }
+            }
+        }
+
+        let update = |world: &mut World, vx: i32, id: u32| {
+            world.chunks_mut().update_voxel(&Vec3(vx, 8, 8), id);
+            ChunkUpdatingSystem.run_now(world.ecs());
+            finish_meshing(world);
+
+            let chunks = world.chunks();
+            (3..12)
+                .map(|vx| {
+                    [LightColor::Red, LightColor::Green, LightColor::Blue]
+                        .map(|color| chunks.get_torch_light(vx, 8, 8, &color))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let lit = update(&mut world, 2, 2);
+        assert_eq!(lit[3], [11, 11, 11]);
+
+        // Past the glass, green and blue are filtered more than red.
+        let filtered = update(&mut world, 5, 3);
+        assert_eq!(filtered[3], [10, 7, 7]);
+
+        // Taking the glass back out lets the light through unfiltered again.
+        assert_eq!(update(&mut world, 5, 0), lit);
+    }
+
+    #[test]
+    fn custom_air_id() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .air_id(7)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Void")
+                .id(7)
+                .is_empty(true)
+                .is_passable(true)
+                .is_transparent(true)
+                .build(),
+        ]);
+        let mut world = ready_world(&config, registry);
+
+        world.chunks_mut().update_voxel(&Vec3(2, 5, 2), 1);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_max_height(2, 2), 5);
+        world.write_resource::<Mesher>().drain();
+
+        // Setting the voxel back to the configured air lowers the height map again.
+        world.chunks_mut().update_voxel(&Vec3(2, 5, 2), 7);
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.chunks().get_max_height(2, 2), 0);
+
+        // Voxels of chunks that aren't loaded read as air.
+        assert_eq!(world.chunks().get_voxel(100, 5, 100), 7);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Dirt").id(2).build(),
+        ]);
+        let mut world = ready_world(&config, registry);
+        world.chunks_mut().set_voxel(4, 1, 4, 2);
+
+        let run = |world: &mut World| {
+            ChunkUpdatingSystem.run_now(world.ecs());
+            world.write_resource::<Mesher>().drain();
+            world.chunks().get_voxel(4, 1, 4)
+        };
+
+        world.chunks_mut().edit_voxel(&Vec3(4, 1, 4), 1);
+        assert_eq!(run(&mut world), 1);
+
+        assert!(world.chunks_mut().undo());
+        assert_eq!(run(&mut world), 2);
+        assert!(!world.chunks_mut().undo());
+
+        assert!(world.chunks_mut().redo());
+        assert_eq!(run(&mut world), 1);
+        assert!(!world.chunks_mut().redo());
+
+        // A fill is undone as a whole.
+        world.chunks_mut().fill(&Vec3(2, 1, 2), &Vec3(6, 1, 6), 2);
+        run(&mut world);
+        assert!(world.chunks_mut().undo());
+        assert_eq!(run(&mut world), 1);
+        assert_eq!(world.chunks().get_voxel(2, 1, 2), 0);
+
+        // Undos and redos are sent to the clients like any other update.
+        let queue = world.read_resource::<MessageQueue>();
+        let updates = queue
+            .iter()
+            .filter(|(message, _)| message.r#type == MessageType::Update as i32)
+            .map(|(message, _)| message.updates.len())
+            .sum::<usize>();
+        assert_eq!(updates, 3 + 25 + 25);
+    }
+
+    #[test]
+    fn undo_limit() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .max_history(2)
+            .build();
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Dirt").id(2).build(),
+            Block::new("Sand").id(3).build(),
+        ]);
+        let mut world = ready_world(&config, registry);
+
+        // Queued edits of the same voxel remember what the one before them set it to.
+        for id in 1..=3 {
+            world.chunks_mut().edit_voxel(&Vec3(4, 1, 4), id);
+        }
+
+        // Only the latest two edits are kept.
+        assert!(world.chunks_mut().undo());
+        assert!(world.chunks_mut().undo());
+        assert!(!world.chunks_mut().undo());
+
+        for _ in 0..3 {
+            ChunkUpdatingSystem.run_now(world.ecs());
+            world.write_resource::<Mesher>().drain();
+        }
+
+        assert_eq!(world.chunks().get_voxel(4, 1, 4), 1);
+    }
+
+    #[test]
+    fn schematics() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_blocks(&[
+            Block::new("Stone").id(1).build(),
+            Block::new("Log").id(2).rotatable(true).build(),
+        ]);
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        for cx in -1..=2 {
+            for cz in -1..=1 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.meshes = Some(HashMap::new());
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        // An L of stone, with a sideways log at its corner.
+        let mut chunks = world.chunks_mut();
+        for vx in 2..5 {
+            chunks.set_voxel(vx, 1, 2, 1);
+        }
+        for vy in 2..5 {
+            chunks.set_voxel(2, vy, 2, 1);
+        }
+        chunks.set_voxel(2, 1, 2, 2);
+        chunks.set_voxel_rotation(2, 1, 2, &BlockRotation::PX(0.0));
+
+        let schematic = chunks.capture(&Vec3(2, 1, 2), &Vec3(4, 4, 2));
+        assert_eq!(schematic.size, Vec3(3, 4, 1));
+        assert_eq!(
+            Schematic::from_bytes(&schematic.to_bytes()),
+            Ok(schematic.clone())
+        );
+        assert!(Schematic::from_bytes(&[1, 2, 3]).is_err());
+
+        // Sizes from a file can't be trusted to fit, or to match the voxels that follow.
+        let compress = |data: &[u32]| {
+            let bytes = data
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>();
+            let mut encoder = Encoder::new(vec![]).unwrap();
+            encoder.write_all(&bytes).unwrap();
+            encoder.finish().into_result().unwrap()
+        };
+        assert_eq!(
+            Schematic::from_bytes(&compress(&[u32::MAX, u32::MAX, u32::MAX])),
+            Err(SchematicError::SizeOverflow)
+        );
+        assert_eq!(
+            Schematic::from_bytes(&compress(&[1, 1, 1, 5, 5])),
+            Err(SchematicError::SizeMismatch(1, 2))
+        );
+        assert_eq!(
+            Schematic::from_bytes(&compress(&[1, 1])),
+            Err(SchematicError::MissingSize)
+        );
+
+        // Pasted across the border of chunks (0, 0) and (1, 0).
+        let origin = Vec3(14, 5, 9);
+        assert_eq!(chunks.paste(&origin, &schematic), 6);
+        drop(chunks);
+
+        ChunkUpdatingSystem.run_now(world.ecs());
+        assert_eq!(world.read_resource::<Mesher>().processed(), 2);
+        world.write_resource::<Mesher>().drain();
+
+        let chunks = world.chunks();
+        for x in 0..3 {
+            for y in 0..4 {
+                assert_eq!(
+                    chunks.get_raw_voxel(origin.0 + x, origin.1 + y, origin.2),
+                    chunks.get_raw_voxel(2 + x, 1 + y, 2)
+                );
+            }
+        }
+        assert_eq!(chunks.get_voxel(16, 5, 9), 1);
+        assert_eq!(chunks.get_voxel(16, 6, 9), 0);
+        assert_eq!(chunks.get_voxel_rotation(14, 5, 9), BlockRotation::PX(0.0));
+    }
+
+    fn streamed(world: &World, r#type: MessageType) -> HashMap<String, HashSet<Vec2<i32>>> {
+        let mut streamed: HashMap<String, HashSet<Vec2<i32>>> = HashMap::new();
+
+        for (message, filter) in world.read_resource::<MessageQueue>().iter() {
+            if message.r#type != r#type as i32 {
+                continue;
+            }
+
+            if let ClientFilter::Direct(id) = filter {
+                streamed
+                    .entry(id.to_owned())
+                    .or_default()
+                    .extend(message.chunks.iter().map(|chunk| Vec2(chunk.x, chunk.z)));
+            }
+        }
+
+        streamed
+    }
+
+    fn streaming_world(stream_radius: usize) -> World {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .max_response_per_tick(32)
+            .stream_radius(stream_radius)
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        for cx in -3..=3 {
+            for cz in -3..=3 {
+                let mut chunk = Chunk::new(
+                    &format!("{cx}:{cz}"),
+                    cx,
+                    cz,
+                    &ChunkOptions {
+                        size: 16,
+                        max_height: 32,
+                        sub_chunks: 1,
+                        min_y: 0,
+                        air_id: 0,
+                    },
+                );
+                chunk.status = ChunkStatus::Ready;
+                chunk.meshes = Some(HashMap::new());
+                world.chunks_mut().add(chunk);
+            }
+        }
+
+        world
+    }
+
+    fn stream(world: &mut World) {
+        world.write_resource::<MessageQueue>().clear();
+        ChunkStreamingSystem.run_now(world.ecs());
+        ChunkRequestsSystem.run_now(world.ecs());
+    }
+
+    #[test]
+    fn chunk_streaming() {
+        let mut world = streaming_world(1);
+
+        let client = world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(ChunkRequestsComp::default())
+            .with(CurrentChunkComp::default())
+            .build();
+
+        stream(&mut world);
+        assert_eq!(
+            streamed(&world, MessageType::Load)["alice"],
+            HashSet::from_iter([Vec2(0, 0), Vec2(1, 0), Vec2(-1, 0), Vec2(0, 1), Vec2(0, -1)])
+        );
+        assert!(streamed(&world, MessageType::Unload).is_empty());
+
+        // Nothing new to send while the client stays put.
+        stream(&mut world);
+        assert!(streamed(&world, MessageType::Load).is_empty());
+
+        world
+            .ecs_mut()
+            .write_storage::<CurrentChunkComp>()
+            .get_mut(client)
+            .unwrap()
+            .coords = Vec2(2, 0);
+        stream(&mut world);
+
+        assert_eq!(
+            streamed(&world, MessageType::Load)["alice"],
+            HashSet::from_iter([Vec2(2, 0), Vec2(3, 0), Vec2(2, 1), Vec2(2, -1)])
+        );
+        assert_eq!(
+            streamed(&world, MessageType::Unload)["alice"],
+            HashSet::from_iter([Vec2(0, 0), Vec2(-1, 0), Vec2(0, 1), Vec2(0, -1)])
+        );
+    }
+
+    #[test]
+    fn streaming_pending_chunks() {
+        let mut world = streaming_world(1);
+        world.chunks_mut().remove(&Vec2(1, 0));
+        world.chunks_mut().remove(&Vec2(-1, 0));
+
+        let client = world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(ChunkRequestsComp::default())
+            .with(CurrentChunkComp::default())
+            .build();
+
+        stream(&mut world);
+        assert_eq!(
+            streamed(&world, MessageType::Load)["alice"],
+            HashSet::from_iter([Vec2(0, 0), Vec2(0, 1), Vec2(0, -1)])
+        );
+
+        // A chunk generated after it was requested is sent once it's ready, and only then.
+        let mut chunk = Chunk::new(
+            "1:0",
+            1,
+            0,
+            &ChunkOptions {
+                size: 16,
+                max_height: 32,
+                sub_chunks: 1,
+                min_y: 0,
+                air_id: 0,
+            },
+        );
+        chunk.status = ChunkStatus::Ready;
+        chunk.meshes = Some(HashMap::new());
+        world.chunks_mut().add(chunk);
+        world
+            .chunks_mut()
+            .add_chunk_to_send(&Vec2(1, 0), &MessageType::Load, false);
+
+        world.write_resource::<MessageQueue>().clear();
+        ChunkSendingSystem.run_now(world.ecs());
+        assert_eq!(
+            streamed(&world, MessageType::Load)["alice"],
+            HashSet::from_iter([Vec2(1, 0)])
+        );
+
+        stream(&mut world);
+        assert!(streamed(&world, MessageType::Load).is_empty());
+
+        // The chunk that never got generated stops being waited on once it's out of range.
+        assert!(world
+            .read_resource::<ChunkInterests>()
+            .has_interests(&Vec2(-1, 0)));
+
+        world
+            .ecs_mut()
+            .write_storage::<CurrentChunkComp>()
+            .get_mut(client)
+            .unwrap()
+            .coords = Vec2(2, 0);
+        stream(&mut world);
+
+        assert!(!world
+            .read_resource::<ChunkInterests>()
+            .has_interests(&Vec2(-1, 0)));
+        assert_eq!(
+            streamed(&world, MessageType::Unload)["alice"],
+            HashSet::from_iter([Vec2(0, 0), Vec2(0, 1), Vec2(0, -1)])
+        );
+    }
+
+    #[test]
+    fn view_radius() {
+        let mut world = streaming_world(2);
+
+        let laptop = world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("laptop"))
+            .with(ChunkRequestsComp::default())
+            .with(CurrentChunkComp::default())
+            .build();
+
+        let mut requests = ChunkRequestsComp::new();
+        requests.set_view_radius(1);
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("phone"))
+            .with(requests)
+            .with(CurrentChunkComp::default())
+            .build();
+
+        stream(&mut world);
+        let loaded = streamed(&world, MessageType::Load);
+
+        let near =
+            HashSet::from_iter([Vec2(0, 0), Vec2(1, 0), Vec2(-1, 0), Vec2(0, 1), Vec2(0, -1)]);
+        assert_eq!(loaded["phone"], near);
+        assert_eq!(loaded["laptop"].len(), 13);
+        assert!(loaded["laptop"].is_superset(&near));
+
+        // Asking for more than the server allows is clamped to the server's radius.
+        world
+            .ecs_mut()
+            .write_storage::<ChunkRequestsComp>()
+            .get_mut(laptop)
+            .unwrap()
+            .set_view_radius(8);
+        stream(&mut world);
+        assert!(streamed(&world, MessageType::Load).is_empty());
+
+        // Lowering the radius unloads the chunks that fell out of it.
+        world
+            .ecs_mut()
+            .write_storage::<ChunkRequestsComp>()
+            .get_mut(laptop)
+            .unwrap()
+            .set_view_radius(1);
+        stream(&mut world);
+
+        let unloaded = streamed(&world, MessageType::Unload);
+        assert_eq!(unloaded["laptop"].len(), 8);
+        assert!(unloaded["laptop"].is_disjoint(&near));
+        assert!(!unloaded.contains_key("phone"));
+
+        // A malformed radius from a client is ignored instead of taking the world down.
+        world.on_request(
+            "phone",
+            Message::new(&MessageType::Method)
+                .method(MethodProtocol {
+                    name: "vox-builtin:set-view-radius".to_owned(),
+                    payload: "{\"radius\":-1}".to_owned(),
+                })
+                .build(),
+        );
+    }
+
+    #[test]
+    fn init_data() {
+        let config = WorldConfig::new()
+            .chunk_size(12)
+            .max_height(64)
+            .seed(42)
+            .spawn([0.5, 40.0, -3.5])
+            .build();
+        let mut world = World::new("world", &config);
+
+        let mut registry = Registry::new();
+        registry.register_block(&Block::new("Stone").id(1).build());
+        registry.generate();
+        world.ecs_mut().insert(registry);
+
+        let message = world.generate_init_message("alice");
+        assert_eq!(message.r#type, MessageType::Init as i32);
+
+        let data: WorldInitData = serde_json::from_str(&message.json).unwrap();
+        assert_eq!(data.id, "alice");
+        assert_eq!(data.name, "world");
+        assert_eq!(data.chunk_size, 12);
+        assert_eq!(data.max_height, 64);
+        assert_eq!(data.seed, 42);
+        assert!(data.blocks.contains_key("stone"));
+        assert_eq!(data.spawn, Vec3(0.5, 40.0, -3.5));
+        assert_eq!(data.options["chunkSize"], 12);
+    }
+
+    #[test]
+    fn facing_priority() {
+        let mut world = streaming_world(3);
+
+        let client = world
+            .ecs_mut()
+            .create_entity()
+            .with(IDComp::new("alice"))
+            .with(ChunkRequestsComp::default())
+            .with(CurrentChunkComp::default())
+            .with(DirectionComp::new(1.0, 0.0, 0.0))
+            .build();
+
+        ChunkStreamingSystem.run_now(world.ecs());
+
+        let storage = world.ecs().read_storage::<ChunkRequestsComp>();
+        let requests = &storage.get(client).unwrap().requests;
+        let order = |coords: Vec2<i32>| requests.iter().position(|c| *c == coords).unwrap();
+
+        // Far chunks ahead come before the ones behind, which are still streamed.
+        assert!(order(Vec2(3, 0)) < order(Vec2(-3, 0)));
+        assert!(order(Vec2(2, 2)) < order(Vec2(-2, 2)));
+
+        // Nearby chunks go by distance alone.
+        assert!(order(Vec2(-1, 0)) < order(Vec2(3, 0)));
+        assert!(order(Vec2(-2, 0)) < order(Vec2(3, 0)));
+    }
+
+    fn add_client(world: &mut World, id: &str, position: &Vec3<f32>) {
+        let Vec3(px, py, pz) = *position;
+
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(ClientFlag::default())
+            .with(IDComp::new(id))
+            .with(PositionComp::new(px, py, pz))
+            .build();
+    }
+
+    fn move_entity(world: &mut World, id: &str, position: &Vec3<f32>) {
+        let ecs = world.ecs();
+        let ids = ecs.read_storage::<IDComp>();
+        let mut positions = ecs.write_storage::<PositionComp>();
+
+        for (entity_id, entity_position) in (&ids, &mut positions).join() {
+            if entity_id.0 == id {
+                entity_position.0 = position.to_owned();
+            }
+        }
+    }
+
+    fn entity_packets(world: &mut World) -> Vec<(String, i32, String)> {
+        EntitiesMetaSystem.run_now(world.ecs());
+        EntitiesSendingSystem.run_now(world.ecs());
+
+        let mut queue = world.write_resource::<MessageQueue>();
+        let packets = queue
+            .iter()
+            .filter(|(message, _)| message.r#type == MessageType::Entity as i32)
+            .flat_map(|(message, filter)| {
+                let client = match filter {
+                    ClientFilter::Direct(id) => id.to_owned(),
+                    _ => "*".to_owned(),
+                };
+
+                message
+                    .entities
+                    .iter()
+                    .map(move |entity| (client.to_owned(), entity.operation, entity.id.to_owned()))
+            })
+            .collect();
+        queue.clear();
+        packets
+    }
+
+    #[test]
+    fn entity_spawning() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .build();
+        let mut world = World::new("world", &config);
+
+        add_client(&mut world, "alice", &Vec3(0.0, 0.0, 0.0));
+        add_client(&mut world, "bob", &Vec3(90.0, 0.0, 90.0));
+
+        world
+            .write_resource::<ChunkInterests>()
+            .add("alice", &Vec2(0, 0));
+        world
+            .write_resource::<ChunkInterests>()
+            .add("bob", &Vec2(5, 5));
+
+        let id = world.spawn_entity("arrow", &Vec3(3.0, 4.0, 5.0));
+
+        let entities = world.entities();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].id, id);
+        assert_eq!(entities[0].kind, "arrow");
+        assert_eq!(entities[0].position, Vec3(3.0, 4.0, 5.0));
+        assert_eq!(entities[0].velocity, Vec3(0.0, 0.0, 0.0));
+
+        // Only the client with the entity's chunk loaded hears of it.
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![(
+                "alice".to_owned(),
+                EntityOperation::Create as i32,
+                id.to_owned()
+            )]
+        );
+
+        assert!(world.despawn_entity(&id));
+        assert!(!world.despawn_entity(&id));
+        assert!(world.entities().is_empty());
+
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![(
+                "alice".to_owned(),
+                EntityOperation::Delete as i32,
+                id.to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn entity_interest_radius() {
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .entity_radius(32.0)
+            .build();
+        let mut world = World::new("world", &config);
+
+        add_client(&mut world, "alice", &Vec3(0.0, 0.0, 0.0));
+        add_client(&mut world, "bob", &Vec3(120.0, 0.0, 0.0));
+
+        let id = world.spawn_entity("arrow", &Vec3(10.0, 0.0, 0.0));
+        let packet = |client: &str, operation: EntityOperation| {
+            (client.to_owned(), operation as i32, id.to_owned())
+        };
+
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![packet("alice", EntityOperation::Create)]
+        );
+
+        move_entity(&mut world, &id, &Vec3(20.0, 0.0, 0.0));
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![packet("alice", EntityOperation::Update)]
+        );
+
+        // Crossing the boundary leaves one client's view and enters the other's.
+        move_entity(&mut world, &id, &Vec3(100.0, 0.0, 0.0));
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![
+                packet("alice", EntityOperation::Leave),
+                packet("bob", EntityOperation::Enter),
+            ]
+        );
+
+        move_entity(&mut world, &id, &Vec3(101.0, 0.0, 0.0));
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![packet("bob", EntityOperation::Update)]
+        );
+
+        move_entity(&mut world, &id, &Vec3(5.0, 0.0, 0.0));
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![
+                packet("alice", EntityOperation::Enter),
+                packet("bob", EntityOperation::Leave),
+            ]
+        );
+
+        // The range reaches past the chunks next to the client's, right up to the radius.
+        move_entity(&mut world, &id, &Vec3(-32.0, 0.0, 0.0));
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![packet("alice", EntityOperation::Update)]
+        );
+
+        move_entity(&mut world, &id, &Vec3(-32.5, 0.0, 0.0));
+        assert_eq!(
+            entity_packets(&mut world),
+            vec![packet("alice", EntityOperation::Leave)]
+        );
+    }
+
+    #[test]
+    fn chunk_eviction() {
+        let save_dir = std::env::temp_dir().join("voxelize-eviction-test");
+        let _ = fs::remove_dir_all(&save_dir);
+
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .max_loaded_chunks(3)
+            .saving(true)
+            .save_dir(save_dir.to_str().unwrap())
+            .build();
+        let mut world = World::new("world", &config);
+
+        let add_chunk = |world: &mut World, cx: i32| {
+            let mut chunk = Chunk::new(
+                &format!("{cx}:0"),
+                cx,
+                0,
+                &ChunkOptions {
+                    size: 16,
+                    max_height: 32,
+                    sub_chunks: 1,
+                    min_y: 0,
+                    air_id: 0,
+                },
+            );
+            chunk.status = ChunkStatus::Ready;
+            world.chunks_mut().add(chunk);
+        };
+        let loaded = |world: &World| {
+            let mut loaded = world
+                .chunks()
+                .loaded_chunks()
+                .map(|(coords, _)| coords.0)
+                .collect::<Vec<_>>();
+            loaded.sort();
+            loaded
+        };
+
+        for cx in 0..5 {
+            add_chunk(&mut world, cx);
+        }
+
+        // A client stands in the coldest chunk, and the oldest chunk is read again.
+        world
+            .ecs_mut()
+            .create_entity()
+            .with(ClientFlag::default())
+            .with(CurrentChunkComp {
+                coords: Vec2(1, 0),
+                changed: false,
+            })
+            .build();
+        assert!(world.chunks().get_block_id(3, 0, 3).is_some());
+
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![0, 1, 4]);
+
+        add_chunk(&mut world, 5);
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![0, 1, 5]);
+
+        // Within the cap, nothing is evicted.
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![0, 1, 5]);
+
+        // Sending a chunk counts as accessing it.
+        world
+            .chunks_mut()
+            .add_chunk_to_send(&Vec2(0, 0), &MessageType::Load, false);
+        ChunkSendingSystem.run_now(world.ecs());
+        add_chunk(&mut world, 6);
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![0, 1, 6]);
+
+        // So does setting a block in it.
+        world.chunks_mut().set_block_id(3, 0, 3, 1).unwrap();
+        add_chunk(&mut world, 7);
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![0, 1, 7]);
+
+        // Updates scheduled in an evicted chunk are dropped with it, not replayed once it's back.
+        world
+            .chunks_mut()
+            .schedule_block_update(&Vec3(7 * 16 + 3, 0, 3), 0);
+        world.chunks_mut().schedule_block_update(&Vec3(3, 0, 3), 0);
+        add_chunk(&mut world, 8);
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![1, 7, 8]);
+
+        add_chunk(&mut world, 0);
+        assert_eq!(
+            world.chunks_mut().take_due_updates(0),
+            vec![Vec3(7 * 16 + 3, 0, 3)]
+        );
+
+        fs::remove_dir_all(&save_dir).unwrap();
+
+        // Without saving, evicted edits would be lost, so nothing is evicted.
+        let config = WorldConfig::new()
+            .chunk_size(16)
+            .max_height(32)
+            .sub_chunks(1)
+            .max_loaded_chunks(3)
+            .build();
+        let mut world = World::new("world", &config);
+
+        for cx in 0..5 {
+            add_chunk(&mut world, cx);
+        }
+
+        ChunkEvictingSystem.run_now(world.ecs());
+        assert_eq!(loaded(&world), vec![0, 1, 2, 3, 4]);
+    }
+}